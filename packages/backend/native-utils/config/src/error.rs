@@ -0,0 +1,17 @@
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum Error {
+    #[error("The config has not been loaded yet")]
+    Uninitialized,
+    #[error("Failed to read {path}: {message}")]
+    IoError { path: String, message: String },
+    #[error("Failed to parse the config: {0}")]
+    ParseError(String),
+    #[error("Invalid config `{key}`: {message}")]
+    Invalid { key: &'static str, message: String },
+}
+
+impl From<serde_yaml::Error> for Error {
+    fn from(value: serde_yaml::Error) -> Self {
+        Self::ParseError(value.to_string())
+    }
+}
@@ -0,0 +1,29 @@
+use crate::impl_into_napi_error;
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum Error {
+    #[error("Invalid acct: {0}")]
+    InvalidAcct(String),
+    #[error("Invalid host: {0}")]
+    InvalidHost(String),
+    #[error("Invalid WebFinger query: {0}")]
+    InvalidQuery(String),
+    #[error("Resource of another host: {0}")]
+    ForeignHost(String),
+    #[error("HTTP error: {0}")]
+    HttpError(#[from] crate::util::http::Error),
+    #[error("WebFinger responded with status {0}")]
+    HttpStatus(u16),
+    #[error("Invalid JRD: {0}")]
+    InvalidJrd(String),
+    #[error("Self link not found")]
+    SelfLinkNotFound,
+    #[error("Failed to get database connection: {0}")]
+    DbConnError(#[from] crate::database::error::Error),
+    #[error("Database operation error: {0}")]
+    DbOperationError(#[from] sea_orm::DbErr),
+    #[error("Requested entity not found")]
+    NotFound,
+}
+
+impl_into_napi_error!(Error);
@@ -0,0 +1,205 @@
+//! Accounts in `user@host` form, ported from `misc/acct.ts` and
+//! `misc/convert-host.ts`, and WebFinger
+
+pub mod error;
+pub mod webfinger;
+
+use std::fmt;
+
+use cfg_if::cfg_if;
+use error::Error;
+use url::{Host, Url};
+
+/// Account of a user. `host` is `None` for local users, and otherwise in
+/// lower-case punycode as `user.host` is stored.
+#[cfg_attr(feature = "napi", napi_derive::napi(object))]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Acct {
+    pub username: String,
+    pub host: Option<String>,
+}
+
+/// Converts the host to lower-case punycode, as `toPuny` does. A port is
+/// kept as is.
+pub fn to_puny(host: &str) -> Result<String, Error> {
+    let invalid = || Error::InvalidHost(host.to_string());
+    let (name, port) = match host.rsplit_once(':') {
+        Some((name, port))
+            if (!name.contains(':') || name.ends_with(']'))
+                && !port.is_empty()
+                && port.bytes().all(|b| b.is_ascii_digit()) =>
+        {
+            (name, Some(port))
+        }
+        _ => (host, None),
+    };
+    if name.is_empty() || name.contains(['/', '@', '?', '#']) {
+        return Err(invalid());
+    }
+    let name = match Host::parse(&name.to_lowercase()).map_err(|_| invalid())? {
+        Host::Domain(domain) => domain,
+        ip => ip.to_string(),
+    };
+    Ok(match port {
+        Some(port) => format!("{}:{}", name, port),
+        None => name,
+    })
+}
+
+/// Returns `config.host`, i.e. the host of `url` with the port.
+pub fn host_of(url: &str) -> Result<String, Error> {
+    let parsed = Url::parse(url).map_err(|_| Error::InvalidHost(url.to_string()))?;
+    match (parsed.host_str(), parsed.port()) {
+        (Some(host), Some(port)) => Ok(format!("{}:{}", host, port)),
+        (Some(host), None) => Ok(host.to_string()),
+        (None, _) => Err(Error::InvalidHost(url.to_string())),
+    }
+}
+
+impl Acct {
+    /// Parses `user`, `@user@host`, or `acct:user@host`. The case of the
+    /// username is kept, and the host is converted with [to_puny].
+    pub fn parse(acct: &str) -> Result<Self, Error> {
+        let invalid = || Error::InvalidAcct(acct.to_string());
+        let trimmed = acct.trim();
+        let trimmed = trimmed.strip_prefix("acct:").unwrap_or(trimmed);
+        let trimmed = trimmed.strip_prefix('@').unwrap_or(trimmed);
+        let (username, host) = match trimmed.split_once('@') {
+            Some((username, host)) => (username, Some(host)),
+            None => (trimmed, None),
+        };
+        if username.is_empty()
+            || username.contains([':', '/'])
+            || username.contains(char::is_whitespace)
+        {
+            return Err(invalid());
+        }
+        let host = match host {
+            Some(host) => Some(to_puny(host).map_err(|_| invalid())?),
+            None => None,
+        };
+
+        Ok(Self {
+            username: username.to_string(),
+            host,
+        })
+    }
+
+    /// Returns the username as `user.username_lower` stores it.
+    pub fn username_lower(&self) -> String {
+        self.username.to_lowercase()
+    }
+
+    /// Returns the account with the username folded, to compare accounts.
+    pub fn to_lowercase(&self) -> Self {
+        Self {
+            username: self.username_lower(),
+            host: self.host.to_owned(),
+        }
+    }
+
+    /// Returns `true` if the account is of the server of `url`.
+    pub fn is_local(&self, url: &str) -> bool {
+        match &self.host {
+            None => true,
+            Some(host) => host_of(url)
+                .and_then(|h| to_puny(&h))
+                .is_ok_and(|h| &h == host),
+        }
+    }
+
+    /// Returns `acct:user@host`. Local accounts need the host of the server.
+    pub fn to_uri(&self, local_host: &str) -> String {
+        format!(
+            "acct:{}@{}",
+            self.username,
+            self.host.as_deref().unwrap_or(local_host)
+        )
+    }
+}
+
+impl fmt::Display for Acct {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.host {
+            Some(host) => write!(f, "{}@{}", self.username, host),
+            None => write!(f, "{}", self.username),
+        }
+    }
+}
+
+cfg_if! {
+    if #[cfg(feature = "napi")] {
+        use napi_derive::napi;
+
+        #[napi]
+        pub fn native_parse_acct(acct: String) -> napi::Result<Acct> {
+            Acct::parse(&acct).map_err(Into::into)
+        }
+
+        #[napi]
+        pub fn native_to_puny(host: String) -> napi::Result<String> {
+            to_puny(&host).map_err(Into::into)
+        }
+
+        /// Returns the JRD of the local user for `GET /.well-known/webfinger`.
+        /// Fails with `ForeignHost` for 422 and `NotFound` for 404.
+        #[napi]
+        pub async fn native_webfinger_local(url: String, resource: String) -> napi::Result<serde_json::Value> {
+            let jrd = webfinger::local_jrd(&url, &resource).await.map_err(Into::<napi::Error>::into)?;
+            serde_json::to_value(jrd).map_err(|e| napi::Error::from_reason(e.to_string()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod unit_test {
+    use pretty_assertions::assert_eq;
+
+    use super::error::Error;
+    use super::{host_of, to_puny, Acct};
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            Acct::parse("@Alice@Example.COM").unwrap(),
+            Acct {
+                username: "Alice".to_string(),
+                host: Some("example.com".to_string()),
+            }
+        );
+        assert_eq!(
+            Acct::parse("acct:bob@ミスキー.example").unwrap(),
+            Acct {
+                username: "bob".to_string(),
+                host: Some("xn--nckxa7i0e.example".to_string()),
+            }
+        );
+        assert_eq!(Acct::parse("carol").unwrap().host, None);
+        assert_eq!(
+            Acct::parse("dave@localhost:3000").unwrap().to_string(),
+            "dave@localhost:3000"
+        );
+        assert_eq!(Acct::parse("@"), Err(Error::InvalidAcct("@".to_string())));
+        assert!(Acct::parse("a@b@c").is_err());
+        assert!(Acct::parse("https://example.com/@a").is_err());
+    }
+
+    #[test]
+    fn normalize() {
+        let acct = Acct::parse("@Ärger@EXAMPLE.com").unwrap();
+        assert_eq!(acct.username_lower(), "ärger");
+        assert_eq!(acct.to_lowercase().to_string(), "ärger@example.com");
+        assert!(acct.is_local("https://Example.com"));
+        assert!(!acct.is_local("https://example.com:3000"));
+        assert!(Acct::parse("a").unwrap().is_local("https://example.com"));
+        assert_eq!(
+            Acct::parse("a").unwrap().to_uri("example.com"),
+            "acct:a@example.com"
+        );
+
+        assert_eq!(to_puny("BÜCHER.example").unwrap(), "xn--bcher-kva.example");
+        assert_eq!(to_puny("[::1]:8080").unwrap(), "[::1]:8080");
+        assert!(to_puny("").is_err());
+        assert_eq!(host_of("http://localhost:3000/").unwrap(), "localhost:3000");
+    }
+}
@@ -0,0 +1,344 @@
+//! WebFinger, ported from `remote/webfinger.ts` and the WebFinger endpoint
+//! of `server/well-known.ts`
+
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use super::error::Error;
+use super::{host_of, Acct};
+use crate::database;
+use crate::model::entity::user;
+use crate::util::http::{HttpClient, Request};
+
+pub const JRD_MEDIA_TYPE: &str = "application/jrd+json";
+/// `Accept` of WebFinger requests.
+pub const ACCEPT: &str = "application/jrd+json, application/json";
+const ACTIVITY_JSON: &str = "application/activity+json";
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Link {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rel: Option<String>,
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub href: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+}
+
+/// JSON Resource Descriptor.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Jrd {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subject: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+    #[serde(default)]
+    pub links: Vec<Link>,
+}
+
+impl Jrd {
+    /// Returns the link to the actor. Links of ActivityStreams types are
+    /// preferred over other `self` links.
+    pub fn self_link(&self) -> Option<&Link> {
+        let is_self = |link: &&Link| {
+            link.href.is_some()
+                && link
+                    .rel
+                    .as_deref()
+                    .is_some_and(|rel| rel.eq_ignore_ascii_case("self"))
+        };
+        let is_activity = |link: &&Link| {
+            link.kind
+                .as_deref()
+                .is_some_and(|t| t == ACTIVITY_JSON || t.starts_with("application/ld+json"))
+        };
+        self.links
+            .iter()
+            .filter(is_self)
+            .find(is_activity)
+            .or_else(|| self.links.iter().find(is_self))
+    }
+
+    /// Returns the account of `subject`.
+    pub fn subject_acct(&self) -> Option<Acct> {
+        self.subject
+            .as_deref()
+            .filter(|s| s.starts_with("acct:"))
+            .and_then(|s| Acct::parse(s).ok())
+    }
+}
+
+/// Local user a WebFinger resource refers to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Resource {
+    Id(String),
+    UsernameLower(String),
+}
+
+fn strip_prefix_ignore_case<'a>(value: &'a str, prefix: &str) -> Option<&'a str> {
+    value
+        .get(..prefix.len())
+        .filter(|head| head.eq_ignore_ascii_case(prefix))
+        .map(|_| &value[prefix.len()..])
+}
+
+/// Parses the resource of a WebFinger request to our server. The resource
+/// is one of the actor id, the profile url, and the acct of a local user.
+/// The scheme, the host and the username are compared case-insensitively,
+/// but the id is not since Ulid ids are upper-case.
+pub fn parse_resource(url: &str, resource: &str) -> Result<Resource, Error> {
+    if let Some(id) = strip_prefix_ignore_case(resource, &format!("{}/users/", url)) {
+        return Ok(Resource::Id(id.to_string()));
+    }
+    let acct = match strip_prefix_ignore_case(resource, &format!("{}/@", url)) {
+        Some(acct) => acct,
+        None => strip_prefix_ignore_case(resource, "acct:").unwrap_or(resource),
+    };
+    let acct = Acct::parse(&acct.to_lowercase())?;
+    if !acct.is_local(&url.to_lowercase()) {
+        return Err(Error::ForeignHost(acct.host.unwrap_or_default()));
+    }
+    Ok(Resource::UsernameLower(acct.username))
+}
+
+/// Renders the JRD of the local user.
+pub fn render_jrd(url: &str, user: &user::Model) -> Result<Jrd, Error> {
+    let host = host_of(url)?;
+    Ok(Jrd {
+        subject: Some(format!("acct:{}@{}", user.username, host)),
+        aliases: Vec::new(),
+        links: vec![
+            Link {
+                rel: Some("self".to_string()),
+                kind: Some(ACTIVITY_JSON.to_string()),
+                href: Some(format!("{}/users/{}", url, user.id)),
+                template: None,
+            },
+            Link {
+                rel: Some("http://webfinger.net/rel/profile-page".to_string()),
+                kind: Some("text/html".to_string()),
+                href: Some(format!("{}/@{}", url, user.username)),
+                template: None,
+            },
+            Link {
+                rel: Some("http://ostatus.org/schema/1.0/subscribe".to_string()),
+                kind: None,
+                href: None,
+                template: Some(format!("{}/authorize-follow?acct={{uri}}", url)),
+            },
+        ],
+    })
+}
+
+/// Finds the local user of the resource and renders the JRD. Suspended
+/// users are not found.
+pub async fn local_jrd(url: &str, resource: &str) -> Result<Jrd, Error> {
+    let query = user::Entity::find()
+        .filter(user::Column::Host.is_null())
+        .filter(user::Column::IsSuspended.eq(false));
+    let query = match parse_resource(url, resource)? {
+        Resource::Id(id) => query.filter(user::Column::Id.eq(id)),
+        Resource::UsernameLower(username) => query.filter(user::Column::UsernameLower.eq(username)),
+    };
+    let user = query
+        .one(database::get_database()?)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    render_jrd(url, &user)
+}
+
+/// Returns the WebFinger url of `query`, which is an acct or a url.
+pub fn webfinger_url(query: &str) -> Result<String, Error> {
+    let invalid = || Error::InvalidQuery(query.to_string());
+    let (host, resource) = if query.starts_with("https://") || query.starts_with("http://") {
+        let url = Url::parse(query).map_err(|_| invalid())?;
+        let host = match (url.host_str(), url.port()) {
+            (Some(host), Some(port)) => format!("{}:{}", host, port),
+            (Some(host), None) => host.to_string(),
+            (None, _) => return Err(invalid()),
+        };
+        (format!("{}://{}", url.scheme(), host), query.to_string())
+    } else {
+        let acct = Acct::parse(query).map_err(|_| invalid())?;
+        let host = acct.host.to_owned().ok_or_else(invalid)?;
+        (format!("https://{}", host), format!("acct:{}", acct))
+    };
+
+    Ok(format!(
+        "{}/.well-known/webfinger?resource={}",
+        host,
+        urlencoding::encode(&resource)
+    ))
+}
+
+/// Fetches the JRD of `query`, which is an acct or a url.
+pub async fn fetch(client: &dyn HttpClient, query: &str) -> Result<Jrd, Error> {
+    let response = client
+        .send(Request::get(&webfinger_url(query)?, ACCEPT))
+        .await?;
+    if !response.is_success() {
+        return Err(Error::HttpStatus(response.status));
+    }
+    response
+        .json()
+        .map_err(|e| Error::InvalidJrd(e.to_string()))
+}
+
+/// Looks up the id of the actor of the remote account, as `resolveSelf` of
+/// `remote/resolve-user.ts` does.
+pub async fn resolve_self(client: &dyn HttpClient, acct: &Acct) -> Result<String, Error> {
+    fetch(client, &acct.to_lowercase().to_string())
+        .await?
+        .self_link()
+        .and_then(|link| link.href.to_owned())
+        .ok_or(Error::SelfLinkNotFound)
+}
+
+#[cfg(test)]
+mod unit_test {
+    use std::collections::HashMap;
+
+    use async_trait::async_trait;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    use super::{parse_resource, render_jrd, resolve_self, webfinger_url, Jrd, Resource};
+    use crate::acct::error::Error;
+    use crate::acct::Acct;
+    use crate::model::entity::user;
+    use crate::util::http::{self, HttpClient, Request, Response};
+
+    const URL: &str = "https://example.com";
+
+    /// Answers with the bodies keyed by urls, and 404 for others.
+    struct StandIn(HashMap<String, String>);
+
+    #[async_trait]
+    impl HttpClient for StandIn {
+        async fn send(&self, request: Request) -> Result<Response, http::Error> {
+            assert_eq!(request.headers["accept"], super::ACCEPT);
+            Ok(match self.0.get(&request.url) {
+                Some(body) => Response {
+                    status: 200,
+                    body: body.to_owned(),
+                    ..Default::default()
+                },
+                None => Response {
+                    status: 404,
+                    ..Default::default()
+                },
+            })
+        }
+    }
+
+    #[test]
+    fn resource() {
+        assert_eq!(
+            parse_resource(URL, "acct:Alice@Example.com"),
+            Ok(Resource::UsernameLower("alice".to_string()))
+        );
+        assert_eq!(
+            parse_resource(URL, "https://example.com/@Alice"),
+            Ok(Resource::UsernameLower("alice".to_string()))
+        );
+        assert_eq!(
+            parse_resource(URL, "alice"),
+            Ok(Resource::UsernameLower("alice".to_string()))
+        );
+        assert_eq!(
+            parse_resource(URL, "https://example.com/users/9a"),
+            Ok(Resource::Id("9a".to_string()))
+        );
+        assert_eq!(
+            parse_resource(URL, "HTTPS://Example.com/users/01H2XA4XJ3W3Z5Q6V8K9N0PBCD"),
+            Ok(Resource::Id("01H2XA4XJ3W3Z5Q6V8K9N0PBCD".to_string()))
+        );
+        assert_eq!(
+            parse_resource(URL, "acct:alice@remote.example"),
+            Err(Error::ForeignHost("remote.example".to_string()))
+        );
+    }
+
+    #[test]
+    fn jrd() {
+        let user = user::Model {
+            id: "9a".to_string(),
+            username: "Alice".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            serde_json::to_value(render_jrd(URL, &user).unwrap()).unwrap(),
+            json!({
+                "subject": "acct:Alice@example.com",
+                "links": [
+                    {
+                        "rel": "self",
+                        "type": "application/activity+json",
+                        "href": "https://example.com/users/9a",
+                    },
+                    {
+                        "rel": "http://webfinger.net/rel/profile-page",
+                        "type": "text/html",
+                        "href": "https://example.com/@Alice",
+                    },
+                    {
+                        "rel": "http://ostatus.org/schema/1.0/subscribe",
+                        "template": "https://example.com/authorize-follow?acct={uri}",
+                    },
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn url() {
+        assert_eq!(
+            webfinger_url("bob@Remote.example").unwrap(),
+            "https://remote.example/.well-known/webfinger?resource=acct%3Abob%40remote.example"
+        );
+        assert_eq!(
+            webfinger_url("http://localhost:3000/users/1").unwrap(),
+            "http://localhost:3000/.well-known/webfinger?resource=http%3A%2F%2Flocalhost%3A3000%2Fusers%2F1"
+        );
+        assert_eq!(
+            webfinger_url("bob"),
+            Err(Error::InvalidQuery("bob".to_string()))
+        );
+    }
+
+    #[tokio::test]
+    async fn resolve() {
+        let client = StandIn(HashMap::from([(
+            "https://remote.example/.well-known/webfinger?resource=acct%3Abob%40remote.example"
+                .to_string(),
+            json!({
+                "subject": "acct:Bob@remote.example",
+                "aliases": ["https://remote.example/@Bob"],
+                "links": [
+                    { "rel": "http://webfinger.net/rel/profile-page", "href": "https://remote.example/@Bob" },
+                    { "rel": "self", "type": "text/html", "href": "https://remote.example/@Bob" },
+                    { "rel": "SELF", "type": "application/ld+json; profile=\"https://www.w3.org/ns/activitystreams\"", "href": "https://remote.example/users/bob" },
+                ],
+            })
+            .to_string(),
+        )]));
+
+        let bob = Acct::parse("@Bob@remote.example").unwrap();
+        assert_eq!(
+            resolve_self(&client, &bob).await,
+            Ok("https://remote.example/users/bob".to_string())
+        );
+        let jrd: Jrd = super::fetch(&client, "bob@remote.example").await.unwrap();
+        assert_eq!(jrd.subject_acct(), Some(bob));
+
+        let carol = Acct::parse("carol@remote.example").unwrap();
+        assert_eq!(
+            resolve_self(&client, &carol).await,
+            Err(Error::HttpStatus(404))
+        );
+    }
+}
@@ -0,0 +1,305 @@
+//! Activities, as `IActivity` and its subtypes of `type.ts`
+
+use serde::{Deserialize, Serialize};
+
+use super::actor::Actor;
+use super::object::{Emoji, Object, Tag};
+use super::value::{deserialize_type, ApObject, Lenient, OneOrMany, Reference};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ActivityType {
+    Create,
+    Update,
+    Delete,
+    Follow,
+    Accept,
+    Reject,
+    Undo,
+    Like,
+    /// `EmojiReact` of Pleroma, also sent as `EmojiReaction` by some.
+    #[serde(alias = "EmojiReaction")]
+    EmojiReact,
+    Announce,
+    Move,
+    Flag,
+    Add,
+    Remove,
+    Block,
+    Read,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Activity {
+    #[serde(rename = "@context", default, skip_serializing_if = "Option::is_none")]
+    pub context: Option<serde_json::Value>,
+    #[serde(rename = "type", deserialize_with = "deserialize_type")]
+    pub kind: ActivityType,
+    /// Missing in transient activities, e.g. `Like` from some servers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub actor: Reference<Actor>,
+    /// An array only in `Flag`, which refers to the reported user and notes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub object: Option<OneOrMany<Reference<Object>>>,
+    /// The new actor of `Move`, or the collection of `Add` and `Remove`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<Reference<Object>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to: Option<OneOrMany<Reference<serde_json::Value>>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cc: Option<OneOrMany<Reference<serde_json::Value>>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub published: Option<String>,
+    /// Comment of `Flag`, or the reaction of `Like` and `EmojiReact`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+    #[serde(
+        rename = "_misskey_reaction",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub misskey_reaction: Option<String>,
+    /// Custom emoji of the reaction.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag: Option<OneOrMany<Lenient<Tag>>>,
+    /// Linked Data Signature, which is not verified.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<serde_json::Value>,
+}
+
+impl ApObject for Activity {
+    fn ap_id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+}
+
+impl Activity {
+    /// Returns the activity with only `type` and `actor`.
+    pub fn new(kind: ActivityType, actor: String) -> Self {
+        Self {
+            context: None,
+            kind,
+            id: None,
+            actor: Reference::Id(actor),
+            object: None,
+            target: None,
+            to: None,
+            cc: None,
+            published: None,
+            content: None,
+            misskey_reaction: None,
+            tag: None,
+            signature: None,
+        }
+    }
+
+    pub fn actor_id(&self) -> Option<&str> {
+        self.actor.id()
+    }
+
+    /// Returns the first object, which is the only one except in `Flag`.
+    pub fn object(&self) -> Option<&Reference<Object>> {
+        self.object.as_ref().and_then(OneOrMany::first)
+    }
+
+    pub fn object_id(&self) -> Option<&str> {
+        self.object().and_then(Reference::id)
+    }
+
+    pub fn object_ids(&self) -> Vec<&str> {
+        self.object.as_ref().map(OneOrMany::ids).unwrap_or_default()
+    }
+
+    /// Returns the reaction of `Like` and `EmojiReact`, as `_misskey_reaction`
+    /// or `content`.
+    pub fn reaction(&self) -> Option<&str> {
+        self.misskey_reaction.as_deref().or(self.content.as_deref())
+    }
+
+    pub fn emojis(&self) -> impl Iterator<Item = &Emoji> {
+        self.tag
+            .iter()
+            .flat_map(OneOrMany::iter)
+            .filter_map(|tag| match tag {
+                Lenient::Known(Tag::Emoji(emoji)) => Some(emoji),
+                _ => None,
+            })
+    }
+}
+
+#[cfg(test)]
+mod unit_test {
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    use super::{Activity, ActivityType};
+    use crate::activitypub::note::NoteType;
+    use crate::activitypub::object::Object;
+
+    #[test]
+    fn create() {
+        let activity: Activity = serde_json::from_value(json!({
+            "@context": "https://www.w3.org/ns/activitystreams",
+            "id": "https://mastodon.example/users/alice/statuses/1/activity",
+            "type": "Create",
+            "actor": "https://mastodon.example/users/alice",
+            "published": "2023-06-01T00:00:00Z",
+            "to": ["https://www.w3.org/ns/activitystreams#Public"],
+            "object": {
+                "id": "https://mastodon.example/users/alice/statuses/1",
+                "type": "Note",
+                "attributedTo": "https://mastodon.example/users/alice",
+                "content": "<p>hi</p>"
+            },
+            "signature": { "type": "RsaSignature2017", "signatureValue": "..." }
+        }))
+        .unwrap();
+
+        assert_eq!(activity.kind, ActivityType::Create);
+        assert_eq!(
+            activity.actor_id(),
+            Some("https://mastodon.example/users/alice")
+        );
+        assert_eq!(
+            activity.object_id(),
+            Some("https://mastodon.example/users/alice/statuses/1")
+        );
+        match activity.object().and_then(|o| o.object()) {
+            Some(Object::Note(note)) => assert_eq!(note.kind, NoteType::Note),
+            object => panic!("unexpected object: {:?}", object),
+        }
+    }
+
+    #[test]
+    fn undo_follow() {
+        let activity: Activity = serde_json::from_value(json!({
+            "id": "https://misskey.example/follows/1/undo",
+            "type": "Undo",
+            "actor": { "id": "https://misskey.example/users/9", "type": "Person" },
+            "object": {
+                "id": "https://misskey.example/follows/1",
+                "type": "Follow",
+                "actor": "https://misskey.example/users/9",
+                "object": "https://mastodon.example/users/alice"
+            }
+        }))
+        .unwrap();
+
+        assert_eq!(activity.actor_id(), Some("https://misskey.example/users/9"));
+        match activity.object().and_then(|o| o.object()) {
+            Some(Object::Activity(follow)) => {
+                assert_eq!(follow.kind, ActivityType::Follow);
+                assert_eq!(
+                    follow.object_id(),
+                    Some("https://mastodon.example/users/alice")
+                );
+            }
+            object => panic!("unexpected object: {:?}", object),
+        }
+    }
+
+    #[test]
+    fn reactions() {
+        let like: Activity = serde_json::from_value(json!({
+            "type": "Like",
+            "actor": "https://misskey.example/users/9",
+            "object": "https://mastodon.example/users/alice/statuses/1",
+            "content": ":blobcat:",
+            "_misskey_reaction": ":blobcat:",
+            "tag": [{
+                "type": "Emoji",
+                "name": ":blobcat:",
+                "icon": { "type": "Image", "url": "https://misskey.example/blobcat.png" }
+            }]
+        }))
+        .unwrap();
+        assert_eq!(like.id, None);
+        assert_eq!(like.reaction(), Some(":blobcat:"));
+        assert_eq!(like.emojis().count(), 1);
+
+        let react: Activity = serde_json::from_value(json!({
+            "id": "https://pleroma.example/activities/1",
+            "type": "EmojiReaction",
+            "actor": "https://pleroma.example/users/carol",
+            "object": "https://mastodon.example/users/alice/statuses/1",
+            "content": "👍"
+        }))
+        .unwrap();
+        assert_eq!(react.kind, ActivityType::EmojiReact);
+        assert_eq!(react.reaction(), Some("👍"));
+    }
+
+    #[test]
+    fn flag_and_move() {
+        let flag: Activity = serde_json::from_value(json!({
+            "id": "https://mastodon.example/reports/1",
+            "type": "Flag",
+            "actor": "https://mastodon.example/actor",
+            "content": "spam",
+            "object": [
+                "https://misskey.example/users/9",
+                "https://misskey.example/notes/9"
+            ]
+        }))
+        .unwrap();
+        assert_eq!(
+            flag.object_ids(),
+            vec![
+                "https://misskey.example/users/9",
+                "https://misskey.example/notes/9"
+            ]
+        );
+
+        let move_: Activity = serde_json::from_value(json!({
+            "id": "https://old.example/users/a#moves/1",
+            "type": "Move",
+            "actor": "https://old.example/users/a",
+            "object": "https://old.example/users/a",
+            "target": "https://new.example/users/a"
+        }))
+        .unwrap();
+        assert_eq!(move_.kind, ActivityType::Move);
+        assert_eq!(
+            move_.target.as_ref().and_then(|t| t.id()),
+            Some("https://new.example/users/a")
+        );
+    }
+
+    #[test]
+    fn objects() {
+        let delete: Activity = serde_json::from_value(json!({
+            "id": "https://mastodon.example/users/alice/statuses/1#delete",
+            "type": "Delete",
+            "actor": "https://mastodon.example/users/alice",
+            "object": {
+                "id": "https://mastodon.example/users/alice/statuses/1",
+                "type": "Tombstone",
+                "atomUri": "https://mastodon.example/users/alice/statuses/1"
+            }
+        }))
+        .unwrap();
+        assert!(matches!(
+            delete.object().and_then(|o| o.object()),
+            Some(Object::Tombstone(_))
+        ));
+
+        let update: Activity = serde_json::from_value(json!({
+            "type": "Update",
+            "actor": "https://example.com/actor",
+            "object": { "id": "https://example.com/unknown", "type": "ChatMessage" }
+        }))
+        .unwrap();
+        assert!(matches!(
+            update.object().and_then(|o| o.object()),
+            Some(Object::Other(_))
+        ));
+        assert_eq!(update.object_id(), Some("https://example.com/unknown"));
+
+        assert!(serde_json::from_value::<Activity>(json!({
+            "type": "Unknown",
+            "actor": "https://example.com/actor",
+        }))
+        .is_err());
+    }
+}
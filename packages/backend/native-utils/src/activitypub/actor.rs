@@ -0,0 +1,285 @@
+//! Actors, as `IActor` of `type.ts`
+
+use serde::{Deserialize, Serialize};
+
+use super::object::{Attachment, Collection, Emoji, Image, Link, Tag};
+use super::value::{deserialize_type, ApObject, Lenient, OneOrMany, Reference};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ActorType {
+    Person,
+    Service,
+    Group,
+    Organization,
+    Application,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Actor {
+    #[serde(rename = "@context", default, skip_serializing_if = "Option::is_none")]
+    pub context: Option<serde_json::Value>,
+    #[serde(rename = "type", deserialize_with = "deserialize_type")]
+    pub kind: ActorType,
+    pub id: String,
+    /// Missing only in actors embedded in other objects, which have to be
+    /// fetched to be used.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inbox: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub outbox: Option<Reference<Collection>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shared_inbox: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub endpoints: Option<Endpoints>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub followers: Option<Reference<Collection>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub following: Option<Reference<Collection>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub featured: Option<Reference<Collection>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preferred_username: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Bio in HTML.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary: Option<String>,
+    /// Bio in MFM.
+    #[serde(
+        rename = "_misskey_summary",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub misskey_summary: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<OneOrMany<Reference<Link>>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub icon: Option<OneOrMany<Image>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image: Option<OneOrMany<Image>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public_key: Option<OneOrMany<PublicKey>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub manually_approves_followers: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub discoverable: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub indexable: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub is_cat: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub speak_as_cat: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub moved_to: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub also_known_as: Option<OneOrMany<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub published: Option<String>,
+    #[serde(
+        rename = "vcard:bday",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub birthday: Option<String>,
+    #[serde(
+        rename = "vcard:Address",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub location: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag: Option<OneOrMany<Lenient<Tag>>>,
+    /// Profile fields.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attachment: Option<OneOrMany<Lenient<Attachment>>>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Endpoints {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shared_inbox: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PublicKey {
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<String>,
+    pub id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    pub public_key_pem: String,
+}
+
+impl ApObject for Actor {
+    fn ap_id(&self) -> Option<&str> {
+        Some(&self.id)
+    }
+}
+
+impl Actor {
+    /// Returns `endpoints.sharedInbox`, or the legacy `sharedInbox`.
+    pub fn shared_inbox(&self) -> Option<&str> {
+        self.endpoints
+            .as_ref()
+            .and_then(|e| e.shared_inbox.as_deref())
+            .or(self.shared_inbox.as_deref())
+    }
+
+    /// Returns the first key, which is the one used to sign requests.
+    pub fn public_key(&self) -> Option<&PublicKey> {
+        self.public_key.as_ref().and_then(OneOrMany::first)
+    }
+
+    pub fn is_bot(&self) -> bool {
+        matches!(self.kind, ActorType::Service | ActorType::Application)
+    }
+
+    pub fn avatar_url(&self) -> Option<&str> {
+        self.icon
+            .as_ref()
+            .and_then(OneOrMany::first)
+            .and_then(Image::url)
+    }
+
+    pub fn banner_url(&self) -> Option<&str> {
+        self.image
+            .as_ref()
+            .and_then(OneOrMany::first)
+            .and_then(Image::url)
+    }
+
+    pub fn emojis(&self) -> impl Iterator<Item = &Emoji> {
+        self.tag
+            .iter()
+            .flat_map(OneOrMany::iter)
+            .filter_map(|tag| match tag {
+                Lenient::Known(Tag::Emoji(emoji)) => Some(emoji),
+                _ => None,
+            })
+    }
+
+    /// Returns the profile fields as pairs of the name and the HTML value.
+    pub fn fields(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.attachment
+            .iter()
+            .flat_map(OneOrMany::iter)
+            .filter_map(Lenient::known)
+            .filter(|a| a.is_property_value())
+            .filter_map(|a| Some((a.name.as_deref()?, a.value.as_deref()?)))
+    }
+}
+
+#[cfg(test)]
+mod unit_test {
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    use super::{Actor, ActorType};
+
+    #[test]
+    fn mastodon_person() {
+        let actor: Actor = serde_json::from_value(json!({
+            "@context": [
+                "https://www.w3.org/ns/activitystreams",
+                "https://w3id.org/security/v1",
+                { "manuallyApprovesFollowers": "as:manuallyApprovesFollowers" }
+            ],
+            "id": "https://mastodon.example/users/alice",
+            "type": "Person",
+            "following": "https://mastodon.example/users/alice/following",
+            "followers": "https://mastodon.example/users/alice/followers",
+            "inbox": "https://mastodon.example/users/alice/inbox",
+            "outbox": "https://mastodon.example/users/alice/outbox",
+            "featured": "https://mastodon.example/users/alice/collections/featured",
+            "preferredUsername": "alice",
+            "name": "Alice",
+            "summary": "<p>hi</p>",
+            "url": "https://mastodon.example/@alice",
+            "manuallyApprovesFollowers": false,
+            "discoverable": true,
+            "published": "2023-01-01T00:00:00Z",
+            "publicKey": {
+                "id": "https://mastodon.example/users/alice#main-key",
+                "owner": "https://mastodon.example/users/alice",
+                "publicKeyPem": "-----BEGIN PUBLIC KEY-----\n...\n-----END PUBLIC KEY-----\n"
+            },
+            "tag": [],
+            "attachment": [
+                { "type": "PropertyValue", "name": "Site", "value": "<a href=\"https://example.com\">example.com</a>" }
+            ],
+            "endpoints": { "sharedInbox": "https://mastodon.example/inbox" },
+            "icon": { "type": "Image", "mediaType": "image/png", "url": "https://mastodon.example/avatar.png" }
+        }))
+        .unwrap();
+
+        assert_eq!(actor.kind, ActorType::Person);
+        assert_eq!(actor.shared_inbox(), Some("https://mastodon.example/inbox"));
+        assert_eq!(
+            actor.public_key().map(|k| k.id.as_str()),
+            Some("https://mastodon.example/users/alice#main-key")
+        );
+        assert_eq!(
+            actor.followers.as_ref().and_then(|f| f.id()),
+            Some("https://mastodon.example/users/alice/followers")
+        );
+        assert_eq!(
+            actor.avatar_url(),
+            Some("https://mastodon.example/avatar.png")
+        );
+        assert_eq!(actor.banner_url(), None);
+        assert_eq!(
+            actor.fields().collect::<Vec<_>>(),
+            vec![("Site", "<a href=\"https://example.com\">example.com</a>")]
+        );
+        assert!(!actor.is_bot());
+    }
+
+    #[test]
+    fn variations() {
+        let actor: Actor = serde_json::from_value(json!({
+            "id": "https://example.com/actor",
+            "type": ["Service", "as:Application"],
+            "inbox": "https://example.com/actor/inbox",
+            "sharedInbox": "https://example.com/inbox",
+            "publicKey": [
+                { "id": "https://example.com/actor#rsa", "publicKeyPem": "rsa" },
+                { "id": "https://example.com/actor#ed25519", "publicKeyPem": "ed25519" }
+            ],
+            "icon": [{ "url": { "type": "Link", "href": "https://example.com/a.png" } }],
+            "url": [{ "type": "Link", "href": "https://example.com/@actor", "mediaType": "text/html" }],
+            "followers": { "id": "https://example.com/actor/followers", "type": "OrderedCollection", "totalItems": 3 },
+            "alsoKnownAs": "https://old.example/actor",
+            "attachment": { "type": "PropertyValue", "name": "a" }
+        }))
+        .unwrap();
+
+        assert_eq!(actor.kind, ActorType::Service);
+        assert!(actor.is_bot());
+        assert_eq!(actor.shared_inbox(), Some("https://example.com/inbox"));
+        assert_eq!(
+            actor.public_key().map(|k| k.public_key_pem.as_str()),
+            Some("rsa")
+        );
+        assert_eq!(actor.avatar_url(), Some("https://example.com/a.png"));
+        assert_eq!(
+            actor.url.as_ref().and_then(|u| u.first_id()),
+            Some("https://example.com/@actor")
+        );
+        assert_eq!(
+            actor
+                .followers
+                .as_ref()
+                .and_then(|f| f.object())
+                .and_then(|f| f.total_items),
+            Some(3)
+        );
+        assert_eq!(
+            actor.also_known_as.clone().map(|a| a.into_vec()),
+            Some(vec!["https://old.example/actor".to_string()])
+        );
+        assert_eq!(actor.fields().count(), 0);
+    }
+}
@@ -0,0 +1,526 @@
+//! Delivery of activities to remote inboxes, ported from
+//! `remote/activitypub/deliver-manager.ts` and `queue/processors/deliver.ts`
+//!
+//! Each call makes one attempt per inbox. A failed attempt results in
+//! [Outcome::Retry] with the delay of exponential backoff, after which the
+//! caller, e.g. the job queue, delivers again, until the inbox accepts the
+//! activity, rejects it with a 4xx status, or the attempts run out. Every
+//! attempt updates the health columns of `instance` on a best-effort basis.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::Utc;
+use rand::{thread_rng, Rng};
+use sea_orm::sea_query::OnConflict;
+use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, IntoActiveModel, QueryFilter, Set};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+use url::Url;
+
+use super::error::Error;
+use super::http_signature::{create_signed_post, Headers, SigningKey};
+use super::policy::get_policy;
+use crate::acct::to_puny;
+use crate::config::Config;
+use crate::database;
+use crate::model::entity::{following, instance, user, user_keypair};
+use crate::util::http::{HttpClient, Method, Request};
+use crate::util::id::create_id;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DeliverConfig {
+    /// Attempts per inbox, including the first one.
+    pub max_attempts: u32,
+    /// Delay before the first retry, which doubles on each retry.
+    pub base_delay: Duration,
+    pub max_backoff: Duration,
+    /// Inboxes delivered to at the same time.
+    pub concurrency: usize,
+}
+
+impl Default for DeliverConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: 12,
+            base_delay: Duration::from_secs(60),
+            max_backoff: Duration::from_secs(8 * 60 * 60),
+            concurrency: 128,
+        }
+    }
+}
+
+impl DeliverConfig {
+    /// Reads `deliverJobMaxAttempts` and `deliverJobConcurrency`.
+    pub fn from_config(config: &Config) -> Self {
+        let default = Self::default();
+        Self {
+            max_attempts: config
+                .deliver_job_max_attempts
+                .filter(|n| *n > 0)
+                .unwrap_or(default.max_attempts),
+            concurrency: config
+                .deliver_job_concurrency
+                .filter(|n| *n > 0)
+                .map_or(default.concurrency, |n| n as usize),
+            ..default
+        }
+    }
+}
+
+/// Returns the delay after `attempts_made` failed attempts, as `apBackoff`
+/// does, with up to 20% of jitter.
+pub fn backoff(attempts_made: u32, config: &DeliverConfig) -> Duration {
+    let factor = (1u64 << attempts_made.min(32)) - 1;
+    let delay = config
+        .base_delay
+        .saturating_mul(u32::try_from(factor).unwrap_or(u32::MAX))
+        .min(config.max_backoff);
+    delay + delay.mul_f64(thread_rng().gen_range(0.0..0.2))
+}
+
+/// Returns the host of the inbox in punycode.
+pub(super) fn inbox_host(inbox: &str) -> Option<String> {
+    let url = Url::parse(inbox).ok()?;
+    let host = match (url.host_str(), url.port()) {
+        (Some(host), Some(port)) => format!("{}:{}", host, port),
+        (Some(host), None) => host.to_string(),
+        (None, _) => return None,
+    };
+    to_puny(&host).ok()
+}
+
+/// Recipients of an activity.
+#[derive(Clone, Debug, Default)]
+pub struct Recipients {
+    /// Whether to deliver to the remote followers of the actor.
+    pub followers: bool,
+    /// Remote users to deliver to.
+    pub direct: Vec<user::Model>,
+}
+
+/// Collapses the recipients into unique inboxes. Shared inboxes are used
+/// whenever known, so that a server receives the activity only once.
+pub fn collect_inboxes(followers: &[following::Model], direct: &[user::Model]) -> Vec<String> {
+    let mut seen = HashSet::new();
+    let mut inboxes = Vec::new();
+    let mut add = |inbox: &str| {
+        if seen.insert(inbox.to_string()) {
+            inboxes.push(inbox.to_string());
+        }
+    };
+
+    for following in followers {
+        let inbox = following
+            .follower_shared_inbox
+            .as_deref()
+            .filter(|i| !i.is_empty())
+            .or(following.follower_inbox.as_deref());
+        if let Some(inbox) = inbox {
+            add(inbox);
+        }
+    }
+    let shared: HashSet<String> = followers
+        .iter()
+        .filter_map(|f| f.follower_shared_inbox.to_owned())
+        .collect();
+    for user in direct {
+        if user.host.is_none()
+            || user
+                .shared_inbox
+                .as_ref()
+                .is_some_and(|s| shared.contains(s))
+        {
+            continue;
+        }
+        if let Some(inbox) = &user.inbox {
+            add(inbox);
+        }
+    }
+
+    inboxes
+}
+
+/// Returns the hosts to skip, which the [FederationPolicy] does not deliver
+/// to, or which are suspended.
+///
+/// [FederationPolicy]: super::policy::FederationPolicy
+pub async fn skipped_hosts(hosts: &[String]) -> Result<HashSet<String>, Error> {
+    let db = database::get_database()?;
+    let policy = get_policy().await?;
+
+    let mut skipped: HashSet<String> = hosts
+        .iter()
+        .filter(|host| !policy.can_deliver(host))
+        .cloned()
+        .collect();
+    let rest: Vec<&String> = hosts.iter().filter(|h| !skipped.contains(*h)).collect();
+    if !rest.is_empty() {
+        let suspended = instance::Entity::find()
+            .filter(instance::Column::Host.is_in(rest))
+            .filter(instance::Column::IsSuspended.eq(true))
+            .all(db)
+            .await?;
+        skipped.extend(suspended.into_iter().map(|i| i.host));
+    }
+
+    Ok(skipped)
+}
+
+/// Returns the instance of the host, registering it if it is new, as
+/// `registerOrFetchInstanceDoc` does.
+pub(super) async fn register_instance(host: &str) -> Result<instance::Model, Error> {
+    let db = database::get_database()?;
+    let find = || {
+        instance::Entity::find()
+            .filter(instance::Column::Host.eq(host))
+            .one(db)
+    };
+    if let Some(model) = find().await? {
+        return Ok(model);
+    }
+
+    // Another task may register the host at the same time, in which case
+    // its row is kept.
+    let now = Utc::now().fixed_offset();
+    instance::Entity::insert(
+        instance::Model {
+            id: create_id(0)?,
+            host: host.to_string(),
+            caught_at: now,
+            last_communicated_at: now,
+            ..Default::default()
+        }
+        .into_active_model()
+        .reset_all(),
+    )
+    .on_conflict(
+        OnConflict::column(instance::Column::Host)
+            .do_nothing()
+            .to_owned(),
+    )
+    .exec_without_returning(db)
+    .await?;
+    find().await?.ok_or(Error::NotFound)
+}
+
+/// Records the result of a request to the host, as the deliver processor
+/// does.
+async fn update_instance(host: &str, status: Option<u16>, ok: bool) -> Result<(), Error> {
+    let db = database::get_database()?;
+    let now = Utc::now().fixed_offset();
+    let mut active = register_instance(host).await?.into_active_model();
+    active.latest_request_sent_at = Set(Some(now));
+    active.latest_status = Set(status.map(i32::from));
+    active.is_not_responding = Set(!ok);
+    if ok {
+        active.last_communicated_at = Set(now);
+    }
+    active.update(db).await?;
+    Ok(())
+}
+
+/// Result of the delivery to an inbox.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Delivered,
+    /// The host is blocked or suspended, or the inbox is invalid.
+    Skipped,
+    /// The inbox answered with a 4xx status, which retries do not change.
+    Rejected(u16),
+    /// The attempt failed. Deliver again after `delay`, passing
+    /// `attempts_made` to [Deliverer::deliver].
+    Retry {
+        attempts_made: u32,
+        delay: Duration,
+        reason: String,
+    },
+    /// The attempts ran out. Has the reason of the last failure.
+    GaveUp(String),
+    /// The activity could not be sent, e.g. it could not be signed.
+    Failed(String),
+}
+
+enum Attempt {
+    Success,
+    ClientError(u16),
+    Retry(String),
+}
+
+pub struct Deliverer {
+    client: Arc<dyn HttpClient>,
+    config: DeliverConfig,
+}
+
+impl Deliverer {
+    pub fn new(client: Arc<dyn HttpClient>, config: DeliverConfig) -> Self {
+        Self { client, config }
+    }
+
+    async fn attempt(
+        &self,
+        key: &SigningKey,
+        inbox: &str,
+        host: &str,
+        body: &str,
+    ) -> Result<Attempt, Error> {
+        let signed = create_signed_post(key, inbox, body, &Headers::new())?;
+        let request = Request {
+            method: Method::Post,
+            url: inbox.to_string(),
+            headers: signed.headers,
+            body: Some(body.to_string()),
+        };
+        let (status, attempt) = match self.client.send(request).await {
+            Ok(r) if r.is_success() => (Some(r.status), Attempt::Success),
+            Ok(r) if (400..500).contains(&r.status) => {
+                (Some(r.status), Attempt::ClientError(r.status))
+            }
+            Ok(r) => (
+                Some(r.status),
+                Attempt::Retry(format!("status {}", r.status)),
+            ),
+            Err(e) => (None, Attempt::Retry(e.to_string())),
+        };
+        // The delivery itself is done, whether or not the health is recorded.
+        let _ = update_instance(host, status, matches!(attempt, Attempt::Success)).await;
+        Ok(attempt)
+    }
+
+    async fn deliver_to_host(
+        &self,
+        key: &SigningKey,
+        inbox: &str,
+        host: &str,
+        body: &str,
+        attempts_made: u32,
+    ) -> Result<Outcome, Error> {
+        Ok(match self.attempt(key, inbox, host, body).await? {
+            Attempt::Success => Outcome::Delivered,
+            Attempt::ClientError(status) => Outcome::Rejected(status),
+            Attempt::Retry(reason) => {
+                let attempts_made = attempts_made + 1;
+                match attempts_made >= self.config.max_attempts {
+                    true => Outcome::GaveUp(reason),
+                    false => Outcome::Retry {
+                        attempts_made,
+                        delay: backoff(attempts_made, &self.config),
+                        reason,
+                    },
+                }
+            }
+        })
+    }
+
+    /// Makes an attempt to deliver the body to the inbox. `attempts_made`
+    /// is `0` for the first attempt, and the one in [Outcome::Retry] for
+    /// the following ones.
+    pub async fn deliver(
+        &self,
+        key: &SigningKey,
+        inbox: &str,
+        body: &str,
+        attempts_made: u32,
+    ) -> Result<Outcome, Error> {
+        let Some(host) = inbox_host(inbox) else {
+            return Ok(Outcome::Skipped);
+        };
+        if !skipped_hosts(&[host.to_owned()]).await?.is_empty() {
+            return Ok(Outcome::Skipped);
+        }
+        self.deliver_to_host(key, inbox, &host, body, attempts_made)
+            .await
+    }
+
+    /// Makes the first attempts to deliver the body to the inboxes
+    /// concurrently, and returns the outcomes in the order of `inboxes`. An error of one delivery is
+    /// reported as [Outcome::Failed] and does not stop the others.
+    pub async fn deliver_all(
+        self: &Arc<Self>,
+        key: SigningKey,
+        inboxes: Vec<String>,
+        body: String,
+    ) -> Result<Vec<(String, Outcome)>, Error> {
+        let hosts: Vec<Option<String>> = inboxes.iter().map(|i| inbox_host(i)).collect();
+        let unique: Vec<String> = hosts
+            .iter()
+            .flatten()
+            .cloned()
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        let skipped = skipped_hosts(&unique).await?;
+
+        let key = Arc::new(key);
+        let body = Arc::new(body);
+        let semaphore = Arc::new(Semaphore::new(self.config.concurrency.max(1)));
+        let mut outcomes: Vec<Option<Outcome>> = vec![None; inboxes.len()];
+        let mut tasks = JoinSet::new();
+        for (i, (inbox, host)) in inboxes.iter().zip(hosts).enumerate() {
+            let host = match host {
+                Some(host) if !skipped.contains(&host) => host,
+                _ => {
+                    outcomes[i] = Some(Outcome::Skipped);
+                    continue;
+                }
+            };
+            let (this, key, body, semaphore) = (
+                Arc::clone(self),
+                Arc::clone(&key),
+                Arc::clone(&body),
+                Arc::clone(&semaphore),
+            );
+            let inbox = inbox.to_owned();
+            tasks.spawn(async move {
+                let _permit = semaphore.acquire_owned().await;
+                (i, this.deliver_to_host(&key, &inbox, &host, &body, 0).await)
+            });
+        }
+
+        let mut panic = None;
+        while let Some(joined) = tasks.join_next().await {
+            match joined {
+                Ok((i, outcome)) => {
+                    outcomes[i] = Some(outcome.unwrap_or_else(|e| Outcome::Failed(e.to_string())))
+                }
+                Err(e) => panic = panic.or(Some(e.into_panic())),
+            }
+        }
+        if let Some(panic) = panic {
+            std::panic::resume_unwind(panic);
+        }
+
+        Ok(inboxes
+            .into_iter()
+            .zip(outcomes)
+            .map(|(inbox, outcome)| (inbox, outcome.unwrap_or(Outcome::Skipped)))
+            .collect())
+    }
+}
+
+/// Delivers the activity of the local user to the recipients, as
+/// `DeliverManager.execute` does. `url` is the origin of our server.
+pub async fn deliver_activity(
+    deliverer: &Arc<Deliverer>,
+    url: &str,
+    actor_id: &str,
+    activity: &serde_json::Value,
+    recipients: &Recipients,
+) -> Result<Vec<(String, Outcome)>, Error> {
+    let db = database::get_database()?;
+    let actor = user::Entity::find_by_id(actor_id.to_string())
+        .one(db)
+        .await?
+        .ok_or(Error::NotFound)?;
+    if actor.host.is_some() {
+        return Ok(Vec::new());
+    }
+    let keypair = user_keypair::Entity::find_by_id(actor_id.to_string())
+        .one(db)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    let followers = match recipients.followers {
+        true => {
+            following::Entity::find()
+                .filter(following::Column::FolloweeId.eq(actor_id))
+                .filter(following::Column::FollowerHost.is_not_null())
+                .all(db)
+                .await?
+        }
+        false => Vec::new(),
+    };
+    let inboxes = collect_inboxes(&followers, &recipients.direct);
+
+    deliverer
+        .deliver_all(
+            SigningKey::from_keypair(url, &keypair),
+            inboxes,
+            activity.to_string(),
+        )
+        .await
+}
+
+#[cfg(test)]
+mod unit_test {
+    use std::time::Duration;
+
+    use pretty_assertions::assert_eq;
+
+    use super::{backoff, collect_inboxes, inbox_host, DeliverConfig};
+    use crate::model::entity::{following, user};
+
+    #[test]
+    fn delay() {
+        let config = DeliverConfig::default();
+        let minutes = |attempts| backoff(attempts, &config).as_secs_f64() / 60.0;
+        assert!((1.0..1.2).contains(&minutes(1)));
+        assert!((7.0..8.4).contains(&minutes(3)));
+        assert!((480.0..576.0).contains(&minutes(10)));
+        assert!((480.0..576.0).contains(&minutes(100)));
+        assert_eq!(backoff(0, &config), Duration::ZERO);
+    }
+
+    #[test]
+    fn hosts() {
+        assert_eq!(
+            inbox_host("https://ミスキー.example/inbox").as_deref(),
+            Some("xn--nckxa7i0e.example")
+        );
+        assert_eq!(
+            inbox_host("http://127.0.0.1:3000/inbox").as_deref(),
+            Some("127.0.0.1:3000")
+        );
+        assert_eq!(inbox_host("not a url"), None);
+    }
+
+    #[test]
+    fn inboxes() {
+        let following = |inbox: &str, shared: Option<&str>| following::Model {
+            follower_host: Some("remote.example".to_string()),
+            follower_inbox: Some(inbox.to_string()),
+            follower_shared_inbox: shared.map(str::to_string),
+            ..Default::default()
+        };
+        let followers = vec![
+            following(
+                "https://a.example/users/1/inbox",
+                Some("https://a.example/inbox"),
+            ),
+            following(
+                "https://a.example/users/2/inbox",
+                Some("https://a.example/inbox"),
+            ),
+            following("https://b.example/users/1/inbox", None),
+        ];
+        let direct = vec![
+            user::Model {
+                host: Some("a.example".to_string()),
+                inbox: Some("https://a.example/users/3/inbox".to_string()),
+                shared_inbox: Some("https://a.example/inbox".to_string()),
+                ..Default::default()
+            },
+            user::Model {
+                host: Some("c.example".to_string()),
+                inbox: Some("https://c.example/users/1/inbox".to_string()),
+                shared_inbox: Some("https://c.example/inbox".to_string()),
+                ..Default::default()
+            },
+            user::Model {
+                host: Some("d.example".to_string()),
+                ..Default::default()
+            },
+        ];
+
+        assert_eq!(
+            collect_inboxes(&followers, &direct),
+            vec![
+                "https://a.example/inbox",
+                "https://b.example/users/1/inbox",
+                "https://c.example/users/1/inbox",
+            ]
+        );
+        assert_eq!(collect_inboxes(&[], &[]), Vec::<String>::new());
+    }
+}
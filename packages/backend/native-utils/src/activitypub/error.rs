@@ -0,0 +1,51 @@
+use crate::impl_into_napi_error;
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum Error {
+    #[error("Invalid url: {0}")]
+    InvalidUrl(String),
+    #[error("Invalid key: {0}")]
+    InvalidKey(String),
+    #[error("Missing header: {0}")]
+    MissingHeader(String),
+    #[error("Invalid signature header: {0}")]
+    InvalidSignatureHeader(String),
+    #[error("Header `{0}` is not signed")]
+    UnsignedHeader(String),
+    #[error("Unsupported algorithm: {0}")]
+    UnsupportedAlgorithm(String),
+    #[error("Clock skew of the request is too large: {0}s")]
+    ClockSkew(i64),
+    #[error("Signature has expired")]
+    SignatureExpired,
+    #[error("Failed to create id: {0}")]
+    IdError(#[from] crate::util::id::Error),
+    #[error("Failed to get database connection: {0}")]
+    DbConnError(#[from] crate::database::error::Error),
+    #[error("Database operation error: {0}")]
+    DbOperationError(#[from] sea_orm::DbErr),
+    #[error("HTTP request failed: {0}")]
+    HttpError(#[from] crate::util::http::Error),
+    #[error("Remote server responded with status {0}")]
+    HttpStatus(u16),
+    #[error("Invalid object: {0}")]
+    InvalidObject(String),
+    #[error("Host is blocked: {0}")]
+    BlockedHost(String),
+    #[error("Object refers to itself: {0}")]
+    CircularReference(String),
+    #[error("Too many objects fetched")]
+    FetchLimitExceeded,
+    #[error("References are nested too deeply")]
+    RecursionLimitExceeded,
+    #[error("Requested entity not found")]
+    NotFound,
+}
+
+impl From<url::ParseError> for Error {
+    fn from(value: url::ParseError) -> Self {
+        Self::InvalidUrl(value.to_string())
+    }
+}
+
+impl_into_napi_error!(Error);
@@ -0,0 +1,530 @@
+//! Metadata of remote instances, ported from
+//! `services/fetch-instance-metadata.ts`
+//!
+//! NodeInfo, the HTML head of the top page, and `manifest.json` are fetched
+//! at the same time. Each of them is optional, so that a server which fails
+//! or times out on one still gets the others recorded.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::Utc;
+use scraper::{Html, Selector};
+use sea_orm::{ActiveModelTrait, IntoActiveModel, Set};
+use serde_json::Value;
+use url::Url;
+
+use super::deliver::register_instance;
+use super::error::Error;
+use crate::acct::webfinger::Jrd;
+use crate::database;
+use crate::model::entity::instance;
+use crate::nodeinfo::Version;
+use crate::util::http::{self, HttpClient, Request, Response};
+
+const NODEINFO_1_0: &str = "http://nodeinfo.diaspora.software/ns/schema/1.0";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InstanceFetchConfig {
+    /// `https`, or `http` for servers in tests.
+    pub scheme: String,
+    /// Time limit of each request.
+    pub timeout: Duration,
+    /// Age after which the metadata is fetched again.
+    pub stale_after: Duration,
+}
+
+impl Default for InstanceFetchConfig {
+    fn default() -> Self {
+        Self {
+            scheme: "https".to_string(),
+            timeout: Duration::from_secs(10),
+            stale_after: Duration::from_secs(24 * 60 * 60),
+        }
+    }
+}
+
+/// Values of `<head>` the metadata is taken from.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Head {
+    pub title: Option<String>,
+    pub site_name: Option<String>,
+    pub description: Option<String>,
+    pub og_description: Option<String>,
+    pub theme_color: Option<String>,
+    /// `href` of `<link>` with `rel` in lower case.
+    pub links: Vec<(String, String)>,
+}
+
+impl Head {
+    pub fn parse(html: &str) -> Self {
+        let document = Html::parse_document(html);
+        let select = |selector: &str| {
+            let selector = Selector::parse(selector).expect("Invalid selector");
+            document
+                .select(&selector)
+                .next()
+                .and_then(|e| e.value().attr("content"))
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+        };
+        let title_selector = Selector::parse("head title").expect("Invalid selector");
+        let link_selector = Selector::parse("link[rel][href]").expect("Invalid selector");
+
+        Self {
+            title: document
+                .select(&title_selector)
+                .next()
+                .map(|e| e.text().collect::<String>().trim().to_string())
+                .filter(|s| !s.is_empty()),
+            site_name: select(r#"meta[property="og:site_name"]"#),
+            description: select(r#"meta[name="description"]"#),
+            og_description: select(r#"meta[property="og:description"]"#),
+            theme_color: select(r#"meta[name="theme-color"]"#),
+            links: document
+                .select(&link_selector)
+                .filter_map(|e| {
+                    let rel = e.value().attr("rel")?.split_whitespace();
+                    let rel = rel.collect::<Vec<_>>().join(" ").to_lowercase();
+                    Some((rel, e.value().attr("href")?.to_string()))
+                })
+                .collect(),
+        }
+    }
+
+    fn link(&self, rels: &[&str]) -> Option<&str> {
+        rels.iter().find_map(|rel| {
+            self.links
+                .iter()
+                .find(|(r, _)| r == rel)
+                .map(|(_, href)| href.as_str())
+        })
+    }
+}
+
+/// Metadata taken from the sources. `None` is not recorded, except the
+/// values of NodeInfo.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct InstanceInfo {
+    /// Whether NodeInfo was found, in which case its values are recorded as
+    /// they are.
+    pub has_nodeinfo: bool,
+    pub software_name: Option<String>,
+    pub software_version: Option<String>,
+    pub open_registrations: Option<bool>,
+    pub maintainer_name: Option<String>,
+    pub maintainer_email: Option<String>,
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub icon_url: Option<String>,
+    pub favicon_url: Option<String>,
+    pub theme_color: Option<String>,
+}
+
+fn str_at(value: Option<&Value>, pointer: &str) -> Option<String> {
+    value?
+        .pointer(pointer)?
+        .as_str()
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+}
+
+/// Resolves `href` against `base`.
+fn resolve(base: &str, href: &str) -> Option<String> {
+    Url::parse(base).ok()?.join(href).ok().map(String::from)
+}
+
+/// Normalizes a CSS colour to `#rrggbb`, as `tinycolor().toHexString()`
+/// does. Only hex and `rgb()` colours are understood.
+pub fn normalize_color(color: &str) -> Option<String> {
+    let color = color.trim().to_lowercase();
+    let rgb: Vec<u8> = if let Some(hex) = color.strip_prefix('#') {
+        if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return None;
+        }
+        match hex.len() {
+            3 | 4 => hex
+                .chars()
+                .take(3)
+                .map(|c| u8::from_str_radix(&c.to_string().repeat(2), 16).ok())
+                .collect::<Option<_>>()?,
+            6 | 8 => (0..6)
+                .step_by(2)
+                .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+                .collect::<Option<_>>()?,
+            _ => return None,
+        }
+    } else {
+        let args = color
+            .strip_prefix("rgba(")
+            .or_else(|| color.strip_prefix("rgb("))?
+            .strip_suffix(')')?;
+        let args: Vec<&str> = args
+            .split([',', ' ', '/'])
+            .filter(|s| !s.is_empty())
+            .collect();
+        if !(3..=4).contains(&args.len()) {
+            return None;
+        }
+        args[..3]
+            .iter()
+            .map(|arg| match arg.strip_suffix('%') {
+                Some(p) => p
+                    .parse::<f64>()
+                    .ok()
+                    .map(|p| (p.clamp(0.0, 100.0) * 2.55).round() as u8),
+                None => arg
+                    .parse::<f64>()
+                    .ok()
+                    .map(|v| v.clamp(0.0, 255.0).round() as u8),
+            })
+            .collect::<Option<_>>()?
+    };
+    Some(format!("#{:02x}{:02x}{:02x}", rgb[0], rgb[1], rgb[2]))
+}
+
+/// Takes the metadata from the sources, preferring NodeInfo over the head
+/// and the head over the manifest. `favicon_url` is found by the caller
+/// since it may need another request.
+pub fn extract(
+    origin: &str,
+    nodeinfo: Option<&Value>,
+    head: Option<&Head>,
+    manifest: Option<&Value>,
+) -> InstanceInfo {
+    let meta = |key: &str| str_at(nodeinfo, &format!("/metadata/{}", key));
+    let icon = str_at(manifest, "/icons/0/src").or_else(|| {
+        head?
+            .link(&["apple-touch-icon", "apple-touch-icon-precomposed", "icon"])
+            .map(str::to_string)
+    });
+
+    InstanceInfo {
+        has_nodeinfo: nodeinfo.is_some(),
+        software_name: str_at(nodeinfo, "/software/name").map(|s| s.to_lowercase()),
+        software_version: str_at(nodeinfo, "/software/version"),
+        open_registrations: nodeinfo
+            .and_then(|info| info.get("openRegistrations"))
+            .and_then(Value::as_bool),
+        maintainer_name: meta("maintainer/name"),
+        maintainer_email: meta("maintainer/email"),
+        name: meta("nodeName")
+            .or_else(|| meta("name"))
+            .or_else(|| head?.site_name.to_owned())
+            .or_else(|| head?.title.to_owned())
+            .or_else(|| str_at(manifest, "/name"))
+            .or_else(|| str_at(manifest, "/short_name")),
+        description: meta("nodeDescription")
+            .or_else(|| meta("description"))
+            .or_else(|| head?.description.to_owned())
+            .or_else(|| head?.og_description.to_owned())
+            .or_else(|| str_at(manifest, "/description")),
+        icon_url: icon.and_then(|href| resolve(origin, &href)),
+        favicon_url: None,
+        theme_color: [
+            meta("themeColor"),
+            head.and_then(|h| h.theme_color.to_owned()),
+            str_at(manifest, "/theme_color"),
+        ]
+        .into_iter()
+        .flatten()
+        .find_map(|color| normalize_color(&color)),
+    }
+}
+
+pub struct InstanceFetcher {
+    client: Arc<dyn HttpClient>,
+    config: InstanceFetchConfig,
+}
+
+impl InstanceFetcher {
+    pub fn new(client: Arc<dyn HttpClient>, config: InstanceFetchConfig) -> Self {
+        Self { client, config }
+    }
+
+    async fn get(&self, url: &str, accept: &str) -> Result<Response, Error> {
+        let response = tokio::time::timeout(
+            self.config.timeout,
+            self.client.send(Request::get(url, accept)),
+        )
+        .await
+        .map_err(|_| http::Error::Timeout)??;
+        match response.is_success() {
+            true => Ok(response),
+            false => Err(Error::HttpStatus(response.status)),
+        }
+    }
+
+    async fn get_json(&self, url: &str) -> Result<Value, Error> {
+        self.get(url, "application/json")
+            .await?
+            .json()
+            .map_err(|e| Error::InvalidObject(e.to_string()))
+    }
+
+    /// Fetches the latest NodeInfo linked from `/.well-known/nodeinfo`.
+    async fn fetch_nodeinfo(&self, origin: &str) -> Result<Value, Error> {
+        let links: Jrd = serde_json::from_value(
+            self.get_json(&format!("{}/.well-known/nodeinfo", origin))
+                .await?,
+        )
+        .map_err(|e| Error::InvalidObject(e.to_string()))?;
+        let rels = Version::ALL
+            .iter()
+            .map(Version::schema_url)
+            .chain([NODEINFO_1_0.to_string()]);
+        let href = rels
+            .into_iter()
+            .find_map(|rel| {
+                links
+                    .links
+                    .iter()
+                    .find(|link| link.rel.as_deref() == Some(&rel))
+                    .and_then(|link| link.href.to_owned())
+            })
+            .ok_or_else(|| Error::InvalidObject("NodeInfo link not found".to_string()))?;
+        self.get_json(&href).await
+    }
+
+    async fn fetch_head(&self, origin: &str) -> Result<Head, Error> {
+        let response = self.get(origin, "text/html").await?;
+        Ok(Head::parse(&response.body))
+    }
+
+    /// Returns the icon of the head, or `/favicon.ico` if it exists.
+    async fn fetch_favicon(&self, origin: &str, head: Option<&Head>) -> Option<String> {
+        if let Some(href) = head.and_then(|h| h.link(&["shortcut icon", "icon"])) {
+            return resolve(origin, href);
+        }
+        let url = format!("{}/favicon.ico", origin);
+        self.get(&url, "image/*").await.ok().map(|_| url)
+    }
+
+    /// Fetches the metadata of `host`. Sources that fail are skipped.
+    pub async fn fetch(&self, host: &str) -> InstanceInfo {
+        let origin = format!("{}://{}", self.config.scheme, host);
+        let manifest_url = format!("{}/manifest.json", origin);
+        let (nodeinfo, head, manifest) = tokio::join!(
+            self.fetch_nodeinfo(&origin),
+            self.fetch_head(&origin),
+            self.get_json(&manifest_url),
+        );
+        let (nodeinfo, head, manifest) = (nodeinfo.ok(), head.ok(), manifest.ok());
+
+        let mut info = extract(&origin, nodeinfo.as_ref(), head.as_ref(), manifest.as_ref());
+        info.favicon_url = self.fetch_favicon(&origin, head.as_ref()).await;
+        info
+    }
+
+    /// Fetches and records the metadata of `host` unless it was updated
+    /// within [InstanceFetchConfig::stale_after] and `force` is `false`.
+    /// Returns the updated instance, or `None` if it is up to date.
+    pub async fn update(&self, host: &str, force: bool) -> Result<Option<instance::Model>, Error> {
+        let model = register_instance(host).await?;
+        let now = Utc::now().fixed_offset();
+        let stale_after =
+            chrono::Duration::from_std(self.config.stale_after).unwrap_or(chrono::Duration::MAX);
+        if !force
+            && model
+                .info_updated_at
+                .is_some_and(|at| now - at < stale_after)
+        {
+            return Ok(None);
+        }
+
+        let info = self.fetch(host).await;
+        let mut active = model.into_active_model();
+        active.info_updated_at = Set(Some(now));
+        if info.has_nodeinfo {
+            active.software_name = Set(info.software_name);
+            active.software_version = Set(info.software_version);
+            active.open_registrations = Set(info.open_registrations);
+            active.maintainer_name = Set(info.maintainer_name);
+            active.maintainer_email = Set(info.maintainer_email);
+        }
+        if let Some(name) = info.name {
+            active.name = Set(Some(name));
+        }
+        if let Some(description) = info.description {
+            active.description = Set(Some(description));
+        }
+        if let Some(icon) = info.icon_url.or_else(|| info.favicon_url.to_owned()) {
+            active.icon_url = Set(Some(icon));
+        }
+        if let Some(favicon) = info.favicon_url {
+            active.favicon_url = Set(Some(favicon));
+        }
+        if let Some(color) = info.theme_color {
+            active.theme_color = Set(Some(color));
+        }
+
+        Ok(Some(active.update(database::get_database()?).await?))
+    }
+}
+
+#[cfg(test)]
+mod unit_test {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use async_trait::async_trait;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    use super::{extract, normalize_color, Head, InstanceFetchConfig, InstanceFetcher};
+    use crate::util::http::{self, HttpClient, Request, Response};
+
+    const HTML: &str = r##"<!DOCTYPE html>
+        <html>
+        <head>
+            <title> Remote </title>
+            <meta name="description" content="A remote server">
+            <meta name="theme-color" content="#86B300">
+            <link rel="Shortcut  Icon" href="/static/favicon.png">
+            <link rel="apple-touch-icon" href="https://cdn.example/touch.png">
+        </head>
+        <body><h1>Hello</h1></body>
+        </html>"##;
+
+    /// Answers the top page after `delay` and the others immediately.
+    struct Slow {
+        delay: Duration,
+    }
+
+    #[async_trait]
+    impl HttpClient for Slow {
+        async fn send(&self, request: Request) -> Result<Response, http::Error> {
+            let body = match request.url.as_str() {
+                "https://remote.example" => {
+                    tokio::time::sleep(self.delay).await;
+                    HTML.to_string()
+                }
+                "https://remote.example/manifest.json" => {
+                    json!({ "name": "Manifest", "theme_color": "red" }).to_string()
+                }
+                _ => {
+                    return Ok(Response {
+                        status: 404,
+                        ..Default::default()
+                    })
+                }
+            };
+            Ok(Response {
+                status: 200,
+                body,
+                ..Default::default()
+            })
+        }
+    }
+
+    #[test]
+    fn head() {
+        let head = Head::parse(HTML);
+        assert_eq!(head.title.as_deref(), Some("Remote"));
+        assert_eq!(head.description.as_deref(), Some("A remote server"));
+        assert_eq!(head.theme_color.as_deref(), Some("#86B300"));
+        assert_eq!(
+            head.links,
+            vec![
+                (
+                    "shortcut icon".to_string(),
+                    "/static/favicon.png".to_string()
+                ),
+                (
+                    "apple-touch-icon".to_string(),
+                    "https://cdn.example/touch.png".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn colors() {
+        assert_eq!(normalize_color("#86B300").as_deref(), Some("#86b300"));
+        assert_eq!(normalize_color("#fa0").as_deref(), Some("#ffaa00"));
+        assert_eq!(normalize_color("#31748fcc").as_deref(), Some("#31748f"));
+        assert_eq!(
+            normalize_color("rgb(49, 116, 143)").as_deref(),
+            Some("#31748f")
+        );
+        assert_eq!(
+            normalize_color("rgba(100% 0% 0% / 0.5)").as_deref(),
+            Some("#ff0000")
+        );
+        assert_eq!(normalize_color("#12345"), None);
+        assert_eq!(normalize_color("red"), None);
+    }
+
+    #[test]
+    fn extraction() {
+        let origin = "https://remote.example";
+        let nodeinfo = json!({
+            "software": { "name": "Mastodon", "version": "4.1.2" },
+            "openRegistrations": true,
+            "metadata": { "nodeName": "", "maintainer": { "name": "Bob" } },
+        });
+        let head = Head::parse(HTML);
+        let manifest = json!({
+            "name": "Manifest",
+            "description": "From the manifest",
+            "icons": [{ "src": "icon-192.png" }],
+        });
+
+        let info = extract(origin, Some(&nodeinfo), Some(&head), Some(&manifest));
+        assert!(info.has_nodeinfo);
+        assert_eq!(info.software_name.as_deref(), Some("mastodon"));
+        assert_eq!(info.software_version.as_deref(), Some("4.1.2"));
+        assert_eq!(info.open_registrations, Some(true));
+        assert_eq!(info.maintainer_name.as_deref(), Some("Bob"));
+        assert_eq!(info.maintainer_email, None);
+        assert_eq!(info.name.as_deref(), Some("Remote"));
+        assert_eq!(info.description.as_deref(), Some("A remote server"));
+        assert_eq!(
+            info.icon_url.as_deref(),
+            Some("https://remote.example/icon-192.png")
+        );
+        assert_eq!(info.theme_color.as_deref(), Some("#86b300"));
+
+        let info = extract(origin, None, Some(&head), None);
+        assert!(!info.has_nodeinfo);
+        assert_eq!(
+            info.icon_url.as_deref(),
+            Some("https://cdn.example/touch.png")
+        );
+
+        let info = extract(origin, None, None, Some(&manifest));
+        assert_eq!(info.name.as_deref(), Some("Manifest"));
+        assert_eq!(info.description.as_deref(), Some("From the manifest"));
+    }
+
+    #[tokio::test]
+    async fn timeouts() {
+        let config = InstanceFetchConfig {
+            timeout: Duration::from_millis(50),
+            ..Default::default()
+        };
+
+        let fetcher = InstanceFetcher::new(
+            Arc::new(Slow {
+                delay: Duration::from_millis(500),
+            }),
+            config.to_owned(),
+        );
+        let info = fetcher.fetch("remote.example").await;
+        assert_eq!(info.name.as_deref(), Some("Manifest"));
+        assert_eq!(info.favicon_url, None);
+
+        let fetcher = InstanceFetcher::new(
+            Arc::new(Slow {
+                delay: Duration::ZERO,
+            }),
+            config,
+        );
+        let info = fetcher.fetch("remote.example").await;
+        assert_eq!(info.name.as_deref(), Some("Remote"));
+        assert_eq!(
+            info.favicon_url.as_deref(),
+            Some("https://remote.example/static/favicon.png")
+        );
+    }
+}
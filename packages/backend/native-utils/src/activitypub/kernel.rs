@@ -0,0 +1,1025 @@
+//! Effects of inbound activities on the database, ported from
+//! `remote/activitypub/kernel`
+//!
+//! Activities must be verified, and their actors must be known remote users.
+//! Nothing is fetched, so activities that refer to unknown objects by id are
+//! ignored. Redelivered activities are recognized by `note.uri`,
+//! `follow_request.request_id`, and the rows they would insert, so that
+//! performing an activity twice has the effect of performing it once.
+
+use std::collections::HashSet;
+
+use cfg_if::cfg_if;
+use chrono::{DateTime, FixedOffset, Utc};
+use sea_orm::sea_query::{Expr, OnConflict, SimpleExpr};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseTransaction, EntityTrait, IntoActiveModel, ModelTrait,
+    QueryFilter, Set, TransactionTrait,
+};
+use serde::Serialize;
+
+use super::activity::{Activity, ActivityType};
+use super::error::Error;
+use super::note::Note;
+use super::object::Object;
+use super::policy::get_policy;
+use super::value::{OneOrMany, Reference};
+use super::PUBLIC;
+use crate::database;
+use crate::mfm::{remote_note_text, MentionedRemoteUser, RemoteContent};
+use crate::model::entity::newtype::{from_i32_vec, from_string_vec, i32_vec, string_vec};
+use crate::model::entity::sea_orm_active_enums::{NoteVisibilityEnum, PollNotevisibilityEnum};
+use crate::model::entity::{
+    blocking, follow_request, following, note, note_reaction, poll, poll_vote, user,
+};
+use crate::model::meta::current_meta;
+use crate::util::id::create_id;
+use crate::util::reaction::decode_reaction;
+
+/// Reaction of `Like` without one, if `meta.default_reaction` is not set.
+const DEFAULT_REACTION: &str = "⭐";
+
+/// Result of [perform].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", content = "reason", rename_all = "camelCase")]
+pub enum Effect {
+    /// The database was changed.
+    Applied,
+    /// The actor now follows the local user.
+    Followed,
+    /// The follow of the actor awaits the approval of the local user.
+    Requested,
+    /// Nothing was left to do, e.g. the activity was redelivered or the
+    /// deleted object is already gone.
+    AlreadyApplied,
+    /// The activity was not performed for the reason.
+    Ignored(String),
+}
+
+fn ignored(reason: &str) -> Result<Effect, Error> {
+    Ok(Effect::Ignored(reason.to_string()))
+}
+
+/// Returns the id of the local object if `ap_id` is its url, i.e.
+/// `{url}/{kind}/{id}`.
+pub(super) fn local_id<'a>(url: &str, kind: &str, ap_id: &'a str) -> Option<&'a str> {
+    ap_id
+        .strip_prefix(url)?
+        .strip_prefix('/')?
+        .strip_prefix(kind)?
+        .strip_prefix('/')
+        .filter(|id| !id.is_empty() && !id.contains(['/', '#', '?']))
+}
+
+fn is_public(id: &str) -> bool {
+    id == PUBLIC || id == "as:Public" || id == "Public"
+}
+
+/// Returns the visibility of a note addressed to `to` and `cc`, as
+/// `getVisibility` does.
+fn visibility(to: &[&str], cc: &[&str], followers_uri: Option<&str>) -> NoteVisibilityEnum {
+    if to.iter().any(|id| is_public(id)) {
+        NoteVisibilityEnum::Public
+    } else if cc.iter().any(|id| is_public(id)) {
+        NoteVisibilityEnum::Home
+    } else if followers_uri.is_some_and(|uri| to.contains(&uri)) {
+        NoteVisibilityEnum::Followers
+    } else {
+        NoteVisibilityEnum::Specified
+    }
+}
+
+/// Converts the reaction of `Like` and `EmojiReact` into the one stored in
+/// `note_reaction`. Custom emojis get the host of the actor, and reactions
+/// that are neither become `default_reaction`.
+fn to_reaction(reaction: Option<&str>, host: &str, default_reaction: &str) -> String {
+    let reaction = reaction.map(str::trim).unwrap_or_default();
+    match decode_reaction(reaction).name {
+        Some(name) => format!(":{}@{}:", name, host),
+        None if emojis::get(reaction).is_some() => reaction.to_string(),
+        None => default_reaction.to_string(),
+    }
+}
+
+/// Adds `delta` to the count of the reaction in `note.reactions`.
+fn count_reaction(reactions: &serde_json::Value, reaction: &str, delta: i64) -> serde_json::Value {
+    let mut reactions = reactions.as_object().cloned().unwrap_or_default();
+    let count = reactions
+        .get(reaction)
+        .and_then(serde_json::Value::as_i64)
+        .unwrap_or_default()
+        + delta;
+    match count > 0 {
+        true => reactions.insert(reaction.to_string(), count.into()),
+        false => reactions.remove(reaction),
+    };
+    serde_json::Value::Object(reactions)
+}
+
+fn parse_date(date: Option<&str>) -> Option<DateTime<FixedOffset>> {
+    date.and_then(|d| DateTime::parse_from_rfc3339(d).ok())
+}
+
+fn ids(refs: &Option<OneOrMany<Reference<serde_json::Value>>>) -> Vec<&str> {
+    refs.as_ref().map(OneOrMany::ids).unwrap_or_default()
+}
+
+/// Adds `delta` to the column, e.g. `followers_count`.
+fn increment<C: ColumnTrait>(column: C, delta: i32) -> SimpleExpr {
+    Expr::col(column).add(delta)
+}
+
+struct Kernel<'a> {
+    url: &'a str,
+    txn: &'a DatabaseTransaction,
+    actor: &'a user::Model,
+}
+
+impl Kernel<'_> {
+    fn actor_host(&self) -> &str {
+        self.actor.host.as_deref().unwrap_or_default()
+    }
+
+    /// Returns `true` if the activity is by the actor, since embedded
+    /// activities are not signed.
+    fn is_own(&self, activity: &Activity) -> bool {
+        activity.actor_id().is_some() && activity.actor_id() == self.actor.uri.as_deref()
+    }
+
+    async fn find_note(&self, ap_id: &str) -> Result<Option<note::Model>, Error> {
+        let query = match local_id(self.url, "notes", ap_id) {
+            Some(id) => note::Entity::find_by_id(id.to_string()),
+            None => note::Entity::find().filter(note::Column::Uri.eq(ap_id)),
+        };
+        Ok(query.one(self.txn).await?)
+    }
+
+    async fn find_local_user(&self, ap_id: &str) -> Result<Option<user::Model>, Error> {
+        match local_id(self.url, "users", ap_id) {
+            Some(id) => Ok(user::Entity::find_by_id(id.to_string())
+                .filter(user::Column::Host.is_null())
+                .one(self.txn)
+                .await?),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the known users of the ids, local or remote.
+    async fn find_users(&self, ap_ids: &[&str]) -> Result<Vec<user::Model>, Error> {
+        let mut users: Vec<user::Model> = Vec::new();
+        for ap_id in ap_ids {
+            let found = match self.find_local_user(ap_id).await? {
+                Some(user) => Some(user),
+                None => {
+                    user::Entity::find()
+                        .filter(user::Column::Uri.eq(*ap_id))
+                        .one(self.txn)
+                        .await?
+                }
+            };
+            if let Some(user) = found.filter(|u| users.iter().all(|v| v.id != u.id)) {
+                users.push(user);
+            }
+        }
+        Ok(users)
+    }
+
+    async fn find_following(
+        &self,
+        follower_id: &str,
+        followee_id: &str,
+    ) -> Result<Option<following::Model>, Error> {
+        Ok(following::Entity::find()
+            .filter(following::Column::FollowerId.eq(follower_id))
+            .filter(following::Column::FolloweeId.eq(followee_id))
+            .one(self.txn)
+            .await?)
+    }
+
+    async fn find_follow_request(
+        &self,
+        follower_id: &str,
+        followee_id: &str,
+    ) -> Result<Option<follow_request::Model>, Error> {
+        Ok(follow_request::Entity::find()
+            .filter(follow_request::Column::FollowerId.eq(follower_id))
+            .filter(follow_request::Column::FolloweeId.eq(followee_id))
+            .one(self.txn)
+            .await?)
+    }
+
+    async fn add_to_count<C: ColumnTrait>(
+        &self,
+        user_id: &str,
+        column: C,
+        delta: i32,
+    ) -> Result<(), Error> {
+        user::Entity::update_many()
+            .col_expr(column, increment(column, delta))
+            .filter(user::Column::Id.eq(user_id))
+            .exec(self.txn)
+            .await?;
+        Ok(())
+    }
+
+    async fn insert_following(
+        &self,
+        follower: &user::Model,
+        followee: &user::Model,
+    ) -> Result<(), Error> {
+        following::Model {
+            id: create_id(0)?,
+            created_at: Utc::now().into(),
+            followee_id: followee.id.to_owned(),
+            follower_id: follower.id.to_owned(),
+            follower_host: follower.host.to_owned(),
+            follower_inbox: follower.inbox.to_owned(),
+            follower_shared_inbox: follower.shared_inbox.to_owned(),
+            followee_host: followee.host.to_owned(),
+            followee_inbox: followee.inbox.to_owned(),
+            followee_shared_inbox: followee.shared_inbox.to_owned(),
+        }
+        .into_active_model()
+        .reset_all()
+        .insert(self.txn)
+        .await?;
+        self.add_to_count(&follower.id, user::Column::FollowingCount, 1)
+            .await?;
+        self.add_to_count(&followee.id, user::Column::FollowersCount, 1)
+            .await
+    }
+
+    /// Returns `true` if there was the following.
+    async fn delete_following(&self, follower_id: &str, followee_id: &str) -> Result<bool, Error> {
+        let deleted = following::Entity::delete_many()
+            .filter(following::Column::FollowerId.eq(follower_id))
+            .filter(following::Column::FolloweeId.eq(followee_id))
+            .exec(self.txn)
+            .await?
+            .rows_affected;
+        if deleted == 0 {
+            return Ok(false);
+        }
+        self.add_to_count(follower_id, user::Column::FollowingCount, -1)
+            .await?;
+        self.add_to_count(followee_id, user::Column::FollowersCount, -1)
+            .await?;
+        Ok(true)
+    }
+
+    async fn delete_follow_request(
+        &self,
+        follower_id: &str,
+        followee_id: &str,
+    ) -> Result<bool, Error> {
+        let deleted = follow_request::Entity::delete_many()
+            .filter(follow_request::Column::FollowerId.eq(follower_id))
+            .filter(follow_request::Column::FolloweeId.eq(followee_id))
+            .exec(self.txn)
+            .await?
+            .rows_affected;
+        Ok(deleted > 0)
+    }
+
+    async fn follow(&self, activity: &Activity) -> Result<Effect, Error> {
+        let followee = match activity.object_id() {
+            Some(id) => self.find_local_user(id).await?,
+            None => None,
+        };
+        let followee = match followee {
+            Some(followee) => followee,
+            None => return ignored("followee not found"),
+        };
+        let blocked = blocking::Entity::find()
+            .filter(blocking::Column::BlockerId.eq(&followee.id))
+            .filter(blocking::Column::BlockeeId.eq(&self.actor.id))
+            .one(self.txn)
+            .await?;
+        if blocked.is_some() {
+            return ignored("blocked by the followee");
+        }
+        if self
+            .find_following(&self.actor.id, &followee.id)
+            .await?
+            .is_some()
+            || self
+                .find_follow_request(&self.actor.id, &followee.id)
+                .await?
+                .is_some()
+        {
+            return Ok(Effect::AlreadyApplied);
+        }
+
+        // Follows from silenced hosts need approval even if the followee is
+        // not locked.
+        if followee.is_locked || get_policy().await?.is_silenced(self.actor_host()) {
+            follow_request::Model {
+                id: create_id(0)?,
+                created_at: Utc::now().into(),
+                followee_id: followee.id.to_owned(),
+                follower_id: self.actor.id.to_owned(),
+                request_id: activity.id.to_owned(),
+                follower_host: self.actor.host.to_owned(),
+                follower_inbox: self.actor.inbox.to_owned(),
+                follower_shared_inbox: self.actor.shared_inbox.to_owned(),
+                ..Default::default()
+            }
+            .into_active_model()
+            .reset_all()
+            .insert(self.txn)
+            .await?;
+            Ok(Effect::Requested)
+        } else {
+            self.insert_following(self.actor, &followee).await?;
+            Ok(Effect::Followed)
+        }
+    }
+
+    /// Returns the local follower of the `Follow` that the activity accepts
+    /// or rejects. The `Follow` is either embedded or referred to by the id
+    /// our renderer gives, `{url}/follows/{follower}/{followee}`.
+    async fn local_follower(&self, activity: &Activity) -> Result<Option<user::Model>, Error> {
+        let follow = match activity.object() {
+            Some(follow) => follow,
+            None => return Ok(None),
+        };
+        let follows = format!("{}/follows/", self.url);
+        let follower_id = match follow.object() {
+            Some(Object::Activity(follow)) if follow.kind == ActivityType::Follow => follow
+                .actor_id()
+                .and_then(|id| local_id(self.url, "users", id)),
+            Some(_) => None,
+            None => follow
+                .id()
+                .and_then(|id| id.strip_prefix(&follows))
+                .and_then(|ids| ids.split('/').next()),
+        };
+        match follower_id {
+            Some(id) => Ok(user::Entity::find_by_id(id.to_string())
+                .filter(user::Column::Host.is_null())
+                .one(self.txn)
+                .await?),
+            None => Ok(None),
+        }
+    }
+
+    async fn accept(&self, activity: &Activity) -> Result<Effect, Error> {
+        let follower = match self.local_follower(activity).await? {
+            Some(follower) => follower,
+            None => return ignored("follower not found"),
+        };
+        if self
+            .find_following(&follower.id, &self.actor.id)
+            .await?
+            .is_some()
+        {
+            return Ok(Effect::AlreadyApplied);
+        }
+        if !self
+            .delete_follow_request(&follower.id, &self.actor.id)
+            .await?
+        {
+            return ignored("follow request not found");
+        }
+        self.insert_following(&follower, self.actor).await?;
+        Ok(Effect::Applied)
+    }
+
+    async fn reject(&self, activity: &Activity) -> Result<Effect, Error> {
+        let follower = match self.local_follower(activity).await? {
+            Some(follower) => follower,
+            None => return ignored("follower not found"),
+        };
+        let requested = self
+            .delete_follow_request(&follower.id, &self.actor.id)
+            .await?;
+        let followed = self.delete_following(&follower.id, &self.actor.id).await?;
+        match requested || followed {
+            true => Ok(Effect::Applied),
+            false => Ok(Effect::AlreadyApplied),
+        }
+    }
+
+    async fn add_reaction(
+        &self,
+        note: note::Model,
+        reaction: &str,
+        delta: i32,
+    ) -> Result<(), Error> {
+        let reactions = count_reaction(&note.reactions, reaction, delta.into());
+        let score = (note.score + delta).max(0);
+        let mut active = note.into_active_model();
+        active.reactions = Set(reactions);
+        active.score = Set(score);
+        active.update(self.txn).await?;
+        Ok(())
+    }
+
+    async fn react(&self, activity: &Activity) -> Result<Effect, Error> {
+        let note = match activity.object_id() {
+            Some(id) => self.find_note(id).await?,
+            None => None,
+        };
+        let note = match note {
+            Some(note) => note,
+            None => return ignored("note not found"),
+        };
+        let reacted = note_reaction::Entity::find()
+            .filter(note_reaction::Column::UserId.eq(&self.actor.id))
+            .filter(note_reaction::Column::NoteId.eq(&note.id))
+            .one(self.txn)
+            .await?;
+        if reacted.is_some() {
+            return Ok(Effect::AlreadyApplied);
+        }
+
+        let default_reaction = current_meta(self.txn)
+            .await?
+            .map(|m| m.default_reaction)
+            .filter(|r| !r.is_empty())
+            .unwrap_or_else(|| DEFAULT_REACTION.to_string());
+        let reaction = to_reaction(activity.reaction(), self.actor_host(), &default_reaction);
+        note_reaction::Model {
+            id: create_id(0)?,
+            created_at: Utc::now().into(),
+            user_id: self.actor.id.to_owned(),
+            note_id: note.id.to_owned(),
+            reaction: reaction.to_owned(),
+        }
+        .into_active_model()
+        .reset_all()
+        .insert(self.txn)
+        .await?;
+        self.add_reaction(note, &reaction, 1).await?;
+        Ok(Effect::Applied)
+    }
+
+    async fn create(&self, activity: &Activity) -> Result<Effect, Error> {
+        let note = match activity.object().and_then(Reference::object) {
+            Some(Object::Note(note)) => note,
+            Some(_) => return ignored("unsupported object"),
+            None => return ignored("object is not embedded"),
+        };
+        if note.author_id().is_none() || note.author_id() != self.actor.uri.as_deref() {
+            return ignored("note is not attributed to the actor");
+        }
+        match self.vote(note).await? {
+            Some(effect) => Ok(effect),
+            None => self.create_note(note).await,
+        }
+    }
+
+    /// Votes if the note answers a local question, since votes are notes
+    /// with the choice as `name`.
+    async fn vote(&self, note: &Note) -> Result<Option<Effect>, Error> {
+        let (choice, question_id) = match (&note.name, &note.in_reply_to) {
+            (Some(choice), Some(reply)) => match reply.id() {
+                Some(id) => (choice, id),
+                None => return Ok(None),
+            },
+            _ => return Ok(None),
+        };
+        let question = match self.find_note(question_id).await? {
+            Some(question) if question.has_poll && question.user_host.is_none() => question,
+            _ => return Ok(None),
+        };
+        let poll = match poll::Entity::find_by_id(question.id.to_owned())
+            .one(self.txn)
+            .await?
+        {
+            Some(poll) => poll,
+            None => return Ok(None),
+        };
+
+        let choices = from_string_vec(poll.choices.to_owned());
+        let index = match choices.iter().position(|c| c == choice) {
+            Some(index) => index,
+            None => return ignored("unknown choice").map(Some),
+        };
+        if poll.expires_at.is_some_and(|at| at < Utc::now()) {
+            return ignored("poll is closed").map(Some);
+        }
+        let votes = poll_vote::Entity::find()
+            .filter(poll_vote::Column::UserId.eq(&self.actor.id))
+            .filter(poll_vote::Column::NoteId.eq(&question.id))
+            .all(self.txn)
+            .await?;
+        if votes.iter().any(|v| v.choice == index as i32) {
+            return Ok(Some(Effect::AlreadyApplied));
+        }
+        if !poll.multiple && !votes.is_empty() {
+            return ignored("already voted").map(Some);
+        }
+
+        poll_vote::Model {
+            id: create_id(0)?,
+            created_at: Utc::now().into(),
+            user_id: self.actor.id.to_owned(),
+            note_id: question.id.to_owned(),
+            choice: index as i32,
+        }
+        .into_active_model()
+        .reset_all()
+        .insert(self.txn)
+        .await?;
+        let mut counts = from_i32_vec(poll.votes.to_owned());
+        counts.resize(choices.len(), 0);
+        counts[index] += 1;
+        let mut active = poll.into_active_model();
+        active.votes = Set(i32_vec(counts));
+        active.update(self.txn).await?;
+        Ok(Some(Effect::Applied))
+    }
+
+    async fn create_note(&self, note: &Note) -> Result<Effect, Error> {
+        let created = note::Entity::find()
+            .filter(note::Column::Uri.eq(&note.id))
+            .one(self.txn)
+            .await?;
+        if created.is_some() {
+            return Ok(Effect::AlreadyApplied);
+        }
+
+        let reply = match note.in_reply_to.as_ref().and_then(Reference::id) {
+            Some(id) => self.find_note(id).await?,
+            None => None,
+        };
+        let quote = match note.quote_id() {
+            Some(id) => self.find_note(id).await?,
+            None => None,
+        };
+        let mentions: Vec<&str> = note.mentions().map(|m| m.href.as_str()).collect();
+        let mentioned = self.find_users(&mentions).await?;
+        let (to, cc) = (ids(&note.to), ids(&note.cc));
+        let visibility = visibility(&to, &cc, self.actor.followers_uri.as_deref());
+        let visible_user_ids: Vec<String> = match visibility {
+            NoteVisibilityEnum::Specified => {
+                let addressed: Vec<&str> = to.iter().chain(&cc).chain(&mentions).copied().collect();
+                self.find_users(&addressed)
+                    .await?
+                    .into_iter()
+                    .map(|u| u.id)
+                    .collect()
+            }
+            _ => Vec::new(),
+        };
+        let mentioned_remote_users: Vec<MentionedRemoteUser> = mentioned
+            .iter()
+            .filter_map(|u| {
+                Some(MentionedRemoteUser {
+                    uri: u.uri.to_owned()?,
+                    url: None,
+                    username: u.username.to_owned(),
+                    host: u.host.to_owned()?,
+                })
+            })
+            .collect();
+
+        let hashtag_names: Vec<String> = note.hashtags().map(|h| h.name.to_owned()).collect();
+        let text = remote_note_text(&RemoteContent {
+            source: note.source.as_ref().map(|s| {
+                (
+                    s.content.as_str(),
+                    s.media_type.as_deref().unwrap_or_default(),
+                )
+            }),
+            misskey_content: note.misskey_content.as_deref(),
+            html: note
+                .content_map
+                .as_ref()
+                .and_then(|m| m.values().next())
+                .or(note.content.as_ref())
+                .map(String::as_str),
+            hashtag_names: &hashtag_names,
+        })
+        .filter(|t| !t.is_empty());
+        let mut tags: Vec<String> = Vec::new();
+        for name in &hashtag_names {
+            let tag = name.trim_start_matches('#').to_lowercase();
+            if !tag.is_empty() && !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        }
+        let emojis: HashSet<String> = note
+            .emojis()
+            .map(|e| e.name.trim_matches(':').to_string())
+            .collect();
+        let url = note
+            .url
+            .as_ref()
+            .and_then(OneOrMany::first_id)
+            .filter(|url| *url != note.id)
+            .map(str::to_string);
+
+        let created_at = parse_date(note.published.as_deref())
+            .filter(|date| *date <= Utc::now())
+            .unwrap_or_else(|| Utc::now().into());
+        let id = create_id(created_at.timestamp_millis())?;
+        let poll = note.poll();
+        let model = note::Model {
+            id: id.to_owned(),
+            created_at,
+            reply_id: reply.as_ref().map(|r| r.id.to_owned()),
+            renote_id: quote.as_ref().map(|q| q.id.to_owned()),
+            text,
+            cw: note.summary.to_owned().filter(|s| !s.is_empty()),
+            user_id: self.actor.id.to_owned(),
+            reactions: serde_json::json!({}),
+            visibility: visibility.to_owned(),
+            uri: Some(note.id.to_owned()),
+            visible_user_ids: string_vec(visible_user_ids),
+            mentions: string_vec(mentioned.iter().map(|u| u.id.to_owned()).collect()),
+            mentioned_remote_users: serde_json::to_string(&mentioned_remote_users)
+                .unwrap_or_else(|_| "[]".to_string()),
+            emojis: string_vec(emojis.into_iter().collect()),
+            tags: string_vec(tags),
+            has_poll: poll.is_some(),
+            user_host: self.actor.host.to_owned(),
+            reply_user_id: reply.as_ref().map(|r| r.user_id.to_owned()),
+            reply_user_host: reply.as_ref().and_then(|r| r.user_host.to_owned()),
+            renote_user_id: quote.as_ref().map(|q| q.user_id.to_owned()),
+            renote_user_host: quote.as_ref().and_then(|q| q.user_host.to_owned()),
+            url,
+            thread_id: Some(match &reply {
+                Some(reply) => reply.thread_id.to_owned().unwrap_or(reply.id.to_owned()),
+                None => id.to_owned(),
+            }),
+            updated_at: parse_date(note.updated.as_deref()),
+            ..Default::default()
+        };
+        // The same note may be stored at the same time, e.g. by the inbox
+        // and the resolver, after the check above.
+        let inserted = note::Entity::insert(model.into_active_model().reset_all())
+            .on_conflict(
+                OnConflict::column(note::Column::Uri)
+                    .do_nothing()
+                    .to_owned(),
+            )
+            .exec_without_returning(self.txn)
+            .await?;
+        if inserted == 0 {
+            return Ok(Effect::AlreadyApplied);
+        }
+
+        if let Some(choices) = poll {
+            poll::Model {
+                note_id: id,
+                expires_at: parse_date(note.end_time.as_deref()),
+                multiple: choices.multiple,
+                choices: string_vec(choices.choices.iter().map(|c| c.name.to_owned()).collect()),
+                votes: i32_vec(choices.choices.iter().map(|c| c.votes() as i32).collect()),
+                note_visibility: match visibility {
+                    NoteVisibilityEnum::Public => PollNotevisibilityEnum::Public,
+                    NoteVisibilityEnum::Home => PollNotevisibilityEnum::Home,
+                    NoteVisibilityEnum::Followers => PollNotevisibilityEnum::Followers,
+                    _ => PollNotevisibilityEnum::Specified,
+                },
+                user_id: self.actor.id.to_owned(),
+                user_host: self.actor.host.to_owned(),
+            }
+            .into_active_model()
+            .reset_all()
+            .insert(self.txn)
+            .await?;
+        }
+        if let Some(reply) = reply {
+            note::Entity::update_many()
+                .col_expr(
+                    note::Column::RepliesCount,
+                    increment(note::Column::RepliesCount, 1),
+                )
+                .filter(note::Column::Id.eq(reply.id))
+                .exec(self.txn)
+                .await?;
+        }
+        self.add_to_count(&self.actor.id, user::Column::NotesCount, 1)
+            .await?;
+        Ok(Effect::Applied)
+    }
+
+    async fn delete_note(&self, note: note::Model) -> Result<(), Error> {
+        poll_vote::Entity::delete_many()
+            .filter(poll_vote::Column::NoteId.eq(&note.id))
+            .exec(self.txn)
+            .await?;
+        note_reaction::Entity::delete_many()
+            .filter(note_reaction::Column::NoteId.eq(&note.id))
+            .exec(self.txn)
+            .await?;
+        poll::Entity::delete_many()
+            .filter(poll::Column::NoteId.eq(&note.id))
+            .exec(self.txn)
+            .await?;
+        if let Some(reply_id) = &note.reply_id {
+            note::Entity::update_many()
+                .col_expr(
+                    note::Column::RepliesCount,
+                    increment(note::Column::RepliesCount, -1),
+                )
+                .filter(note::Column::Id.eq(reply_id))
+                .filter(note::Column::RepliesCount.gt(0))
+                .exec(self.txn)
+                .await?;
+        }
+        let user_id = note.user_id.to_owned();
+        note.delete(self.txn).await?;
+        self.add_to_count(&user_id, user::Column::NotesCount, -1)
+            .await
+    }
+
+    /// Deletes the note of the actor, or marks the actor as deleted.
+    async fn delete(&self, activity: &Activity) -> Result<Effect, Error> {
+        let object_id = match activity.object_id() {
+            Some(id) => id,
+            None => return ignored("no object"),
+        };
+        if Some(object_id) == self.actor.uri.as_deref() {
+            if self.actor.is_deleted {
+                return Ok(Effect::AlreadyApplied);
+            }
+            let mut active = self.actor.to_owned().into_active_model();
+            active.is_deleted = Set(true);
+            active.update(self.txn).await?;
+            return Ok(Effect::Applied);
+        }
+
+        let note = note::Entity::find()
+            .filter(note::Column::Uri.eq(object_id))
+            .filter(note::Column::UserId.eq(&self.actor.id))
+            .one(self.txn)
+            .await?;
+        match note {
+            Some(note) => {
+                self.delete_note(note).await?;
+                Ok(Effect::Applied)
+            }
+            None => Ok(Effect::AlreadyApplied),
+        }
+    }
+
+    async fn undo(&self, activity: &Activity) -> Result<Effect, Error> {
+        let object = match activity.object() {
+            Some(object) => object,
+            None => return ignored("no object"),
+        };
+        match object.object() {
+            Some(Object::Activity(inner)) if self.is_own(inner) => match inner.kind {
+                ActivityType::Follow => self.undo_follow(inner).await,
+                ActivityType::Like | ActivityType::EmojiReact => self.undo_react(inner).await,
+                ActivityType::Announce => match &inner.id {
+                    Some(id) => self.undo_announce(id).await,
+                    None => ignored("no object"),
+                },
+                kind => Ok(Effect::Ignored(format!(
+                    "Undo of {:?} is not supported",
+                    kind
+                ))),
+            },
+            Some(Object::Activity(_)) => ignored("object is not by the actor"),
+            Some(_) => ignored("unsupported object"),
+            // only follow requests and renotes are stored with the ids
+            None => match object.id() {
+                Some(id) => {
+                    let deleted = follow_request::Entity::delete_many()
+                        .filter(follow_request::Column::FollowerId.eq(&self.actor.id))
+                        .filter(follow_request::Column::RequestId.eq(id))
+                        .exec(self.txn)
+                        .await?
+                        .rows_affected;
+                    match deleted {
+                        0 => self.undo_announce(id).await,
+                        _ => Ok(Effect::Applied),
+                    }
+                }
+                None => ignored("no object"),
+            },
+        }
+    }
+
+    async fn undo_follow(&self, follow: &Activity) -> Result<Effect, Error> {
+        let followee = match follow.object_id() {
+            Some(id) => self.find_local_user(id).await?,
+            None => None,
+        };
+        let followee = match followee {
+            Some(followee) => followee,
+            None => return ignored("followee not found"),
+        };
+        let requested = self
+            .delete_follow_request(&self.actor.id, &followee.id)
+            .await?;
+        let followed = self.delete_following(&self.actor.id, &followee.id).await?;
+        match requested || followed {
+            true => Ok(Effect::Applied),
+            false => Ok(Effect::AlreadyApplied),
+        }
+    }
+
+    async fn undo_react(&self, reaction: &Activity) -> Result<Effect, Error> {
+        let note = match reaction.object_id() {
+            Some(id) => self.find_note(id).await?,
+            None => None,
+        };
+        let note = match note {
+            Some(note) => note,
+            None => return Ok(Effect::AlreadyApplied),
+        };
+        let reacted = note_reaction::Entity::find()
+            .filter(note_reaction::Column::UserId.eq(&self.actor.id))
+            .filter(note_reaction::Column::NoteId.eq(&note.id))
+            .one(self.txn)
+            .await?;
+        match reacted {
+            Some(reacted) => {
+                let reaction = reacted.reaction.to_owned();
+                reacted.delete(self.txn).await?;
+                self.add_reaction(note, &reaction, -1).await?;
+                Ok(Effect::Applied)
+            }
+            None => Ok(Effect::AlreadyApplied),
+        }
+    }
+
+    async fn undo_announce(&self, id: &str) -> Result<Effect, Error> {
+        let renote = note::Entity::find()
+            .filter(note::Column::Uri.eq(id))
+            .filter(note::Column::UserId.eq(&self.actor.id))
+            .filter(note::Column::RenoteId.is_not_null())
+            .one(self.txn)
+            .await?;
+        match renote {
+            Some(renote) => {
+                self.delete_note(renote).await?;
+                Ok(Effect::Applied)
+            }
+            None => Ok(Effect::AlreadyApplied),
+        }
+    }
+}
+
+/// Stores the note of the remote actor, whose replied and quoted notes
+/// are already stored if they are to be linked. Returns
+/// [Effect::AlreadyApplied] if the note is already stored.
+pub(super) async fn store_note(
+    url: &str,
+    actor: &user::Model,
+    note: &Note,
+) -> Result<Effect, Error> {
+    let txn = database::get_database()?.begin().await?;
+    let effect = Kernel {
+        url,
+        txn: &txn,
+        actor,
+    }
+    .create_note(note)
+    .await?;
+    txn.commit().await?;
+
+    Ok(effect)
+}
+
+/// Performs the verified activity in a transaction, as `performActivity`
+/// does. `url` is the origin of our server.
+pub async fn perform(url: &str, activity: &Activity) -> Result<Effect, Error> {
+    let db = database::get_database()?;
+    let actor_id = match activity.actor_id() {
+        Some(id) => id,
+        None => return ignored("no actor"),
+    };
+    let actor = user::Entity::find()
+        .filter(user::Column::Uri.eq(actor_id))
+        .filter(user::Column::Host.is_not_null())
+        .one(db)
+        .await?;
+    let actor = match actor {
+        Some(actor) if actor.is_suspended => return ignored("actor is suspended"),
+        Some(actor) => actor,
+        None => return ignored("actor not found"),
+    };
+    if !get_policy()
+        .await?
+        .can_accept(actor.host.as_deref().unwrap_or_default())
+    {
+        return ignored("host is blocked");
+    }
+
+    let txn = db.begin().await?;
+    let kernel = Kernel {
+        url,
+        txn: &txn,
+        actor: &actor,
+    };
+    let effect = match activity.kind {
+        ActivityType::Create => kernel.create(activity).await?,
+        ActivityType::Delete => kernel.delete(activity).await?,
+        ActivityType::Follow => kernel.follow(activity).await?,
+        ActivityType::Accept => kernel.accept(activity).await?,
+        ActivityType::Reject => kernel.reject(activity).await?,
+        ActivityType::Like | ActivityType::EmojiReact => kernel.react(activity).await?,
+        ActivityType::Undo => kernel.undo(activity).await?,
+        kind => Effect::Ignored(format!("{:?} is not supported", kind)),
+    };
+    txn.commit().await?;
+
+    Ok(effect)
+}
+
+cfg_if! {
+    if #[cfg(feature = "napi")] {
+        use napi_derive::napi;
+
+        /// Performs the verified activity and returns the effect, e.g.
+        /// `{ type: "ignored", reason: "note not found" }`.
+        #[napi]
+        pub async fn native_perform_activity(url: String, activity: serde_json::Value) -> napi::Result<serde_json::Value> {
+            let activity: Activity = serde_json::from_value(activity)
+                .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+            let effect = perform(&url, &activity).await.map_err(Into::<napi::Error>::into)?;
+            serde_json::to_value(effect).map_err(|e| napi::Error::from_reason(e.to_string()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod unit_test {
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    use super::{count_reaction, local_id, to_reaction, visibility, Effect};
+    use crate::activitypub::PUBLIC;
+    use crate::model::entity::sea_orm_active_enums::NoteVisibilityEnum;
+
+    const URL: &str = "https://example.com";
+
+    #[test]
+    fn local_ids() {
+        assert_eq!(
+            local_id(URL, "notes", "https://example.com/notes/9a"),
+            Some("9a")
+        );
+        assert_eq!(
+            local_id(URL, "users", "https://example.com/users/9a"),
+            Some("9a")
+        );
+        assert_eq!(local_id(URL, "users", "https://example.com/notes/9a"), None);
+        assert_eq!(
+            local_id(URL, "notes", "https://example.com/notes/9a/activity"),
+            None
+        );
+        assert_eq!(
+            local_id(URL, "notes", "https://example.com.evil/notes/9a"),
+            None
+        );
+        assert_eq!(
+            local_id(URL, "notes", "https://remote.example/notes/9a"),
+            None
+        );
+    }
+
+    #[test]
+    fn visibilities() {
+        let followers = "https://remote.example/users/bob/followers";
+        assert_eq!(visibility(&[PUBLIC], &[], None), NoteVisibilityEnum::Public);
+        assert_eq!(
+            visibility(&["as:Public"], &[], None),
+            NoteVisibilityEnum::Public
+        );
+        assert_eq!(
+            visibility(&[followers], &[PUBLIC], Some(followers)),
+            NoteVisibilityEnum::Home
+        );
+        assert_eq!(
+            visibility(&[followers], &[], Some(followers)),
+            NoteVisibilityEnum::Followers
+        );
+        assert_eq!(
+            visibility(&[followers], &[], None),
+            NoteVisibilityEnum::Specified
+        );
+    }
+
+    #[test]
+    fn reactions() {
+        assert_eq!(to_reaction(Some("👍"), "remote.example", "⭐"), "👍");
+        assert_eq!(
+            to_reaction(Some(":blobcat:"), "remote.example", "⭐"),
+            ":blobcat@remote.example:"
+        );
+        assert_eq!(to_reaction(None, "remote.example", "❤️"), "❤️");
+        assert_eq!(to_reaction(Some("nice"), "remote.example", "⭐"), "⭐");
+
+        let reactions = json!({ "👍": 1 });
+        assert_eq!(count_reaction(&reactions, "👍", 1), json!({ "👍": 2 }));
+        assert_eq!(count_reaction(&reactions, "👍", -1), json!({}));
+        assert_eq!(count_reaction(&json!(null), "⭐", 1), json!({ "⭐": 1 }));
+
+        assert_eq!(
+            serde_json::to_value(Effect::Ignored("note not found".to_string())).unwrap(),
+            json!({ "type": "ignored", "reason": "note not found" })
+        );
+        assert_eq!(
+            serde_json::to_value(Effect::Requested).unwrap(),
+            json!({ "type": "requested" })
+        );
+    }
+}
@@ -0,0 +1,28 @@
+//! ActivityPub objects, ported from `remote/activitypub/type.ts`
+//!
+//! The types accept the variations of JSON-LD other servers send: a single
+//! value or an array, the id of an object or the object itself, and `type`
+//! as an array. Unknown properties are ignored.
+
+pub mod activity;
+pub mod actor;
+pub mod deliver;
+pub mod error;
+pub mod http_signature;
+pub mod instance;
+pub mod kernel;
+pub mod note;
+pub mod object;
+pub mod policy;
+pub mod renderer;
+pub mod resolver;
+pub mod value;
+
+pub use activity::{Activity, ActivityType};
+pub use actor::{Actor, ActorType};
+pub use note::{Note, NoteType};
+pub use object::Object;
+pub use value::{ApObject, OneOrMany, Reference};
+
+/// The special collection that makes objects public.
+pub const PUBLIC: &str = "https://www.w3.org/ns/activitystreams#Public";
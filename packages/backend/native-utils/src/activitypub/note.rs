@@ -0,0 +1,358 @@
+//! Notes, including questions, as `IPost` and `IQuestion` of `type.ts`
+
+use serde::{Deserialize, Serialize};
+
+use super::actor::Actor;
+use super::object::{
+    Attachment, Collection, Emoji, Hashtag, LanguageMap, Link, Mention, Source, Tag,
+};
+use super::value::{deserialize_type, ApObject, Lenient, OneOrMany, Reference};
+
+/// Types of objects that are stored as notes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NoteType {
+    Note,
+    Question,
+    Article,
+    Audio,
+    Document,
+    Image,
+    Page,
+    Video,
+    Event,
+}
+
+/// A note. Questions are notes with `oneOf` or `anyOf`, so that both are
+/// handled alike as in TypeScript.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Note {
+    #[serde(rename = "@context", default, skip_serializing_if = "Option::is_none")]
+    pub context: Option<serde_json::Value>,
+    #[serde(rename = "type", deserialize_with = "deserialize_type")]
+    pub kind: NoteType,
+    pub id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attributed_to: Option<OneOrMany<Reference<Actor>>>,
+    /// Content in HTML.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_map: Option<LanguageMap>,
+    #[serde(
+        rename = "_misskey_content",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub misskey_content: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<Source>,
+    /// Content warning.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub published: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<OneOrMany<Reference<Link>>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to: Option<OneOrMany<Reference<serde_json::Value>>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cc: Option<OneOrMany<Reference<serde_json::Value>>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub in_reply_to: Option<Reference<Note>>,
+    #[serde(
+        rename = "_misskey_quote",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub misskey_quote: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quote_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quote_uri: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sensitive: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag: Option<OneOrMany<Lenient<Tag>>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attachment: Option<OneOrMany<Lenient<Attachment>>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub replies: Option<Reference<Collection>>,
+    /// Choices of a single-choice question.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub one_of: Option<Vec<Choice>>,
+    /// Choices of a multiple-choice question.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub any_of: Option<Vec<Choice>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end_time: Option<String>,
+    /// When the question was closed, or `true` from some servers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub closed: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub voters_count: Option<u32>,
+}
+
+/// Entry of `oneOf` and `anyOf`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Choice {
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<String>,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub replies: Option<Collection>,
+    #[serde(
+        rename = "_misskey_votes",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub misskey_votes: Option<u64>,
+}
+
+impl Choice {
+    /// Returns the number of votes, preferring `replies.totalItems`.
+    pub fn votes(&self) -> u64 {
+        self.replies
+            .as_ref()
+            .and_then(|r| r.total_items)
+            .or(self.misskey_votes)
+            .unwrap_or(0)
+    }
+}
+
+/// Choices of a question, from either `oneOf` or `anyOf`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Poll<'a> {
+    pub choices: &'a [Choice],
+    pub multiple: bool,
+}
+
+impl ApObject for Note {
+    fn ap_id(&self) -> Option<&str> {
+        Some(&self.id)
+    }
+}
+
+impl Note {
+    /// Returns the id of the author as `getOneApId(attributedTo)` does.
+    pub fn author_id(&self) -> Option<&str> {
+        self.attributed_to.as_ref().and_then(OneOrMany::first_id)
+    }
+
+    /// Returns the poll if the note is a question with choices.
+    pub fn poll(&self) -> Option<Poll<'_>> {
+        match (&self.one_of, &self.any_of) {
+            (_, Some(choices)) if !choices.is_empty() => Some(Poll {
+                choices,
+                multiple: true,
+            }),
+            (Some(choices), _) => Some(Poll {
+                choices,
+                multiple: false,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Returns the id of the quoted note, as TypeScript takes it from
+    /// `_misskey_quote`, `quoteUrl`, or `quoteUri`.
+    pub fn quote_id(&self) -> Option<&str> {
+        self.misskey_quote
+            .as_deref()
+            .or(self.quote_url.as_deref())
+            .or(self.quote_uri.as_deref())
+    }
+
+    fn tags(&self) -> impl Iterator<Item = &Tag> {
+        self.tag
+            .iter()
+            .flat_map(OneOrMany::iter)
+            .filter_map(Lenient::known)
+    }
+
+    pub fn mentions(&self) -> impl Iterator<Item = &Mention> {
+        self.tags().filter_map(|tag| match tag {
+            Tag::Mention(mention) => Some(mention),
+            _ => None,
+        })
+    }
+
+    pub fn hashtags(&self) -> impl Iterator<Item = &Hashtag> {
+        self.tags().filter_map(|tag| match tag {
+            Tag::Hashtag(hashtag) => Some(hashtag),
+            _ => None,
+        })
+    }
+
+    pub fn emojis(&self) -> impl Iterator<Item = &Emoji> {
+        self.tags().filter_map(|tag| match tag {
+            Tag::Emoji(emoji) => Some(emoji),
+            _ => None,
+        })
+    }
+
+    /// Returns the files, skipping malformed attachments.
+    pub fn attachments(&self) -> impl Iterator<Item = &Attachment> {
+        self.attachment
+            .iter()
+            .flat_map(OneOrMany::iter)
+            .filter_map(Lenient::known)
+    }
+}
+
+#[cfg(test)]
+mod unit_test {
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    use super::{Note, NoteType};
+
+    #[test]
+    fn mastodon_note() {
+        let note: Note = serde_json::from_value(json!({
+            "@context": ["https://www.w3.org/ns/activitystreams", { "sensitive": "as:sensitive" }],
+            "id": "https://mastodon.example/users/alice/statuses/1",
+            "type": "Note",
+            "summary": null,
+            "inReplyTo": null,
+            "published": "2023-06-01T00:00:00Z",
+            "url": "https://mastodon.example/@alice/1",
+            "attributedTo": "https://mastodon.example/users/alice",
+            "to": ["https://www.w3.org/ns/activitystreams#Public"],
+            "cc": ["https://mastodon.example/users/alice/followers"],
+            "sensitive": false,
+            "content": "<p>Hello <a href=\"https://misskey.example/@bob\" class=\"u-url mention\">@bob</a></p>",
+            "contentMap": { "en": "<p>Hello</p>" },
+            "attachment": [
+                {
+                    "type": "Document",
+                    "mediaType": "image/png",
+                    "url": "https://mastodon.example/a.png",
+                    "name": null,
+                    "blurhash": "UBL_:rOpGG-oBUNG,qRj2so|=eE1w^n4S5NH",
+                    "width": 640,
+                    "height": 480
+                },
+                "https://mastodon.example/broken"
+            ],
+            "tag": [
+                { "type": "Mention", "href": "https://misskey.example/users/9", "name": "@bob@misskey.example" },
+                { "type": "Hashtag", "href": "https://mastodon.example/tags/rust", "name": "#rust" },
+                { "type": "Emoji", "id": "https://mastodon.example/emojis/1", "name": ":blobcat:",
+                  "icon": { "type": "Image", "mediaType": "image/png", "url": "https://mastodon.example/blobcat.png" } },
+                { "type": "Hashtag" }
+            ],
+            "replies": {
+                "id": "https://mastodon.example/users/alice/statuses/1/replies",
+                "type": "Collection",
+                "first": { "type": "CollectionPage", "items": [] }
+            }
+        }))
+        .unwrap();
+
+        assert_eq!(note.kind, NoteType::Note);
+        assert_eq!(
+            note.author_id(),
+            Some("https://mastodon.example/users/alice")
+        );
+        assert_eq!(note.in_reply_to, None);
+        assert_eq!(
+            note.mentions().map(|m| m.href.as_str()).collect::<Vec<_>>(),
+            vec!["https://misskey.example/users/9"]
+        );
+        assert_eq!(
+            note.hashtags().map(|h| h.name.as_str()).collect::<Vec<_>>(),
+            vec!["#rust"]
+        );
+        assert_eq!(
+            note.emojis().next().and_then(|e| e.icon.url()),
+            Some("https://mastodon.example/blobcat.png")
+        );
+        assert_eq!(
+            note.attachments().map(|a| a.url()).collect::<Vec<_>>(),
+            vec![Some("https://mastodon.example/a.png")]
+        );
+        assert_eq!(note.poll(), None);
+    }
+
+    #[test]
+    fn misskey_note() {
+        let note: Note = serde_json::from_value(json!({
+            "id": "https://misskey.example/notes/9",
+            "type": "Note",
+            "attributedTo": { "type": "Person", "id": "https://misskey.example/users/9", "inbox": "https://misskey.example/users/9/inbox" },
+            "content": "<p><span>hi</span></p>",
+            "_misskey_content": "hi $[spin 🍮]",
+            "source": { "content": "hi $[spin 🍮]", "mediaType": "text/x.misskeymarkdown" },
+            "_misskey_quote": "https://mastodon.example/users/alice/statuses/1",
+            "quoteUrl": "https://mastodon.example/users/alice/statuses/1",
+            "inReplyTo": { "id": "https://misskey.example/notes/8", "type": "Note" },
+            "to": "https://www.w3.org/ns/activitystreams#Public",
+            "tag": { "type": "Hashtag", "name": "#misskey" },
+            "attachment": [],
+        }))
+        .unwrap();
+
+        assert_eq!(note.author_id(), Some("https://misskey.example/users/9"));
+        assert_eq!(
+            note.in_reply_to.as_ref().and_then(|r| r.id()),
+            Some("https://misskey.example/notes/8")
+        );
+        assert_eq!(
+            note.to.as_ref().map(|to| to.ids()),
+            Some(vec!["https://www.w3.org/ns/activitystreams#Public"])
+        );
+        assert_eq!(
+            note.quote_id(),
+            Some("https://mastodon.example/users/alice/statuses/1")
+        );
+        assert_eq!(note.hashtags().count(), 1);
+    }
+
+    #[test]
+    fn question() {
+        let note: Note = serde_json::from_value(json!({
+            "id": "https://mastodon.example/users/alice/statuses/2",
+            "type": ["Question"],
+            "attributedTo": ["https://mastodon.example/users/alice"],
+            "content": "<p>Which?</p>",
+            "endTime": "2023-06-02T00:00:00Z",
+            "closed": true,
+            "votersCount": 3,
+            "anyOf": [
+                { "type": "Note", "name": "a", "replies": { "type": "Collection", "totalItems": 2 } },
+                { "type": "Note", "name": "b", "_misskey_votes": 1 }
+            ]
+        }))
+        .unwrap();
+
+        assert_eq!(note.kind, NoteType::Question);
+        assert_eq!(
+            note.author_id(),
+            Some("https://mastodon.example/users/alice")
+        );
+        let poll = note.poll().unwrap();
+        assert!(poll.multiple);
+        assert_eq!(
+            poll.choices
+                .iter()
+                .map(|c| (c.name.as_str(), c.votes()))
+                .collect::<Vec<_>>(),
+            vec![("a", 2), ("b", 1)]
+        );
+    }
+
+    #[test]
+    fn unknown_type() {
+        assert!(serde_json::from_value::<Note>(json!({
+            "id": "https://example.com/1",
+            "type": "Person",
+        }))
+        .is_err());
+    }
+}
@@ -0,0 +1,254 @@
+//! Objects embedded in actors, notes, and activities, and [Object], which is
+//! any object an activity may refer to
+
+use std::collections::BTreeMap;
+
+use serde::de::value::StrDeserializer;
+use serde::de::{DeserializeOwned, Error as _};
+use serde::{Deserialize, Deserializer, Serialize};
+
+use super::activity::{Activity, ActivityType};
+use super::actor::{Actor, ActorType};
+use super::note::{Note, NoteType};
+use super::value::{type_names, ApObject, OneOrMany, Reference};
+
+/// `https://www.w3.org/ns/activitystreams#Link`. `url` properties may hold
+/// these instead of plain urls.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Link {
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<String>,
+    pub href: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub media_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rel: Option<OneOrMany<String>>,
+}
+
+impl ApObject for Link {
+    fn ap_id(&self) -> Option<&str> {
+        Some(&self.href)
+    }
+}
+
+/// `icon` and `image` of actors and custom emojis.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Image {
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<Reference<Link>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub media_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sensitive: Option<bool>,
+}
+
+impl Image {
+    pub fn url(&self) -> Option<&str> {
+        self.url.as_ref().and_then(Reference::id)
+    }
+}
+
+/// Entry of `tag`, as `IApMention`, `IApHashtag`, and `IApEmoji` of
+/// `type.ts`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum Tag {
+    Mention(Mention),
+    Hashtag(Hashtag),
+    Emoji(Emoji),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Mention {
+    /// Id of the mentioned actor.
+    pub href: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Hashtag {
+    /// Name of the hashtag, usually with `#`.
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub href: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Emoji {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// Name of the custom emoji, with colons.
+    pub name: String,
+    pub icon: Image,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated: Option<String>,
+}
+
+/// Entry of `attachment`, which is either a file (`Document`, `Image`, ...)
+/// or a profile field (`PropertyValue`).
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Attachment {
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<OneOrMany<Reference<Link>>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub media_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Value of a profile field, in HTML.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blurhash: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sensitive: Option<bool>,
+}
+
+impl Attachment {
+    pub fn is_property_value(&self) -> bool {
+        self.kind.as_deref() == Some("PropertyValue")
+    }
+
+    pub fn url(&self) -> Option<&str> {
+        self.url.as_ref().and_then(OneOrMany::first_id)
+    }
+}
+
+/// `source` of notes, which holds the original text when it is not HTML.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Source {
+    pub content: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub media_type: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Tombstone {
+    pub id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub former_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted: Option<String>,
+}
+
+impl ApObject for Tombstone {
+    fn ap_id(&self) -> Option<&str> {
+        Some(&self.id)
+    }
+}
+
+/// `Collection`, `OrderedCollection`, and their pages.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Collection {
+    #[serde(rename = "@context", default, skip_serializing_if = "Option::is_none")]
+    pub context: Option<serde_json::Value>,
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total_items: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub items: Option<OneOrMany<Reference<Object>>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ordered_items: Option<OneOrMany<Reference<Object>>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub first: Option<Reference<Collection>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next: Option<Reference<Collection>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub part_of: Option<String>,
+}
+
+impl ApObject for Collection {
+    fn ap_id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+}
+
+impl Collection {
+    /// Returns `orderedItems` or `items`, whichever is present.
+    pub fn items(&self) -> impl Iterator<Item = &Reference<Object>> {
+        self.ordered_items
+            .iter()
+            .chain(self.items.iter())
+            .flat_map(OneOrMany::iter)
+    }
+}
+
+/// Any object an activity may refer to, told apart by `type`. Objects of
+/// unknown types are kept as they are.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(untagged)]
+pub enum Object {
+    Actor(Actor),
+    Note(Note),
+    Activity(Activity),
+    Tombstone(Tombstone),
+    Collection(Collection),
+    Other(serde_json::Value),
+}
+
+const COLLECTION_TYPES: [&str; 4] = [
+    "Collection",
+    "OrderedCollection",
+    "CollectionPage",
+    "OrderedCollectionPage",
+];
+
+fn is_type<T: DeserializeOwned>(name: &str) -> bool {
+    T::deserialize(StrDeserializer::<serde::de::value::Error>::new(name)).is_ok()
+}
+
+impl<'de> Deserialize<'de> for Object {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = serde_json::Value::deserialize(deserializer)?;
+        let names = type_names(&value);
+        let object = if names.iter().any(|n| is_type::<ActorType>(n)) {
+            serde_json::from_value(value).map(Self::Actor)
+        } else if names.iter().any(|n| is_type::<NoteType>(n)) {
+            serde_json::from_value(value).map(Self::Note)
+        } else if names.iter().any(|n| is_type::<ActivityType>(n)) {
+            serde_json::from_value(value).map(Self::Activity)
+        } else if names.contains(&"Tombstone") {
+            serde_json::from_value(value).map(Self::Tombstone)
+        } else if names.iter().any(|n| COLLECTION_TYPES.contains(n)) {
+            serde_json::from_value(value).map(Self::Collection)
+        } else {
+            Ok(Self::Other(value))
+        };
+        object.map_err(D::Error::custom)
+    }
+}
+
+impl ApObject for Object {
+    fn ap_id(&self) -> Option<&str> {
+        match self {
+            Self::Actor(actor) => actor.ap_id(),
+            Self::Note(note) => note.ap_id(),
+            Self::Activity(activity) => activity.ap_id(),
+            Self::Tombstone(tombstone) => tombstone.ap_id(),
+            Self::Collection(collection) => collection.ap_id(),
+            Self::Other(value) => value.ap_id(),
+        }
+    }
+}
+
+/// Language-tagged values, e.g. `contentMap`.
+pub type LanguageMap = BTreeMap<String, String>;
@@ -0,0 +1,383 @@
+//! Federation policy compiled from `meta`, ported from
+//! `misc/should-block-instance.ts` and the host checks of the inbox and
+//! deliver processors
+//!
+//! Hosts and patterns are compared in lower-case punycode. A pattern matches
+//! the host and its subdomains, and `*` in a pattern matches any characters,
+//! e.g. `*.example.com` matches the subdomains but not `example.com` itself.
+//!
+//! The compiled policy is reloaded after [POLICY_TTL], as `misc/fetch-meta.ts`
+//! refetches `meta` every 10 seconds, so that changes made by any worker
+//! reach every worker. [invalidate_policy] reloads it right away.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use cfg_if::cfg_if;
+use once_cell::sync::Lazy;
+use regex::RegexSet;
+use sea_orm::EntityTrait;
+
+use super::error::Error;
+use crate::acct::to_puny;
+use crate::database;
+use crate::model::entity::newtype::from_string_vec;
+use crate::model::entity::{meta, user_profile};
+use crate::model::meta::current_meta;
+
+/// The compiled policy is reloaded after this long.
+pub const POLICY_TTL: Duration = Duration::from_secs(10);
+
+/// The compiled policy with the time it was compiled.
+type Compiled = Option<(Instant, Arc<FederationPolicy>)>;
+
+/// Dropped by [invalidate_policy].
+static POLICY: Lazy<RwLock<Compiled>> = Lazy::new(Default::default);
+/// Incremented by [invalidate_policy], so that a policy compiled from
+/// `meta` read before the invalidation is not kept.
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Compiled muted instances are cached for this long, as muted words are.
+const MUTES_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// Compiled muted instances keyed by user ids, with the time they were
+/// cached.
+type Mutes = HashMap<String, (Instant, Arc<HostMatcher>)>;
+
+static MUTES: Lazy<RwLock<Mutes>> = Lazy::new(Default::default);
+
+/// Converts the host or a label to lower-case punycode, keeping it as is
+/// if it is not a valid host.
+fn normalize(host: &str) -> String {
+    let host = host.trim().to_lowercase();
+    to_puny(&host).unwrap_or(host)
+}
+
+/// Set of host patterns.
+#[derive(Clone, Debug, Default)]
+pub struct HostMatcher {
+    hosts: HashSet<String>,
+    wildcards: Option<RegexSet>,
+}
+
+impl HostMatcher {
+    pub fn new<S: AsRef<str>>(patterns: impl IntoIterator<Item = S>) -> Self {
+        let mut hosts = HashSet::new();
+        let mut wildcards = Vec::new();
+        for pattern in patterns {
+            let pattern = pattern.as_ref().trim();
+            if pattern.is_empty() {
+                continue;
+            }
+            if !pattern.contains('*') {
+                hosts.insert(normalize(pattern));
+                continue;
+            }
+            let labels: Vec<String> = pattern
+                .split('.')
+                .map(|label| match label.contains('*') {
+                    true => regex::escape(&label.to_lowercase()).replace(r"\*", ".*"),
+                    false => regex::escape(&normalize(label)),
+                })
+                .collect();
+            wildcards.push(format!("^{}$", labels.join(r"\.")));
+        }
+
+        Self {
+            hosts,
+            // Patterns are escaped, so that they always compile.
+            wildcards: (!wildcards.is_empty())
+                .then(|| RegexSet::new(wildcards).expect("Invalid host pattern")),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hosts.is_empty() && self.wildcards.is_none()
+    }
+
+    /// Returns `true` if the host or its parent domain matches. Patterns
+    /// without a port match the host on any port.
+    pub fn matches(&self, host: &str) -> bool {
+        if self.is_empty() {
+            return false;
+        }
+        let host = normalize(host);
+        let hostname = match host.rsplit_once(':') {
+            Some((name, port)) if port.bytes().all(|b| b.is_ascii_digit()) => name,
+            _ => host.as_str(),
+        };
+
+        let mut suffixes = vec![host.as_str()];
+        let mut rest = hostname;
+        loop {
+            suffixes.push(rest);
+            match rest.split_once('.') {
+                Some((_, parent)) if !parent.is_empty() => rest = parent,
+                _ => break,
+            }
+        }
+        suffixes.iter().any(|s| self.hosts.contains(*s))
+            || self
+                .wildcards
+                .as_ref()
+                .is_some_and(|set| set.is_match(&host) || set.is_match(hostname))
+    }
+}
+
+/// Which remote hosts the server federates with.
+#[derive(Clone, Debug, Default)]
+pub struct FederationPolicy {
+    blocked: HostMatcher,
+    silenced: HostMatcher,
+    allowed: HostMatcher,
+    secure_mode: bool,
+    private_mode: bool,
+}
+
+impl FederationPolicy {
+    pub fn new(meta: &meta::Model) -> Self {
+        let blocked = from_string_vec(meta.blocked_hosts.to_owned());
+        let silenced = from_string_vec(meta.silenced_hosts.to_owned());
+        let allowed = meta
+            .allowed_hosts
+            .to_owned()
+            .map(from_string_vec)
+            .unwrap_or_default();
+        Self {
+            blocked: HostMatcher::new(blocked),
+            silenced: HostMatcher::new(silenced),
+            allowed: HostMatcher::new(allowed),
+            secure_mode: meta.secure_mode.unwrap_or_default(),
+            private_mode: meta.private_mode.unwrap_or_default(),
+        }
+    }
+
+    /// Returns `true` if the host is blocked, or not allowed in private
+    /// mode. Blocking takes precedence over allowing.
+    pub fn is_blocked(&self, host: &str) -> bool {
+        self.blocked.matches(host) || (self.private_mode && !self.allowed.matches(host))
+    }
+
+    /// Returns `true` if activities may be sent to or fetched from the host.
+    pub fn can_deliver(&self, host: &str) -> bool {
+        !self.is_blocked(host)
+    }
+
+    /// Returns `true` if activities from the host may be processed.
+    pub fn can_accept(&self, host: &str) -> bool {
+        !self.is_blocked(host)
+    }
+
+    /// Returns `true` if follows from the host need approval, and its notes
+    /// are hidden from the global timeline.
+    pub fn is_silenced(&self, host: &str) -> bool {
+        self.silenced.matches(host)
+    }
+
+    /// Returns `true` if `GET` of ActivityPub objects must be signed.
+    pub fn requires_signed_fetch(&self) -> bool {
+        self.secure_mode || self.private_mode
+    }
+}
+
+/// Compiles `user_profile.muted_instances`. Values other than an array of
+/// strings mute nothing.
+pub fn muted_matcher(muted_instances: &serde_json::Value) -> HostMatcher {
+    match muted_instances.as_array() {
+        Some(hosts) => HostMatcher::new(hosts.iter().filter_map(|h| h.as_str())),
+        None => HostMatcher::default(),
+    }
+}
+
+/// Returns `true` if the host is in `user_profile.muted_instances`.
+pub fn is_muted(host: &str, muted_instances: &serde_json::Value) -> bool {
+    muted_matcher(muted_instances).matches(host)
+}
+
+/// Returns the policy compiled from `meta`, compiling it again if it is
+/// older than [POLICY_TTL] or [invalidate_policy] was called. Without
+/// `meta`, every host is allowed.
+pub async fn get_policy() -> Result<Arc<FederationPolicy>, Error> {
+    if let Some((compiled_at, policy)) = POLICY.read().unwrap().as_ref() {
+        if compiled_at.elapsed() < POLICY_TTL {
+            return Ok(policy.to_owned());
+        }
+    }
+
+    let generation = GENERATION.load(Ordering::SeqCst);
+    let meta = current_meta(database::get_database()?).await?;
+    let policy = Arc::new(meta.as_ref().map(FederationPolicy::new).unwrap_or_default());
+
+    let mut cached = POLICY.write().unwrap();
+    if GENERATION.load(Ordering::SeqCst) == generation {
+        *cached = Some((Instant::now(), policy.to_owned()));
+    }
+    Ok(policy)
+}
+
+/// Drops the compiled policy of this process. Call this after updating
+/// `meta`; other processes pick up the change within [POLICY_TTL].
+pub fn invalidate_policy() {
+    GENERATION.fetch_add(1, Ordering::SeqCst);
+    *POLICY.write().unwrap() = None;
+}
+
+/// Returns the muted instances of the user, compiled and cached. Users
+/// without a profile mute nothing.
+pub async fn get_muted_instances(user_id: &str) -> Result<Arc<HostMatcher>, Error> {
+    if let Some((cached_at, muted)) = MUTES.read().unwrap().get(user_id) {
+        if cached_at.elapsed() < MUTES_TTL {
+            return Ok(muted.to_owned());
+        }
+    }
+
+    let profile = user_profile::Entity::find_by_id(user_id.to_string())
+        .one(database::get_database()?)
+        .await?;
+    let muted = Arc::new(
+        profile
+            .map(|p| muted_matcher(&p.muted_instances))
+            .unwrap_or_default(),
+    );
+    let mut mutes = MUTES.write().unwrap();
+    // Drop expired entries so that users who went away do not pile up.
+    mutes.retain(|_, (cached_at, _)| cached_at.elapsed() < MUTES_TTL);
+    mutes.insert(user_id.to_string(), (Instant::now(), muted.to_owned()));
+    Ok(muted)
+}
+
+/// Drops the cached muted instances of the user. Call this after updating
+/// `user_profile.muted_instances`.
+pub fn invalidate_muted_instances(user_id: &str) {
+    MUTES.write().unwrap().remove(user_id);
+}
+
+/// Returns `true` if the user muted the host.
+pub async fn is_muted_for_user(user_id: &str, host: &str) -> Result<bool, Error> {
+    Ok(get_muted_instances(user_id).await?.matches(host))
+}
+
+cfg_if! {
+    if #[cfg(feature = "napi")] {
+        use napi_derive::napi;
+
+        #[napi]
+        pub async fn native_can_deliver(host: String) -> napi::Result<bool> {
+            let policy = get_policy().await.map_err(Into::<napi::Error>::into)?;
+            Ok(policy.can_deliver(&host))
+        }
+
+        #[napi]
+        pub async fn native_can_accept(host: String) -> napi::Result<bool> {
+            let policy = get_policy().await.map_err(Into::<napi::Error>::into)?;
+            Ok(policy.can_accept(&host))
+        }
+
+        #[napi]
+        pub async fn native_is_silenced_host(host: String) -> napi::Result<bool> {
+            let policy = get_policy().await.map_err(Into::<napi::Error>::into)?;
+            Ok(policy.is_silenced(&host))
+        }
+
+        #[napi]
+        pub async fn native_requires_signed_fetch() -> napi::Result<bool> {
+            let policy = get_policy().await.map_err(Into::<napi::Error>::into)?;
+            Ok(policy.requires_signed_fetch())
+        }
+
+        #[napi]
+        pub async fn native_is_muted_for_user(user_id: String, host: String) -> napi::Result<bool> {
+            is_muted_for_user(&user_id, &host).await.map_err(Into::into)
+        }
+
+        #[napi]
+        pub fn native_invalidate_federation_policy() {
+            invalidate_policy()
+        }
+
+        #[napi]
+        pub fn native_invalidate_muted_instances(user_id: String) {
+            invalidate_muted_instances(&user_id)
+        }
+    }
+}
+
+#[cfg(test)]
+mod unit_test {
+    use serde_json::json;
+
+    use super::{is_muted, FederationPolicy, HostMatcher};
+    use crate::model::entity::meta;
+    use crate::model::entity::newtype::string_vec;
+
+    #[test]
+    fn hosts() {
+        let matcher = HostMatcher::new(["blocked.example", " Bücher.Example ", ""]);
+        assert!(matcher.matches("blocked.example"));
+        assert!(matcher.matches("sub.blocked.example"));
+        assert!(matcher.matches("BLOCKED.example:8443"));
+        assert!(!matcher.matches("notblocked.example"));
+        assert!(!matcher.matches("example"));
+        assert!(matcher.matches("xn--bcher-kva.example"));
+        assert!(matcher.matches("a.bücher.example"));
+
+        let matcher = HostMatcher::new(["*.wild.example", "bad*.example", "localhost:3000"]);
+        assert!(matcher.matches("a.b.wild.example"));
+        assert!(!matcher.matches("wild.example"));
+        assert!(matcher.matches("badhost.example"));
+        assert!(!matcher.matches("good.example"));
+        assert!(matcher.matches("localhost:3000"));
+        assert!(!matcher.matches("localhost:3001"));
+        assert!(!matcher.matches("localhost"));
+
+        assert!(HostMatcher::default().is_empty());
+        assert!(!HostMatcher::default().matches("example.com"));
+    }
+
+    #[test]
+    fn policy() {
+        let mut meta = meta::Model {
+            blocked_hosts: string_vec(vec!["blocked.example".to_string()]),
+            silenced_hosts: string_vec(vec!["*.silenced.example".to_string()]),
+            allowed_hosts: Some(string_vec(vec!["friend.example".to_string()])),
+            ..Default::default()
+        };
+
+        let policy = FederationPolicy::new(&meta);
+        assert!(policy.can_deliver("remote.example"));
+        assert!(!policy.can_deliver("sub.blocked.example"));
+        assert!(!policy.can_accept("blocked.example"));
+        assert!(policy.is_silenced("a.silenced.example"));
+        assert!(!policy.is_silenced("silenced.example"));
+        assert!(!policy.requires_signed_fetch());
+
+        meta.private_mode = Some(true);
+        meta.allowed_hosts = Some(string_vec(vec![
+            "friend.example".to_string(),
+            "blocked.example".to_string(),
+        ]));
+        let policy = FederationPolicy::new(&meta);
+        assert!(policy.can_accept("friend.example"));
+        assert!(!policy.can_accept("remote.example"));
+        assert!(!policy.can_deliver("blocked.example"));
+        assert!(policy.requires_signed_fetch());
+
+        let policy = FederationPolicy::new(&meta::Model {
+            secure_mode: Some(true),
+            ..Default::default()
+        });
+        assert!(policy.can_deliver("remote.example"));
+        assert!(policy.requires_signed_fetch());
+    }
+
+    #[test]
+    fn mutes() {
+        let muted = json!(["muted.example", 1]);
+        assert!(is_muted("muted.example", &muted));
+        assert!(is_muted("sub.muted.example", &muted));
+        assert!(!is_muted("remote.example", &muted));
+        assert!(!is_muted("muted.example", &json!({})));
+    }
+}
@@ -0,0 +1,81 @@
+//! Ported from `renderer/flag.ts`
+
+use crate::activitypub::activity::{Activity, ActivityType};
+use crate::activitypub::value::{OneOrMany, Reference};
+use crate::model::entity::{abuse_user_report, user};
+
+/// Renders the report forwarded to the server of the reported user. The
+/// actor is the instance actor, not the reporter, to anonymise reporters.
+/// `None` if the reported user has no uri, i.e. is local.
+pub fn render_flag(
+    url: &str,
+    report: &abuse_user_report::Model,
+    instance_actor: &user::Model,
+    target_user: &user::Model,
+) -> Option<Activity> {
+    let target_uri = target_user.uri.to_owned()?;
+    Some(Activity {
+        object: Some(OneOrMany::Many(vec![Reference::Id(target_uri)])),
+        content: Some(report.comment.to_owned()),
+        ..Activity::new(
+            ActivityType::Flag,
+            format!("{}/users/{}", url, instance_actor.id),
+        )
+    })
+}
+
+#[cfg(test)]
+mod unit_test {
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    use super::render_flag;
+    use crate::model::entity::{abuse_user_report, user};
+
+    #[test]
+    fn flag() {
+        let report = abuse_user_report::Model {
+            id: "r1".to_string(),
+            target_user_id: "9b".to_string(),
+            reporter_id: "9c".to_string(),
+            comment: "spam".to_string(),
+            ..Default::default()
+        };
+        let instance_actor = user::Model {
+            id: "9a".to_string(),
+            username: "instance.actor".to_string(),
+            ..Default::default()
+        };
+        let target = user::Model {
+            id: "9b".to_string(),
+            host: Some("remote.example".to_string()),
+            uri: Some("https://remote.example/users/b".to_string()),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            serde_json::to_value(render_flag(
+                "https://example.com",
+                &report,
+                &instance_actor,
+                &target
+            ))
+            .unwrap(),
+            json!({
+                "type": "Flag",
+                "actor": "https://example.com/users/9a",
+                "object": ["https://remote.example/users/b"],
+                "content": "spam",
+            })
+        );
+        assert_eq!(
+            render_flag(
+                "https://example.com",
+                &report,
+                &instance_actor,
+                &instance_actor
+            ),
+            None
+        );
+    }
+}
@@ -0,0 +1,72 @@
+//! Ported from `renderer/follow.ts`
+
+use super::user_uri;
+use crate::activitypub::activity::{Activity, ActivityType};
+use crate::activitypub::value::{OneOrMany, Reference};
+use crate::model::entity::user;
+
+/// Renders the follow of `followee` by `follower`, which is either a
+/// `following` row or a follow request. `request_id` is the id of the
+/// `Follow` received from a remote follower, which must be used in the
+/// `Accept` or `Reject`.
+pub fn render_follow(
+    url: &str,
+    follower: &user::Model,
+    followee: &user::Model,
+    request_id: Option<&str>,
+) -> Activity {
+    Activity {
+        id: Some(
+            request_id
+                .map(str::to_string)
+                .unwrap_or_else(|| format!("{}/follows/{}/{}", url, follower.id, followee.id)),
+        ),
+        object: Some(OneOrMany::One(Reference::Id(user_uri(url, followee)))),
+        ..Activity::new(ActivityType::Follow, user_uri(url, follower))
+    }
+}
+
+#[cfg(test)]
+mod unit_test {
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    use super::render_follow;
+    use crate::model::entity::user;
+
+    #[test]
+    fn follow() {
+        let local = user::Model {
+            id: "9a".to_string(),
+            ..Default::default()
+        };
+        let remote = user::Model {
+            id: "9b".to_string(),
+            host: Some("remote.example".to_string()),
+            uri: Some("https://remote.example/users/b".to_string()),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            serde_json::to_value(render_follow("https://example.com", &local, &remote, None))
+                .unwrap(),
+            json!({
+                "type": "Follow",
+                "id": "https://example.com/follows/9a/9b",
+                "actor": "https://example.com/users/9a",
+                "object": "https://remote.example/users/b",
+            })
+        );
+        assert_eq!(
+            render_follow(
+                "https://example.com",
+                &remote,
+                &local,
+                Some("https://remote.example/follows/1")
+            )
+            .id
+            .as_deref(),
+            Some("https://remote.example/follows/1")
+        );
+    }
+}
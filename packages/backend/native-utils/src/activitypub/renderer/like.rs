@@ -0,0 +1,132 @@
+//! Ported from `renderer/like.ts`
+
+use super::render_emoji;
+use crate::activitypub::activity::{Activity, ActivityType};
+use crate::activitypub::object::Tag;
+use crate::activitypub::value::{Lenient, OneOrMany, Reference};
+use crate::model::entity::{emoji, note, note_reaction};
+
+fn render_reaction(
+    kind: ActivityType,
+    url: &str,
+    reaction: &note_reaction::Model,
+    note: &note::Model,
+    emojis: &[emoji::Model],
+) -> Activity {
+    let emoji = reaction
+        .reaction
+        .starts_with(':')
+        .then(|| reaction.reaction.replace(':', ""))
+        .and_then(|name| emojis.iter().find(|e| e.name == name && e.host.is_none()));
+
+    Activity {
+        id: Some(format!("{}/likes/{}", url, reaction.id)),
+        object: Some(OneOrMany::One(Reference::Id(
+            note.uri
+                .to_owned()
+                .unwrap_or_else(|| format!("{}/notes/{}", url, reaction.note_id)),
+        ))),
+        tag: emoji.map(|e| OneOrMany::Many(vec![Lenient::Known(Tag::Emoji(render_emoji(url, e)))])),
+        ..Activity::new(kind, format!("{}/users/{}", url, reaction.user_id))
+    }
+}
+
+/// Renders the reaction as `Like`, which is a favourite on servers without
+/// reactions. Reactions other than `default_reaction` of the instance are
+/// sent in `content` and `_misskey_reaction`. `emojis` should contain the
+/// custom emoji of the reaction.
+pub fn render_like(
+    url: &str,
+    reaction: &note_reaction::Model,
+    note: &note::Model,
+    default_reaction: &str,
+    emojis: &[emoji::Model],
+) -> Activity {
+    let like = render_reaction(ActivityType::Like, url, reaction, note, emojis);
+    if default_reaction.contains(&reaction.reaction) {
+        like
+    } else {
+        Activity {
+            content: Some(reaction.reaction.to_owned()),
+            misskey_reaction: Some(reaction.reaction.to_owned()),
+            ..like
+        }
+    }
+}
+
+/// Renders the reaction as `EmojiReact` of Pleroma, which always carries the
+/// emoji.
+pub fn render_emoji_react(
+    url: &str,
+    reaction: &note_reaction::Model,
+    note: &note::Model,
+    emojis: &[emoji::Model],
+) -> Activity {
+    Activity {
+        content: Some(reaction.reaction.to_owned()),
+        ..render_reaction(ActivityType::EmojiReact, url, reaction, note, emojis)
+    }
+}
+
+#[cfg(test)]
+mod unit_test {
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    use super::{render_emoji_react, render_like};
+    use crate::model::entity::{emoji, note, note_reaction};
+
+    const URL: &str = "https://example.com";
+
+    #[test]
+    fn like() {
+        let note = note::Model {
+            id: "9b".to_string(),
+            uri: Some("https://remote.example/notes/1".to_string()),
+            ..Default::default()
+        };
+        let star = note_reaction::Model {
+            id: "r1".to_string(),
+            user_id: "9a".to_string(),
+            note_id: "9b".to_string(),
+            reaction: "⭐".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            serde_json::to_value(render_like(URL, &star, &note, "⭐", &[])).unwrap(),
+            json!({
+                "type": "Like",
+                "id": "https://example.com/likes/r1",
+                "actor": "https://example.com/users/9a",
+                "object": "https://remote.example/notes/1",
+            })
+        );
+
+        let custom = note_reaction::Model {
+            reaction: ":blobcat:".to_string(),
+            ..star
+        };
+        let emojis = vec![emoji::Model {
+            name: "blobcat".to_string(),
+            public_url: "https://example.com/files/blobcat.png".to_string(),
+            updated_at: Some(chrono::DateTime::parse_from_rfc3339("2023-06-01T00:00:00Z").unwrap()),
+            ..Default::default()
+        }];
+        let like = serde_json::to_value(render_like(URL, &custom, &note, "⭐", &emojis)).unwrap();
+        assert_eq!(like["content"], ":blobcat:");
+        assert_eq!(like["_misskey_reaction"], ":blobcat:");
+        assert_eq!(like["tag"][0]["name"], ":blobcat:");
+
+        let react = render_emoji_react(URL, &custom, &note::Model::default(), &[]);
+        assert_eq!(
+            serde_json::to_value(react).unwrap(),
+            json!({
+                "type": "EmojiReact",
+                "id": "https://example.com/likes/r1",
+                "actor": "https://example.com/users/9a",
+                "object": "https://example.com/notes/9b",
+                "content": ":blobcat:",
+            })
+        );
+    }
+}
@@ -0,0 +1,255 @@
+//! Renderers of our models into ActivityPub objects, ported from
+//! `remote/activitypub/renderer`
+//!
+//! `url` arguments are the origin of the server, e.g. `https://example.com`.
+//! Related models are passed in by the caller, so that the same output is
+//! produced wherever the objects are rendered.
+
+pub mod flag;
+pub mod follow;
+pub mod like;
+pub mod note;
+pub mod person;
+
+use chrono::{SecondsFormat, Utc};
+use sea_orm::prelude::DateTimeWithTimeZone;
+use serde_json::json;
+
+use super::object::{Attachment, Emoji, Hashtag, Image, Mention};
+use super::value::Reference;
+use crate::model::entity::{drive_file, emoji, user};
+use crate::util::random::gen_string;
+
+pub use flag::render_flag;
+pub use follow::render_follow;
+pub use like::{render_emoji_react, render_like};
+pub use note::{render_note, NoteRelations};
+pub use person::render_person;
+
+/// Returns the `@context` of the activities we send, as `renderActivity`
+/// does.
+pub fn context() -> serde_json::Value {
+    json!([
+        "https://www.w3.org/ns/activitystreams",
+        "https://w3id.org/security/v1",
+        {
+            // as non-standards
+            "manuallyApprovesFollowers": "as:manuallyApprovesFollowers",
+            "movedToUri": "as:movedTo",
+            "sensitive": "as:sensitive",
+            "Hashtag": "as:Hashtag",
+            "quoteUri": "fedibird:quoteUri",
+            "quoteUrl": "as:quoteUrl",
+            // Mastodon
+            "toot": "http://joinmastodon.org/ns#",
+            "Emoji": "toot:Emoji",
+            "featured": "toot:featured",
+            "discoverable": "toot:discoverable",
+            "indexable": "toot:indexable",
+            // schema
+            "schema": "http://schema.org#",
+            "PropertyValue": "schema:PropertyValue",
+            "value": "schema:value",
+            // Firefish
+            "firefish": "https://joinfirefish.org/ns#",
+            "speakAsCat": "firefish:speakAsCat",
+            // Misskey
+            "misskey": "https://misskey-hub.net/ns#",
+            "_misskey_talk": "misskey:_misskey_talk",
+            "_misskey_reaction": "misskey:_misskey_reaction",
+            "_misskey_votes": "misskey:_misskey_votes",
+            "_misskey_summary": "misskey:_misskey_summary",
+            "isCat": "misskey:isCat",
+            // Fedibird
+            "fedibird": "http://fedibird.com/ns#",
+            // vcard
+            "vcard": "http://www.w3.org/2006/vcard/ns#",
+        },
+    ])
+}
+
+/// Adds `@context` to the top-level object, and a random `id` if it has none.
+/// The object is taken as a [serde_json::Value] so that the caller handles
+/// serialization errors instead of delivering `null`.
+pub fn render_activity(url: &str, mut value: serde_json::Value) -> serde_json::Value {
+    if let serde_json::Value::Object(map) = &mut value {
+        map.insert("@context".to_string(), context());
+        if !map.contains_key("id") {
+            map.insert(
+                "id".to_string(),
+                json!(format!("{}/{}", url, gen_string(16))),
+            );
+        }
+    }
+    value
+}
+
+/// Returns the time in ISO 8601 with milliseconds, as `toISOString` does.
+pub(crate) fn format_date(date: &DateTimeWithTimeZone) -> String {
+    date.with_timezone(&Utc)
+        .to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+/// Returns the id of the actor of the user.
+pub fn user_uri(url: &str, user: &user::Model) -> String {
+    match (&user.host, &user.uri) {
+        (Some(_), Some(uri)) => uri.to_owned(),
+        _ => format!("{}/users/{}", url, user.id),
+    }
+}
+
+/// Returns the local custom emojis of `names` in the same order.
+fn local_emojis<'a>(names: &[String], emojis: &'a [emoji::Model]) -> Vec<&'a emoji::Model> {
+    names
+        .iter()
+        .filter_map(|name| emojis.iter().find(|e| &e.name == name && e.host.is_none()))
+        .collect()
+}
+
+pub fn render_emoji(url: &str, emoji: &emoji::Model) -> Emoji {
+    Emoji {
+        id: Some(format!("{}/emojis/{}", url, emoji.name)),
+        name: format!(":{}:", emoji.name),
+        updated: Some(format_date(
+            &emoji
+                .updated_at
+                .unwrap_or_else(|| Utc::now().fixed_offset()),
+        )),
+        icon: Image {
+            kind: Some("Image".to_string()),
+            media_type: Some(
+                emoji
+                    .r#type
+                    .to_owned()
+                    .filter(|t| !t.is_empty())
+                    .unwrap_or_else(|| "image/png".to_string()),
+            ),
+            url: Some(Reference::Id(if emoji.public_url.is_empty() {
+                emoji.original_url.to_owned()
+            } else {
+                emoji.public_url.to_owned()
+            })),
+            ..Default::default()
+        },
+    }
+}
+
+pub fn render_hashtag(url: &str, tag: &str) -> Hashtag {
+    Hashtag {
+        href: Some(format!("{}/tags/{}", url, urlencoding::encode(tag))),
+        name: format!("#{}", tag),
+    }
+}
+
+pub fn render_mention(url: &str, user: &user::Model) -> Mention {
+    Mention {
+        href: user_uri(url, user),
+        name: Some(match &user.host {
+            Some(host) => format!("@{}@{}", user.username, host),
+            None => format!("@{}", user.username),
+        }),
+    }
+}
+
+/// Renders the avatar or the banner.
+pub fn render_image(file: &drive_file::Model) -> Image {
+    Image {
+        kind: Some("Image".to_string()),
+        url: file.public_url(false).map(Reference::Id),
+        sensitive: Some(file.is_sensitive),
+        name: file.comment.to_owned(),
+        ..Default::default()
+    }
+}
+
+/// Renders the file attached to a note.
+pub fn render_document(file: &drive_file::Model) -> Attachment {
+    Attachment {
+        kind: Some("Document".to_string()),
+        media_type: Some(file.r#type.to_owned()),
+        url: file.public_url(false).map(|u| Reference::Id(u).into()),
+        name: file.comment.to_owned(),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod unit_test {
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    use super::{render_activity, render_emoji, render_hashtag, user_uri};
+    use crate::activitypub::activity::{Activity, ActivityType};
+    use crate::activitypub::object::Tag;
+    use crate::model::entity::{emoji, user};
+
+    const URL: &str = "https://example.com";
+
+    #[test]
+    fn activity() {
+        let activity = render_activity(
+            URL,
+            serde_json::to_value(Activity::new(
+                ActivityType::Flag,
+                format!("{}/users/1", URL),
+            ))
+            .unwrap(),
+        );
+        assert_eq!(
+            activity["@context"][0],
+            "https://www.w3.org/ns/activitystreams"
+        );
+        assert!(activity["id"]
+            .as_str()
+            .is_some_and(|id| id.starts_with("https://example.com/")));
+        assert_eq!(activity["type"], "Flag");
+    }
+
+    #[test]
+    fn uri() {
+        let local = user::Model {
+            id: "1".to_string(),
+            ..Default::default()
+        };
+        let remote = user::Model {
+            id: "2".to_string(),
+            host: Some("remote.example".to_string()),
+            uri: Some("https://remote.example/users/a".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(user_uri(URL, &local), "https://example.com/users/1");
+        assert_eq!(user_uri(URL, &remote), "https://remote.example/users/a");
+    }
+
+    #[test]
+    fn tags() {
+        assert_eq!(
+            serde_json::to_value(Tag::Hashtag(render_hashtag(URL, "ミスキー"))).unwrap(),
+            json!({
+                "type": "Hashtag",
+                "name": "#ミスキー",
+                "href": "https://example.com/tags/%E3%83%9F%E3%82%B9%E3%82%AD%E3%83%BC",
+            })
+        );
+        let emoji = emoji::Model {
+            name: "blobcat".to_string(),
+            original_url: "https://example.com/files/blobcat.png".to_string(),
+            updated_at: Some(chrono::DateTime::parse_from_rfc3339("2023-06-01T00:00:00Z").unwrap()),
+            ..Default::default()
+        };
+        assert_eq!(
+            serde_json::to_value(Tag::Emoji(render_emoji(URL, &emoji))).unwrap(),
+            json!({
+                "type": "Emoji",
+                "id": "https://example.com/emojis/blobcat",
+                "name": ":blobcat:",
+                "updated": "2023-06-01T00:00:00.000Z",
+                "icon": {
+                    "type": "Image",
+                    "mediaType": "image/png",
+                    "url": "https://example.com/files/blobcat.png",
+                },
+            })
+        );
+    }
+}
@@ -0,0 +1,334 @@
+//! Ported from `renderer/note.ts`
+
+use chrono::Utc;
+
+use super::{
+    format_date, local_emojis, render_document, render_emoji, render_hashtag, render_mention,
+};
+use crate::activitypub::note::{Choice, Note, NoteType};
+use crate::activitypub::object::{Collection, LanguageMap, Source, Tag};
+use crate::activitypub::value::{Lenient, OneOrMany, Reference};
+use crate::activitypub::PUBLIC;
+use crate::mfm::from_html::MFM_MEDIA_TYPE;
+use crate::mfm::{parse, to_html, MentionedRemoteUser};
+use crate::model::entity::newtype::{from_i32_vec, from_string_vec};
+use crate::model::entity::sea_orm_active_enums::NoteVisibilityEnum;
+use crate::model::entity::{drive_file, emoji, note, poll, user};
+
+/// Models referred to by a note, which the caller fetches.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoteRelations<'a> {
+    pub reply: Option<&'a note::Model>,
+    pub renote: Option<&'a note::Model>,
+    /// Users of `note.mentions`.
+    pub mentioned_users: &'a [user::Model],
+    /// Files of `note.file_ids`, in any order.
+    pub files: &'a [drive_file::Model],
+    pub poll: Option<&'a poll::Model>,
+    /// Custom emojis of `note.emojis`.
+    pub emojis: &'a [emoji::Model],
+    /// Language of the text, which becomes the key of `contentMap`.
+    pub lang: Option<&'a str>,
+}
+
+fn note_uri(url: &str, note: &note::Model) -> String {
+    note.uri
+        .to_owned()
+        .unwrap_or_else(|| format!("{}/notes/{}", url, note.id))
+}
+
+/// Renders the note, or the question if it has a poll. Replies to local
+/// notes refer to them by id instead of embedding them.
+pub fn render_note(url: &str, note: &note::Model, relations: NoteRelations) -> Note {
+    let attributed_to = format!("{}/users/{}", url, note.user_id);
+    let followers = format!("{}/followers", attributed_to);
+    let mentioned_remote_users = MentionedRemoteUser::from_note(note);
+    let mentions = mentioned_remote_users.iter().map(|u| u.uri.to_owned());
+
+    let (to, cc): (Vec<String>, Vec<String>) = match note.visibility {
+        NoteVisibilityEnum::Public => (
+            vec![PUBLIC.to_string()],
+            std::iter::once(followers).chain(mentions).collect(),
+        ),
+        NoteVisibilityEnum::Home => (
+            vec![followers],
+            std::iter::once(PUBLIC.to_string())
+                .chain(mentions)
+                .collect(),
+        ),
+        NoteVisibilityEnum::Followers => (vec![followers], mentions.collect()),
+        NoteVisibilityEnum::Specified | NoteVisibilityEnum::Hidden => {
+            (mentions.collect(), Vec::new())
+        }
+    };
+    let audience = |ids: Vec<String>| {
+        Some(OneOrMany::Many(
+            ids.into_iter().map(Reference::Id).collect(),
+        ))
+    };
+
+    let quote = relations.renote.map(|renote| note_uri(url, renote));
+    let text = note.text.to_owned().unwrap_or_default();
+    let render_html = |text: &str| {
+        if text.is_empty() {
+            String::new()
+        } else {
+            to_html(&parse(text), url, &mentioned_remote_users)
+        }
+    };
+    let content = render_html(&match &quote {
+        Some(quote) => format!("{}\n\nRE: {}", text, quote),
+        None => text.to_owned(),
+    });
+
+    let files: Vec<&drive_file::Model> = from_string_vec(note.file_ids.to_owned())
+        .iter()
+        .filter_map(|id| relations.files.iter().find(|f| &f.id == id))
+        .collect();
+
+    let tag: Vec<Lenient<Tag>> = from_string_vec(note.tags.to_owned())
+        .iter()
+        .map(|tag| Tag::Hashtag(render_hashtag(url, tag)))
+        .chain(
+            relations
+                .mentioned_users
+                .iter()
+                .map(|user| Tag::Mention(render_mention(url, user))),
+        )
+        .chain(
+            local_emojis(&from_string_vec(note.emojis.to_owned()), relations.emojis)
+                .into_iter()
+                .map(|emoji| Tag::Emoji(render_emoji(url, emoji))),
+        )
+        .map(Lenient::Known)
+        .collect();
+
+    let mut rendered = Note {
+        context: None,
+        kind: NoteType::Note,
+        id: format!("{}/notes/{}", url, note.id),
+        summary: note.cw.as_ref().map(|cw| {
+            if cw.is_empty() {
+                "\u{200b}".to_string()
+            } else {
+                cw.to_owned()
+            }
+        }),
+        content_map: relations
+            .lang
+            .map(|lang| LanguageMap::from([(lang.to_string(), content.to_owned())])),
+        content: Some(content),
+        misskey_content: None,
+        source: Some(Source {
+            content: text.to_owned(),
+            media_type: Some(MFM_MEDIA_TYPE.to_string()),
+        }),
+        quote_uri: quote.to_owned(),
+        quote_url: quote,
+        misskey_quote: None,
+        published: Some(format_date(&note.created_at)),
+        updated: None,
+        url: None,
+        to: audience(to),
+        cc: audience(cc),
+        in_reply_to: relations
+            .reply
+            .map(|reply| Reference::Id(note_uri(url, reply))),
+        attachment: Some(OneOrMany::Many(
+            files
+                .iter()
+                .map(|f| Lenient::Known(render_document(f)))
+                .collect(),
+        )),
+        sensitive: Some(note.cw.is_some() || files.iter().any(|f| f.is_sensitive)),
+        tag: Some(OneOrMany::Many(tag)),
+        attributed_to: Some(OneOrMany::One(Reference::Id(attributed_to))),
+        name: None,
+        replies: None,
+        one_of: None,
+        any_of: None,
+        end_time: None,
+        closed: None,
+        voters_count: None,
+    };
+
+    if let Some(poll) = relations.poll {
+        let votes = from_i32_vec(poll.votes.to_owned());
+        let choices: Vec<Choice> = from_string_vec(poll.choices.to_owned())
+            .iter()
+            .enumerate()
+            .map(|(i, name)| Choice {
+                kind: Some("Note".to_string()),
+                name: name.to_owned(),
+                replies: Some(Collection {
+                    kind: Some("Collection".to_string()),
+                    total_items: Some(
+                        votes
+                            .get(i)
+                            .and_then(|v| u64::try_from(*v).ok())
+                            .unwrap_or(0),
+                    ),
+                    ..Default::default()
+                }),
+                misskey_votes: None,
+            })
+            .collect();
+        rendered.kind = NoteType::Question;
+        rendered.content = Some(render_html(&text));
+        match poll.expires_at {
+            Some(expires_at) if expires_at < Utc::now() => {
+                rendered.closed = Some(format_date(&expires_at).into())
+            }
+            expires_at => rendered.end_time = expires_at.as_ref().map(format_date),
+        }
+        if poll.multiple {
+            rendered.any_of = Some(choices);
+        } else {
+            rendered.one_of = Some(choices);
+        }
+    }
+
+    rendered
+}
+
+#[cfg(test)]
+mod unit_test {
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    use super::{render_note, NoteRelations};
+    use crate::model::entity::newtype::{i32_vec, string_vec};
+    use crate::model::entity::sea_orm_active_enums::NoteVisibilityEnum;
+    use crate::model::entity::{drive_file, note, poll, user};
+
+    const URL: &str = "https://example.com";
+
+    fn date(s: &str) -> chrono::DateTime<chrono::FixedOffset> {
+        chrono::DateTime::parse_from_rfc3339(s).unwrap()
+    }
+
+    #[test]
+    fn note() {
+        let note = note::Model {
+            id: "9b".to_string(),
+            user_id: "9a".to_string(),
+            created_at: date("2023-06-01T00:00:00Z"),
+            text: Some("hi @bob@remote.example #rust".to_string()),
+            cw: Some(String::new()),
+            visibility: NoteVisibilityEnum::Home,
+            mentions: string_vec(vec!["u2".to_string()]),
+            mentioned_remote_users: r#"[{"uri": "https://remote.example/users/bob", "username": "bob", "host": "remote.example"}]"#.to_string(),
+            tags: string_vec(vec!["rust".to_string()]),
+            file_ids: string_vec(vec!["f1".to_string()]),
+            ..Default::default()
+        };
+        let reply = note::Model {
+            id: "9c".to_string(),
+            uri: Some("https://remote.example/notes/1".to_string()),
+            ..Default::default()
+        };
+        let renote = note::Model {
+            id: "9d".to_string(),
+            ..Default::default()
+        };
+        let bob = user::Model {
+            id: "u2".to_string(),
+            username: "bob".to_string(),
+            host: Some("remote.example".to_string()),
+            uri: Some("https://remote.example/users/bob".to_string()),
+            ..Default::default()
+        };
+        let file = drive_file::Model {
+            id: "f1".to_string(),
+            r#type: "image/png".to_string(),
+            url: "https://example.com/files/a.png".to_string(),
+            is_sensitive: true,
+            ..Default::default()
+        };
+
+        let rendered = render_note(
+            URL,
+            &note,
+            NoteRelations {
+                reply: Some(&reply),
+                renote: Some(&renote),
+                mentioned_users: &[bob],
+                files: &[file],
+                lang: Some("en"),
+                ..Default::default()
+            },
+        );
+        let content = "<p><span>hi </span><a href=\"https://remote.example/users/bob\" class=\"u-url mention\">@bob@remote.example</a>\
+            <span> </span><a href=\"https://example.com/tags/rust\" rel=\"tag\">#rust</a><span><br><br>RE: </span>\
+            <a href=\"https://example.com/notes/9d\">https://example.com/notes/9d</a></p>";
+        assert_eq!(
+            serde_json::to_value(rendered).unwrap(),
+            json!({
+                "type": "Note",
+                "id": "https://example.com/notes/9b",
+                "attributedTo": "https://example.com/users/9a",
+                "summary": "\u{200b}",
+                "content": content,
+                "contentMap": { "en": content },
+                "source": { "content": "hi @bob@remote.example #rust", "mediaType": "text/x.misskeymarkdown" },
+                "quoteUri": "https://example.com/notes/9d",
+                "quoteUrl": "https://example.com/notes/9d",
+                "published": "2023-06-01T00:00:00.000Z",
+                "to": ["https://example.com/users/9a/followers"],
+                "cc": ["https://www.w3.org/ns/activitystreams#Public", "https://remote.example/users/bob"],
+                "inReplyTo": "https://remote.example/notes/1",
+                "attachment": [{ "type": "Document", "mediaType": "image/png", "url": "https://example.com/files/a.png" }],
+                "sensitive": true,
+                "tag": [
+                    { "type": "Hashtag", "name": "#rust", "href": "https://example.com/tags/rust" },
+                    { "type": "Mention", "href": "https://remote.example/users/bob", "name": "@bob@remote.example" },
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn question() {
+        let note = note::Model {
+            id: "9b".to_string(),
+            user_id: "9a".to_string(),
+            text: Some("which?".to_string()),
+            visibility: NoteVisibilityEnum::Specified,
+            mentioned_remote_users: "[]".to_string(),
+            has_poll: true,
+            ..Default::default()
+        };
+        let poll = poll::Model {
+            note_id: "9b".to_string(),
+            expires_at: Some(date("2000-01-01T00:00:00Z")),
+            multiple: false,
+            choices: string_vec(vec!["a".to_string(), "b".to_string()]),
+            votes: i32_vec(vec![2, 0]),
+            ..Default::default()
+        };
+
+        let rendered = render_note(
+            URL,
+            &note,
+            NoteRelations {
+                poll: Some(&poll),
+                ..Default::default()
+            },
+        );
+        let value = serde_json::to_value(&rendered).unwrap();
+        assert_eq!(value["type"], "Question");
+        assert_eq!(value["content"], "<p><span>which?</span></p>");
+        assert_eq!(value["closed"], "2000-01-01T00:00:00.000Z");
+        assert_eq!(value["endTime"], serde_json::Value::Null);
+        assert_eq!(value["to"], json!([]));
+        assert_eq!(value["sensitive"], false);
+        assert_eq!(
+            value["oneOf"],
+            json!([
+                { "type": "Note", "name": "a", "replies": { "type": "Collection", "totalItems": 2 } },
+                { "type": "Note", "name": "b", "replies": { "type": "Collection", "totalItems": 0 } },
+            ])
+        );
+        assert_eq!(rendered.poll().map(|p| p.multiple), Some(false));
+    }
+}
@@ -0,0 +1,264 @@
+//! Ported from `renderer/person.ts` and `renderer/key.ts`
+
+use serde::Deserialize;
+
+use super::{local_emojis, render_emoji, render_hashtag, render_image};
+use crate::activitypub::actor::{Actor, ActorType, Endpoints, PublicKey};
+use crate::activitypub::object::{Attachment, Tag};
+use crate::activitypub::value::{Lenient, OneOrMany, Reference};
+use crate::mfm::{parse, to_html};
+use crate::model::entity::newtype::from_string_vec;
+use crate::model::entity::{drive_file, emoji, user, user_keypair, user_profile};
+
+/// Entry of `user_profile.fields`.
+#[derive(Deserialize)]
+struct Field {
+    name: String,
+    #[serde(default)]
+    value: Option<String>,
+}
+
+/// Links the value if it is a url, as profile pages do.
+fn render_field_value(value: &str) -> String {
+    if value.starts_with("http:") || value.starts_with("https:") {
+        if let Ok(url) = url::Url::parse(value) {
+            return format!(
+                "<a href=\"{0}\" rel=\"me nofollow noopener\" target=\"_blank\">{0}</a>",
+                url
+            );
+        }
+    }
+    value.to_string()
+}
+
+/// Renders the local user as an actor. `files` should contain the avatar and
+/// the banner, and `emojis` the custom emojis of `user.emojis`.
+pub fn render_person(
+    url: &str,
+    user: &user::Model,
+    profile: &user_profile::Model,
+    keypair: &user_keypair::Model,
+    files: &[drive_file::Model],
+    emojis: &[emoji::Model],
+) -> Actor {
+    let id = format!("{}/users/{}", url, user.id);
+    let file = |file_id: &Option<String>| {
+        file_id
+            .as_ref()
+            .and_then(|file_id| files.iter().find(|f| &f.id == file_id))
+            .map(|f| OneOrMany::One(render_image(f)))
+    };
+
+    let fields: Vec<Field> = serde_json::from_value(profile.fields.to_owned()).unwrap_or_default();
+    let attachment: Vec<Lenient<Attachment>> = fields
+        .into_iter()
+        .map(|field| {
+            Lenient::Known(Attachment {
+                kind: Some("PropertyValue".to_string()),
+                name: Some(field.name),
+                value: field.value.as_deref().map(render_field_value),
+                ..Default::default()
+            })
+        })
+        .collect();
+
+    let tag: Vec<Lenient<Tag>> = local_emojis(&from_string_vec(user.emojis.to_owned()), emojis)
+        .into_iter()
+        .map(|emoji| Tag::Emoji(render_emoji(url, emoji)))
+        .chain(
+            from_string_vec(user.tags.to_owned())
+                .iter()
+                .map(|tag| Tag::Hashtag(render_hashtag(url, tag))),
+        )
+        .map(Lenient::Known)
+        .collect();
+
+    let shared_inbox = format!("{}/inbox", url);
+
+    Actor {
+        context: None,
+        kind: if user.username.contains('.') {
+            ActorType::Application
+        } else if user.is_bot {
+            ActorType::Service
+        } else {
+            ActorType::Person
+        },
+        inbox: Some(format!("{}/inbox", id)),
+        outbox: Some(Reference::Id(format!("{}/outbox", id))),
+        followers: Some(Reference::Id(format!("{}/followers", id))),
+        following: Some(Reference::Id(format!("{}/following", id))),
+        featured: Some(Reference::Id(format!("{}/collections/featured", id))),
+        shared_inbox: Some(shared_inbox.to_owned()),
+        endpoints: Some(Endpoints {
+            shared_inbox: Some(shared_inbox),
+        }),
+        url: Some(OneOrMany::One(Reference::Id(format!(
+            "{}/@{}",
+            url, user.username
+        )))),
+        preferred_username: Some(user.username.to_owned()),
+        name: user.name.to_owned(),
+        summary: profile
+            .description
+            .as_deref()
+            .filter(|d| !d.is_empty())
+            .map(|d| to_html(&parse(d), url, &[])),
+        misskey_summary: profile.description.to_owned(),
+        icon: file(&user.avatar_id),
+        image: file(&user.banner_id),
+        tag: Some(OneOrMany::Many(tag)),
+        manually_approves_followers: Some(user.is_locked),
+        discoverable: Some(user.is_explorable),
+        public_key: Some(OneOrMany::One(PublicKey {
+            kind: Some("Key".to_string()),
+            id: format!("{}#main-key", id),
+            owner: Some(id.to_owned()),
+            public_key_pem: keypair.public_key.to_owned(),
+        })),
+        is_cat: Some(user.is_cat),
+        speak_as_cat: Some(user.speak_as_cat),
+        indexable: Some(user.is_indexable),
+        attachment: (!attachment.is_empty()).then_some(OneOrMany::Many(attachment)),
+        moved_to: user.moved_to_uri.to_owned(),
+        also_known_as: user
+            .also_known_as
+            .as_ref()
+            .map(|uris| OneOrMany::Many(uris.split(',').map(str::to_string).collect())),
+        birthday: profile.birthday.to_owned(),
+        location: profile.location.to_owned(),
+        published: None,
+        id,
+    }
+}
+
+#[cfg(test)]
+mod unit_test {
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    use super::render_person;
+    use crate::model::entity::newtype::string_vec;
+    use crate::model::entity::{drive_file, emoji, user, user_keypair, user_profile};
+
+    #[test]
+    fn person() {
+        let user = user::Model {
+            id: "9a".to_string(),
+            username: "alice".to_string(),
+            name: Some("Alice".to_string()),
+            avatar_id: Some("f1".to_string()),
+            tags: string_vec(vec!["rust".to_string()]),
+            emojis: string_vec(vec!["blobcat".to_string(), "missing".to_string()]),
+            is_locked: true,
+            also_known_as: Some("https://old.example/users/a".to_string()),
+            ..Default::default()
+        };
+        let profile = user_profile::Model {
+            user_id: "9a".to_string(),
+            description: Some("**hi**".to_string()),
+            fields: json!([
+                { "name": "Site", "value": "https://example.com" },
+                { "name": "Pronouns", "value": "she/her" }
+            ]),
+            birthday: Some("2000-01-01".to_string()),
+            ..Default::default()
+        };
+        let keypair = user_keypair::Model {
+            user_id: "9a".to_string(),
+            public_key: "-----BEGIN PUBLIC KEY-----".to_string(),
+            private_key: String::new(),
+        };
+        let files = vec![drive_file::Model {
+            id: "f1".to_string(),
+            r#type: "image/png".to_string(),
+            url: "https://example.com/files/a.png".to_string(),
+            ..Default::default()
+        }];
+        let emojis = vec![emoji::Model {
+            name: "blobcat".to_string(),
+            public_url: "https://example.com/files/blobcat.png".to_string(),
+            updated_at: Some(chrono::DateTime::parse_from_rfc3339("2023-06-01T00:00:00Z").unwrap()),
+            ..Default::default()
+        }];
+
+        let actor = render_person(
+            "https://example.com",
+            &user,
+            &profile,
+            &keypair,
+            &files,
+            &emojis,
+        );
+        assert_eq!(
+            serde_json::to_value(actor).unwrap(),
+            json!({
+                "type": "Person",
+                "id": "https://example.com/users/9a",
+                "inbox": "https://example.com/users/9a/inbox",
+                "outbox": "https://example.com/users/9a/outbox",
+                "followers": "https://example.com/users/9a/followers",
+                "following": "https://example.com/users/9a/following",
+                "featured": "https://example.com/users/9a/collections/featured",
+                "sharedInbox": "https://example.com/inbox",
+                "endpoints": { "sharedInbox": "https://example.com/inbox" },
+                "url": "https://example.com/@alice",
+                "preferredUsername": "alice",
+                "name": "Alice",
+                "summary": "<p><b><span>hi</span></b></p>",
+                "_misskey_summary": "**hi**",
+                "icon": {
+                    "type": "Image",
+                    "url": "https://example.com/files/a.png",
+                    "sensitive": false,
+                },
+                "tag": [
+                    {
+                        "type": "Emoji",
+                        "id": "https://example.com/emojis/blobcat",
+                        "name": ":blobcat:",
+                        "updated": "2023-06-01T00:00:00.000Z",
+                        "icon": {
+                            "type": "Image",
+                            "mediaType": "image/png",
+                            "url": "https://example.com/files/blobcat.png",
+                        },
+                    },
+                    { "type": "Hashtag", "name": "#rust", "href": "https://example.com/tags/rust" },
+                ],
+                "manuallyApprovesFollowers": true,
+                "discoverable": false,
+                "publicKey": {
+                    "type": "Key",
+                    "id": "https://example.com/users/9a#main-key",
+                    "owner": "https://example.com/users/9a",
+                    "publicKeyPem": "-----BEGIN PUBLIC KEY-----",
+                },
+                "isCat": false,
+                "speakAsCat": false,
+                "indexable": false,
+                "attachment": [
+                    {
+                        "type": "PropertyValue",
+                        "name": "Site",
+                        "value": "<a href=\"https://example.com/\" rel=\"me nofollow noopener\" target=\"_blank\">https://example.com/</a>",
+                    },
+                    { "type": "PropertyValue", "name": "Pronouns", "value": "she/her" },
+                ],
+                "alsoKnownAs": ["https://old.example/users/a"],
+                "vcard:bday": "2000-01-01",
+            })
+        );
+
+        let bot = user::Model {
+            username: "instance.actor".to_string(),
+            ..Default::default()
+        };
+        let actor = render_person("https://example.com", &bot, &profile, &keypair, &[], &[]);
+        assert_eq!(
+            actor.kind,
+            crate::activitypub::actor::ActorType::Application
+        );
+        assert_eq!(actor.icon, None);
+    }
+}
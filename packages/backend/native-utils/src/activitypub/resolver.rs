@@ -0,0 +1,445 @@
+//! Resolution of remote actors and notes, ported from
+//! `remote/activitypub/resolver.ts`, `db-resolver.ts`, and the
+//! `resolvePerson` and `resolveNote` of `models/`
+//!
+//! The database is looked up first, and objects are fetched only if they are
+//! unknown, or if actors were fetched longer than [ResolverConfig::actor_ttl]
+//! ago. Avatars, banners, and attachments are not downloaded.
+
+use std::collections::HashSet;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::Utc;
+use sea_orm::sea_query::{Expr, OnConflict};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, EntityTrait, IntoActiveModel, QueryFilter, Set, TransactionTrait,
+};
+use serde_json::json;
+
+use super::actor::Actor;
+use super::deliver::{inbox_host, register_instance, skipped_hosts};
+use super::error::Error;
+use super::http_signature::{create_signed_get, Headers, SigningKey};
+use super::kernel::{local_id, store_note};
+use super::object::{Object, Tag};
+use super::value::{ApObject, Lenient, OneOrMany, Reference};
+use crate::database;
+use crate::mfm::html_to_mfm;
+use crate::model::entity::newtype::string_vec;
+use crate::model::entity::{instance, note, user, user_profile, user_publickey};
+use crate::util::http::{HttpClient, Method, Request};
+use crate::util::id::create_id;
+
+/// `Accept` of unsigned fetches.
+pub const ACCEPT: &str =
+    "application/activity+json, application/ld+json; profile=\"https://www.w3.org/ns/activitystreams\"";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ResolverConfig {
+    /// Objects a [Resolver] may fetch, as `Resolver.recursionLimit`.
+    pub max_fetches: usize,
+    /// Depth of replies and quotes resolved along with a note.
+    pub max_depth: usize,
+    /// Age after which stored actors are fetched again.
+    pub actor_ttl: Duration,
+}
+
+impl Default for ResolverConfig {
+    fn default() -> Self {
+        Self {
+            max_fetches: 100,
+            max_depth: 16,
+            actor_ttl: Duration::from_secs(24 * 60 * 60),
+        }
+    }
+}
+
+type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Resolver for one job, e.g. an inbound activity. Every object is fetched
+/// at most once, so that circular references end.
+pub struct Resolver {
+    client: Arc<dyn HttpClient>,
+    url: String,
+    key: Option<SigningKey>,
+    config: ResolverConfig,
+    history: HashSet<String>,
+    depth: usize,
+}
+
+impl Resolver {
+    /// `url` is the origin of our server. Fetches are signed with `key`,
+    /// usually the one of the instance actor, which servers in secure mode
+    /// require.
+    pub fn new(
+        client: Arc<dyn HttpClient>,
+        url: &str,
+        key: Option<SigningKey>,
+        config: ResolverConfig,
+    ) -> Self {
+        Self {
+            client,
+            url: url.to_string(),
+            key,
+            config,
+            history: HashSet::new(),
+            depth: 0,
+        }
+    }
+
+    /// Returns the number of objects fetched so far.
+    pub fn fetches(&self) -> usize {
+        self.history.len()
+    }
+
+    fn is_local(&self, uri: &str) -> bool {
+        uri == self.url || uri.starts_with(&format!("{}/", self.url))
+    }
+
+    fn is_stale(&self, user: &user::Model) -> bool {
+        match user.last_fetched_at {
+            Some(at) => (Utc::now() - at.with_timezone(&Utc))
+                .to_std()
+                .is_ok_and(|age| age > self.config.actor_ttl),
+            None => true,
+        }
+    }
+
+    /// Fetches the remote object, as `Resolver.resolve` does. The id of the
+    /// object must be on the host it is fetched from.
+    pub async fn fetch(&mut self, uri: &str) -> Result<Object, Error> {
+        let host = match inbox_host(uri) {
+            Some(host) if !self.is_local(uri) => host,
+            _ => return Err(Error::InvalidUrl(uri.to_string())),
+        };
+        if self.history.contains(uri) {
+            return Err(Error::CircularReference(uri.to_string()));
+        }
+        if self.history.len() >= self.config.max_fetches {
+            return Err(Error::FetchLimitExceeded);
+        }
+        if skipped_hosts(&[host.to_owned()]).await?.contains(&host) {
+            return Err(Error::BlockedHost(host));
+        }
+        self.history.insert(uri.to_string());
+
+        let request = match &self.key {
+            Some(key) => Request {
+                method: Method::Get,
+                url: uri.to_string(),
+                headers: create_signed_get(key, uri, &Headers::new())?.headers,
+                body: None,
+            },
+            None => Request::get(uri, ACCEPT),
+        };
+        let response = self.client.send(request).await?;
+        if !response.is_success() {
+            return Err(Error::HttpStatus(response.status));
+        }
+        let object: Object = response
+            .json()
+            .map_err(|e| Error::InvalidObject(e.to_string()))?;
+        match object.ap_id().and_then(inbox_host) {
+            Some(id_host) if id_host == host => Ok(object),
+            _ => Err(Error::InvalidObject(format!(
+                "id of {} is not on {}",
+                uri, host
+            ))),
+        }
+    }
+
+    async fn fetch_actor(&mut self, uri: &str) -> Result<Actor, Error> {
+        match self.fetch(uri).await? {
+            Object::Actor(actor) => Ok(actor),
+            _ => Err(Error::InvalidObject(format!("{} is not an actor", uri))),
+        }
+    }
+
+    /// Returns the user of the actor. Stored users are fetched again if they
+    /// are stale, and kept as they are if that fails.
+    pub async fn resolve_user(&mut self, uri: &str) -> Result<user::Model, Error> {
+        let db = database::get_database()?;
+        if self.is_local(uri) {
+            return match local_id(&self.url, "users", uri) {
+                Some(id) => user::Entity::find_by_id(id.to_string())
+                    .filter(user::Column::Host.is_null())
+                    .one(db)
+                    .await?
+                    .ok_or(Error::NotFound),
+                None => Err(Error::NotFound),
+            };
+        }
+
+        let stored = user::Entity::find()
+            .filter(user::Column::Uri.eq(uri))
+            .one(db)
+            .await?;
+        match stored {
+            Some(user) if !self.is_stale(&user) => Ok(user),
+            Some(user) => match self.fetch_actor(uri).await {
+                Ok(actor) => store_actor(Some(user), &actor).await,
+                Err(_) => Ok(user),
+            },
+            None => {
+                let actor = self.fetch_actor(uri).await?;
+                let stored = match actor.id != uri {
+                    true => {
+                        user::Entity::find()
+                            .filter(user::Column::Uri.eq(&actor.id))
+                            .one(db)
+                            .await?
+                    }
+                    false => None,
+                };
+                store_actor(stored, &actor).await
+            }
+        }
+    }
+
+    /// Returns the note, storing it with its author, and the replied and
+    /// quoted notes up to [ResolverConfig::max_depth]. References that
+    /// cannot be resolved are left unlinked.
+    pub fn resolve_note<'a>(
+        &'a mut self,
+        uri: &'a str,
+    ) -> BoxFuture<'a, Result<note::Model, Error>> {
+        Box::pin(async move {
+            let db = database::get_database()?;
+            if self.is_local(uri) {
+                return match local_id(&self.url, "notes", uri) {
+                    Some(id) => note::Entity::find_by_id(id.to_string())
+                        .one(db)
+                        .await?
+                        .ok_or(Error::NotFound),
+                    None => Err(Error::NotFound),
+                };
+            }
+            let find = |uri: String| note::Entity::find().filter(note::Column::Uri.eq(uri));
+            if let Some(note) = find(uri.to_string()).one(db).await? {
+                return Ok(note);
+            }
+            if self.depth >= self.config.max_depth {
+                return Err(Error::RecursionLimitExceeded);
+            }
+
+            let note = match self.fetch(uri).await? {
+                Object::Note(note) => note,
+                _ => return Err(Error::InvalidObject(format!("{} is not a note", uri))),
+            };
+            if note.id != uri {
+                if let Some(stored) = find(note.id.to_owned()).one(db).await? {
+                    return Ok(stored);
+                }
+            }
+            let author_id = match note.author_id() {
+                Some(id) if inbox_host(id) == inbox_host(&note.id) => id,
+                _ => {
+                    return Err(Error::InvalidObject(format!(
+                        "author of {} is not on its host",
+                        note.id
+                    )))
+                }
+            };
+            let author = self.resolve_user(author_id).await?;
+
+            let references: Vec<String> = note
+                .in_reply_to
+                .as_ref()
+                .and_then(Reference::id)
+                .into_iter()
+                .chain(note.quote_id())
+                .map(str::to_string)
+                .collect();
+            self.depth += 1;
+            for reference in references {
+                let _ = self.resolve_note(&reference).await;
+            }
+            self.depth -= 1;
+
+            store_note(&self.url, &author, &note).await?;
+            find(note.id.to_owned())
+                .one(db)
+                .await?
+                .ok_or(Error::NotFound)
+        })
+    }
+}
+
+/// Stores the remote actor, creating the user if `stored` is `None`, along
+/// with the profile and the public key.
+async fn store_actor(stored: Option<user::Model>, actor: &Actor) -> Result<user::Model, Error> {
+    let username = match actor.preferred_username.as_deref() {
+        Some(username) if !username.is_empty() => username,
+        _ => {
+            return Err(Error::InvalidObject(format!(
+                "{} has no preferredUsername",
+                actor.id
+            )))
+        }
+    };
+    let host = inbox_host(&actor.id).ok_or_else(|| Error::InvalidUrl(actor.id.to_owned()))?;
+    // Otherwise another server could register its key for the actor
+    if let Some(key) = actor.public_key() {
+        if inbox_host(&key.id).as_ref() != Some(&host) {
+            return Err(Error::InvalidObject(format!(
+                "publicKey.id of {} has different host",
+                actor.id
+            )));
+        }
+        if key.owner.as_ref().is_some_and(|owner| owner != &actor.id) {
+            return Err(Error::InvalidObject(format!(
+                "publicKey.owner of {} is not the actor",
+                actor.id
+            )));
+        }
+    }
+    let now = Utc::now().fixed_offset();
+    let is_new = stored.is_none();
+    let mut active = match stored {
+        Some(user) => {
+            let mut active = user.into_active_model();
+            active.updated_at = Set(Some(now));
+            active
+        }
+        None => user::Model {
+            id: create_id(0)?,
+            created_at: now,
+            username: username.to_string(),
+            username_lower: username.to_lowercase(),
+            host: Some(host.to_owned()),
+            ..Default::default()
+        }
+        .into_active_model()
+        .reset_all(),
+    };
+
+    let tags: Vec<String> = actor
+        .tag
+        .iter()
+        .flat_map(OneOrMany::iter)
+        .filter_map(|tag| match tag {
+            Lenient::Known(Tag::Hashtag(hashtag)) => {
+                Some(hashtag.name.trim_start_matches('#').to_lowercase())
+            }
+            _ => None,
+        })
+        .filter(|tag| !tag.is_empty())
+        .collect();
+    active.last_fetched_at = Set(Some(now));
+    active.name = Set(actor.name.to_owned().filter(|n| !n.is_empty()));
+    active.inbox = Set(actor.inbox.to_owned());
+    active.shared_inbox = Set(actor.shared_inbox().map(str::to_string));
+    active.featured = Set(actor
+        .featured
+        .as_ref()
+        .and_then(Reference::id)
+        .map(str::to_string));
+    active.followers_uri = Set(actor
+        .followers
+        .as_ref()
+        .and_then(Reference::id)
+        .map(str::to_string));
+    active.uri = Set(Some(actor.id.to_owned()));
+    active.is_bot = Set(actor.is_bot());
+    active.is_cat = Set(actor.is_cat.unwrap_or_default());
+    active.speak_as_cat = Set(actor.speak_as_cat.unwrap_or_default());
+    active.is_locked = Set(actor.manually_approves_followers.unwrap_or_default());
+    active.is_explorable = Set(actor.discoverable.unwrap_or_default());
+    active.is_indexable = Set(actor.indexable.unwrap_or(true));
+    active.emojis = Set(string_vec(
+        actor
+            .emojis()
+            .map(|e| e.name.trim_matches(':').to_string())
+            .collect(),
+    ));
+    active.tags = Set(string_vec(tags));
+    active.moved_to_uri = Set(actor.moved_to.to_owned());
+    active.also_known_as = Set(actor
+        .also_known_as
+        .as_ref()
+        .map(|uris| uris.iter().cloned().collect::<Vec<_>>().join(","))
+        .filter(|uris| !uris.is_empty()));
+
+    let txn = database::get_database()?.begin().await?;
+    let user = match is_new {
+        true => active.insert(&txn).await?,
+        false => active.update(&txn).await?,
+    };
+
+    let profile = user_profile::Model {
+        user_id: user.id.to_owned(),
+        user_host: user.host.to_owned(),
+        description: actor
+            .misskey_summary
+            .to_owned()
+            .or_else(|| actor.summary.as_deref().map(|s| html_to_mfm(s, None)))
+            .filter(|d| !d.is_empty()),
+        fields: actor
+            .fields()
+            .map(|(name, value)| json!({ "name": name, "value": html_to_mfm(value, None) }))
+            .collect(),
+        url: actor
+            .url
+            .as_ref()
+            .and_then(OneOrMany::first_id)
+            .map(str::to_string),
+        birthday: actor.birthday.to_owned(),
+        location: actor.location.to_owned(),
+        client_data: json!({}),
+        room: json!({}),
+        muted_words: json!([]),
+        muted_instances: json!([]),
+        email_notification_types: json!(["follow", "receiveFollowRequest", "groupInvited"]),
+        ..Default::default()
+    };
+    user_profile::Entity::insert::<user_profile::ActiveModel>(profile.into())
+        .on_conflict(
+            OnConflict::column(user_profile::Column::UserId)
+                .update_columns([
+                    user_profile::Column::Description,
+                    user_profile::Column::Fields,
+                    user_profile::Column::Url,
+                    user_profile::Column::Birthday,
+                    user_profile::Column::Location,
+                ])
+                .to_owned(),
+        )
+        .exec(&txn)
+        .await?;
+    if let Some(key) = actor.public_key() {
+        let public_key = user_publickey::Model {
+            user_id: user.id.to_owned(),
+            key_id: key.id.to_owned(),
+            key_pem: key.public_key_pem.to_owned(),
+        };
+        user_publickey::Entity::insert::<user_publickey::ActiveModel>(public_key.into())
+            .on_conflict(
+                OnConflict::column(user_publickey::Column::UserId)
+                    .update_columns([
+                        user_publickey::Column::KeyId,
+                        user_publickey::Column::KeyPem,
+                    ])
+                    .to_owned(),
+            )
+            .exec(&txn)
+            .await?;
+    }
+    txn.commit().await?;
+
+    if is_new {
+        let instance = register_instance(&host).await?;
+        instance::Entity::update_many()
+            .col_expr(
+                instance::Column::UsersCount,
+                Expr::col(instance::Column::UsersCount).add(1),
+            )
+            .filter(instance::Column::Id.eq(instance.id))
+            .exec(database::get_database()?)
+            .await?;
+    }
+
+    Ok(user)
+}
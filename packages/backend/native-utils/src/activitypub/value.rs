@@ -0,0 +1,209 @@
+//! Containers for the JSON-LD variations, ported from `getApId`, `getApIds`,
+//! `getOneApId`, and `getApType` of `remote/activitypub/type.ts`
+
+use serde::de::{DeserializeOwned, Error as _, IntoDeserializer};
+use serde::{Deserialize, Deserializer, Serialize};
+
+/// A property that holds either a single value or an array of them.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum OneOrMany<T> {
+    // tried first so that arrays are not taken for a single `T` that accepts
+    // any JSON value
+    Many(Vec<T>),
+    One(T),
+}
+
+impl<T> OneOrMany<T> {
+    pub fn iter(&self) -> std::slice::Iter<'_, T> {
+        match self {
+            Self::One(value) => std::slice::from_ref(value).iter(),
+            Self::Many(values) => values.iter(),
+        }
+    }
+
+    pub fn first(&self) -> Option<&T> {
+        self.iter().next()
+    }
+
+    pub fn into_vec(self) -> Vec<T> {
+        match self {
+            Self::One(value) => vec![value],
+            Self::Many(values) => values,
+        }
+    }
+}
+
+impl<T> Default for OneOrMany<T> {
+    fn default() -> Self {
+        Self::Many(Vec::new())
+    }
+}
+
+impl<T> From<T> for OneOrMany<T> {
+    fn from(value: T) -> Self {
+        Self::One(value)
+    }
+}
+
+impl<'a, T> IntoIterator for &'a OneOrMany<T> {
+    type Item = &'a T;
+    type IntoIter = std::slice::Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Anything with an `id`, which references to it may use instead.
+pub trait ApObject {
+    fn ap_id(&self) -> Option<&str>;
+}
+
+impl ApObject for serde_json::Value {
+    fn ap_id(&self) -> Option<&str> {
+        self.get("id").and_then(serde_json::Value::as_str)
+    }
+}
+
+/// A property that holds either the id of an object or the object itself.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Reference<T> {
+    Id(String),
+    Object(Box<T>),
+}
+
+impl<T: ApObject> Reference<T> {
+    /// Returns the id as `getApId` does. Embedded objects without an id
+    /// have none.
+    pub fn id(&self) -> Option<&str> {
+        match self {
+            Self::Id(id) => Some(id),
+            Self::Object(object) => object.ap_id(),
+        }
+    }
+}
+
+impl<T> Reference<T> {
+    /// Returns the object if it is embedded.
+    pub fn object(&self) -> Option<&T> {
+        match self {
+            Self::Id(_) => None,
+            Self::Object(object) => Some(object),
+        }
+    }
+}
+
+impl<T> From<String> for Reference<T> {
+    fn from(id: String) -> Self {
+        Self::Id(id)
+    }
+}
+
+impl<T: ApObject> OneOrMany<Reference<T>> {
+    /// Returns the ids as `getApIds` does, skipping objects without an id.
+    pub fn ids(&self) -> Vec<&str> {
+        self.iter().filter_map(Reference::id).collect()
+    }
+
+    /// Returns the id of the first element as `getOneApId` does.
+    pub fn first_id(&self) -> Option<&str> {
+        self.first().and_then(Reference::id)
+    }
+}
+
+/// An element of an array that is kept even if it does not match `T`, so
+/// that a single unexpected tag or attachment does not reject the object.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Lenient<T> {
+    Known(T),
+    Unknown(serde_json::Value),
+}
+
+impl<T> Lenient<T> {
+    pub fn known(&self) -> Option<&T> {
+        match self {
+            Self::Known(value) => Some(value),
+            Self::Unknown(_) => None,
+        }
+    }
+}
+
+/// Returns the types of the JSON-LD object, as `getApType` does but keeping
+/// all of them when `type` is an array.
+pub fn type_names(value: &serde_json::Value) -> Vec<&str> {
+    match value.get("type") {
+        Some(serde_json::Value::String(name)) => vec![name],
+        Some(serde_json::Value::Array(names)) => names.iter().filter_map(|n| n.as_str()).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Deserializes `type` into an enum of the known types. If `type` is an
+/// array, the first known one is taken.
+pub(super) fn deserialize_type<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: DeserializeOwned,
+{
+    let names = OneOrMany::<String>::deserialize(deserializer)?;
+    names
+        .iter()
+        .find_map(|name| {
+            T::deserialize(
+                IntoDeserializer::<serde::de::value::Error>::into_deserializer(name.as_str()),
+            )
+            .ok()
+        })
+        .ok_or_else(|| {
+            D::Error::custom(format!(
+                "unknown type `{}`",
+                names.iter().cloned().collect::<Vec<_>>().join(", ")
+            ))
+        })
+}
+
+#[cfg(test)]
+mod unit_test {
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    use super::{type_names, OneOrMany, Reference};
+
+    #[test]
+    fn one_or_many() {
+        let one: OneOrMany<String> = serde_json::from_value(json!("a")).unwrap();
+        let many: OneOrMany<String> = serde_json::from_value(json!(["a", "b"])).unwrap();
+        assert_eq!(one.into_vec(), vec!["a"]);
+        assert_eq!(many.first().map(String::as_str), Some("a"));
+        assert_eq!(many.into_vec(), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn reference() {
+        let refs: OneOrMany<Reference<serde_json::Value>> = serde_json::from_value(json!([
+            "https://example.com/users/1",
+            { "id": "https://example.com/users/2", "type": "Person" },
+            { "type": "Link" },
+        ]))
+        .unwrap();
+        assert_eq!(
+            refs.ids(),
+            vec!["https://example.com/users/1", "https://example.com/users/2"]
+        );
+        assert_eq!(refs.first_id(), Some("https://example.com/users/1"));
+        assert!(refs.iter().nth(1).unwrap().object().is_some());
+    }
+
+    #[test]
+    fn types() {
+        assert_eq!(type_names(&json!({ "type": "Note" })), vec!["Note"]);
+        assert_eq!(
+            type_names(&json!({ "type": ["Person", "as:Actor"] })),
+            vec!["Person", "as:Actor"]
+        );
+        assert_eq!(type_names(&json!({})), Vec::<&str>::new());
+    }
+}
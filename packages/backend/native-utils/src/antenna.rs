@@ -0,0 +1,412 @@
+//! Antenna matching, ported from `misc/check-hit-antenna.ts`
+//!
+//! Only the checks that need nothing but the antenna, the note, and its
+//! author are done here. Blockings and muted words of the antenna owner are
+//! left to the caller.
+
+pub mod timeline;
+
+use cfg_if::cfg_if;
+
+use crate::acct::to_puny;
+use crate::model::entity::newtype::from_string_vec;
+use crate::model::entity::note;
+use crate::model::entity::sea_orm_active_enums::NoteVisibilityEnum;
+use crate::model::schema::Antenna;
+
+cfg_if! {
+    if #[cfg(feature = "napi")] {
+        use crate::model::schema::AntennaSrc::{instances as Instances, users as Users};
+    } else {
+        use crate::model::schema::AntennaSrc::{Instances, Users};
+    }
+}
+
+/// Author of the note to match.
+#[cfg_attr(feature = "napi", napi_derive::napi(object))]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Author {
+    pub id: String,
+    pub username: String,
+    /// `None` if the author is local.
+    pub host: Option<String>,
+}
+
+/// Converts the host to lower-case punycode, keeping it in lower case if it
+/// is not a valid host.
+fn normalize_host(host: &str) -> String {
+    to_puny(host).unwrap_or_else(|_| host.to_lowercase())
+}
+
+/// Returns the account in lower case with the host in punycode, as
+/// `getFullApAccount` does. Accounts on `local_host` have no host, so
+/// `alice`, `@alice` and `@alice@<local host>` are the same account.
+fn normalize_acct(
+    username: &str,
+    host: Option<&str>,
+    local_host: &str,
+) -> (String, Option<String>) {
+    (
+        username.to_lowercase(),
+        host.map(normalize_host).filter(|h| h != local_host),
+    )
+}
+
+/// Splits `@username@host` or `username@host` into the username and the
+/// host.
+fn split_acct(acct: &str) -> (&str, Option<&str>) {
+    let acct = acct.strip_prefix('@').unwrap_or(acct);
+    match acct.split_once('@') {
+        None => (acct, None),
+        Some((username, host)) => (username, Some(host)),
+    }
+}
+
+/// Drops empty keywords and then empty groups.
+fn clean_keywords(keywords: &[Vec<String>]) -> Vec<Vec<&str>> {
+    keywords
+        .iter()
+        .map(|and| {
+            and.iter()
+                .map(String::as_str)
+                .filter(|k| !k.is_empty())
+                .collect::<Vec<_>>()
+        })
+        .filter(|and| !and.is_empty())
+        .collect()
+}
+
+/// Returns `true` if all the keywords of any group are in `text`.
+fn contains_any(text: &str, keywords: &[Vec<&str>], case_sensitive: bool) -> bool {
+    match case_sensitive {
+        true => keywords
+            .iter()
+            .any(|and| and.iter().all(|k| text.contains(k))),
+        false => {
+            let text = text.to_lowercase();
+            keywords
+                .iter()
+                .any(|and| and.iter().all(|k| text.contains(&k.to_lowercase())))
+        }
+    }
+}
+
+/// Returns `true` if the note posted by `author` hits the antenna.
+///
+/// Keywords are ORed groups of ANDed words: the note must contain all the
+/// words of at least one group of `keywords` and must not contain all the
+/// words of any group of `exclude_keywords`. `local_host` is `config.host`.
+pub fn matches(antenna: &Antenna, note: &note::Model, author: &Author, local_host: &str) -> bool {
+    if matches!(
+        note.visibility,
+        NoteVisibilityEnum::Specified | NoteVisibilityEnum::Home
+    ) {
+        return false;
+    }
+    if !antenna.with_replies && note.reply_id.is_some() {
+        return false;
+    }
+    if antenna.with_file && from_string_vec(note.file_ids.to_owned()).is_empty() {
+        return false;
+    }
+
+    match antenna.src {
+        Users => {
+            let local_host = normalize_host(local_host);
+            let author_acct = normalize_acct(&author.username, author.host.as_deref(), &local_host);
+            if !antenna.users.iter().any(|acct| {
+                let (username, host) = split_acct(acct);
+                normalize_acct(username, host, &local_host) == author_acct
+            }) {
+                return false;
+            }
+        }
+        Instances => {
+            let host = author.host.as_deref().unwrap_or_default().to_lowercase();
+            if !antenna
+                .instances
+                .iter()
+                .any(|i| !i.is_empty() && i.to_lowercase() == host)
+            {
+                return false;
+            }
+        }
+        _ => {}
+    }
+
+    let keywords = clean_keywords(&antenna.keywords);
+    let exclude_keywords = clean_keywords(&antenna.exclude_keywords);
+    if keywords.is_empty() && exclude_keywords.is_empty() {
+        return true;
+    }
+    let text = match &note.text {
+        None => return false,
+        Some(text) => text,
+    };
+
+    (keywords.is_empty() || contains_any(text, &keywords, antenna.case_sensitive))
+        && !contains_any(text, &exclude_keywords, antenna.case_sensitive)
+}
+
+cfg_if! {
+    if #[cfg(feature = "napi")] {
+        use napi_derive::napi;
+
+        use crate::model::entity::newtype::string_vec;
+
+        /// Fields of a note needed by [matches].
+        #[napi(object)]
+        #[derive(Clone, Debug, Default, PartialEq, Eq)]
+        pub struct NativeAntennaNote {
+            pub text: Option<String>,
+            pub reply_id: Option<String>,
+            pub file_ids: Vec<String>,
+            /// One of `public`, `home`, `followers`, `specified`, and
+            /// `hidden`.
+            pub visibility: String,
+        }
+
+        impl From<NativeAntennaNote> for note::Model {
+            fn from(value: NativeAntennaNote) -> Self {
+                let visibility = match value.visibility.as_str() {
+                    "home" => NoteVisibilityEnum::Home,
+                    "followers" => NoteVisibilityEnum::Followers,
+                    "specified" => NoteVisibilityEnum::Specified,
+                    "hidden" => NoteVisibilityEnum::Hidden,
+                    _ => NoteVisibilityEnum::Public,
+                };
+                Self {
+                    text: value.text,
+                    reply_id: value.reply_id,
+                    file_ids: string_vec(value.file_ids),
+                    visibility,
+                    ..Default::default()
+                }
+            }
+        }
+
+        #[napi]
+        pub fn native_check_hit_antenna(antenna: Antenna, note: NativeAntennaNote, author: Author, local_host: String) -> bool {
+            matches(&antenna, &note.into(), &author, &local_host)
+        }
+
+        /// Returns the ids of the antennas hit by the note.
+        #[napi]
+        pub fn native_filter_hit_antennas(antennas: Vec<Antenna>, note: NativeAntennaNote, author: Author, local_host: String) -> Vec<String> {
+            let note = note.into();
+            antennas
+                .into_iter()
+                .filter(|antenna| matches(antenna, &note, &author, &local_host))
+                .map(|antenna| antenna.id)
+                .collect()
+        }
+    }
+}
+
+#[cfg(all(test, not(feature = "napi")))]
+mod unit_test {
+    use chrono::Utc;
+
+    use crate::model::entity::newtype::string_vec;
+    use crate::model::entity::note;
+    use crate::model::entity::sea_orm_active_enums::NoteVisibilityEnum;
+    use crate::model::schema::{Antenna, AntennaSrc};
+
+    use super::{matches, Author};
+
+    const LOCAL_HOST: &str = "local.example";
+
+    fn antenna(keywords: &[&[&str]], exclude_keywords: &[&[&str]]) -> Antenna {
+        let to_vec = |groups: &[&[&str]]| -> Vec<Vec<String>> {
+            groups
+                .iter()
+                .map(|and| and.iter().map(|k| k.to_string()).collect())
+                .collect()
+        };
+        Antenna {
+            id: "9fil64s6g7cskdrb".to_string(),
+            created_at: Utc::now(),
+            name: "Test".to_string(),
+            keywords: to_vec(keywords),
+            exclude_keywords: to_vec(exclude_keywords),
+            src: AntennaSrc::All,
+            user_list_id: None,
+            user_group_id: None,
+            users: vec![],
+            instances: vec![],
+            case_sensitive: false,
+            notify: false,
+            with_replies: false,
+            with_file: false,
+            has_unread_note: false,
+        }
+    }
+
+    fn note(text: &str) -> note::Model {
+        note::Model {
+            text: Some(text.to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn alice() -> Author {
+        Author {
+            id: "9fil65jzhtjpi3xn".to_string(),
+            username: "Alice".to_string(),
+            host: Some("Example.com".to_string()),
+        }
+    }
+
+    #[test]
+    fn keywords() {
+        let antenna = antenna(&[&["foo", "bar"], &["baz"]], &[&["qux"], &[""]]);
+
+        assert!(matches(
+            &antenna,
+            &note("FOO and bar"),
+            &alice(),
+            LOCAL_HOST
+        ));
+        assert!(matches(&antenna, &note("baz"), &alice(), LOCAL_HOST));
+        assert!(!matches(&antenna, &note("foo"), &alice(), LOCAL_HOST));
+        assert!(!matches(&antenna, &note("baz qux"), &alice(), LOCAL_HOST));
+        assert!(!matches(
+            &antenna,
+            &note::Model::default(),
+            &alice(),
+            LOCAL_HOST
+        ));
+
+        let antenna = Antenna {
+            case_sensitive: true,
+            ..antenna
+        };
+        assert!(!matches(
+            &antenna,
+            &note("FOO and bar"),
+            &alice(),
+            LOCAL_HOST
+        ));
+    }
+
+    #[test]
+    fn empty_keywords() {
+        let no_keywords = antenna(&[&[""], &[]], &[]);
+        assert!(matches(
+            &no_keywords,
+            &note::Model::default(),
+            &alice(),
+            LOCAL_HOST
+        ));
+
+        let exclude_only = antenna(&[], &[&["bar"]]);
+        assert!(matches(&exclude_only, &note("foo"), &alice(), LOCAL_HOST));
+        assert!(!matches(&exclude_only, &note("bar"), &alice(), LOCAL_HOST));
+    }
+
+    #[test]
+    fn note_conditions() {
+        let antenna = antenna(&[], &[]);
+        let home = note::Model {
+            visibility: NoteVisibilityEnum::Home,
+            ..note("foo")
+        };
+        let reply = note::Model {
+            reply_id: Some("9fil66brl1udxau2".to_string()),
+            ..note("foo")
+        };
+        assert!(!matches(&antenna, &home, &alice(), LOCAL_HOST));
+        assert!(!matches(&antenna, &reply, &alice(), LOCAL_HOST));
+        assert!(matches(
+            &Antenna {
+                with_replies: true,
+                ..antenna.to_owned()
+            },
+            &reply,
+            &alice(),
+            LOCAL_HOST
+        ));
+
+        let with_file = Antenna {
+            with_file: true,
+            ..antenna
+        };
+        let note_with_file = note::Model {
+            file_ids: string_vec(vec!["9fil66brl1udxau2".to_string()]),
+            ..note("foo")
+        };
+        assert!(!matches(&with_file, &note("foo"), &alice(), LOCAL_HOST));
+        assert!(matches(&with_file, &note_with_file, &alice(), LOCAL_HOST));
+    }
+
+    #[test]
+    fn sources() {
+        let users = Antenna {
+            src: AntennaSrc::Users,
+            users: vec!["@bob".to_string(), "alice@example.com".to_string()],
+            ..antenna(&[], &[])
+        };
+        let local_bob = Author {
+            username: "bob".to_string(),
+            host: None,
+            ..alice()
+        };
+        assert!(matches(&users, &note("foo"), &alice(), LOCAL_HOST));
+        assert!(matches(&users, &note("foo"), &local_bob, LOCAL_HOST));
+        assert!(!matches(
+            &users,
+            &note("foo"),
+            &Author {
+                host: Some("example.org".to_string()),
+                ..alice()
+            },
+            LOCAL_HOST
+        ));
+
+        // Accounts on the local host are local.
+        let local_alice = Author {
+            host: None,
+            ..alice()
+        };
+        let users = Antenna {
+            src: AntennaSrc::Users,
+            users: vec!["@alice@Local.Example".to_string()],
+            ..antenna(&[], &[])
+        };
+        assert!(matches(&users, &note("foo"), &local_alice, LOCAL_HOST));
+        assert!(!matches(&users, &note("foo"), &alice(), LOCAL_HOST));
+        assert!(matches(
+            &users,
+            &note("foo"),
+            &Author {
+                host: Some(LOCAL_HOST.to_string()),
+                ..alice()
+            },
+            LOCAL_HOST
+        ));
+
+        // Hosts are compared in punycode.
+        let users = Antenna {
+            src: AntennaSrc::Users,
+            users: vec!["@alice@ÉXAMPLE.テスト".to_string()],
+            ..antenna(&[], &[])
+        };
+        assert!(matches(
+            &users,
+            &note("foo"),
+            &Author {
+                host: Some("xn--xample-9ua.xn--zckzah".to_string()),
+                ..alice()
+            },
+            LOCAL_HOST
+        ));
+
+        let instances = Antenna {
+            src: AntennaSrc::Instances,
+            instances: vec!["".to_string(), "EXAMPLE.COM".to_string()],
+            ..antenna(&[], &[])
+        };
+        assert!(matches(&instances, &note("foo"), &alice(), LOCAL_HOST));
+        assert!(!matches(&instances, &note("foo"), &local_bob, LOCAL_HOST));
+    }
+}
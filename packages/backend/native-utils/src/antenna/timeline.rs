@@ -0,0 +1,320 @@
+//! Antenna timelines stored in the cache
+//!
+//! Each antenna has a Redis stream `{prefix}:antennaTimeline:{antennaId}`
+//! whose entries have the note id in the `note` field and the timestamp of
+//! the note as the entry id, as `services/add-note-to-antenna.ts` does. The
+//! id of the newest entry the owner has read is kept in
+//! `{prefix}:antennaLastRead:{antennaId}`, which is also written by
+//! `server/api/endpoints/antennas/notes.ts` when the newest notes are shown.
+
+use cfg_if::cfg_if;
+
+use crate::cache::{self, error::Error, parse_stream_id};
+use crate::util::id::get_timestamp;
+
+/// Antenna timelines are trimmed to about this length on every push.
+pub const TIMELINE_MAXLEN: usize = 200;
+
+fn timeline_key(antenna_id: &str) -> String {
+    format!("antennaTimeline:{}", antenna_id)
+}
+
+fn last_read_key(antenna_id: &str) -> String {
+    format!("antennaLastRead:{}", antenna_id)
+}
+
+/// Returns the creation time of the note in milliseconds, or `None` if the
+/// id is malformed.
+fn note_timestamp(note_id: &str) -> Option<u64> {
+    if note_id.len() < 8 {
+        return None;
+    }
+    u64::try_from(get_timestamp(note_id)).ok()
+}
+
+/// Returns the id of the newest entry of the timeline.
+async fn newest_entry_id(antenna_id: &str) -> Result<Option<String>, Error> {
+    let cache = cache::get_cache()?;
+    let entries = cache
+        .backend()
+        .xrevrange(&cache.prefixed_key(&timeline_key(antenna_id)), "+", "-", 1)
+        .await?;
+    Ok(entries.into_iter().next().map(|e| e.id))
+}
+
+/// Pushes the note into the antenna timeline and returns the entry id.
+/// Returns `None` without pushing if the note is older than the newest note
+/// in the timeline, because stream entries must be in order.
+pub async fn push_note(antenna_id: &str, note_id: &str) -> Result<Option<String>, Error> {
+    let id = match note_timestamp(note_id) {
+        None => "*".to_string(),
+        Some(ms) => {
+            let newest = newest_entry_id(antenna_id).await?;
+            if newest
+                .and_then(|id| parse_stream_id(&id))
+                .map(|(newest_ms, _)| ms < newest_ms)
+                == Some(true)
+            {
+                return Ok(None);
+            }
+            format!("{}-*", ms)
+        }
+    };
+
+    let cache = cache::get_cache()?;
+    let entry_id = cache
+        .backend()
+        .xadd(
+            &cache.prefixed_key(&timeline_key(antenna_id)),
+            &id,
+            &[("note", note_id)],
+            Some(TIMELINE_MAXLEN),
+        )
+        .await?;
+    Ok(Some(entry_id))
+}
+
+/// Returns the creation time of the note as a stream entry id for a range.
+fn range_bound(note_id: &str) -> Result<String, Error> {
+    note_timestamp(note_id)
+        .map(|ms| ms.to_string())
+        .ok_or_else(|| Error::InvalidStreamId(note_id.to_string()))
+}
+
+/// Returns at most `limit` note ids in the timeline, newest first. Notes
+/// posted at the same time as `since_id` or `until_id` may be included,
+/// as the timeline is indexed by time, but those notes themselves are not.
+/// Returns [Error::InvalidStreamId] if the time of `since_id` or `until_id`
+/// is unknown.
+pub async fn get_note_ids(
+    antenna_id: &str,
+    since_id: Option<&str>,
+    until_id: Option<&str>,
+    limit: usize,
+) -> Result<Vec<String>, Error> {
+    // since_id and until_id themselves are in the range
+    let count = limit + usize::from(since_id.is_some()) + usize::from(until_id.is_some());
+    let end = match until_id {
+        None => "+".to_string(),
+        Some(id) => range_bound(id)?,
+    };
+    let start = match since_id {
+        None => "-".to_string(),
+        Some(id) => range_bound(id)?,
+    };
+
+    let cache = cache::get_cache()?;
+    let entries = cache
+        .backend()
+        .xrevrange(
+            &cache.prefixed_key(&timeline_key(antenna_id)),
+            &end,
+            &start,
+            count,
+        )
+        .await?;
+    Ok(entries
+        .into_iter()
+        .filter_map(|mut e| e.fields.remove("note"))
+        .filter(|id| Some(id.as_str()) != since_id && Some(id.as_str()) != until_id)
+        .take(limit)
+        .collect())
+}
+
+/// Removes old notes so that at most `maxlen` notes remain and returns the
+/// number of removed notes.
+pub async fn trim(antenna_id: &str, maxlen: usize) -> Result<usize, Error> {
+    let cache = cache::get_cache()?;
+    cache
+        .backend()
+        .xtrim(&cache.prefixed_key(&timeline_key(antenna_id)), maxlen)
+        .await
+}
+
+/// Marks all the notes in the timeline as read.
+pub async fn read(antenna_id: &str) -> Result<(), Error> {
+    match newest_entry_id(antenna_id).await? {
+        None => Ok(()),
+        Some(id) => {
+            cache::get_cache()?
+                .set(&last_read_key(antenna_id), &id, None)
+                .await
+        }
+    }
+}
+
+/// Returns `true` if the timeline has notes newer than the ones marked as
+/// read by [read].
+pub async fn has_unread_note(antenna_id: &str) -> Result<bool, Error> {
+    Ok(has_unread_notes(&[antenna_id.to_string()]).await?[0])
+}
+
+/// Returns [has_unread_note] of each antenna, with two round trips to the
+/// cache in total.
+pub async fn has_unread_notes(antenna_ids: &[String]) -> Result<Vec<bool>, Error> {
+    let cache = cache::get_cache()?;
+    let keys = |key: fn(&str) -> String| -> Vec<String> {
+        antenna_ids
+            .iter()
+            .map(|id| cache.prefixed_key(&key(id)))
+            .collect()
+    };
+    let newest = cache.backend().xlast_ids(&keys(timeline_key)).await?;
+    let last_read = cache.backend().mget(&keys(last_read_key)).await?;
+    Ok(newest
+        .into_iter()
+        .zip(last_read)
+        .map(|(newest, last_read)| {
+            match (
+                newest.and_then(|id| parse_stream_id(&id)),
+                last_read.and_then(|id| parse_stream_id(&id)),
+            ) {
+                (None, _) => false,
+                (Some(_), None) => true,
+                (Some(newest), Some(last_read)) => newest > last_read,
+            }
+        })
+        .collect())
+}
+
+/// Removes the timeline and the read state of the antenna.
+pub async fn delete(antenna_id: &str) -> Result<(), Error> {
+    let cache = cache::get_cache()?;
+    cache.del(&timeline_key(antenna_id)).await?;
+    cache.del(&last_read_key(antenna_id)).await
+}
+
+cfg_if! {
+    if #[cfg(feature = "napi")] {
+        use napi_derive::napi;
+
+        /// Returns the entry id, or `null` if the note is older than the
+        /// timeline.
+        #[napi]
+        pub async fn native_push_antenna_note(antenna_id: String, note_id: String) -> napi::Result<Option<String>> {
+            push_note(&antenna_id, &note_id).await.map_err(Into::into)
+        }
+
+        #[napi]
+        pub async fn native_get_antenna_note_ids(
+            antenna_id: String,
+            since_id: Option<String>,
+            until_id: Option<String>,
+            limit: u32,
+        ) -> napi::Result<Vec<String>> {
+            get_note_ids(&antenna_id, since_id.as_deref(), until_id.as_deref(), limit as usize)
+                .await
+                .map_err(Into::into)
+        }
+
+        #[napi]
+        pub async fn native_trim_antenna_timeline(antenna_id: String, maxlen: u32) -> napi::Result<u32> {
+            trim(&antenna_id, maxlen as usize).await.map(|n| n as u32).map_err(Into::into)
+        }
+
+        #[napi]
+        pub async fn native_read_antenna(antenna_id: String) -> napi::Result<()> {
+            read(&antenna_id).await.map_err(Into::into)
+        }
+
+        #[napi]
+        pub async fn native_has_unread_antenna_note(antenna_id: String) -> napi::Result<bool> {
+            has_unread_note(&antenna_id).await.map_err(Into::into)
+        }
+    }
+}
+
+#[cfg(test)]
+mod unit_test {
+    use basen::BASE36;
+    use pretty_assertions::assert_eq;
+
+    use crate::cache::{error::Error, init_memory_cache};
+
+    use super::{
+        delete, get_note_ids, has_unread_note, has_unread_notes, push_note, read, trim,
+        TIMELINE_MAXLEN,
+    };
+
+    /// Returns an id created at `ms`, without touching the global generator.
+    fn create_id(ms: u64) -> String {
+        let time = ms - 946_684_800_000;
+        format!(
+            "{:0>8}{:0>8}",
+            BASE36.encode_var_len(&time),
+            ms % 100_000_000
+        )
+    }
+
+    #[tokio::test]
+    async fn timeline() {
+        init_memory_cache("example.com").unwrap();
+        let antenna_id = "9fil64s6g7cskdrb";
+        let note_ids: Vec<String> = (1..=5)
+            .map(|i| create_id(1_690_000_000_000 + i * 1000))
+            .collect();
+
+        assert!(!has_unread_note(antenna_id).await.unwrap());
+        for id in &note_ids {
+            assert!(push_note(antenna_id, id).await.unwrap().is_some());
+        }
+        // older notes are not pushed
+        let old_id = create_id(1_600_000_000_000);
+        assert_eq!(push_note(antenna_id, &old_id).await.unwrap(), None);
+        assert!(has_unread_note(antenna_id).await.unwrap());
+
+        let newest_first: Vec<String> = note_ids.iter().rev().cloned().collect();
+        assert_eq!(
+            get_note_ids(antenna_id, None, None, 10).await.unwrap(),
+            newest_first
+        );
+        assert_eq!(
+            get_note_ids(antenna_id, None, None, 2).await.unwrap(),
+            newest_first[..2]
+        );
+        assert_eq!(
+            get_note_ids(antenna_id, Some(&note_ids[1]), Some(&note_ids[3]), 10)
+                .await
+                .unwrap(),
+            vec![note_ids[2].to_owned()]
+        );
+        for malformed in ["9fil", "not-an-id"] {
+            assert_eq!(
+                get_note_ids(antenna_id, Some(malformed), None, 10).await,
+                Err(Error::InvalidStreamId(malformed.to_string()))
+            );
+            assert_eq!(
+                get_note_ids(antenna_id, None, Some(malformed), 10).await,
+                Err(Error::InvalidStreamId(malformed.to_string()))
+            );
+        }
+
+        read(antenna_id).await.unwrap();
+        assert!(!has_unread_note(antenna_id).await.unwrap());
+        assert_eq!(
+            has_unread_notes(&[antenna_id.to_string(), "9fil64s6g7cskdrc".to_string()])
+                .await
+                .unwrap(),
+            vec![false, false]
+        );
+        let new_id = create_id(1_700_000_000_000);
+        push_note(antenna_id, &new_id).await.unwrap();
+        assert!(has_unread_note(antenna_id).await.unwrap());
+        assert_eq!(has_unread_notes(&[]).await.unwrap(), Vec::<bool>::new());
+
+        assert_eq!(trim(antenna_id, 2).await.unwrap(), 4);
+        assert_eq!(
+            get_note_ids(antenna_id, None, None, TIMELINE_MAXLEN)
+                .await
+                .unwrap(),
+            vec![new_id, note_ids[4].to_owned()]
+        );
+
+        delete(antenna_id).await.unwrap();
+        assert!(get_note_ids(antenna_id, None, None, 10)
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(!has_unread_note(antenna_id).await.unwrap());
+    }
+}
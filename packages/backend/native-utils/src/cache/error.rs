@@ -0,0 +1,29 @@
+use crate::impl_into_napi_error;
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum Error {
+    #[error("The cache connection has not been initialized yet")]
+    Uninitialized,
+    #[error("The cache has already been initialized with different settings")]
+    AlreadyInitialized,
+    #[error("Redis error: {0}")]
+    RedisError(String),
+    #[error("Failed to (de)serialize cached value: {0}")]
+    SerdeError(String),
+    #[error("Cannot convert the id to a stream entry id: {0}")]
+    InvalidStreamId(String),
+}
+
+impl From<redis::RedisError> for Error {
+    fn from(value: redis::RedisError) -> Self {
+        Self::RedisError(value.to_string())
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(value: serde_json::Error) -> Self {
+        Self::SerdeError(value.to_string())
+    }
+}
+
+impl_into_napi_error!(Error);
@@ -0,0 +1,202 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+
+use super::error::Error;
+use super::{parse_stream_id, Backend, StreamEntry};
+
+/// Values keyed by keys, with the time they expire at.
+type Entries = HashMap<String, (String, Option<Instant>)>;
+
+/// In-memory [Backend] for tests and single-process setups. Expired entries
+/// are dropped when they are accessed. Streams never expire.
+#[derive(Debug, Default)]
+pub struct MemoryBackend {
+    entries: Mutex<Entries>,
+    /// Stream entries keyed by keys, in ascending order of ids.
+    streams: Mutex<HashMap<String, Vec<StreamEntry>>>,
+}
+
+impl MemoryBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Runs `f` on the live entry of `key`, removing it first if expired.
+    fn with_entry<T>(&self, key: &str, f: impl FnOnce(&mut Entries) -> T) -> T {
+        let mut entries = self.entries.lock().unwrap();
+        if let Some((_, Some(expires_at))) = entries.get(key) {
+            if *expires_at <= Instant::now() {
+                entries.remove(key);
+            }
+        }
+        f(&mut entries)
+    }
+}
+
+#[async_trait]
+impl Backend for MemoryBackend {
+    async fn get(&self, key: &str) -> Result<Option<String>, Error> {
+        Ok(self.with_entry(key, |entries| entries.get(key).map(|(v, _)| v.to_owned())))
+    }
+
+    async fn set(&self, key: &str, value: &str, ttl: Option<Duration>) -> Result<(), Error> {
+        let expires_at = ttl.map(|ttl| Instant::now() + ttl);
+        self.with_entry(key, |entries| {
+            entries.insert(key.to_string(), (value.to_string(), expires_at))
+        });
+        Ok(())
+    }
+
+    async fn del(&self, key: &str) -> Result<(), Error> {
+        self.with_entry(key, |entries| entries.remove(key));
+        self.streams.lock().unwrap().remove(key);
+        Ok(())
+    }
+
+    async fn mget(&self, keys: &[String]) -> Result<Vec<Option<String>>, Error> {
+        let mut values = Vec::with_capacity(keys.len());
+        for key in keys {
+            values.push(self.get(key).await?);
+        }
+        Ok(values)
+    }
+
+    async fn incr(&self, key: &str, delta: i64) -> Result<i64, Error> {
+        self.with_entry(key, |entries| {
+            let (value, _) = entries
+                .entry(key.to_string())
+                .or_insert_with(|| ("0".to_string(), None));
+            let n = value
+                .parse::<i64>()
+                .map_err(|_| Error::RedisError("value is not an integer".to_string()))?
+                + delta;
+            *value = n.to_string();
+            Ok(n)
+        })
+    }
+
+    async fn expire(&self, key: &str, ttl: Duration) -> Result<(), Error> {
+        self.with_entry(key, |entries| {
+            if let Some((_, expires_at)) = entries.get_mut(key) {
+                *expires_at = Some(Instant::now() + ttl);
+            }
+        });
+        Ok(())
+    }
+
+    async fn xadd(
+        &self,
+        key: &str,
+        id: &str,
+        fields: &[(&str, &str)],
+        maxlen: Option<usize>,
+    ) -> Result<String, Error> {
+        let invalid = || Error::RedisError(format!("Invalid stream ID specified: {}", id));
+        let mut streams = self.streams.lock().unwrap();
+        let stream = streams.entry(key.to_string()).or_default();
+        let last = stream
+            .last()
+            .and_then(|e| parse_stream_id(&e.id))
+            .unwrap_or_default();
+
+        let (ms, seq) = match id.split_once('-') {
+            _ if id == "*" => {
+                let now = chrono::Utc::now().timestamp_millis() as u64;
+                match now > last.0 {
+                    true => (now, 0),
+                    false => (last.0, last.1 + 1),
+                }
+            }
+            Some((ms, "*")) => {
+                let ms: u64 = ms.parse().map_err(|_| invalid())?;
+                match ms == last.0 && !stream.is_empty() {
+                    true => (ms, last.1 + 1),
+                    false => (ms, 0),
+                }
+            }
+            _ => parse_stream_id(id).ok_or_else(invalid)?,
+        };
+        if !stream.is_empty() && (ms, seq) <= last {
+            return Err(Error::RedisError(
+                "The ID specified in XADD is equal or smaller than the target stream top item"
+                    .to_string(),
+            ));
+        }
+
+        let id = format!("{}-{}", ms, seq);
+        stream.push(StreamEntry {
+            id: id.to_owned(),
+            fields: fields
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        });
+        if let Some(maxlen) = maxlen {
+            let excess = stream.len().saturating_sub(maxlen);
+            stream.drain(..excess);
+        }
+        Ok(id)
+    }
+
+    async fn xrevrange(
+        &self,
+        key: &str,
+        end: &str,
+        start: &str,
+        count: usize,
+    ) -> Result<Vec<StreamEntry>, Error> {
+        let bound = |id: &str, default: (u64, u64), seq: u64| match id {
+            "-" | "+" => Some(default),
+            _ if !id.contains('-') => Some((id.parse().ok()?, seq)),
+            _ => parse_stream_id(id),
+        };
+        let invalid = || Error::RedisError("Invalid stream ID specified".to_string());
+        let end = bound(end, (u64::MAX, u64::MAX), u64::MAX).ok_or_else(invalid)?;
+        let start = bound(start, (0, 0), 0).ok_or_else(invalid)?;
+
+        let streams = self.streams.lock().unwrap();
+        Ok(streams
+            .get(key)
+            .map(|stream| {
+                stream
+                    .iter()
+                    .rev()
+                    .filter(|e| {
+                        let id = parse_stream_id(&e.id).unwrap_or_default();
+                        start <= id && id <= end
+                    })
+                    .take(count)
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    async fn xlast_ids(&self, keys: &[String]) -> Result<Vec<Option<String>>, Error> {
+        let streams = self.streams.lock().unwrap();
+        Ok(keys
+            .iter()
+            .map(|key| {
+                streams
+                    .get(key)
+                    .and_then(|stream| stream.last())
+                    .map(|e| e.id.to_owned())
+            })
+            .collect())
+    }
+
+    async fn xtrim(&self, key: &str, maxlen: usize) -> Result<usize, Error> {
+        let mut streams = self.streams.lock().unwrap();
+        Ok(match streams.get_mut(key) {
+            None => 0,
+            Some(stream) => {
+                let excess = stream.len().saturating_sub(maxlen);
+                stream.drain(..excess);
+                excess
+            }
+        })
+    }
+}
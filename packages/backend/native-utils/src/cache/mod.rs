@@ -0,0 +1,361 @@
+pub mod error;
+pub mod memory;
+pub mod redis;
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use cfg_if::cfg_if;
+use error::Error;
+use serde::{de::DeserializeOwned, Serialize};
+
+pub use self::memory::MemoryBackend;
+pub use self::redis::RedisBackend;
+
+/// Settings the global cache was initialized with.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Settings {
+    /// `None` for [MemoryBackend].
+    url: Option<String>,
+    prefix: String,
+}
+
+static CACHE: once_cell::sync::OnceCell<(Settings, Cache)> = once_cell::sync::OnceCell::new();
+
+/// Key-value store behind [Cache]. Keys passed to the backend are already
+/// prefixed.
+#[async_trait]
+pub trait Backend: Send + Sync {
+    async fn get(&self, key: &str) -> Result<Option<String>, Error>;
+    /// Sets the value, which expires after `ttl` if specified.
+    async fn set(&self, key: &str, value: &str, ttl: Option<Duration>) -> Result<(), Error>;
+    async fn del(&self, key: &str) -> Result<(), Error>;
+    /// Returns the values of the keys in one round trip like `MGET`.
+    async fn mget(&self, keys: &[String]) -> Result<Vec<Option<String>>, Error>;
+    /// Increments the integer value by `delta` and returns the new value. A
+    /// missing key is treated as `0`.
+    async fn incr(&self, key: &str, delta: i64) -> Result<i64, Error>;
+    /// Sets the time to live of an existing key.
+    async fn expire(&self, key: &str, ttl: Duration) -> Result<(), Error>;
+    /// Appends an entry to the stream like `XADD key MAXLEN ~ maxlen id
+    /// field value ...` and returns the entry id. `id` is `*`, `{ms}-*`, or
+    /// `{ms}-{seq}` and must be greater than the last entry id.
+    async fn xadd(
+        &self,
+        key: &str,
+        id: &str,
+        fields: &[(&str, &str)],
+        maxlen: Option<usize>,
+    ) -> Result<String, Error>;
+    /// Returns at most `count` entries between `start` and `end` in reverse
+    /// order like `XREVRANGE key end start COUNT count`. `end` may be `+`,
+    /// and `start` may be `-`.
+    async fn xrevrange(
+        &self,
+        key: &str,
+        end: &str,
+        start: &str,
+        count: usize,
+    ) -> Result<Vec<StreamEntry>, Error>;
+    /// Returns the id of the newest entry of each stream, or `None` if the
+    /// stream is empty, in one round trip.
+    async fn xlast_ids(&self, keys: &[String]) -> Result<Vec<Option<String>>, Error>;
+    /// Removes old entries so that at most `maxlen` entries remain and
+    /// returns the number of removed ones.
+    async fn xtrim(&self, key: &str, maxlen: usize) -> Result<usize, Error>;
+}
+
+/// Entry of a Redis stream.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StreamEntry {
+    /// `{ms}-{seq}`
+    pub id: String,
+    pub fields: HashMap<String, String>,
+}
+
+/// Parses a stream entry id into `(ms, seq)`.
+pub fn parse_stream_id(id: &str) -> Option<(u64, u64)> {
+    match id.split_once('-') {
+        None => Some((id.parse().ok()?, 0)),
+        Some((ms, seq)) => Some((ms.parse().ok()?, seq.parse().ok()?)),
+    }
+}
+
+/// Cache shared with TypeScript. All keys are prefixed with `{prefix}:`, as
+/// `keyPrefix` of ioredis does.
+pub struct Cache {
+    prefix: String,
+    backend: Box<dyn Backend>,
+}
+
+impl Cache {
+    pub fn new(prefix: impl Into<String>, backend: impl Backend + 'static) -> Self {
+        Self {
+            prefix: prefix.into(),
+            backend: Box::new(backend),
+        }
+    }
+
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    /// Returns the key as stored in the backend, e.g.
+    /// `{prefix}:antennaTimeline:{antennaId}` for `antennaTimeline:{antennaId}`.
+    pub fn prefixed_key(&self, key: &str) -> String {
+        format!("{}:{}", self.prefix, key)
+    }
+
+    /// Returns the backend, which does not prefix keys. Use
+    /// [Cache::prefixed_key] to build keys for it.
+    pub fn backend(&self) -> &dyn Backend {
+        self.backend.as_ref()
+    }
+
+    pub async fn get(&self, key: &str) -> Result<Option<String>, Error> {
+        self.backend.get(&self.prefixed_key(key)).await
+    }
+
+    pub async fn set(&self, key: &str, value: &str, ttl: Option<Duration>) -> Result<(), Error> {
+        self.backend.set(&self.prefixed_key(key), value, ttl).await
+    }
+
+    pub async fn del(&self, key: &str) -> Result<(), Error> {
+        self.backend.del(&self.prefixed_key(key)).await
+    }
+
+    pub async fn incr(&self, key: &str, delta: i64) -> Result<i64, Error> {
+        self.backend.incr(&self.prefixed_key(key), delta).await
+    }
+
+    pub async fn expire(&self, key: &str, ttl: Duration) -> Result<(), Error> {
+        self.backend.expire(&self.prefixed_key(key), ttl).await
+    }
+
+    /// Returns the value stored as JSON by [Cache::set_json].
+    pub async fn get_json<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>, Error> {
+        match self.get(key).await? {
+            None => Ok(None),
+            Some(value) => Ok(Some(serde_json::from_str(&value)?)),
+        }
+    }
+
+    pub async fn set_json<T: Serialize + Sync>(
+        &self,
+        key: &str,
+        value: &T,
+        ttl: Option<Duration>,
+    ) -> Result<(), Error> {
+        self.set(key, &serde_json::to_string(value)?, ttl).await
+    }
+}
+
+/// Returns `Ok(true)` if the global cache is initialized with the same
+/// settings.
+fn is_initialized(settings: &Settings) -> Result<bool, Error> {
+    match CACHE.get() {
+        None => Ok(false),
+        Some((initialized, _)) if initialized == settings => Ok(true),
+        Some(_) => Err(Error::AlreadyInitialized),
+    }
+}
+
+/// Sets the global cache unless another one was set in the meantime.
+fn set_cache(settings: Settings, backend: impl Backend + 'static) -> Result<(), Error> {
+    let prefix = settings.prefix.to_owned();
+    let (initialized, _) = CACHE.get_or_init(|| (settings.to_owned(), Cache::new(prefix, backend)));
+    match *initialized == settings {
+        true => Ok(()),
+        false => Err(Error::AlreadyInitialized),
+    }
+}
+
+/// Connects to Redis at `url`, e.g. `CACHE_URL` set by the migrator, and
+/// prefixes keys with `prefix`, e.g. `CACHE_PREFIX`. Calling this again
+/// with the same settings does nothing, and with different ones returns
+/// [Error::AlreadyInitialized].
+pub async fn init_cache(url: &str, prefix: impl Into<String>) -> Result<(), Error> {
+    let settings = Settings {
+        url: Some(url.to_string()),
+        prefix: prefix.into(),
+    };
+    if is_initialized(&settings)? {
+        return Ok(());
+    }
+    let backend = RedisBackend::connect(url).await?;
+    set_cache(settings, backend)
+}
+
+/// Initializes the cache with [MemoryBackend] instead of Redis. See
+/// [init_cache].
+pub fn init_memory_cache(prefix: impl Into<String>) -> Result<(), Error> {
+    let settings = Settings {
+        url: None,
+        prefix: prefix.into(),
+    };
+    if is_initialized(&settings)? {
+        return Ok(());
+    }
+    set_cache(settings, MemoryBackend::new())
+}
+
+pub fn get_cache() -> Result<&'static Cache, Error> {
+    CACHE
+        .get()
+        .map(|(_, cache)| cache)
+        .ok_or(Error::Uninitialized)
+}
+
+cfg_if! {
+    if #[cfg(feature = "napi")] {
+        use napi_derive::napi;
+
+        #[napi]
+        pub async fn native_init_cache(url: String, prefix: String) -> napi::Result<()> {
+            init_cache(&url, prefix).await.map_err(Into::into)
+        }
+
+        /// `key` must not be prefixed.
+        #[napi]
+        pub async fn native_cache_get(key: String) -> napi::Result<Option<String>> {
+            get_cache().map_err(Into::<napi::Error>::into)?.get(&key).await.map_err(Into::into)
+        }
+
+        /// Sets the value of the unprefixed `key`, which expires after
+        /// `ttl_ms` milliseconds if specified.
+        #[napi]
+        pub async fn native_cache_set(key: String, value: String, ttl_ms: Option<u32>) -> napi::Result<()> {
+            let ttl = ttl_ms.map(|ms| Duration::from_millis(ms.into()));
+            get_cache().map_err(Into::<napi::Error>::into)?.set(&key, &value, ttl).await.map_err(Into::into)
+        }
+
+        #[napi]
+        pub async fn native_cache_del(key: String) -> napi::Result<()> {
+            get_cache().map_err(Into::<napi::Error>::into)?.del(&key).await.map_err(Into::into)
+        }
+    }
+}
+
+#[cfg(test)]
+mod unit_test {
+    use std::time::Duration;
+
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    use super::{
+        error::Error, get_cache, init_memory_cache, parse_stream_id, Backend, Cache, MemoryBackend,
+    };
+
+    #[tokio::test]
+    async fn can_get_and_set() {
+        let cache = Cache::new("example.com", MemoryBackend::new());
+        assert_eq!(cache.prefixed_key("foo"), "example.com:foo");
+
+        cache.set("foo", "bar", None).await.unwrap();
+        assert_eq!(cache.get("foo").await.unwrap(), Some("bar".to_string()));
+        assert_eq!(
+            cache.backend().get("example.com:foo").await.unwrap(),
+            Some("bar".to_string())
+        );
+        assert_eq!(cache.backend().get("foo").await.unwrap(), None);
+
+        cache.del("foo").await.unwrap();
+        assert_eq!(cache.get("foo").await.unwrap(), None);
+
+        cache
+            .set_json("user", &json!({ "id": "9fil64s6g7cskdrb" }), None)
+            .await
+            .unwrap();
+        let user: Option<serde_json::Value> = cache.get_json("user").await.unwrap();
+        assert_eq!(user, Some(json!({ "id": "9fil64s6g7cskdrb" })));
+    }
+
+    #[tokio::test]
+    async fn can_expire() {
+        let cache = Cache::new("example.com", MemoryBackend::new());
+
+        cache
+            .set("foo", "bar", Some(Duration::from_millis(10)))
+            .await
+            .unwrap();
+        assert_eq!(cache.incr("count", 2).await.unwrap(), 2);
+        assert_eq!(cache.incr("count", -1).await.unwrap(), 1);
+        cache
+            .expire("count", Duration::from_millis(10))
+            .await
+            .unwrap();
+
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(cache.get("foo").await.unwrap(), None);
+        assert_eq!(cache.incr("count", 1).await.unwrap(), 1);
+        cache.set("foo", "bar", None).await.unwrap();
+        assert!(matches!(
+            cache.incr("foo", 1).await,
+            Err(Error::RedisError(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn can_use_streams() {
+        let backend = MemoryBackend::new();
+        let add = |id: &'static str| {
+            let backend = &backend;
+            async move { backend.xadd("stream", id, &[("note", id)], Some(3)).await }
+        };
+
+        assert_eq!(add("1000-*").await.unwrap(), "1000-0");
+        assert_eq!(add("1000-*").await.unwrap(), "1000-1");
+        assert_eq!(add("2000-5").await.unwrap(), "2000-5");
+        assert!(add("1500-*").await.is_err());
+        assert_eq!(add("3000-*").await.unwrap(), "3000-0");
+
+        let ids = |entries: Vec<super::StreamEntry>| -> Vec<String> {
+            entries.into_iter().map(|e| e.id).collect()
+        };
+        // the oldest entry is trimmed by MAXLEN
+        assert_eq!(
+            ids(backend.xrevrange("stream", "+", "-", 10).await.unwrap()),
+            vec!["3000-0", "2000-5", "1000-1"]
+        );
+        assert_eq!(
+            ids(backend
+                .xrevrange("stream", "2000", "1000", 1)
+                .await
+                .unwrap()),
+            vec!["2000-5"]
+        );
+        let entry = &backend.xrevrange("stream", "+", "3000", 1).await.unwrap()[0];
+        assert_eq!(entry.fields.get("note"), Some(&"3000-*".to_string()));
+
+        assert_eq!(backend.xtrim("stream", 1).await.unwrap(), 2);
+        assert_eq!(
+            ids(backend.xrevrange("stream", "+", "-", 10).await.unwrap()),
+            vec!["3000-0"]
+        );
+        assert!(backend
+            .xrevrange("missing", "+", "-", 10)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn stream_id() {
+        assert_eq!(parse_stream_id("1690000000000-1"), Some((1690000000000, 1)));
+        assert_eq!(parse_stream_id("1690000000000"), Some((1690000000000, 0)));
+        assert_eq!(parse_stream_id("foo-1"), None);
+    }
+
+    #[test]
+    fn global_cache() {
+        assert_eq!(init_memory_cache("example.com"), Ok(()));
+        assert_eq!(init_memory_cache("example.com"), Ok(()));
+        assert_eq!(get_cache().unwrap().prefix(), "example.com");
+        assert_eq!(
+            init_memory_cache("example.org"),
+            Err(Error::AlreadyInitialized)
+        );
+    }
+}
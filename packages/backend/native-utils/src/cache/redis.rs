@@ -0,0 +1,143 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use redis::aio::ConnectionManager;
+use redis::streams::{StreamMaxlen, StreamRangeReply};
+use redis::AsyncCommands;
+
+use super::error::Error;
+use super::{Backend, StreamEntry};
+
+/// [Backend] connected to Redis. The connection is re-established
+/// automatically when it is lost.
+#[derive(Clone)]
+pub struct RedisBackend {
+    conn: ConnectionManager,
+}
+
+impl RedisBackend {
+    /// Connects to `redis://` or `rediss://` URL.
+    pub async fn connect(url: &str) -> Result<Self, Error> {
+        let client = redis::Client::open(url)?;
+        Ok(Self {
+            conn: ConnectionManager::new(client).await?,
+        })
+    }
+}
+
+#[async_trait]
+impl Backend for RedisBackend {
+    async fn get(&self, key: &str) -> Result<Option<String>, Error> {
+        Ok(self.conn.clone().get(key).await?)
+    }
+
+    async fn set(&self, key: &str, value: &str, ttl: Option<Duration>) -> Result<(), Error> {
+        let mut conn = self.conn.clone();
+        match ttl {
+            None => conn.set(key, value).await?,
+            Some(ttl) => conn.pset_ex(key, value, ttl.as_millis() as usize).await?,
+        }
+        Ok(())
+    }
+
+    async fn del(&self, key: &str) -> Result<(), Error> {
+        Ok(self.conn.clone().del(key).await?)
+    }
+
+    async fn mget(&self, keys: &[String]) -> Result<Vec<Option<String>>, Error> {
+        if keys.is_empty() {
+            return Ok(Vec::new());
+        }
+        // `get` sends `GET` instead of `MGET` for a single key.
+        Ok(redis::cmd("MGET")
+            .arg(keys)
+            .query_async(&mut self.conn.clone())
+            .await?)
+    }
+
+    async fn incr(&self, key: &str, delta: i64) -> Result<i64, Error> {
+        Ok(self.conn.clone().incr(key, delta).await?)
+    }
+
+    async fn expire(&self, key: &str, ttl: Duration) -> Result<(), Error> {
+        Ok(self
+            .conn
+            .clone()
+            .pexpire(key, ttl.as_millis() as usize)
+            .await?)
+    }
+
+    async fn xadd(
+        &self,
+        key: &str,
+        id: &str,
+        fields: &[(&str, &str)],
+        maxlen: Option<usize>,
+    ) -> Result<String, Error> {
+        let mut conn = self.conn.clone();
+        Ok(match maxlen {
+            None => conn.xadd(key, id, fields).await?,
+            Some(maxlen) => {
+                conn.xadd_maxlen(key, StreamMaxlen::Approx(maxlen), id, fields)
+                    .await?
+            }
+        })
+    }
+
+    async fn xrevrange(
+        &self,
+        key: &str,
+        end: &str,
+        start: &str,
+        count: usize,
+    ) -> Result<Vec<StreamEntry>, Error> {
+        let reply: StreamRangeReply = self
+            .conn
+            .clone()
+            .xrevrange_count(key, end, start, count)
+            .await?;
+        reply
+            .ids
+            .into_iter()
+            .map(|entry| {
+                let fields = entry
+                    .map
+                    .iter()
+                    .map(|(k, v)| Ok((k.to_owned(), redis::from_redis_value(v)?)))
+                    .collect::<Result<_, Error>>()?;
+                Ok(StreamEntry {
+                    id: entry.id,
+                    fields,
+                })
+            })
+            .collect()
+    }
+
+    async fn xlast_ids(&self, keys: &[String]) -> Result<Vec<Option<String>>, Error> {
+        if keys.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut pipe = redis::pipe();
+        for key in keys {
+            pipe.cmd("XREVRANGE")
+                .arg(key)
+                .arg("+")
+                .arg("-")
+                .arg("COUNT")
+                .arg(1);
+        }
+        let replies: Vec<StreamRangeReply> = pipe.query_async(&mut self.conn.clone()).await?;
+        Ok(replies
+            .into_iter()
+            .map(|reply| reply.ids.into_iter().next().map(|entry| entry.id))
+            .collect())
+    }
+
+    async fn xtrim(&self, key: &str, maxlen: usize) -> Result<usize, Error> {
+        Ok(self
+            .conn
+            .clone()
+            .xtrim(key, StreamMaxlen::Equals(maxlen))
+            .await?)
+    }
+}
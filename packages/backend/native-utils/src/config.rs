@@ -0,0 +1,20 @@
+//! Firefish config file, kept in the `config` crate so that the migrator
+//! can load it without building this library
+
+use cfg_if::cfg_if;
+
+pub use ::config::*;
+
+cfg_if! {
+    if #[cfg(feature = "napi")] {
+        use napi_derive::napi;
+
+        /// Loads the config file at `path` and returns it in the same shape
+        /// as `config/load.ts` does.
+        #[napi]
+        pub fn native_init_config(path: String) -> napi::Result<serde_json::Value> {
+            let config = init_config(path).map_err(|e| napi::Error::from_reason(e.to_string()))?;
+            serde_json::to_value(config).map_err(|e| napi::Error::from_reason(e.to_string()))
+        }
+    }
+}
@@ -1,7 +1,13 @@
+pub mod acct;
+pub mod activitypub;
+pub mod antenna;
+pub mod cache;
+pub mod config;
 pub mod database;
 pub mod macros;
+pub mod mastodon_api;
+pub mod mfm;
 pub mod model;
+pub mod nodeinfo;
 pub mod util;
-
-#[cfg(feature = "napi")]
-pub mod mastodon_api;
+pub mod word_mute;
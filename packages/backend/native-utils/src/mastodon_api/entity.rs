@@ -0,0 +1,65 @@
+//! Entities of Mastodon API v1, built from our models
+//!
+//! Ids are converted by [super::id], and `url` arguments are the origin of
+//! the server, e.g. `https://example.com`.
+
+pub mod account;
+pub mod media_attachment;
+pub mod notification;
+pub mod poll;
+pub mod relationship;
+pub mod status;
+
+use chrono::{SecondsFormat, Utc};
+use sea_orm::prelude::DateTimeWithTimeZone;
+use serde::Serialize;
+
+use crate::model::schema::PopulatedEmoji;
+
+pub use account::{Account, Field};
+pub use media_attachment::{MediaAttachment, MediaType};
+pub use notification::{Notification, NotificationType};
+pub use poll::{Poll, PollOption};
+pub use relationship::Relationship;
+pub use status::{Mention, Status, Tag, ViewerState, Visibility};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct CustomEmoji {
+    pub shortcode: String,
+    pub url: String,
+    pub static_url: String,
+    pub visible_in_picker: bool,
+    pub category: Option<String>,
+}
+
+impl From<PopulatedEmoji> for CustomEmoji {
+    fn from(value: PopulatedEmoji) -> Self {
+        Self {
+            shortcode: value.name,
+            static_url: value.url.to_owned(),
+            url: value.url,
+            visible_in_picker: true,
+            category: None,
+        }
+    }
+}
+
+/// Returns the time in ISO 8601 with milliseconds, as Mastodon does.
+pub(crate) fn format_date(date: &DateTimeWithTimeZone) -> String {
+    date.with_timezone(&Utc)
+        .to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+/// Escapes the plain text into HTML, wrapped in a paragraph.
+pub(crate) fn text_to_html(text: &str) -> String {
+    format!("<p>{}</p>", escape_html(text).replace('\n', "<br>"))
+}
+
+pub(crate) fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+        .replace("\r\n", "\n")
+}
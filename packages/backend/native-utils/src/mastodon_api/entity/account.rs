@@ -0,0 +1,220 @@
+use serde::{Deserialize, Serialize};
+
+use crate::model::entity::sea_orm_active_enums::UserProfileFfvisibilityEnum;
+use crate::model::entity::{drive_file, user, user_profile};
+use crate::model::schema::PopulatedEmoji;
+
+use super::super::error::Error;
+use super::super::id::to_mastodon_id;
+use super::{escape_html, format_date, text_to_html, CustomEmoji};
+
+/// <https://docs.joinmastodon.org/entities/Account/>
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Account {
+    pub id: String,
+    pub username: String,
+    /// `username` for local users and `username@host` for remote ones.
+    pub acct: String,
+    pub url: String,
+    pub display_name: String,
+    /// Bio in HTML.
+    pub note: String,
+    pub avatar: String,
+    pub avatar_static: String,
+    pub header: String,
+    pub header_static: String,
+    pub locked: bool,
+    pub fields: Vec<Field>,
+    pub emojis: Vec<CustomEmoji>,
+    pub bot: bool,
+    pub group: bool,
+    pub discoverable: bool,
+    pub noindex: bool,
+    pub created_at: String,
+    pub last_status_at: Option<String>,
+    pub statuses_count: i32,
+    pub followers_count: i32,
+    pub following_count: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub moved: Option<Box<Account>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suspended: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limited: Option<bool>,
+}
+
+/// Profile field, i.e. an entry of `user_profile.fields`.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Field {
+    pub name: String,
+    /// Value in HTML.
+    pub value: String,
+    #[serde(default)]
+    pub verified_at: Option<String>,
+}
+
+impl Account {
+    /// Builds the account without the profile, the avatar, the banner, and
+    /// the emojis, which are added by the `with_` methods.
+    pub fn new(user: &user::Model, url: &str) -> Result<Self, Error> {
+        let acct = match &user.host {
+            None => user.username.to_owned(),
+            Some(host) => format!("{}@{}", user.username, host),
+        };
+        let avatar = format!("{}/identicon/{}", url, user.id);
+        let header = format!("{}/static-assets/transparent.png", url);
+
+        Ok(Self {
+            id: to_mastodon_id(&user.id)?,
+            username: user.username.to_owned(),
+            url: user
+                .uri
+                .to_owned()
+                .unwrap_or_else(|| format!("{}/@{}", url, acct)),
+            acct,
+            display_name: user
+                .name
+                .to_owned()
+                .filter(|n| !n.is_empty())
+                .unwrap_or_else(|| user.username.to_owned()),
+            note: String::new(),
+            avatar_static: avatar.to_owned(),
+            avatar,
+            header_static: header.to_owned(),
+            header,
+            locked: user.is_locked,
+            fields: vec![],
+            emojis: vec![],
+            bot: user.is_bot,
+            group: false,
+            discoverable: user.is_explorable,
+            noindex: !user.is_indexable,
+            created_at: format_date(&user.created_at),
+            last_status_at: None,
+            statuses_count: user.notes_count,
+            followers_count: user.followers_count,
+            following_count: user.following_count,
+            moved: None,
+            suspended: user.is_suspended.then_some(true),
+            limited: user.is_silenced.then_some(true),
+        })
+    }
+
+    /// Adds the bio, the fields, and the profile url. The follower and
+    /// following counts are hidden unless the user makes them public.
+    pub fn with_profile(self, profile: &user_profile::Model) -> Self {
+        let fields: Vec<Field> =
+            serde_json::from_value(profile.fields.to_owned()).unwrap_or_default();
+        let hide_counts = profile.ff_visibility != UserProfileFfvisibilityEnum::Public;
+        Self {
+            note: profile
+                .description
+                .as_deref()
+                .map(text_to_html)
+                .unwrap_or_default(),
+            fields: fields
+                .into_iter()
+                .map(|f| Field {
+                    name: f.name,
+                    value: escape_html(&f.value),
+                    verified_at: None,
+                })
+                .collect(),
+            url: profile.url.to_owned().unwrap_or(self.url),
+            followers_count: if hide_counts { 0 } else { self.followers_count },
+            following_count: if hide_counts { 0 } else { self.following_count },
+            ..self
+        }
+    }
+
+    pub fn with_avatar(self, avatar: &drive_file::Model) -> Self {
+        match avatar.public_url(true) {
+            None => self,
+            Some(url) => Self {
+                avatar_static: url.to_owned(),
+                avatar: url,
+                ..self
+            },
+        }
+    }
+
+    pub fn with_header(self, banner: &drive_file::Model) -> Self {
+        match banner.public_url(false) {
+            None => self,
+            Some(url) => Self {
+                header_static: url.to_owned(),
+                header: url,
+                ..self
+            },
+        }
+    }
+
+    pub fn with_emojis(self, emojis: Vec<PopulatedEmoji>) -> Self {
+        Self {
+            emojis: emojis.into_iter().map(CustomEmoji::from).collect(),
+            ..self
+        }
+    }
+
+    /// Sets the account the user has moved to.
+    pub fn with_moved(self, moved: Account) -> Self {
+        Self {
+            moved: Some(Box::new(moved)),
+            ..self
+        }
+    }
+}
+
+#[cfg(test)]
+mod unit_test {
+    use chrono::Utc;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    use crate::model::entity::sea_orm_active_enums::UserProfileFfvisibilityEnum;
+    use crate::model::entity::{user, user_profile};
+
+    use super::Account;
+
+    #[test]
+    fn can_convert() {
+        let user = user::Model {
+            id: "9fil64s6g7cskdrb".to_string(),
+            created_at: Utc::now().into(),
+            username: "alice".to_string(),
+            host: Some("example.org".to_string()),
+            followers_count: 10,
+            following_count: 20,
+            notes_count: 30,
+            is_indexable: true,
+            ..Default::default()
+        };
+        let profile = user_profile::Model {
+            description: Some("Hello,\n<world>".to_string()),
+            fields: json!([{ "name": "Website", "value": "https://example.org" }]),
+            ff_visibility: UserProfileFfvisibilityEnum::Followers,
+            ..Default::default()
+        };
+        let account = Account::new(&user, "https://example.com")
+            .unwrap()
+            .with_profile(&profile);
+        let json = serde_json::to_value(&account).unwrap();
+
+        assert_eq!(json["acct"], "alice@example.org");
+        assert_eq!(json["display_name"], "alice");
+        assert_eq!(json["url"], "https://example.com/@alice@example.org");
+        assert_eq!(json["note"], "<p>Hello,<br>&lt;world&gt;</p>");
+        assert_eq!(json["fields"][0]["name"], "Website");
+        assert_eq!(json["fields"][0]["verified_at"], json!(null));
+        assert_eq!(
+            json["avatar"],
+            "https://example.com/identicon/9fil64s6g7cskdrb"
+        );
+        assert_eq!(json["statuses_count"], 30);
+        assert_eq!(json["followers_count"], 0);
+        assert_eq!(json["noindex"], false);
+        assert!(json.get("moved").is_none());
+        assert!(json.get("suspended").is_none());
+        assert_ne!(json["id"], "9fil64s6g7cskdrb");
+    }
+}
@@ -0,0 +1,125 @@
+use serde::Serialize;
+
+use crate::model::entity::drive_file;
+
+use super::super::error::Error;
+use super::super::id::to_mastodon_id;
+
+/// <https://docs.joinmastodon.org/entities/MediaAttachment/>
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct MediaAttachment {
+    pub id: String,
+    pub r#type: MediaType,
+    pub url: String,
+    pub preview_url: Option<String>,
+    /// Url of the original file on the remote server.
+    pub remote_url: Option<String>,
+    pub meta: Option<MediaMeta>,
+    /// Alt text.
+    pub description: Option<String>,
+    pub blurhash: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MediaType {
+    Image,
+    Gifv,
+    Video,
+    Audio,
+    Unknown,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct MediaMeta {
+    pub original: MediaSize,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct MediaSize {
+    pub width: u32,
+    pub height: u32,
+    /// `{width}x{height}`
+    pub size: String,
+    pub aspect: f64,
+}
+
+impl From<&str> for MediaType {
+    /// Returns the type of the MIME type.
+    fn from(mime: &str) -> Self {
+        match mime.split_once('/').map(|(t, _)| t) {
+            Some("image") => Self::Image,
+            Some("video") => Self::Video,
+            Some("audio") => Self::Audio,
+            _ => Self::Unknown,
+        }
+    }
+}
+
+impl MediaAttachment {
+    pub fn new(file: &drive_file::Model) -> Result<Self, Error> {
+        let properties = file.public_properties();
+        let meta = match (properties.width, properties.height) {
+            (Some(width), Some(height)) if width > 0 && height > 0 => Some(MediaMeta {
+                original: MediaSize {
+                    width,
+                    height,
+                    size: format!("{}x{}", width, height),
+                    aspect: f64::from(width) / f64::from(height),
+                },
+            }),
+            _ => None,
+        };
+        let url = file
+            .public_url(false)
+            .unwrap_or_else(|| file.url.to_owned());
+
+        Ok(Self {
+            id: to_mastodon_id(&file.id)?,
+            r#type: MediaType::from(file.r#type.as_str()),
+            preview_url: file.public_url(true).or_else(|| Some(url.to_owned())),
+            remote_url: file.uri.to_owned(),
+            url,
+            meta,
+            description: file.comment.to_owned(),
+            blurhash: file.blurhash.to_owned(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod unit_test {
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    use crate::model::entity::drive_file;
+
+    use super::{MediaAttachment, MediaType};
+
+    #[test]
+    fn can_convert() {
+        let file = drive_file::Model {
+            id: "9fil64s6g7cskdrb".to_string(),
+            r#type: "image/png".to_string(),
+            url: "https://example.com/files/original".to_string(),
+            thumbnail_url: Some("https://example.com/files/thumbnail".to_string()),
+            properties: json!({ "width": 400, "height": 200 }),
+            comment: Some("A cat".to_string()),
+            ..Default::default()
+        };
+        let json = serde_json::to_value(MediaAttachment::new(&file).unwrap()).unwrap();
+
+        assert_eq!(json["type"], "image");
+        assert_eq!(json["url"], "https://example.com/files/original");
+        assert_eq!(json["preview_url"], "https://example.com/files/thumbnail");
+        assert_eq!(json["remote_url"], json!(null));
+        assert_eq!(json["description"], "A cat");
+        assert_eq!(
+            json["meta"],
+            json!({ "original": { "width": 400, "height": 200, "size": "400x200", "aspect": 2.0 } })
+        );
+
+        assert_eq!(MediaType::from("video/mp4"), MediaType::Video);
+        assert_eq!(MediaType::from("application/pdf"), MediaType::Unknown);
+    }
+}
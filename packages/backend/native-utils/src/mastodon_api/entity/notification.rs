@@ -0,0 +1,130 @@
+use serde::Serialize;
+
+use crate::model::entity::notification;
+use crate::model::entity::sea_orm_active_enums::NotificationTypeEnum;
+
+use super::super::error::Error;
+use super::super::id::to_mastodon_id;
+use super::{format_date, Account, Status};
+
+/// <https://docs.joinmastodon.org/entities/Notification/>
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Notification {
+    pub id: String,
+    pub r#type: NotificationType,
+    pub created_at: String,
+    /// Account that caused the notification.
+    pub account: Account,
+    pub status: Option<Status>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationType {
+    Mention,
+    Status,
+    Reblog,
+    Follow,
+    FollowRequest,
+    Favourite,
+    Poll,
+    Update,
+}
+
+impl TryFrom<&NotificationTypeEnum> for NotificationType {
+    type Error = Error;
+
+    /// Replies and quotes are mentions, and reactions are favourites.
+    /// Notifications of apps and group invitations have no counterparts.
+    fn try_from(value: &NotificationTypeEnum) -> Result<Self, Self::Error> {
+        match value {
+            NotificationTypeEnum::Mention
+            | NotificationTypeEnum::Reply
+            | NotificationTypeEnum::Quote => Ok(Self::Mention),
+            NotificationTypeEnum::Renote => Ok(Self::Reblog),
+            NotificationTypeEnum::Follow | NotificationTypeEnum::FollowRequestAccepted => {
+                Ok(Self::Follow)
+            }
+            NotificationTypeEnum::ReceiveFollowRequest => Ok(Self::FollowRequest),
+            NotificationTypeEnum::Reaction => Ok(Self::Favourite),
+            NotificationTypeEnum::PollVote | NotificationTypeEnum::PollEnded => Ok(Self::Poll),
+            NotificationTypeEnum::App | NotificationTypeEnum::GroupInvited => {
+                Err(Error::UnsupportedNotificationType(format!("{:?}", value)))
+            }
+        }
+    }
+}
+
+impl Notification {
+    /// Builds the notification caused by `account` about `status`. Returns
+    /// [Error::UnsupportedNotificationType] if Mastodon has no such type,
+    /// and such notifications should be skipped.
+    pub fn new(
+        notification: &notification::Model,
+        account: Account,
+        status: Option<Status>,
+    ) -> Result<Self, Error> {
+        Ok(Self {
+            id: to_mastodon_id(&notification.id)?,
+            r#type: NotificationType::try_from(&notification.r#type)?,
+            created_at: format_date(&notification.created_at),
+            account,
+            status,
+        })
+    }
+}
+
+#[cfg(test)]
+mod unit_test {
+    use chrono::Utc;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    use crate::mastodon_api::error::Error;
+    use crate::model::entity::sea_orm_active_enums::NotificationTypeEnum;
+    use crate::model::entity::{notification, user};
+
+    use super::{Account, Notification};
+
+    #[test]
+    fn can_convert() {
+        let user = user::Model {
+            id: "9fil64s6g7cskdrb".to_string(),
+            username: "alice".to_string(),
+            created_at: Utc::now().into(),
+            ..Default::default()
+        };
+        let account = Account::new(&user, "https://example.com").unwrap();
+        let model = notification::Model {
+            id: "9fil65jzhtjpi3xn".to_string(),
+            created_at: Utc::now().into(),
+            r#type: NotificationTypeEnum::ReceiveFollowRequest,
+            ..Default::default()
+        };
+
+        let json =
+            serde_json::to_value(Notification::new(&model, account.to_owned(), None).unwrap())
+                .unwrap();
+        assert_eq!(json["type"], "follow_request");
+        assert_eq!(json["status"], json!(null));
+        assert_eq!(json["account"]["acct"], "alice");
+
+        let reaction = notification::Model {
+            r#type: NotificationTypeEnum::Reaction,
+            ..model.to_owned()
+        };
+        let json =
+            serde_json::to_value(Notification::new(&reaction, account.to_owned(), None).unwrap())
+                .unwrap();
+        assert_eq!(json["type"], "favourite");
+
+        let app = notification::Model {
+            r#type: NotificationTypeEnum::App,
+            ..model
+        };
+        assert!(matches!(
+            Notification::new(&app, account, None),
+            Err(Error::UnsupportedNotificationType(_))
+        ));
+    }
+}
@@ -0,0 +1,117 @@
+use chrono::Utc;
+use serde::Serialize;
+
+use crate::model::entity::newtype::{from_i32_vec, from_string_vec};
+use crate::model::entity::{poll, poll_vote};
+
+use super::super::error::Error;
+use super::super::id::to_mastodon_id;
+use super::{format_date, CustomEmoji};
+
+/// <https://docs.joinmastodon.org/entities/Poll/>
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Poll {
+    /// Id of the note, as polls have no ids of their own.
+    pub id: String,
+    pub expires_at: Option<String>,
+    pub expired: bool,
+    pub multiple: bool,
+    pub votes_count: i32,
+    /// Unknown as only the votes are counted.
+    pub voters_count: Option<i32>,
+    pub options: Vec<PollOption>,
+    pub emojis: Vec<CustomEmoji>,
+    /// Only present if there is a viewer.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub voted: Option<bool>,
+    /// Indices of the options the viewer voted for.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub own_votes: Option<Vec<i32>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct PollOption {
+    pub title: String,
+    pub votes_count: Option<i32>,
+}
+
+impl Poll {
+    pub fn new(poll: &poll::Model) -> Result<Self, Error> {
+        let choices = from_string_vec(poll.choices.to_owned());
+        let votes = from_i32_vec(poll.votes.to_owned());
+        let options: Vec<PollOption> = choices
+            .into_iter()
+            .enumerate()
+            .map(|(i, title)| PollOption {
+                title,
+                votes_count: Some(votes.get(i).copied().unwrap_or_default()),
+            })
+            .collect();
+
+        Ok(Self {
+            id: to_mastodon_id(&poll.note_id)?,
+            expires_at: poll.expires_at.as_ref().map(format_date),
+            expired: poll.expires_at.is_some_and(|e| e < Utc::now()),
+            multiple: poll.multiple,
+            votes_count: options.iter().filter_map(|o| o.votes_count).sum(),
+            voters_count: None,
+            options,
+            emojis: vec![],
+            voted: None,
+            own_votes: None,
+        })
+    }
+
+    /// Sets the votes of the viewer on this poll.
+    pub fn with_own_votes(self, votes: &[poll_vote::Model]) -> Self {
+        let mut own_votes: Vec<i32> = votes.iter().map(|v| v.choice).collect();
+        own_votes.sort_unstable();
+        Self {
+            voted: Some(!own_votes.is_empty()),
+            own_votes: Some(own_votes),
+            ..self
+        }
+    }
+}
+
+#[cfg(test)]
+mod unit_test {
+    use chrono::{Duration, Utc};
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    use crate::model::entity::newtype::{i32_vec, string_vec};
+    use crate::model::entity::{poll, poll_vote};
+
+    use super::Poll;
+
+    #[test]
+    fn can_convert() {
+        let model = poll::Model {
+            note_id: "9fil64s6g7cskdrb".to_string(),
+            expires_at: Some((Utc::now() - Duration::days(1)).into()),
+            multiple: true,
+            choices: string_vec(vec!["Yes".to_string(), "No".to_string()]),
+            votes: i32_vec(vec![3, 2]),
+            ..Default::default()
+        };
+        let vote = poll_vote::Model {
+            choice: 1,
+            ..Default::default()
+        };
+
+        let json = serde_json::to_value(Poll::new(&model).unwrap()).unwrap();
+        assert_eq!(json["expired"], true);
+        assert_eq!(json["votes_count"], 5);
+        assert_eq!(json["voters_count"], json!(null));
+        assert_eq!(
+            json["options"][1],
+            json!({ "title": "No", "votes_count": 2 })
+        );
+        assert!(json.get("voted").is_none());
+
+        let poll = Poll::new(&model).unwrap().with_own_votes(&[vote]);
+        assert_eq!(poll.voted, Some(true));
+        assert_eq!(poll.own_votes, Some(vec![1]));
+    }
+}
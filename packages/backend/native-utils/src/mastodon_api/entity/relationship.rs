@@ -0,0 +1,142 @@
+use serde::Serialize;
+
+use crate::model::entity::{blocking, follow_request, following, muting, renote_muting};
+
+use super::super::error::Error;
+use super::super::id::to_mastodon_id;
+
+/// Relationship of the viewer with another user.
+///
+/// <https://docs.joinmastodon.org/entities/Relationship/>
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct Relationship {
+    /// Id of the other user.
+    pub id: String,
+    pub following: bool,
+    pub showing_reblogs: bool,
+    pub notifying: bool,
+    pub followed_by: bool,
+    pub blocking: bool,
+    pub blocked_by: bool,
+    pub muting: bool,
+    pub muting_notifications: bool,
+    pub requested: bool,
+    pub requested_by: bool,
+    pub domain_blocking: bool,
+    pub endorsed: bool,
+    pub note: String,
+    #[serde(skip)]
+    viewer_id: String,
+    #[serde(skip)]
+    user_id: String,
+}
+
+impl Relationship {
+    /// Builds the relationship of `viewer_id` with `user_id`, which is
+    /// filled by the `with_` methods. Models between other users are
+    /// ignored, so that they can be loaded for many users at once.
+    pub fn new(viewer_id: &str, user_id: &str) -> Result<Self, Error> {
+        Ok(Self {
+            id: to_mastodon_id(user_id)?,
+            showing_reblogs: true,
+            viewer_id: viewer_id.to_string(),
+            user_id: user_id.to_string(),
+            ..Default::default()
+        })
+    }
+
+    /// Returns whether the edge from `from` to `to` is outgoing, i.e. from
+    /// the viewer to the user, and whether it is incoming.
+    fn directions(&self, from: &str, to: &str) -> (bool, bool) {
+        (
+            from == self.viewer_id && to == self.user_id,
+            from == self.user_id && to == self.viewer_id,
+        )
+    }
+
+    pub fn with_followings(mut self, followings: &[following::Model]) -> Self {
+        for f in followings {
+            let (outgoing, incoming) = self.directions(&f.follower_id, &f.followee_id);
+            self.following |= outgoing;
+            self.followed_by |= incoming;
+        }
+        self
+    }
+
+    pub fn with_follow_requests(mut self, requests: &[follow_request::Model]) -> Self {
+        for r in requests {
+            let (outgoing, incoming) = self.directions(&r.follower_id, &r.followee_id);
+            self.requested |= outgoing;
+            self.requested_by |= incoming;
+        }
+        self
+    }
+
+    pub fn with_blockings(mut self, blockings: &[blocking::Model]) -> Self {
+        for b in blockings {
+            let (outgoing, incoming) = self.directions(&b.blocker_id, &b.blockee_id);
+            self.blocking |= outgoing;
+            self.blocked_by |= incoming;
+        }
+        self
+    }
+
+    /// Mutes also hide notifications from the user.
+    pub fn with_mutings(mut self, mutings: &[muting::Model]) -> Self {
+        for m in mutings {
+            let (outgoing, _) = self.directions(&m.muter_id, &m.mutee_id);
+            self.muting |= outgoing;
+            self.muting_notifications |= outgoing;
+        }
+        self
+    }
+
+    pub fn with_renote_mutings(mut self, mutings: &[renote_muting::Model]) -> Self {
+        for m in mutings {
+            let (outgoing, _) = self.directions(&m.muter_id, &m.mutee_id);
+            self.showing_reblogs &= !outgoing;
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod unit_test {
+    use pretty_assertions::assert_eq;
+
+    use crate::model::entity::{blocking, following, renote_muting};
+
+    use super::Relationship;
+
+    #[test]
+    fn can_build() {
+        let following = |follower: &str, followee: &str| following::Model {
+            follower_id: follower.to_string(),
+            followee_id: followee.to_string(),
+            ..Default::default()
+        };
+        let relationship = Relationship::new("alice", "bob")
+            .unwrap()
+            .with_followings(&[following("bob", "alice"), following("alice", "carol")])
+            .with_blockings(&[blocking::Model {
+                blocker_id: "alice".to_string(),
+                blockee_id: "bob".to_string(),
+                ..Default::default()
+            }])
+            .with_renote_mutings(&[renote_muting::Model {
+                muter_id: "alice".to_string(),
+                mutee_id: "bob".to_string(),
+                ..Default::default()
+            }]);
+
+        assert!(!relationship.following);
+        assert!(relationship.followed_by);
+        assert!(relationship.blocking);
+        assert!(!relationship.blocked_by);
+        assert!(!relationship.showing_reblogs);
+
+        let json = serde_json::to_value(&relationship).unwrap();
+        assert_eq!(json.as_object().unwrap().len(), 14);
+        assert!(json.get("viewer_id").is_none());
+    }
+}
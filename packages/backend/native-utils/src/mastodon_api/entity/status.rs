@@ -0,0 +1,277 @@
+use serde::Serialize;
+
+use crate::model::entity::newtype::from_string_vec;
+use crate::model::entity::sea_orm_active_enums::NoteVisibilityEnum;
+use crate::model::entity::{note, user};
+use crate::model::schema::PopulatedEmoji;
+
+use super::super::error::Error;
+use super::super::id::to_mastodon_id;
+use super::{format_date, text_to_html, Account, CustomEmoji, MediaAttachment, Poll};
+
+/// <https://docs.joinmastodon.org/entities/Status/>
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Status {
+    pub id: String,
+    pub uri: String,
+    pub url: Option<String>,
+    pub created_at: String,
+    pub edited_at: Option<String>,
+    pub account: Account,
+    /// Text in HTML.
+    pub content: String,
+    pub visibility: Visibility,
+    pub sensitive: bool,
+    /// Content warning.
+    pub spoiler_text: String,
+    pub media_attachments: Vec<MediaAttachment>,
+    pub application: Option<serde_json::Value>,
+    pub mentions: Vec<Mention>,
+    pub tags: Vec<Tag>,
+    pub emojis: Vec<CustomEmoji>,
+    pub reblogs_count: i32,
+    /// Total count of reactions.
+    pub favourites_count: i32,
+    pub replies_count: i32,
+    pub in_reply_to_id: Option<String>,
+    pub in_reply_to_account_id: Option<String>,
+    pub reblog: Option<Box<Status>>,
+    pub poll: Option<Poll>,
+    pub card: Option<serde_json::Value>,
+    pub language: Option<String>,
+    /// Only present if there is a viewer.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub favourited: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reblogged: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub muted: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bookmarked: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pinned: Option<bool>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Visibility {
+    Public,
+    Unlisted,
+    Private,
+    Direct,
+}
+
+impl From<&NoteVisibilityEnum> for Visibility {
+    fn from(value: &NoteVisibilityEnum) -> Self {
+        match value {
+            NoteVisibilityEnum::Public => Self::Public,
+            NoteVisibilityEnum::Home => Self::Unlisted,
+            NoteVisibilityEnum::Followers | NoteVisibilityEnum::Hidden => Self::Private,
+            NoteVisibilityEnum::Specified => Self::Direct,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Mention {
+    pub id: String,
+    pub username: String,
+    pub url: String,
+    pub acct: String,
+}
+
+impl Mention {
+    pub fn new(user: &user::Model, url: &str) -> Result<Self, Error> {
+        let account = Account::new(user, url)?;
+        Ok(Self {
+            id: account.id,
+            username: account.username,
+            url: account.url,
+            acct: account.acct,
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Tag {
+    pub name: String,
+    pub url: String,
+}
+
+/// Interactions of the viewer with a status.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ViewerState {
+    pub favourited: bool,
+    pub reblogged: bool,
+    pub muted: bool,
+    pub bookmarked: bool,
+    pub pinned: bool,
+}
+
+impl Status {
+    /// Builds the status of the note posted by `account`. The attachments,
+    /// the poll, the mentions, the emojis, the renote, and the state of the
+    /// viewer are added by the `with_` methods.
+    pub fn new(note: &note::Model, account: Account, url: &str) -> Result<Self, Error> {
+        let uri = note
+            .uri
+            .to_owned()
+            .unwrap_or_else(|| format!("{}/notes/{}", url, note.id));
+        let favourites_count = note
+            .reactions
+            .as_object()
+            .map(|r| {
+                r.values()
+                    .filter_map(serde_json::Value::as_i64)
+                    .sum::<i64>()
+            })
+            .unwrap_or_default();
+        let convert = |id: &Option<String>| id.as_deref().map(to_mastodon_id).transpose();
+
+        Ok(Self {
+            id: to_mastodon_id(&note.id)?,
+            url: Some(note.url.to_owned().unwrap_or_else(|| uri.to_owned())),
+            uri,
+            created_at: format_date(&note.created_at),
+            edited_at: note.updated_at.as_ref().map(format_date),
+            account,
+            content: note.text.as_deref().map(text_to_html).unwrap_or_default(),
+            visibility: Visibility::from(&note.visibility),
+            sensitive: note.cw.is_some(),
+            spoiler_text: note.cw.to_owned().unwrap_or_default(),
+            media_attachments: vec![],
+            application: None,
+            mentions: vec![],
+            tags: from_string_vec(note.tags.to_owned())
+                .into_iter()
+                .map(|name| Tag {
+                    url: format!("{}/tags/{}", url, name),
+                    name,
+                })
+                .collect(),
+            emojis: vec![],
+            reblogs_count: note.renote_count.into(),
+            favourites_count: i32::try_from(favourites_count).unwrap_or(i32::MAX),
+            replies_count: note.replies_count.into(),
+            in_reply_to_id: convert(&note.reply_id)?,
+            in_reply_to_account_id: convert(&note.reply_user_id)?,
+            reblog: None,
+            poll: None,
+            card: None,
+            language: None,
+            favourited: None,
+            reblogged: None,
+            muted: None,
+            bookmarked: None,
+            pinned: None,
+        })
+    }
+
+    /// Sets the attached files. The status becomes sensitive if any of them
+    /// is.
+    pub fn with_media(self, attachments: Vec<MediaAttachment>, sensitive: bool) -> Self {
+        Self {
+            media_attachments: attachments,
+            sensitive: self.sensitive || sensitive,
+            ..self
+        }
+    }
+
+    pub fn with_poll(self, poll: Poll) -> Self {
+        Self {
+            poll: Some(poll),
+            ..self
+        }
+    }
+
+    pub fn with_mentions(self, mentions: Vec<Mention>) -> Self {
+        Self { mentions, ..self }
+    }
+
+    pub fn with_emojis(self, emojis: Vec<PopulatedEmoji>) -> Self {
+        Self {
+            emojis: emojis.into_iter().map(CustomEmoji::from).collect(),
+            ..self
+        }
+    }
+
+    /// Sets the renoted status. Pure renotes have no text of their own.
+    pub fn with_reblog(self, reblog: Status) -> Self {
+        Self {
+            reblog: Some(Box::new(reblog)),
+            ..self
+        }
+    }
+
+    pub fn with_viewer_state(self, state: ViewerState) -> Self {
+        Self {
+            favourited: Some(state.favourited),
+            reblogged: Some(state.reblogged),
+            muted: Some(state.muted),
+            bookmarked: Some(state.bookmarked),
+            pinned: Some(state.pinned),
+            ..self
+        }
+    }
+}
+
+#[cfg(test)]
+mod unit_test {
+    use chrono::Utc;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    use crate::model::entity::newtype::string_vec;
+    use crate::model::entity::sea_orm_active_enums::NoteVisibilityEnum;
+    use crate::model::entity::{note, user};
+
+    use super::{Account, Status, ViewerState};
+
+    #[test]
+    fn can_convert() {
+        let user = user::Model {
+            id: "9fil64s6g7cskdrb".to_string(),
+            username: "alice".to_string(),
+            created_at: Utc::now().into(),
+            ..Default::default()
+        };
+        let note = note::Model {
+            id: "9fil65jzhtjpi3xn".to_string(),
+            created_at: Utc::now().into(),
+            text: Some("Hello, #firefish".to_string()),
+            cw: Some("greeting".to_string()),
+            reply_id: Some("9fil66brl1udxau2".to_string()),
+            reactions: json!({ "👍": 2, ":blobcat:": 1 }),
+            visibility: NoteVisibilityEnum::Home,
+            tags: string_vec(vec!["firefish".to_string()]),
+            user_id: user.id.to_owned(),
+            ..Default::default()
+        };
+        let account = Account::new(&user, "https://example.com").unwrap();
+        let status = Status::new(&note, account, "https://example.com").unwrap();
+        let json = serde_json::to_value(&status).unwrap();
+
+        assert_eq!(json["uri"], "https://example.com/notes/9fil65jzhtjpi3xn");
+        assert_eq!(json["content"], "<p>Hello, #firefish</p>");
+        assert_eq!(json["visibility"], "unlisted");
+        assert_eq!(json["sensitive"], true);
+        assert_eq!(json["spoiler_text"], "greeting");
+        assert_eq!(json["favourites_count"], 3);
+        assert_eq!(json["account"]["username"], "alice");
+        assert_eq!(
+            json["tags"],
+            json!([{ "name": "firefish", "url": "https://example.com/tags/firefish" }])
+        );
+        assert_ne!(json["in_reply_to_id"], json!(null));
+        assert_eq!(json["in_reply_to_account_id"], json!(null));
+        assert_eq!(json["reblog"], json!(null));
+        assert!(json.get("favourited").is_none());
+
+        let status = status.with_viewer_state(ViewerState {
+            favourited: true,
+            ..Default::default()
+        });
+        assert_eq!(status.favourited, Some(true));
+        assert_eq!(status.pinned, Some(false));
+    }
+}
@@ -0,0 +1,11 @@
+use crate::impl_into_napi_error;
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum Error {
+    #[error("Failed to convert id: {0}")]
+    IdError(#[from] super::id::Error),
+    #[error("Notification of type {0} is not supported by Mastodon API")]
+    UnsupportedNotificationType(String),
+}
+
+impl_into_napi_error!(Error);
@@ -0,0 +1,235 @@
+//! Conversion between our ids and Mastodon ids
+//!
+//! Mastodon ids are decimal numbers, and clients order them numerically. An
+//! id is encoded as a base 63 number whose digits are its characters, `0-9`,
+//! `A-Z`, and `a-z` mapped to `1` to `62` in ASCII order, and which is padded
+//! on the right with `0` up to [SORTABLE_LENGTH] digits. Therefore
+//!
+//! - leading zeros of ids are kept as they are nonzero digits,
+//! - the padding is removed unambiguously when decoding, and
+//! - Mastodon ids are in the same order as the ids compared as strings, which
+//!   is the order of time for every id format, as long as the ids are not
+//!   longer than [SORTABLE_LENGTH].
+//!
+//! Longer ids are converted without padding, so they round-trip but sort
+//! after all the shorter ones.
+//!
+//! Unlike the former scheme, ids are not lowercased, so that upper-case ids
+//! such as ulids round-trip. An id and its lowercase form are different ids
+//! and get different Mastodon ids.
+//!
+//! Mastodon ids issued before this scheme are lowercase base 36 ids read as
+//! decimal numbers. They have fewer than [SORTABLE_LENGTH] digits in base
+//! 63, so [from_mastodon_id] still accepts them, and decodes them in lower
+//! case as they were encoded.
+
+use crate::impl_into_napi_error;
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum Error {
+    #[error("The id is empty")]
+    EmptyId,
+    #[error("Invalid character in the id: {0:?}")]
+    InvalidCharacter(char),
+    #[error("Invalid Mastodon id: {0}")]
+    InvalidMastodonId(String),
+}
+
+impl_into_napi_error!(Error);
+
+/// Ids up to this length are ordered consistently when converted.
+pub const SORTABLE_LENGTH: usize = 32;
+
+const BASE: u32 = 63;
+const ALPHABET: &[u8; 62] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+const LEGACY_BASE: u32 = 36;
+const LEGACY_ALPHABET: &[u8; 36] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+
+fn digit_of(c: char) -> Result<u32, Error> {
+    match c {
+        '0'..='9' => Ok(c as u32 - '0' as u32 + 1),
+        'A'..='Z' => Ok(c as u32 - 'A' as u32 + 11),
+        'a'..='z' => Ok(c as u32 - 'a' as u32 + 37),
+        _ => Err(Error::InvalidCharacter(c)),
+    }
+}
+
+/// Converts our id into a Mastodon id. The case of the id is kept.
+pub fn to_mastodon_id(id: &str) -> Result<String, Error> {
+    if id.is_empty() {
+        return Err(Error::EmptyId);
+    }
+    let mut digits = id.chars().map(digit_of).collect::<Result<Vec<_>, _>>()?;
+    digits.resize(digits.len().max(SORTABLE_LENGTH), 0);
+
+    // little-endian decimal digits
+    let mut decimal: Vec<u32> = vec![];
+    for digit in digits {
+        let mut carry = digit;
+        for d in decimal.iter_mut() {
+            let n = *d * BASE + carry;
+            *d = n % 10;
+            carry = n / 10;
+        }
+        while carry > 0 {
+            decimal.push(carry % 10);
+            carry /= 10;
+        }
+    }
+    Ok(decimal
+        .iter()
+        .rev()
+        .map(|&d| char::from_digit(d, 10).unwrap())
+        .collect())
+}
+
+/// Converts big-endian decimal digits without leading zeros into
+/// little-endian digits in `base`.
+fn from_decimal(mut decimal: Vec<u32>, base: u32) -> Vec<u32> {
+    let mut digits: Vec<u32> = vec![];
+    while !decimal.is_empty() {
+        let mut remainder = 0;
+        for d in decimal.iter_mut() {
+            let n = remainder * 10 + *d;
+            *d = n / base;
+            remainder = n % base;
+        }
+        digits.push(remainder);
+        let leading_zeros = decimal.iter().take_while(|&&d| d == 0).count();
+        decimal.drain(..leading_zeros);
+    }
+    digits
+}
+
+/// Converts a Mastodon id made by [to_mastodon_id], or by the former base 36
+/// scheme, back into our id.
+pub fn from_mastodon_id(mastodon_id: &str) -> Result<String, Error> {
+    let invalid = || Error::InvalidMastodonId(mastodon_id.to_string());
+    // big-endian decimal digits
+    let decimal = mastodon_id
+        .chars()
+        .map(|c| c.to_digit(10))
+        .collect::<Option<Vec<u32>>>()
+        .filter(|d| d.first().is_some_and(|&d| d != 0))
+        .ok_or_else(invalid)?;
+
+    // little-endian base 63 digits
+    let digits = from_decimal(decimal.to_owned(), BASE);
+    if digits.len() < SORTABLE_LENGTH {
+        return Ok(from_decimal(decimal, LEGACY_BASE)
+            .iter()
+            .rev()
+            .map(|&d| char::from(LEGACY_ALPHABET[d as usize]))
+            .collect());
+    }
+    let padding = digits.iter().take_while(|&&d| d == 0).count();
+    if digits.len() > SORTABLE_LENGTH && padding > 0 {
+        return Err(invalid());
+    }
+    digits
+        .iter()
+        .rev()
+        .take(digits.len() - padding)
+        .map(|&d| match d {
+            0 => Err(invalid()),
+            d => Ok(char::from(ALPHABET[d as usize - 1])),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod unit_test {
+    use pretty_assertions::{assert_eq, assert_ne};
+    use proptest::prelude::*;
+
+    use super::{from_mastodon_id, to_mastodon_id, Error};
+
+    #[test]
+    fn examples() {
+        for id in [
+            "9fil64s6g7cskdrb",
+            "0000000000",
+            "9fil64s6g7cskdrbpqz1qlz8",
+            "01H5ZKX3PV0000000000000000",
+            "81897b5e2e00000000000000",
+        ] {
+            let mastodon_id = to_mastodon_id(id).unwrap();
+            assert!(mastodon_id.chars().all(|c| c.is_ascii_digit()));
+            assert_eq!(from_mastodon_id(&mastodon_id).unwrap(), id);
+        }
+        assert!(to_mastodon_id("00").unwrap() < to_mastodon_id("01").unwrap());
+    }
+
+    #[test]
+    fn keeps_case() {
+        let upper = to_mastodon_id("9FIL64S6G7CSKDRB").unwrap();
+        let lower = to_mastodon_id("9fil64s6g7cskdrb").unwrap();
+        assert_ne!(upper, lower);
+        assert_eq!(from_mastodon_id(&upper).unwrap(), "9FIL64S6G7CSKDRB");
+        assert_eq!(from_mastodon_id(&lower).unwrap(), "9fil64s6g7cskdrb");
+    }
+
+    #[test]
+    fn invalid() {
+        assert_eq!(
+            to_mastodon_id("9fil-64s6"),
+            Err(Error::InvalidCharacter('-'))
+        );
+        assert_eq!(to_mastodon_id(""), Err(Error::EmptyId));
+        for mastodon_id in ["", "0123", "12a"] {
+            assert_eq!(
+                from_mastodon_id(mastodon_id),
+                Err(Error::InvalidMastodonId(mastodon_id.to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn legacy() {
+        assert_eq!(
+            from_mastodon_id("109358936829186058").unwrap(),
+            "twsia0w0sga"
+        );
+        assert_eq!(from_mastodon_id("1").unwrap(), "1");
+        for id in ["9fil64s6g7cskdrb", "9fil64s6g7cskdrbpqz1qlz8", "a000000000"] {
+            let legacy_id = u128::from_str_radix(id, 36).unwrap().to_string();
+            assert_eq!(from_mastodon_id(&legacy_id).unwrap(), id);
+            // converted into the current scheme from then on
+            let mastodon_id = to_mastodon_id(id).unwrap();
+            assert_ne!(mastodon_id, legacy_id);
+            assert_eq!(from_mastodon_id(&mastodon_id).unwrap(), id);
+        }
+    }
+
+    /// Compares Mastodon ids numerically as clients do.
+    fn numeric_order(a: &str, b: &str) -> std::cmp::Ordering {
+        a.len().cmp(&b.len()).then_with(|| a.cmp(b))
+    }
+
+    proptest! {
+        #[test]
+        fn round_trip(id in "[0-9A-Za-z]{1,64}") {
+            let mastodon_id = to_mastodon_id(&id).unwrap();
+            prop_assert_eq!(from_mastodon_id(&mastodon_id).unwrap(), id);
+        }
+
+        #[test]
+        fn keeps_order(a in "[0-9a-z]{1,32}", b in "[0-9A-Za-z]{1,32}") {
+            let (ma, mb) = (to_mastodon_id(&a).unwrap(), to_mastodon_id(&b).unwrap());
+            prop_assert_eq!(numeric_order(&ma, &mb), a.cmp(&b));
+        }
+
+        #[test]
+        fn rejects_or_round_trips(mastodon_id in "[1-9][0-9]{56,80}") {
+            if let Ok(id) = from_mastodon_id(&mastodon_id) {
+                prop_assert_eq!(to_mastodon_id(&id).unwrap(), mastodon_id);
+            }
+        }
+
+        #[test]
+        fn decodes_legacy(id in "[1-9a-z][0-9a-z]{0,23}") {
+            let legacy_id = u128::from_str_radix(&id, 36).unwrap().to_string();
+            prop_assert_eq!(from_mastodon_id(&legacy_id).unwrap(), id);
+        }
+    }
+}
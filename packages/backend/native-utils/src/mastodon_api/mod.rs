@@ -0,0 +1,28 @@
+pub mod entity;
+pub mod error;
+pub mod id;
+
+use cfg_if::cfg_if;
+
+cfg_if! {
+    if #[cfg(feature = "napi")] {
+        use napi_derive::napi;
+
+        #[napi]
+        pub enum IdConvertType {
+            MastodonId,
+            FirefishId,
+        }
+
+        /// Converts our id into a Mastodon id or the other way around. See
+        /// [id] for the encoding.
+        #[napi]
+        pub fn convert_id(in_id: String, id_convert_type: IdConvertType) -> napi::Result<String> {
+            match id_convert_type {
+                IdConvertType::MastodonId => id::to_mastodon_id(&in_id),
+                IdConvertType::FirefishId => id::from_mastodon_id(&in_id),
+            }
+            .map_err(Into::into)
+        }
+    }
+}
@@ -0,0 +1,123 @@
+//! Extraction of `note.mentions`, `note.tags`, and `note.emojis`, ported from
+//! `misc/extract-mentions.ts`, `misc/extract-hashtags.ts`, and
+//! `misc/extract-custom-emojis-from-mfm.ts`
+
+use super::node::{walk, MentionProps, MfmNode};
+
+/// Custom emojis with longer names are ignored.
+const MAX_EMOJI_NAME_LEN: usize = 100;
+
+/// Removes duplicates, keeping the first occurrences in order.
+fn unique(values: Vec<String>) -> Vec<String> {
+    let mut result: Vec<String> = Vec::with_capacity(values.len());
+    for value in values {
+        if !result.contains(&value) {
+            result.push(value);
+        }
+    }
+    result
+}
+
+/// Returns the mentions in order of appearance. Duplicates are kept, as
+/// TypeScript does.
+pub fn extract_mentions(nodes: &[MfmNode]) -> Vec<MentionProps> {
+    let mut mentions = Vec::new();
+    walk(nodes, &mut |node| {
+        if let MfmNode::Mention { props } = node {
+            mentions.push(props.to_owned());
+        }
+    });
+    mentions
+}
+
+/// Returns the distinct hashtags, without `#`.
+pub fn extract_hashtags(nodes: &[MfmNode]) -> Vec<String> {
+    let mut hashtags = Vec::new();
+    walk(nodes, &mut |node| {
+        if let MfmNode::Hashtag { props } = node {
+            hashtags.push(props.hashtag.to_owned());
+        }
+    });
+    unique(hashtags)
+}
+
+/// Returns the distinct names of the custom emojis.
+pub fn extract_custom_emojis(nodes: &[MfmNode]) -> Vec<String> {
+    let mut names = Vec::new();
+    walk(nodes, &mut |node| {
+        if let MfmNode::EmojiCode { props } = node {
+            if props.name.chars().count() <= MAX_EMOJI_NAME_LEN {
+                names.push(props.name.to_owned());
+            }
+        }
+    });
+    unique(names)
+}
+
+#[cfg(test)]
+mod unit_test {
+    use pretty_assertions::assert_eq;
+
+    use super::{extract_custom_emojis, extract_hashtags, extract_mentions};
+    use crate::mfm::node::MentionProps;
+    use crate::mfm::parser::parse;
+
+    #[test]
+    fn mentions() {
+        let mention = |username: &str, host: Option<&str>| MentionProps {
+            username: username.to_string(),
+            host: host.map(str::to_string),
+            acct: match host {
+                Some(host) => format!("@{}@{}", username, host),
+                None => format!("@{}", username),
+            },
+        };
+
+        // same as test/extract-mentions.ts
+        assert_eq!(
+            extract_mentions(&parse("@foo @bar @baz")),
+            vec![
+                mention("foo", None),
+                mention("bar", None),
+                mention("baz", None)
+            ]
+        );
+        assert_eq!(
+            extract_mentions(&parse("@foo **@bar** @baz")),
+            vec![
+                mention("foo", None),
+                mention("bar", None),
+                mention("baz", None)
+            ]
+        );
+        assert_eq!(
+            extract_mentions(&parse("@foo@example.com > @foo@example.com")),
+            vec![
+                mention("foo", Some("example.com")),
+                mention("foo", Some("example.com")),
+            ]
+        );
+    }
+
+    #[test]
+    fn hashtags() {
+        assert_eq!(
+            extract_hashtags(&parse(
+                "#foo <i>#bar</i> #foo `#baz` [#qux](https://example.com)"
+            )),
+            vec!["foo", "bar"]
+        );
+    }
+
+    #[test]
+    fn custom_emojis() {
+        let long = "a".repeat(101);
+        assert_eq!(
+            extract_custom_emojis(&parse(&format!(
+                ":blobcat: $[spin :ai_yay:] :blobcat: :{}: 👍",
+                long
+            ))),
+            vec!["blobcat", "ai_yay"]
+        );
+    }
+}
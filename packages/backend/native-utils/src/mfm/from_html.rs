@@ -0,0 +1,430 @@
+//! Conversion of HTML from remote servers into MFM, ported from
+//! `mfm/from-html.ts` and `remote/activitypub/misc/html-to-mfm.ts`
+//!
+//! Unlike TypeScript, the contents of `<script>`, `<style>`, and the like
+//! are dropped, control characters other than line breaks and tabs are
+//! removed, and deeply nested elements are flattened into their text.
+
+use ego_tree::iter::Edge;
+use ego_tree::NodeRef;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use scraper::{Html, Node};
+
+/// Media type of `source.content` in MFM, sent by Misskey and its forks.
+pub const MFM_MEDIA_TYPE: &str = "text/x.misskeymarkdown";
+
+/// Elements deeper than this are converted into their text only, so that
+/// malicious HTML cannot exhaust the stack.
+const MAX_DEPTH: usize = 64;
+
+// some AP servers like Pixelfed use br tags as well as newlines
+static BR_NEWLINE: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?i)<br\s?/?>\r?\n").unwrap());
+static URL: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^https?://[A-Za-z0-9_/:%#@$&?!()\[\]~.,=+\-]+").unwrap());
+static URL_FULL: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^https?://[A-Za-z0-9_/:%#@$&?!()\[\]~.,=+\-]+$").unwrap());
+
+/// Elements whose contents are never shown.
+fn is_ignored(name: &str) -> bool {
+    matches!(
+        name,
+        "script" | "style" | "template" | "noscript" | "iframe" | "object" | "head" | "title"
+    )
+}
+
+fn push_sanitized(dest: &mut String, text: &str) {
+    dest.extend(
+        text.chars()
+            .filter(|c| !c.is_control() || matches!(c, '\n' | '\r' | '\t')),
+    );
+}
+
+/// Returns the text of the node, with `<br>` as line breaks.
+fn get_text(node: NodeRef<Node>) -> String {
+    let mut text = String::new();
+    let mut ignored = 0;
+    for edge in node.traverse() {
+        match edge {
+            Edge::Open(n) => match n.value() {
+                Node::Element(e) if is_ignored(e.name()) => ignored += 1,
+                _ if ignored > 0 => {}
+                Node::Text(t) => push_sanitized(&mut text, t),
+                Node::Element(e) if e.name() == "br" => text.push('\n'),
+                _ => {}
+            },
+            Edge::Close(n) => match n.value() {
+                Node::Element(e) if is_ignored(e.name()) => ignored -= 1,
+                _ => {}
+            },
+        }
+    }
+    text
+}
+
+struct Converter {
+    text: String,
+    /// Lowercased names of the hashtags attached to the note, with `#`.
+    hashtag_names: Option<Vec<String>>,
+}
+
+impl Converter {
+    fn append_children(&mut self, node: NodeRef<Node>, depth: usize) {
+        for child in node.children() {
+            self.analyze(child, depth + 1);
+        }
+    }
+
+    fn analyze(&mut self, node: NodeRef<Node>, depth: usize) {
+        let element = match node.value() {
+            Node::Text(t) => return push_sanitized(&mut self.text, t),
+            Node::Element(e) => e,
+            // skip comments and doctypes
+            _ => return,
+        };
+        if depth > MAX_DEPTH {
+            return self.text.push_str(&get_text(node));
+        }
+
+        match element.name() {
+            name if is_ignored(name) => {}
+            "br" => self.text.push('\n'),
+            "a" => {
+                let txt = get_text(node);
+                let rel = element.attr("rel");
+                let href = element.attr("href");
+
+                let is_hashtag = href.is_some()
+                    && self
+                        .hashtag_names
+                        .as_ref()
+                        .is_some_and(|names| names.contains(&txt.to_lowercase()));
+                if is_hashtag {
+                    self.text.push_str(&txt);
+                } else if txt.starts_with('@') && !rel.is_some_and(|r| r.starts_with("me ")) {
+                    match (txt.split('@').count(), href) {
+                        // restore the omitted host
+                        (2, Some(href)) => match url::Url::parse(href)
+                            .ok()
+                            .and_then(|u| u.host_str().map(str::to_string))
+                        {
+                            Some(host) => self.text.push_str(&format!("{}@{}", txt, host)),
+                            None => self.text.push_str(&txt),
+                        },
+                        (3, _) => self.text.push_str(&txt),
+                        _ => {}
+                    }
+                } else {
+                    self.text.push_str(&generate_link(&txt, href));
+                }
+            }
+            "h1" => {
+                self.append_children(node, depth);
+                self.text.push('\n');
+            }
+            "b" | "strong" => {
+                self.text.push_str("**");
+                self.append_children(node, depth);
+                self.text.push_str("**");
+            }
+            "small" => {
+                self.text.push_str("<small>");
+                self.append_children(node, depth);
+                self.text.push_str("</small>");
+            }
+            "s" | "del" => {
+                self.text.push_str("~~");
+                self.append_children(node, depth);
+                self.text.push_str("~~");
+            }
+            "i" | "em" => {
+                self.text.push_str("<i>");
+                self.append_children(node, depth);
+                self.text.push_str("</i>");
+            }
+            // block code (<pre><code>)
+            "pre" => {
+                let mut children = node.children();
+                match (children.next(), children.next()) {
+                    (Some(code), None) if matches!(code.value(), Node::Element(e) if e.name() == "code") =>
+                    {
+                        self.text.push_str("\n```\n");
+                        self.text.push_str(&get_text(code));
+                        self.text.push_str("\n```\n");
+                    }
+                    _ => self.append_children(node, depth),
+                }
+            }
+            // inline code (<code>)
+            "code" => {
+                self.text.push('`');
+                self.append_children(node, depth);
+                self.text.push('`');
+            }
+            "blockquote" => {
+                let t = get_text(node);
+                if !t.is_empty() {
+                    self.text.push_str("\n> ");
+                    self.text.push_str(&t.replace('\n', "\n> "));
+                }
+            }
+            "p" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                self.text.push_str("\n\n");
+                self.append_children(node, depth);
+            }
+            // other block elements
+            "div" | "header" | "footer" | "article" | "li" | "dt" | "dd" => {
+                self.text.push('\n');
+                self.append_children(node, depth);
+            }
+            // includes inline elements
+            _ => self.append_children(node, depth),
+        }
+    }
+}
+
+fn generate_link(txt: &str, href: Option<&str>) -> String {
+    match href {
+        None => txt.to_string(),
+        // #6383: Missing text node
+        Some(href) if txt.is_empty() || txt == href => {
+            if URL_FULL.is_match(href) {
+                href.to_string()
+            } else {
+                format!("<{}>", href)
+            }
+        }
+        Some(href) if URL.is_match(href) && !URL_FULL.is_match(href) => {
+            format!("[{}](<{}>)", txt, href) // #6846
+        }
+        Some(href) => format!("[{}]({})", txt, href),
+    }
+}
+
+/// Converts the HTML into MFM. Links whose text is one of `hashtag_names`,
+/// the names of the `Hashtag` objects attached to the note such as `#tag`,
+/// are converted into hashtags.
+pub fn html_to_mfm(html: &str, hashtag_names: Option<&[String]>) -> String {
+    let html = BR_NEWLINE.replace_all(html, "\n");
+    let fragment = Html::parse_fragment(&html);
+
+    let mut converter = Converter {
+        text: String::new(),
+        hashtag_names: hashtag_names.map(|names| names.iter().map(|n| n.to_lowercase()).collect()),
+    };
+    for node in fragment.root_element().children() {
+        converter.analyze(node, 0);
+    }
+    converter.text.trim().to_string()
+}
+
+/// Content of a remote note, from which `note.text` is made.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RemoteContent<'a> {
+    /// `source.content` and `source.mediaType`.
+    pub source: Option<(&'a str, &'a str)>,
+    /// `_misskey_content`, sent by older versions of Misskey.
+    pub misskey_content: Option<&'a str>,
+    /// The first entry of `contentMap`, or `content`, in HTML.
+    pub html: Option<&'a str>,
+    pub hashtag_names: &'a [String],
+}
+
+/// Returns the text of the remote note in MFM, preferring the MFM source
+/// sent by Misskey over converting the HTML.
+pub fn remote_note_text(content: &RemoteContent) -> Option<String> {
+    if let Some((text, MFM_MEDIA_TYPE)) = content.source {
+        return Some(text.to_string());
+    }
+    if let Some(text) = content.misskey_content {
+        return Some(text.to_string());
+    }
+    content
+        .html
+        .map(|html| html_to_mfm(html, Some(content.hashtag_names)))
+}
+
+#[cfg(test)]
+mod unit_test {
+    use pretty_assertions::assert_eq;
+    use proptest::prelude::*;
+
+    use super::{html_to_mfm, remote_note_text, RemoteContent, MFM_MEDIA_TYPE};
+
+    fn from_html(html: &str) -> String {
+        html_to_mfm(html, None)
+    }
+
+    #[test]
+    fn blocks() {
+        // same as test/mfm.ts
+        assert_eq!(from_html("<p>a</p><p>b</p>"), "a\n\nb");
+        assert_eq!(from_html("<div>a</div><div>b</div>"), "a\nb");
+        assert_eq!(from_html("<ul><li>a</li><li>b</li></ul>"), "a\nb");
+        assert_eq!(from_html("<pre><code>a\nb</code></pre>"), "```\na\nb\n```");
+        assert_eq!(from_html("<code>a</code>"), "`a`");
+        assert_eq!(from_html("<blockquote>a\nb</blockquote>"), "> a\n> b");
+        assert_eq!(from_html("<p>abc<br><br/>d</p>"), "abc\n\nd");
+        assert_eq!(from_html("<p>a<br>\nb</p>"), "a\nb");
+        assert_eq!(
+            from_html("<h1>T</h1><b>a</b><em>b</em><del>c</del><small>d</small>"),
+            "T\n**a**<i>b</i>~~c~~<small>d</small>"
+        );
+    }
+
+    #[test]
+    fn links() {
+        // same as test/mfm.ts
+        assert_eq!(
+            from_html(r#"<p>a <a href="https://joinfirefish.org/b">c</a> d</p>"#),
+            "a [c](https://joinfirefish.org/b) d"
+        );
+        assert_eq!(
+            from_html(r#"<p>a <a href="https://joinfirefish.org/ä">c</a> d</p>"#),
+            "a [c](<https://joinfirefish.org/ä>) d"
+        );
+        assert_eq!(
+            from_html(
+                r#"<p>a <a href="https://joinfirefish.org/b">https://joinfirefish.org/b</a> d</p>"#
+            ),
+            "a https://joinfirefish.org/b d"
+        );
+        assert_eq!(
+            from_html(
+                r#"<p>a <a href="https://joinfirefish.org/ä">https://joinfirefish.org/ä</a> d</p>"#
+            ),
+            "a <https://joinfirefish.org/ä> d"
+        );
+        assert_eq!(from_html(r#"<p>a <a href="b">c</a> d</p>"#), "a [c](b) d");
+        assert_eq!(from_html("<p>a <a>c</a> d</p>"), "a c d");
+        assert_eq!(
+            from_html(r#"<p>a <a href="https://joinfirefish.org/b"></a> d</p>"#),
+            "a https://joinfirefish.org/b d"
+        );
+        assert_eq!(from_html("<p>a <a></a> d</p>"), "a  d");
+    }
+
+    #[test]
+    fn mentions_and_hashtags() {
+        // same as test/mfm.ts
+        assert_eq!(
+            from_html(
+                r#"<p>a <a href="https://joinfirefish.org/@user" class="u-url mention">@user</a> d</p>"#
+            ),
+            "a @user@joinfirefish.org d"
+        );
+        assert_eq!(
+            html_to_mfm(
+                r#"<p>a <a href="https://joinfirefish.org/tags/a">#a</a> d</p>"#,
+                Some(&["#A".to_string()])
+            ),
+            "a #a d"
+        );
+
+        assert_eq!(
+            from_html(
+                r#"<span class="h-card"><a href="https://xn--r8jz45g.example/@user">@<span>user</span></a></span>"#
+            ),
+            "@user@xn--r8jz45g.example"
+        );
+        assert_eq!(
+            from_html(r#"<a href="https://a.example/@b">@b@c.example</a>"#),
+            "@b@c.example"
+        );
+        assert_eq!(
+            from_html(r#"<a href="https://a.example/@me" rel="me nofollow">@me</a>"#),
+            "[@me](https://a.example/@me)"
+        );
+        // hashtags that are not attached are links
+        assert_eq!(
+            from_html(r#"<a href="https://a.example/tags/a">#a</a>"#),
+            "[#a](https://a.example/tags/a)"
+        );
+    }
+
+    #[test]
+    fn sanitization() {
+        assert_eq!(
+            from_html("<p>a<script>alert(1)</script><style>p {}</style>b</p>"),
+            "ab"
+        );
+        assert_eq!(
+            from_html("<blockquote>a<script>alert(1)</script></blockquote>"),
+            "> a"
+        );
+        assert_eq!(from_html("<p>a\u{0}b\u{1b}[31mc\td</p>"), "ab[31mc\td");
+        assert_eq!(
+            from_html("<!-- comment --><p>a &amp; &lt;b&gt;</p>"),
+            "a & <b>"
+        );
+
+        let deep = format!("{}a{}", "<div>".repeat(5000), "</div>".repeat(5000));
+        assert_eq!(from_html(&deep), "a");
+    }
+
+    #[test]
+    fn remote_content() {
+        let hashtag_names = vec!["#tag".to_string()];
+        let html = r#"<p><a href="https://a.example/tags/tag">#tag</a> <b>text</b></p>"#;
+        let content = RemoteContent {
+            source: Some(("$[tada #tag]", MFM_MEDIA_TYPE)),
+            misskey_content: Some("#tag **text**"),
+            html: Some(html),
+            hashtag_names: &hashtag_names,
+        };
+        assert_eq!(remote_note_text(&content).unwrap(), "$[tada #tag]");
+
+        let content = RemoteContent {
+            source: Some(("#tag text", "text/markdown")),
+            ..content
+        };
+        assert_eq!(remote_note_text(&content).unwrap(), "#tag **text**");
+
+        let content = RemoteContent {
+            misskey_content: None,
+            ..content
+        };
+        assert_eq!(remote_note_text(&content).unwrap(), "#tag **text**");
+        assert_eq!(remote_note_text(&RemoteContent::default()), None);
+    }
+
+    /// Fragments of HTML to build random documents from.
+    fn html_fragment() -> impl Strategy<Value = String> {
+        prop_oneof![
+            Just("<p>".to_string()),
+            Just("</p>".to_string()),
+            Just("<br>".to_string()),
+            Just("<b>".to_string()),
+            Just("</b>".to_string()),
+            Just("<pre><code>".to_string()),
+            Just("</code></pre>".to_string()),
+            Just("<blockquote>".to_string()),
+            Just("</blockquote>".to_string()),
+            Just("<a href=\"https://a.example/@x\">".to_string()),
+            Just("</a>".to_string()),
+            Just("<script>secret</script>".to_string()),
+            Just("<style>secret</style>".to_string()),
+            "[a-z@# \n]{0,8}",
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn never_panics(html in any::<String>()) {
+            let text = from_html(&html);
+            prop_assert_eq!(text.trim(), text.as_str());
+            prop_assert!(text.chars().all(|c| !c.is_control() || matches!(c, '\n' | '\r' | '\t')));
+        }
+
+        #[test]
+        fn drops_hidden_contents(fragments in prop::collection::vec(html_fragment(), 0..64)) {
+            let text = from_html(&fragments.concat());
+            prop_assert!(!text.contains("secret"));
+        }
+
+        #[test]
+        fn keeps_plain_text(text in "[A-Za-z0-9][A-Za-z0-9 ]{0,40}[A-Za-z0-9]") {
+            prop_assert_eq!(from_html(&format!("<p>{}</p>", text)), text);
+        }
+    }
+}
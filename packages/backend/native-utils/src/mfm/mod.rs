@@ -0,0 +1,87 @@
+//! MFM (Misskey Flavored Markdown), ported from `mfm-js` and `src/mfm`
+//!
+//! Notes are parsed into [MfmNode]s, from which `note.mentions`,
+//! `note.tags`, and `note.emojis` are extracted and the HTML for
+//! ActivityPub is rendered. HTML of remote notes is converted back into MFM
+//! by [from_html].
+
+pub mod extract;
+pub mod from_html;
+pub mod node;
+pub mod parser;
+pub mod to_html;
+
+use cfg_if::cfg_if;
+
+pub use extract::{extract_custom_emojis, extract_hashtags, extract_mentions};
+pub use from_html::{html_to_mfm, remote_note_text, RemoteContent};
+pub use node::MfmNode;
+pub use parser::{parse, parse_simple, parse_with_options, ParseOptions};
+pub use to_html::{to_html, MentionedRemoteUser};
+
+cfg_if! {
+    if #[cfg(feature = "napi")] {
+        use napi_derive::napi;
+
+        fn from_value<T: serde::de::DeserializeOwned>(value: serde_json::Value) -> napi::Result<T> {
+            serde_json::from_value(value).map_err(|e| napi::Error::from_reason(e.to_string()))
+        }
+
+        fn to_value(value: impl serde::Serialize) -> napi::Result<serde_json::Value> {
+            serde_json::to_value(value).map_err(|e| napi::Error::from_reason(e.to_string()))
+        }
+
+        /// Returns the same nodes as `mfm.parse` of `mfm-js`.
+        #[napi]
+        pub fn native_parse_mfm(text: String, nest_limit: Option<u32>) -> napi::Result<serde_json::Value> {
+            let options = ParseOptions {
+                nest_limit: nest_limit.map_or(parser::DEFAULT_NEST_LIMIT, |n| n as usize),
+                ..Default::default()
+            };
+            to_value(parse_with_options(&text, &options))
+        }
+
+        /// Returns the same nodes as `mfm.parseSimple` of `mfm-js`.
+        #[napi]
+        pub fn native_parse_simple_mfm(text: String) -> napi::Result<serde_json::Value> {
+            to_value(parse_simple(&text))
+        }
+
+        #[napi]
+        pub fn native_extract_mentions(nodes: serde_json::Value) -> napi::Result<serde_json::Value> {
+            to_value(extract_mentions(&from_value::<Vec<MfmNode>>(nodes)?))
+        }
+
+        #[napi]
+        pub fn native_extract_hashtags(nodes: serde_json::Value) -> napi::Result<Vec<String>> {
+            Ok(extract_hashtags(&from_value::<Vec<MfmNode>>(nodes)?))
+        }
+
+        #[napi]
+        pub fn native_extract_custom_emojis_from_mfm(nodes: serde_json::Value) -> napi::Result<Vec<String>> {
+            Ok(extract_custom_emojis(&from_value::<Vec<MfmNode>>(nodes)?))
+        }
+
+        /// Renders the nodes as `toHtml` does. `url` is the origin of the
+        /// server.
+        #[napi]
+        pub fn native_mfm_to_html(
+            nodes: serde_json::Value,
+            url: String,
+            mentioned_remote_users: Option<serde_json::Value>,
+        ) -> napi::Result<String> {
+            let users: Vec<MentionedRemoteUser> = match mentioned_remote_users {
+                Some(users) => from_value(users)?,
+                None => Vec::new(),
+            };
+            Ok(to_html(&from_value::<Vec<MfmNode>>(nodes)?, &url, &users))
+        }
+
+        /// Converts the HTML of a remote note into MFM, as `htmlToMfm` does
+        /// with the names of the attached hashtags.
+        #[napi]
+        pub fn native_html_to_mfm(html: String, hashtag_names: Option<Vec<String>>) -> String {
+            html_to_mfm(&html, hashtag_names.as_deref())
+        }
+    }
+}
@@ -0,0 +1,189 @@
+//! Nodes of the MFM syntax tree, serialized in the same shape as `mfm-js`
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum MfmNode {
+    Quote {
+        children: Vec<MfmNode>,
+    },
+    Search {
+        props: SearchProps,
+    },
+    BlockCode {
+        props: BlockCodeProps,
+    },
+    MathBlock {
+        props: MathProps,
+    },
+    Center {
+        children: Vec<MfmNode>,
+    },
+    UnicodeEmoji {
+        props: UnicodeEmojiProps,
+    },
+    EmojiCode {
+        props: EmojiCodeProps,
+    },
+    Bold {
+        children: Vec<MfmNode>,
+    },
+    Small {
+        children: Vec<MfmNode>,
+    },
+    Italic {
+        children: Vec<MfmNode>,
+    },
+    Strike {
+        children: Vec<MfmNode>,
+    },
+    InlineCode {
+        props: InlineCodeProps,
+    },
+    MathInline {
+        props: MathProps,
+    },
+    Mention {
+        props: MentionProps,
+    },
+    Hashtag {
+        props: HashtagProps,
+    },
+    Url {
+        props: UrlProps,
+    },
+    Link {
+        props: LinkProps,
+        children: Vec<MfmNode>,
+    },
+    Fn {
+        props: FnProps,
+        children: Vec<MfmNode>,
+    },
+    Plain {
+        children: Vec<MfmNode>,
+    },
+    Text {
+        props: TextProps,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SearchProps {
+    pub query: String,
+    /// The whole line including the search button, e.g. `foo [search]`.
+    pub content: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockCodeProps {
+    pub code: String,
+    pub lang: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MathProps {
+    pub formula: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UnicodeEmojiProps {
+    pub emoji: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EmojiCodeProps {
+    /// Name of the custom emoji, without colons.
+    pub name: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InlineCodeProps {
+    pub code: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MentionProps {
+    pub username: String,
+    /// `None` if the host is omitted.
+    pub host: Option<String>,
+    /// `@username` or `@username@host`, as written.
+    pub acct: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HashtagProps {
+    /// Name of the hashtag, without `#`.
+    pub hashtag: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UrlProps {
+    pub url: String,
+    /// `Some(true)` if the url is enclosed in `<>`, omitted otherwise.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub brackets: Option<bool>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LinkProps {
+    /// `true` for `?[label](url)`, which has no link preview.
+    pub silent: bool,
+    pub url: String,
+}
+
+/// Argument of `$[name.key=value ...]`. Arguments without a value are flags.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum FnArg {
+    Flag(bool),
+    Value(String),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FnProps {
+    pub name: String,
+    pub args: BTreeMap<String, FnArg>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TextProps {
+    pub text: String,
+}
+
+impl MfmNode {
+    pub fn text(text: impl Into<String>) -> Self {
+        Self::Text {
+            props: TextProps { text: text.into() },
+        }
+    }
+
+    /// Returns the child nodes, or `None` if the node cannot have any.
+    pub fn children(&self) -> Option<&[MfmNode]> {
+        match self {
+            Self::Quote { children }
+            | Self::Center { children }
+            | Self::Bold { children }
+            | Self::Small { children }
+            | Self::Italic { children }
+            | Self::Strike { children }
+            | Self::Link { children, .. }
+            | Self::Fn { children, .. }
+            | Self::Plain { children } => Some(children),
+            _ => None,
+        }
+    }
+}
+
+/// Calls `f` on the nodes and all their descendants, parents first.
+pub fn walk<'a>(nodes: &'a [MfmNode], f: &mut impl FnMut(&'a MfmNode)) {
+    for node in nodes {
+        f(node);
+        if let Some(children) = node.children() {
+            walk(children, f);
+        }
+    }
+}
@@ -0,0 +1,1344 @@
+//! MFM parser, ported from `mfm-js`
+//!
+//! The grammar is the same as `mfm-js` 0.23, so that notes parsed here and
+//! in TypeScript have the same mentions, hashtags, and emojis. Block syntax
+//! like quotes and code blocks must start at the beginning of a line.
+
+use std::collections::{BTreeMap, HashMap};
+
+use super::node::{
+    BlockCodeProps, EmojiCodeProps, FnArg, FnProps, HashtagProps, InlineCodeProps, LinkProps,
+    MathProps, MentionProps, MfmNode, SearchProps, UnicodeEmojiProps, UrlProps,
+};
+
+/// Default of [ParseOptions::nest_limit], the same as `mfm-js`.
+pub const DEFAULT_NEST_LIMIT: usize = 20;
+
+/// Rules are tried at most this many times per character of the input, and
+/// the rest of the input is left as text after that. Notes usually need one
+/// or two, but syntax left unclosed again and again needs a number
+/// proportional to the length of the input.
+const MAX_STEPS_PER_CHAR: usize = 100;
+
+/// Longest emoji sequence in code points, e.g. families and subdivision flags.
+const MAX_EMOJI_LEN: usize = 10;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseOptions {
+    /// Maximum depth of nested nodes. Syntax nested deeper is left as text.
+    pub nest_limit: usize,
+    /// Names of `$[name ...]` to accept. Any name is accepted if `None`.
+    pub fn_name_list: Option<Vec<String>>,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            nest_limit: DEFAULT_NEST_LIMIT,
+            fn_name_list: None,
+        }
+    }
+}
+
+/// Parses the text with the full syntax, e.g. of note texts and bios.
+pub fn parse(input: &str) -> Vec<MfmNode> {
+    parse_with_options(input, &ParseOptions::default())
+}
+
+pub fn parse_with_options(input: &str, options: &ParseOptions) -> Vec<MfmNode> {
+    let mut parser = Parser::new(input, options);
+    merge_text(parser.many(0, Parser::full))
+}
+
+/// Parses the text with emojis and `<plain>` only, e.g. of user names.
+pub fn parse_simple(input: &str) -> Vec<MfmNode> {
+    let options = ParseOptions::default();
+    let mut parser = Parser::new(input, &options);
+    merge_text(parser.many(0, Parser::simple))
+}
+
+/// Parsed node or a string to be merged into adjacent text.
+#[derive(Clone)]
+enum Item {
+    Node(MfmNode),
+    Text(String),
+}
+
+type Rule<'a, T> = fn(&mut Parser<'a>, usize) -> Option<T>;
+
+/// Joins adjacent strings and text nodes into single text nodes.
+fn merge_text(items: Vec<Item>) -> Vec<MfmNode> {
+    let mut nodes = Vec::with_capacity(items.len());
+    let mut text = String::new();
+    for item in items {
+        match item {
+            Item::Text(s) => text.push_str(&s),
+            Item::Node(MfmNode::Text { props }) => text.push_str(&props.text),
+            Item::Node(node) => {
+                if !text.is_empty() {
+                    nodes.push(MfmNode::text(std::mem::take(&mut text)));
+                }
+                nodes.push(node);
+            }
+        }
+    }
+    if !text.is_empty() {
+        nodes.push(MfmNode::text(text));
+    }
+    nodes
+}
+
+fn is_space(c: char) -> bool {
+    matches!(c, ' ' | '\u{3000}' | '\t')
+}
+
+fn is_hashtag_char(c: char) -> bool {
+    !is_space(c)
+        && !matches!(
+            c,
+            '\r' | '\n'
+                | '.'
+                | ','
+                | '!'
+                | '?'
+                | '\''
+                | '"'
+                | '#'
+                | ':'
+                | '/'
+                | '['
+                | ']'
+                | '【'
+                | '】'
+                | '('
+                | ')'
+                | '「'
+                | '」'
+                | '（'
+                | '）'
+                | '<'
+                | '>'
+        )
+}
+
+fn is_url_char(c: char) -> bool {
+    c.is_ascii_alphanumeric()
+        || matches!(
+            c,
+            '.' | ','
+                | '_'
+                | '/'
+                | ':'
+                | '%'
+                | '#'
+                | '@'
+                | '$'
+                | '&'
+                | '?'
+                | '!'
+                | '~'
+                | '='
+                | '+'
+                | '-'
+        )
+}
+
+/// Set of rules tried at a position.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Syntax {
+    Full,
+    Inline,
+    Simple,
+}
+
+/// Result of [Syntax] at a position, keyed by the syntax, the position, the
+/// depth, and whether in a link label, which are all the result depends on.
+type Memo = HashMap<(Syntax, usize, usize, bool), Option<(usize, Item)>>;
+
+struct Parser<'a> {
+    input: Vec<char>,
+    options: &'a ParseOptions,
+    depth: usize,
+    /// Whether parsing the label of a link, where links, urls, mentions, and
+    /// hashtags are not allowed.
+    link_label: bool,
+    /// Without this, unclosed syntax such as `<b><b><b>...` is parsed again
+    /// for every enclosing syntax, which takes exponential time.
+    memo: Memo,
+    /// Number of times rules were tried, which is at most [Self::max_steps].
+    steps: usize,
+    max_steps: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(input: &str, options: &'a ParseOptions) -> Self {
+        Self {
+            input: input.chars().collect(),
+            options,
+            depth: 0,
+            link_label: false,
+            memo: Memo::new(),
+            steps: 0,
+            max_steps: input.chars().count().saturating_mul(MAX_STEPS_PER_CHAR),
+        }
+    }
+
+    fn char_at(&self, i: usize) -> Option<char> {
+        self.input.get(i).copied()
+    }
+
+    fn slice(&self, from: usize, to: usize) -> String {
+        self.input[from..to].iter().collect()
+    }
+
+    /// Returns the index after `s` if the input continues with it.
+    fn str_at(&self, i: usize, s: &str) -> Option<usize> {
+        let mut j = i;
+        for c in s.chars() {
+            if self.char_at(j) != Some(c) {
+                return None;
+            }
+            j += 1;
+        }
+        Some(j)
+    }
+
+    /// Same as [Self::str_at], ignoring ASCII case.
+    fn str_at_ignore_case(&self, i: usize, s: &str) -> Option<usize> {
+        let mut j = i;
+        for c in s.chars() {
+            if !self.char_at(j)?.eq_ignore_ascii_case(&c) {
+                return None;
+            }
+            j += 1;
+        }
+        Some(j)
+    }
+
+    fn take_while(&self, i: usize, pred: impl Fn(char) -> bool) -> usize {
+        let mut j = i;
+        while self.char_at(j).is_some_and(&pred) {
+            j += 1;
+        }
+        j
+    }
+
+    /// Returns the index after `\r\n`, `\r`, or `\n`.
+    fn newline(&self, i: usize) -> Option<usize> {
+        match self.char_at(i)? {
+            '\r' if self.char_at(i + 1) == Some('\n') => Some(i + 2),
+            '\r' | '\n' => Some(i + 1),
+            _ => None,
+        }
+    }
+
+    fn newline_opt(&self, i: usize) -> usize {
+        self.newline(i).unwrap_or(i)
+    }
+
+    fn line_begin(&self, i: usize) -> bool {
+        i == 0 || matches!(self.char_at(i - 1), Some('\r' | '\n'))
+    }
+
+    fn line_end(&self, i: usize) -> bool {
+        i == self.input.len() || self.newline(i).is_some()
+    }
+
+    /// Whether the preceding character is alphanumeric, in which case
+    /// mentions, hashtags, and italics are not allowed.
+    fn after_alnum(&self, i: usize) -> bool {
+        i > 0 && self.input[i - 1].is_ascii_alphanumeric()
+    }
+
+    /// Runs `rule` one level deeper, or `fallback` if the nest limit is hit.
+    fn nest<T>(&mut self, i: usize, rule: Rule<'a, T>, fallback: Rule<'a, T>) -> Option<T> {
+        if self.depth + 1 >= self.options.nest_limit {
+            return fallback(self, i);
+        }
+        self.depth += 1;
+        let result = rule(self, i);
+        self.depth -= 1;
+        result
+    }
+
+    fn many(&mut self, i: usize, rule: Rule<'a, (usize, Item)>) -> Vec<Item> {
+        let mut i = i;
+        let mut items = Vec::new();
+        while let Some((j, item)) = rule(self, i) {
+            items.push(item);
+            i = j;
+        }
+        items
+    }
+
+    /// Parses at least one nested node until `is_close` matches.
+    fn children_until(
+        &mut self,
+        i: usize,
+        is_close: impl Fn(&Self, usize) -> bool,
+        rule: Rule<'a, (usize, Item)>,
+    ) -> Option<(usize, Vec<Item>)> {
+        let mut i = i;
+        let mut items = Vec::new();
+        while !is_close(self, i) {
+            match self.nest(i, rule, Self::text) {
+                Some((j, item)) => {
+                    items.push(item);
+                    i = j;
+                }
+                None => break,
+            }
+        }
+        (!items.is_empty()).then_some((i, items))
+    }
+
+    /// Parses `open`, nested nodes, and `close`.
+    fn tag(
+        &mut self,
+        i: usize,
+        open: &str,
+        close: &str,
+        rule: Rule<'a, (usize, Item)>,
+    ) -> Option<(usize, Vec<MfmNode>)> {
+        let j = self.str_at(i, open)?;
+        let (j, items) = self.children_until(j, |p, k| p.str_at(k, close).is_some(), rule)?;
+        let j = self.str_at(j, close)?;
+        Some((j, merge_text(items)))
+    }
+
+    /// Tries `rules` in order, remembering the result.
+    fn first_match(
+        &mut self,
+        syntax: Syntax,
+        i: usize,
+        rules: &[Rule<'a, (usize, Item)>],
+    ) -> Option<(usize, Item)> {
+        self.steps += 1;
+        if self.steps > self.max_steps {
+            return self.text(i);
+        }
+        let key = (syntax, i, self.depth, self.link_label);
+        if let Some(result) = self.memo.get(&key) {
+            return result.to_owned();
+        }
+        let result = rules.iter().find_map(|rule| rule(self, i));
+        self.memo.insert(key, result.to_owned());
+        result
+    }
+
+    fn full(&mut self, i: usize) -> Option<(usize, Item)> {
+        let rules: [Rule<'a, (usize, Item)>; 27] = [
+            Parser::unicode_emoji,
+            Parser::center_tag,
+            Parser::small_tag,
+            Parser::plain_tag,
+            Parser::bold_tag,
+            Parser::italic_tag,
+            Parser::strike_tag,
+            Parser::url_alt,
+            Parser::big,
+            Parser::bold_asta,
+            Parser::italic_asta,
+            Parser::bold_under,
+            Parser::italic_under,
+            Parser::code_block,
+            Parser::inline_code,
+            Parser::quote,
+            Parser::math_block,
+            Parser::math_inline,
+            Parser::strike_wave,
+            Parser::fn_,
+            Parser::mention,
+            Parser::hashtag,
+            Parser::emoji_code,
+            Parser::link,
+            Parser::url,
+            Parser::search,
+            Parser::text,
+        ];
+        self.first_match(Syntax::Full, i, &rules)
+    }
+
+    fn inline(&mut self, i: usize) -> Option<(usize, Item)> {
+        let rules: [Rule<'a, (usize, Item)>; 22] = [
+            Parser::unicode_emoji,
+            Parser::small_tag,
+            Parser::plain_tag,
+            Parser::bold_tag,
+            Parser::italic_tag,
+            Parser::strike_tag,
+            Parser::url_alt,
+            Parser::big,
+            Parser::bold_asta,
+            Parser::italic_asta,
+            Parser::bold_under,
+            Parser::italic_under,
+            Parser::inline_code,
+            Parser::math_inline,
+            Parser::strike_wave,
+            Parser::fn_,
+            Parser::mention,
+            Parser::hashtag,
+            Parser::emoji_code,
+            Parser::link,
+            Parser::url,
+            Parser::text,
+        ];
+        self.first_match(Syntax::Inline, i, &rules)
+    }
+
+    fn simple(&mut self, i: usize) -> Option<(usize, Item)> {
+        let rules: [Rule<'a, (usize, Item)>; 4] = [
+            Parser::unicode_emoji,
+            Parser::emoji_code,
+            Parser::plain_tag,
+            Parser::text,
+        ];
+        self.first_match(Syntax::Simple, i, &rules)
+    }
+
+    fn text(&mut self, i: usize) -> Option<(usize, Item)> {
+        self.char_at(i).map(|c| (i + 1, Item::Text(c.to_string())))
+    }
+
+    fn quote(&mut self, i: usize) -> Option<(usize, Item)> {
+        let j = self.newline_opt(i);
+        let j = self.newline_opt(j);
+        if !self.line_begin(j) {
+            return None;
+        }
+
+        let (mut j, line) = self.quote_line(j)?;
+        let mut lines = vec![line];
+        while let Some((k, line)) = self.newline(j).and_then(|k| self.quote_line(k)) {
+            lines.push(line);
+            j = k;
+        }
+        if lines.len() == 1 && lines[0].is_empty() {
+            return None;
+        }
+        let j = self.newline_opt(j);
+        let j = self.newline_opt(j);
+
+        let inner = lines.join("\n");
+        let mut parser = Parser {
+            input: inner.chars().collect(),
+            options: self.options,
+            depth: self.depth,
+            link_label: self.link_label,
+            memo: Memo::new(),
+            steps: self.steps,
+            max_steps: self.max_steps,
+        };
+        let mut items = Vec::new();
+        let mut k = 0;
+        while let Some((l, item)) = parser.nest(k, Self::full, Self::text) {
+            items.push(item);
+            k = l;
+        }
+        self.steps = parser.steps;
+        Some((
+            j,
+            Item::Node(MfmNode::Quote {
+                children: merge_text(items),
+            }),
+        ))
+    }
+
+    /// Parses `> content` and returns the content.
+    fn quote_line(&self, i: usize) -> Option<(usize, String)> {
+        let mut j = self.str_at(i, ">")?;
+        if self.char_at(j).is_some_and(is_space) {
+            j += 1;
+        }
+        let end = self.take_while(j, |c| c != '\r' && c != '\n');
+        Some((end, self.slice(j, end)))
+    }
+
+    fn search(&mut self, i: usize) -> Option<(usize, Item)> {
+        let start = self.newline_opt(i);
+        if !self.line_begin(start) {
+            return None;
+        }
+
+        let mut j = start;
+        while j < self.input.len() && self.newline(j).is_none() {
+            let is_button = self.char_at(j).is_some_and(is_space)
+                && self.search_button(j + 1).is_some_and(|k| self.line_end(k));
+            if is_button {
+                break;
+            }
+            j += 1;
+        }
+        if j == start || !self.char_at(j).is_some_and(is_space) {
+            return None;
+        }
+        let end = self.search_button(j + 1)?;
+        if !self.line_end(end) {
+            return None;
+        }
+
+        let props = SearchProps {
+            query: self.slice(start, j),
+            content: self.slice(start, end),
+        };
+        Some((self.newline_opt(end), Item::Node(MfmNode::Search { props })))
+    }
+
+    fn search_button(&self, i: usize) -> Option<usize> {
+        ["[検索]", "[search]", "検索", "search"]
+            .iter()
+            .find_map(|button| self.str_at_ignore_case(i, button))
+    }
+
+    fn code_block(&mut self, i: usize) -> Option<(usize, Item)> {
+        let j = self.newline_opt(i);
+        if !self.line_begin(j) {
+            return None;
+        }
+        let lang_start = self.str_at(j, "```")?;
+        let lang_end = self.take_while(lang_start, |c| c != '\r' && c != '\n');
+        let code_start = self.newline(lang_end)?;
+
+        let is_close = |k: usize| {
+            self.newline(k)
+                .and_then(|l| self.str_at(l, "```"))
+                .is_some_and(|l| self.line_end(l))
+        };
+        let mut code_end = code_start;
+        while code_end < self.input.len() && !is_close(code_end) {
+            code_end += 1;
+        }
+        if code_end == code_start {
+            return None;
+        }
+        let j = self.newline(code_end)?;
+        let j = self.str_at(j, "```")?;
+        if !self.line_end(j) {
+            return None;
+        }
+
+        let lang = self.slice(lang_start, lang_end).trim().to_string();
+        let props = BlockCodeProps {
+            code: self.slice(code_start, code_end),
+            lang: (!lang.is_empty()).then_some(lang),
+        };
+        Some((
+            self.newline_opt(j),
+            Item::Node(MfmNode::BlockCode { props }),
+        ))
+    }
+
+    fn math_block(&mut self, i: usize) -> Option<(usize, Item)> {
+        let j = self.newline_opt(i);
+        if !self.line_begin(j) {
+            return None;
+        }
+        let start = self.newline_opt(self.str_at(j, "\\[")?);
+        let end = {
+            let mut k = start;
+            while k < self.input.len() && self.str_at(self.newline_opt(k), "\\]").is_none() {
+                k += 1;
+            }
+            k
+        };
+        if end == start {
+            return None;
+        }
+        let j = self.str_at(self.newline_opt(end), "\\]")?;
+        if !self.line_end(j) {
+            return None;
+        }
+
+        let props = MathProps {
+            formula: self.slice(start, end),
+        };
+        Some((
+            self.newline_opt(j),
+            Item::Node(MfmNode::MathBlock { props }),
+        ))
+    }
+
+    fn center_tag(&mut self, i: usize) -> Option<(usize, Item)> {
+        let j = self.newline_opt(i);
+        if !self.line_begin(j) {
+            return None;
+        }
+        let j = self.newline_opt(self.str_at(j, "<center>")?);
+        let (j, items) = self.children_until(
+            j,
+            |p, k| p.str_at(p.newline_opt(k), "</center>").is_some(),
+            Self::inline,
+        )?;
+        let j = self.str_at(self.newline_opt(j), "</center>")?;
+        if !self.line_end(j) {
+            return None;
+        }
+        Some((
+            self.newline_opt(j),
+            Item::Node(MfmNode::Center {
+                children: merge_text(items),
+            }),
+        ))
+    }
+
+    fn unicode_emoji(&mut self, i: usize) -> Option<(usize, Item)> {
+        let first = self.char_at(i)?;
+        if first.is_ascii() && !matches!(first, '#' | '*' | '0'..='9') {
+            return None;
+        }
+        let max = (self.input.len() - i).min(MAX_EMOJI_LEN);
+        (1..=max).rev().find_map(|len| {
+            let emoji = self.slice(i, i + len);
+            emojis::get(&emoji).map(|_| {
+                let props = UnicodeEmojiProps { emoji };
+                (i + len, Item::Node(MfmNode::UnicodeEmoji { props }))
+            })
+        })
+    }
+
+    fn emoji_code(&mut self, i: usize) -> Option<(usize, Item)> {
+        let start = self.str_at(i, ":")?;
+        let end = self.take_while(start, |c| {
+            c.is_ascii_alphanumeric() || matches!(c, '_' | '+' | '-')
+        });
+        if end == start {
+            return None;
+        }
+        let j = self.str_at(end, ":")?;
+        if !self.line_end(j) && self.char_at(j).is_some_and(|c| c.is_ascii_alphanumeric()) {
+            return None;
+        }
+        let props = EmojiCodeProps {
+            name: self.slice(start, end),
+        };
+        Some((j, Item::Node(MfmNode::EmojiCode { props })))
+    }
+
+    fn small_tag(&mut self, i: usize) -> Option<(usize, Item)> {
+        let (j, children) = self.tag(i, "<small>", "</small>", Self::inline)?;
+        Some((j, Item::Node(MfmNode::Small { children })))
+    }
+
+    fn bold_tag(&mut self, i: usize) -> Option<(usize, Item)> {
+        let (j, children) = self.tag(i, "<b>", "</b>", Self::inline)?;
+        Some((j, Item::Node(MfmNode::Bold { children })))
+    }
+
+    fn italic_tag(&mut self, i: usize) -> Option<(usize, Item)> {
+        let (j, children) = self.tag(i, "<i>", "</i>", Self::inline)?;
+        Some((j, Item::Node(MfmNode::Italic { children })))
+    }
+
+    fn strike_tag(&mut self, i: usize) -> Option<(usize, Item)> {
+        let (j, children) = self.tag(i, "<s>", "</s>", Self::inline)?;
+        Some((j, Item::Node(MfmNode::Strike { children })))
+    }
+
+    fn plain_tag(&mut self, i: usize) -> Option<(usize, Item)> {
+        let start = self.newline_opt(self.str_at(i, "<plain>")?);
+        let mut end = start;
+        while end < self.input.len() && self.str_at(self.newline_opt(end), "</plain>").is_none() {
+            end += 1;
+        }
+        if end == start {
+            return None;
+        }
+        let j = self.str_at(self.newline_opt(end), "</plain>")?;
+        Some((
+            j,
+            Item::Node(MfmNode::Plain {
+                children: vec![MfmNode::text(self.slice(start, end))],
+            }),
+        ))
+    }
+
+    /// `***text***`, which is the same as `$[tada text]`.
+    fn big(&mut self, i: usize) -> Option<(usize, Item)> {
+        let (j, children) = self.tag(i, "***", "***", Self::inline)?;
+        let props = FnProps {
+            name: "tada".to_string(),
+            args: BTreeMap::new(),
+        };
+        Some((j, Item::Node(MfmNode::Fn { props, children })))
+    }
+
+    fn bold_asta(&mut self, i: usize) -> Option<(usize, Item)> {
+        let (j, children) = self.tag(i, "**", "**", Self::inline)?;
+        Some((j, Item::Node(MfmNode::Bold { children })))
+    }
+
+    /// Parses `mark`, alphanumerics and spaces, and `mark`.
+    fn alnum_enclosed(&self, i: usize, mark: &str) -> Option<(usize, Vec<MfmNode>)> {
+        let start = self.str_at(i, mark)?;
+        let end = self.take_while(start, |c| c.is_ascii_alphanumeric() || is_space(c));
+        if end == start {
+            return None;
+        }
+        let j = self.str_at(end, mark)?;
+        Some((j, vec![MfmNode::text(self.slice(start, end))]))
+    }
+
+    fn bold_under(&mut self, i: usize) -> Option<(usize, Item)> {
+        let (j, children) = self.alnum_enclosed(i, "__")?;
+        Some((j, Item::Node(MfmNode::Bold { children })))
+    }
+
+    fn italic_asta(&mut self, i: usize) -> Option<(usize, Item)> {
+        let (j, children) = self.alnum_enclosed(i, "*")?;
+        if self.after_alnum(i) {
+            return None;
+        }
+        Some((j, Item::Node(MfmNode::Italic { children })))
+    }
+
+    fn italic_under(&mut self, i: usize) -> Option<(usize, Item)> {
+        let (j, children) = self.alnum_enclosed(i, "_")?;
+        if self.after_alnum(i) {
+            return None;
+        }
+        Some((j, Item::Node(MfmNode::Italic { children })))
+    }
+
+    fn strike_wave(&mut self, i: usize) -> Option<(usize, Item)> {
+        let j = self.str_at(i, "~~")?;
+        let (j, items) = self.children_until(
+            j,
+            |p, k| p.str_at(k, "~~").is_some() || p.newline(k).is_some(),
+            Self::inline,
+        )?;
+        let j = self.str_at(j, "~~")?;
+        Some((
+            j,
+            Item::Node(MfmNode::Strike {
+                children: merge_text(items),
+            }),
+        ))
+    }
+
+    fn inline_code(&mut self, i: usize) -> Option<(usize, Item)> {
+        let start = self.str_at(i, "`")?;
+        let end = self.take_while(start, |c| !matches!(c, '`' | '´' | '\r' | '\n'));
+        if end == start {
+            return None;
+        }
+        let j = self.str_at(end, "`")?;
+        let props = InlineCodeProps {
+            code: self.slice(start, end),
+        };
+        Some((j, Item::Node(MfmNode::InlineCode { props })))
+    }
+
+    fn math_inline(&mut self, i: usize) -> Option<(usize, Item)> {
+        let start = self.str_at(i, "\\(")?;
+        let mut end = start;
+        while end < self.input.len()
+            && self.str_at(end, "\\)").is_none()
+            && self.newline(end).is_none()
+        {
+            end += 1;
+        }
+        if end == start {
+            return None;
+        }
+        let j = self.str_at(end, "\\)")?;
+        let props = MathProps {
+            formula: self.slice(start, end),
+        };
+        Some((j, Item::Node(MfmNode::MathInline { props })))
+    }
+
+    fn fn_(&mut self, i: usize) -> Option<(usize, Item)> {
+        let start = self.str_at(i, "$[")?;
+        let end = self.take_while(start, |c| c.is_ascii_alphanumeric() || c == '_');
+        if end == start {
+            return None;
+        }
+        let name = self.slice(start, end);
+        if let Some(names) = &self.options.fn_name_list {
+            if !names.contains(&name) {
+                return None;
+            }
+        }
+
+        let (j, args) = match self.char_at(end) {
+            Some('.') => self.fn_args(end + 1).unwrap_or((end, BTreeMap::new())),
+            _ => (end, BTreeMap::new()),
+        };
+        let j = self.str_at(j, " ")?;
+        let (j, items) = self.children_until(j, |p, k| p.char_at(k) == Some(']'), Self::inline)?;
+        let j = self.str_at(j, "]")?;
+        Some((
+            j,
+            Item::Node(MfmNode::Fn {
+                props: FnProps { name, args },
+                children: merge_text(items),
+            }),
+        ))
+    }
+
+    /// Parses `key=value,flag,...`. Later keys overwrite earlier ones.
+    fn fn_args(&self, i: usize) -> Option<(usize, BTreeMap<String, FnArg>)> {
+        let mut args = BTreeMap::new();
+        let (mut j, key, value) = self.fn_arg(i)?;
+        args.insert(key, value);
+        while let Some((k, key, value)) = self.str_at(j, ",").and_then(|k| self.fn_arg(k)) {
+            args.insert(key, value);
+            j = k;
+        }
+        Some((j, args))
+    }
+
+    fn fn_arg(&self, i: usize) -> Option<(usize, String, FnArg)> {
+        let key_end = self.take_while(i, |c| c.is_ascii_alphanumeric() || c == '_');
+        if key_end == i {
+            return None;
+        }
+        let key = self.slice(i, key_end);
+        if let Some(start) = self.str_at(key_end, "=") {
+            let end = self.take_while(start, |c| {
+                c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-')
+            });
+            if end > start {
+                return Some((end, key, FnArg::Value(self.slice(start, end))));
+            }
+        }
+        Some((key_end, key, FnArg::Flag(true)))
+    }
+
+    fn mention(&mut self, i: usize) -> Option<(usize, Item)> {
+        if self.link_label {
+            return None;
+        }
+        let name_start = self.str_at(i, "@")?;
+        let name_end = self.take_while(name_start, |c| {
+            c.is_ascii_alphanumeric() || matches!(c, '_' | '-')
+        });
+        if name_end == name_start {
+            return None;
+        }
+        let mut end = name_end;
+        let mut host = None;
+        if let Some(host_start) = self.str_at(name_end, "@") {
+            let host_end = self.take_while(host_start, |c| {
+                c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-')
+            });
+            if host_end > host_start {
+                host = Some(self.slice(host_start, host_end));
+                end = host_end;
+            }
+        }
+        if self.after_alnum(i) {
+            return None;
+        }
+
+        let mut invalid = false;
+        // trailing dots and hyphens are not part of the host
+        let host = match host {
+            Some(host) => {
+                let trimmed = host.trim_end_matches(['.', '-']);
+                if trimmed.is_empty() {
+                    invalid = true;
+                    None
+                } else {
+                    Some(trimmed.to_string())
+                }
+            }
+            None => None,
+        };
+        let username = self.slice(name_start, name_end);
+        let trimmed = username.trim_end_matches('-');
+        let username = if trimmed.len() == username.len() {
+            username.to_owned()
+        } else if host.is_none() {
+            trimmed.to_string()
+        } else {
+            // the username cannot be trimmed if followed by the host
+            invalid = true;
+            username.to_owned()
+        };
+        if username.is_empty() || username.starts_with('-') {
+            invalid = true;
+        }
+        if host.as_deref().is_some_and(|h| h.starts_with(['.', '-'])) {
+            invalid = true;
+        }
+        if invalid {
+            return Some((end, Item::Text(self.slice(i, end))));
+        }
+
+        let acct = match &host {
+            Some(host) => format!("@{}@{}", username, host),
+            None => format!("@{}", username),
+        };
+        let props = MentionProps {
+            username,
+            host,
+            acct,
+        };
+        Some((
+            i + props.acct.chars().count(),
+            Item::Node(MfmNode::Mention { props }),
+        ))
+    }
+
+    fn hashtag_char(&mut self, i: usize) -> Option<usize> {
+        self.char_at(i)
+            .filter(|c| is_hashtag_char(*c))
+            .map(|_| i + 1)
+    }
+
+    /// Parses a hashtag character, or balanced brackets containing them.
+    fn hashtag_item(&mut self, i: usize) -> Option<usize> {
+        for (open, close) in [('(', ')'), ('[', ']'), ('「', '」'), ('（', '）')] {
+            if self.char_at(i) == Some(open) {
+                let mut j = i + 1;
+                while let Some(k) = self.nest(j, Self::hashtag_item, Self::hashtag_char) {
+                    j = k;
+                }
+                if self.char_at(j) == Some(close) {
+                    return Some(j + 1);
+                }
+            }
+        }
+        self.hashtag_char(i)
+    }
+
+    fn hashtag(&mut self, i: usize) -> Option<(usize, Item)> {
+        if self.link_label {
+            return None;
+        }
+        let start = self.str_at(i, "#")?;
+        let mut end = start;
+        while let Some(j) = self.hashtag_item(end) {
+            end = j;
+        }
+        if end == start || self.after_alnum(i) {
+            return None;
+        }
+        let hashtag = self.slice(start, end);
+        // numbers only are not hashtags, e.g. issue numbers
+        if hashtag.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        let props = HashtagProps { hashtag };
+        Some((end, Item::Node(MfmNode::Hashtag { props })))
+    }
+
+    fn scheme(&self, i: usize) -> Option<usize> {
+        self.str_at(i, "https://")
+            .or_else(|| self.str_at(i, "http://"))
+    }
+
+    fn url_char(&mut self, i: usize) -> Option<usize> {
+        self.char_at(i).filter(|c| is_url_char(*c)).map(|_| i + 1)
+    }
+
+    /// Parses a url character, or balanced brackets containing them.
+    fn url_item(&mut self, i: usize) -> Option<usize> {
+        for (open, close) in [('(', ')'), ('[', ']')] {
+            if self.char_at(i) == Some(open) {
+                let mut j = i + 1;
+                while let Some(k) = self.nest(j, Self::url_item, Self::url_char) {
+                    j = k;
+                }
+                if self.char_at(j) == Some(close) {
+                    return Some(j + 1);
+                }
+            }
+        }
+        self.url_char(i)
+    }
+
+    fn url(&mut self, i: usize) -> Option<(usize, Item)> {
+        if self.link_label {
+            return None;
+        }
+        let start = self.scheme(i)?;
+        let mut end = start;
+        while let Some(j) = self.url_item(end) {
+            end = j;
+        }
+        if end == start {
+            return None;
+        }
+
+        // trailing periods and commas are usually punctuation
+        let mut trimmed = end;
+        while trimmed > start && matches!(self.input[trimmed - 1], '.' | ',') {
+            trimmed -= 1;
+        }
+        if trimmed == start {
+            return Some((end, Item::Text(self.slice(i, end))));
+        }
+        let props = UrlProps {
+            url: self.slice(i, trimmed),
+            brackets: None,
+        };
+        Some((trimmed, Item::Node(MfmNode::Url { props })))
+    }
+
+    /// `<https://...>`, which allows any characters but spaces.
+    fn url_alt(&mut self, i: usize) -> Option<(usize, Item)> {
+        if self.link_label {
+            return None;
+        }
+        let start = self.str_at(i, "<")?;
+        let content_start = self.scheme(start)?;
+        let end = self.take_while(content_start, |c| c != '>' && !is_space(c));
+        if end == content_start {
+            return None;
+        }
+        let j = self.str_at(end, ">")?;
+        let props = UrlProps {
+            url: self.slice(start, end),
+            brackets: Some(true),
+        };
+        Some((j, Item::Node(MfmNode::Url { props })))
+    }
+
+    fn link_label_inline(&mut self, i: usize) -> Option<(usize, Item)> {
+        self.link_label = true;
+        let result = self.inline(i);
+        self.link_label = false;
+        result
+    }
+
+    fn link(&mut self, i: usize) -> Option<(usize, Item)> {
+        if self.link_label {
+            return None;
+        }
+        let (j, silent) = match self.str_at(i, "?[") {
+            Some(j) => (j, true),
+            None => (self.str_at(i, "[")?, false),
+        };
+        let (j, items) = self.children_until(
+            j,
+            |p, k| p.char_at(k) == Some(']') || p.newline(k).is_some(),
+            Self::link_label_inline,
+        )?;
+        let j = self.str_at(j, "]")?;
+        let j = self.str_at(j, "(")?;
+        let (j, url) = match self.url_alt(j).or_else(|| self.url(j))? {
+            (j, Item::Node(MfmNode::Url { props })) => (j, props.url),
+            _ => return None,
+        };
+        let j = self.str_at(j, ")")?;
+        Some((
+            j,
+            Item::Node(MfmNode::Link {
+                props: LinkProps { silent, url },
+                children: merge_text(items),
+            }),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod unit_test {
+    use pretty_assertions::assert_eq;
+    use serde_json::{json, Value};
+
+    use super::{parse, parse_simple, parse_with_options, ParseOptions};
+
+    /// Expected values are the output of `mfm-js` 0.23.3.
+    fn parsed(input: &str) -> Value {
+        serde_json::to_value(parse(input)).unwrap()
+    }
+
+    fn text(text: &str) -> Value {
+        json!({ "type": "text", "props": { "text": text } })
+    }
+
+    #[test]
+    fn text_and_emojis() {
+        assert_eq!(parsed("abc"), json!([text("abc")]));
+        assert_eq!(
+            parsed("今起きた👍 :ai_yay:"),
+            json!([
+                text("今起きた"),
+                { "type": "unicodeEmoji", "props": { "emoji": "👍" } },
+                text(" "),
+                { "type": "emojiCode", "props": { "name": "ai_yay" } },
+            ])
+        );
+        assert_eq!(
+            parsed("👨‍👩‍👧‍👦🇯🇵"),
+            json!([
+                { "type": "unicodeEmoji", "props": { "emoji": "👨‍👩‍👧‍👦" } },
+                { "type": "unicodeEmoji", "props": { "emoji": "🇯🇵" } },
+            ])
+        );
+        assert_eq!(parsed("a:bar:b"), json!([text("a:bar:b")]));
+    }
+
+    #[test]
+    fn simple() {
+        assert_eq!(
+            serde_json::to_value(parse_simple("**bold** :emoji: <plain>:a:</plain>")).unwrap(),
+            json!([
+                text("**bold** "),
+                { "type": "emojiCode", "props": { "name": "emoji" } },
+                text(" "),
+                { "type": "plain", "children": [text(":a:")] },
+            ])
+        );
+    }
+
+    #[test]
+    fn emphasis() {
+        assert_eq!(
+            parsed("**bold** <i>italic</i> ~~strike~~ <small>small</small>"),
+            json!([
+                { "type": "bold", "children": [text("bold")] },
+                text(" "),
+                { "type": "italic", "children": [text("italic")] },
+                text(" "),
+                { "type": "strike", "children": [text("strike")] },
+                text(" "),
+                { "type": "small", "children": [text("small")] },
+            ])
+        );
+        assert_eq!(
+            parsed("***big***"),
+            json!([{
+                "type": "fn",
+                "props": { "name": "tada", "args": {} },
+                "children": [text("big")],
+            }])
+        );
+        assert_eq!(
+            parsed("__bold__ *italic* _italic_"),
+            json!([
+                { "type": "bold", "children": [text("bold")] },
+                text(" "),
+                { "type": "italic", "children": [text("italic")] },
+                text(" "),
+                { "type": "italic", "children": [text("italic")] },
+            ])
+        );
+        // italics after alphanumerics are not allowed, e.g. in snake_case_name
+        assert_eq!(parsed("snake_case_name"), json!([text("snake_case_name")]));
+        assert_eq!(parsed("~~a\nb~~"), json!([text("~~a\nb~~")]));
+    }
+
+    #[test]
+    fn blocks() {
+        assert_eq!(
+            parsed("> foo\n>bar\nbaz"),
+            json!([
+                { "type": "quote", "children": [text("foo\nbar")] },
+                text("baz"),
+            ])
+        );
+        assert_eq!(parsed(">"), json!([text(">")]));
+        assert_eq!(
+            parsed("abc\n```js\nconst a = 1;\n```\ndef"),
+            json!([
+                text("abc"),
+                { "type": "blockCode", "props": { "code": "const a = 1;", "lang": "js" } },
+                text("def"),
+            ])
+        );
+        assert_eq!(
+            parsed("```\na\n```"),
+            json!([{ "type": "blockCode", "props": { "code": "a", "lang": null } }])
+        );
+        assert_eq!(
+            parsed("\\[\na = 1\n\\]"),
+            json!([{ "type": "mathBlock", "props": { "formula": "a = 1" } }])
+        );
+        assert_eq!(
+            parsed("<center>\nabc\n</center>"),
+            json!([{ "type": "center", "children": [text("abc")] }])
+        );
+        assert_eq!(
+            parsed("MFM 書き方 123 Search"),
+            json!([{
+                "type": "search",
+                "props": { "query": "MFM 書き方 123", "content": "MFM 書き方 123 Search" },
+            }])
+        );
+        assert_eq!(
+            parsed("a\nMFM [検索]\nb"),
+            json!([
+                text("a"),
+                {
+                    "type": "search",
+                    "props": { "query": "MFM", "content": "MFM [検索]" },
+                },
+                text("b"),
+            ])
+        );
+    }
+
+    #[test]
+    fn code() {
+        assert_eq!(
+            parsed("`var x = \"Strawberry Pasta\";` \\(x^2\\)"),
+            json!([
+                { "type": "inlineCode", "props": { "code": "var x = \"Strawberry Pasta\";" } },
+                text(" "),
+                { "type": "mathInline", "props": { "formula": "x^2" } },
+            ])
+        );
+        assert_eq!(parsed("`foo´bar`"), json!([text("`foo´bar`")]));
+    }
+
+    #[test]
+    fn mention() {
+        let mention = |username: &str, host: Option<&str>, acct: &str| {
+            json!({
+                "type": "mention",
+                "props": { "username": username, "host": host, "acct": acct },
+            })
+        };
+        assert_eq!(
+            parsed("@abc @abc@misskey.io"),
+            json!([
+                mention("abc", None, "@abc"),
+                text(" "),
+                mention("abc", Some("misskey.io"), "@abc@misskey.io"),
+            ])
+        );
+        assert_eq!(
+            parsed("@abc-. @abc@misskey.io."),
+            json!([
+                mention("abc", None, "@abc"),
+                text("-. "),
+                mention("abc", Some("misskey.io"), "@abc@misskey.io"),
+                text("."),
+            ])
+        );
+        assert_eq!(parsed("abc@example.com"), json!([text("abc@example.com")]));
+        assert_eq!(
+            parsed("@-abc @abc-@host @abc@.host"),
+            json!([text("@-abc @abc-@host @abc@.host")])
+        );
+    }
+
+    #[test]
+    fn hashtag() {
+        let hashtag = |tag: &str| json!({ "type": "hashtag", "props": { "hashtag": tag } });
+        assert_eq!(
+            parsed("#abc, #a(b)c #foo[bar #123 a#b"),
+            json!([
+                hashtag("abc"),
+                text(", "),
+                hashtag("a(b)c"),
+                text(" "),
+                hashtag("foo"),
+                text("[bar #123 a#b"),
+            ])
+        );
+        assert_eq!(
+            parsed("（#ミスキー）"),
+            json!([text("（"), hashtag("ミスキー"), text("）")])
+        );
+    }
+
+    #[test]
+    fn url_and_link() {
+        assert_eq!(
+            parsed("https://misskey.io/@ai. <https://example.com/foo bar>"),
+            json!([
+                { "type": "url", "props": { "url": "https://misskey.io/@ai" } },
+                text(". <"),
+                { "type": "url", "props": { "url": "https://example.com/foo" } },
+                text(" bar>"),
+            ])
+        );
+        assert_eq!(
+            parsed("<https://example.com/ä> https://example.com/(foo)"),
+            json!([
+                { "type": "url", "props": { "url": "https://example.com/ä", "brackets": true } },
+                text(" "),
+                { "type": "url", "props": { "url": "https://example.com/(foo)" } },
+            ])
+        );
+        assert_eq!(
+            parsed("[official **instance**](https://misskey.io/@ai) ?[@a #b](https://a.example)"),
+            json!([
+                {
+                    "type": "link",
+                    "props": { "silent": false, "url": "https://misskey.io/@ai" },
+                    "children": [
+                        text("official "),
+                        { "type": "bold", "children": [text("instance")] },
+                    ],
+                },
+                text(" "),
+                {
+                    "type": "link",
+                    "props": { "silent": true, "url": "https://a.example" },
+                    "children": [text("@a #b")],
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn fn_() {
+        assert_eq!(
+            parsed("$[spin.speed=1.5s,alternate :cat:]"),
+            json!([{
+                "type": "fn",
+                "props": { "name": "spin", "args": { "speed": "1.5s", "alternate": true } },
+                "children": [{ "type": "emojiCode", "props": { "name": "cat" } }],
+            }])
+        );
+        assert_eq!(
+            parsed("$[spin.speed=1s, a]"),
+            json!([text("$[spin.speed=1s, a]")])
+        );
+
+        let options = ParseOptions {
+            fn_name_list: Some(vec!["tada".to_string()]),
+            ..Default::default()
+        };
+        assert_eq!(
+            serde_json::to_value(parse_with_options("$[spin a]", &options)).unwrap(),
+            json!([text("$[spin a]")])
+        );
+    }
+
+    #[test]
+    fn nest_limit() {
+        let options = ParseOptions {
+            nest_limit: 2,
+            ..Default::default()
+        };
+        let parsed =
+            |input: &str| serde_json::to_value(parse_with_options(input, &options)).unwrap();
+        assert_eq!(
+            parsed("<b><b>***abc***</b></b>"),
+            json!([{
+                "type": "bold",
+                "children": [{ "type": "bold", "children": [text("***abc***")] }],
+            }])
+        );
+        assert_eq!(
+            parsed(">>> abc"),
+            json!([{
+                "type": "quote",
+                "children": [{ "type": "quote", "children": [text("> abc")] }],
+            }])
+        );
+        assert_eq!(
+            parsed("<b>#a(b(c))</b>"),
+            json!([{
+                "type": "bold",
+                "children": [{ "type": "hashtag", "props": { "hashtag": "a" } }, text("(b(c))")],
+            }])
+        );
+    }
+
+    #[test]
+    fn unclosed() {
+        // These take exponential time without memoization.
+        for syntax in ["<b>", "$[x ", "<i>**[", "<small>~~$[x ?["] {
+            let input = syntax.repeat(1000 / syntax.len());
+            assert_eq!(parsed(&input), json!([text(&input)]));
+        }
+    }
+}
@@ -0,0 +1,220 @@
+//! HTML for ActivityPub, ported from `mfm/to-html.ts`
+
+use serde::{Deserialize, Serialize};
+
+use super::node::MfmNode;
+use crate::model::entity::note;
+
+/// Entry of `note.mentioned_remote_users`, which is stored as JSON text.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct MentionedRemoteUser {
+    pub uri: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    pub username: String,
+    pub host: String,
+}
+
+impl MentionedRemoteUser {
+    /// Parses `note.mentioned_remote_users`. Broken values are treated as
+    /// empty, since they only change where mentions link to.
+    pub fn from_note(note: &note::Model) -> Vec<Self> {
+        serde_json::from_str(&note.mentioned_remote_users).unwrap_or_default()
+    }
+}
+
+fn escape_text(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('\u{a0}', "&nbsp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn escape_attr(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('\u{a0}', "&nbsp;")
+        .replace('"', "&quot;")
+}
+
+struct Renderer<'a> {
+    url: &'a str,
+    mentioned_remote_users: &'a [MentionedRemoteUser],
+}
+
+impl Renderer<'_> {
+    fn element(&self, html: &mut String, tag: &str, children: &[MfmNode]) {
+        html.push_str(&format!("<{}>", tag));
+        self.render(html, children);
+        html.push_str(&format!("</{}>", tag));
+    }
+
+    fn link(html: &mut String, href: &str, text: &str) {
+        html.push_str(&format!(
+            "<a href=\"{}\">{}</a>",
+            escape_attr(href),
+            escape_text(text)
+        ));
+    }
+
+    fn render(&self, html: &mut String, nodes: &[MfmNode]) {
+        for node in nodes {
+            match node {
+                MfmNode::Bold { children } => self.element(html, "b", children),
+                MfmNode::Small { children } => self.element(html, "small", children),
+                MfmNode::Strike { children } => self.element(html, "del", children),
+                MfmNode::Italic { children } | MfmNode::Fn { children, .. } => {
+                    self.element(html, "i", children)
+                }
+                MfmNode::Center { children } => self.element(html, "div", children),
+                MfmNode::Quote { children } => self.element(html, "blockquote", children),
+                MfmNode::Plain { children } => self.element(html, "span", children),
+                MfmNode::BlockCode { props } => html.push_str(&format!(
+                    "<pre><code>{}</code></pre>",
+                    escape_text(&props.code)
+                )),
+                MfmNode::InlineCode { props } => {
+                    html.push_str(&format!("<code>{}</code>", escape_text(&props.code)))
+                }
+                MfmNode::MathInline { props } | MfmNode::MathBlock { props } => {
+                    html.push_str(&format!("<code>{}</code>", escape_text(&props.formula)))
+                }
+                MfmNode::EmojiCode { props } => {
+                    html.push_str(&format!("\u{200b}:{}:\u{200b}", escape_text(&props.name)))
+                }
+                MfmNode::UnicodeEmoji { props } => html.push_str(&escape_text(&props.emoji)),
+                MfmNode::Hashtag { props } => html.push_str(&format!(
+                    "<a href=\"{}\" rel=\"tag\">#{}</a>",
+                    escape_attr(&format!("{}/tags/{}", self.url, props.hashtag)),
+                    escape_text(&props.hashtag)
+                )),
+                MfmNode::Link { props, children } => {
+                    html.push_str(&format!("<a href=\"{}\">", escape_attr(&props.url)));
+                    self.render(html, children);
+                    html.push_str("</a>");
+                }
+                MfmNode::Mention { props } => {
+                    let remote_user = self.mentioned_remote_users.iter().find(|u| {
+                        u.username == props.username && Some(&u.host) == props.host.as_ref()
+                    });
+                    let href = match remote_user {
+                        Some(u) => u.url.to_owned().unwrap_or_else(|| u.uri.to_owned()),
+                        None => format!("{}/{}", self.url, props.acct),
+                    };
+                    html.push_str(&format!(
+                        "<a href=\"{}\" class=\"u-url mention\">{}</a>",
+                        escape_attr(&href),
+                        escape_text(&props.acct)
+                    ));
+                }
+                MfmNode::Text { props } => {
+                    let lines: Vec<String> = props
+                        .text
+                        .split("\r\n")
+                        .flat_map(|s| s.split(['\r', '\n']))
+                        .map(escape_text)
+                        .collect();
+                    html.push_str(&format!("<span>{}</span>", lines.join("<br>")));
+                }
+                MfmNode::Url { props } => Self::link(html, &props.url, &props.url),
+                MfmNode::Search { props } => {
+                    Self::link(html, &format!("/search/{}", props.query), &props.content)
+                }
+            }
+        }
+    }
+}
+
+/// Renders the nodes into HTML wrapped in a paragraph. `url` is the origin of
+/// the server, e.g. `https://example.com`, to which local mentions and
+/// hashtags link.
+pub fn to_html(
+    nodes: &[MfmNode],
+    url: &str,
+    mentioned_remote_users: &[MentionedRemoteUser],
+) -> String {
+    let renderer = Renderer {
+        url,
+        mentioned_remote_users,
+    };
+    let mut html = String::from("<p>");
+    renderer.render(&mut html, nodes);
+    html.push_str("</p>");
+    html
+}
+
+#[cfg(test)]
+mod unit_test {
+    use pretty_assertions::assert_eq;
+
+    use super::{to_html, MentionedRemoteUser};
+    use crate::mfm::parser::parse;
+    use crate::model::entity::note;
+
+    const URL: &str = "https://example.com";
+
+    fn html(text: &str) -> String {
+        to_html(&parse(text), URL, &[])
+    }
+
+    #[test]
+    fn br() {
+        // same as test/mfm.ts
+        assert_eq!(
+            html("foo\nbar\nbaz"),
+            "<p><span>foo<br>bar<br>baz</span></p>"
+        );
+        assert_eq!(
+            html("foo\r\nbar\rbaz"),
+            "<p><span>foo<br>bar<br>baz</span></p>"
+        );
+    }
+
+    #[test]
+    fn nodes() {
+        assert_eq!(
+            html("**a** ~~b~~ $[spin c] <plain><b></plain>"),
+            "<p><b><span>a</span></b><span> </span><del><span>b</span></del><span> </span>\
+            <i><span>c</span></i><span> </span><span><span>&lt;b&gt;</span></span></p>"
+        );
+        assert_eq!(
+            html("> `a & b`\n```\n<script>\n```"),
+            "<p><blockquote><code>a &amp; b</code></blockquote>\
+            <pre><code>&lt;script&gt;</code></pre></p>"
+        );
+        assert_eq!(
+            html(":blobcat: 🍮 #tag"),
+            "<p>\u{200b}:blobcat:\u{200b}<span> </span>🍮<span> </span>\
+            <a href=\"https://example.com/tags/tag\" rel=\"tag\">#tag</a></p>"
+        );
+        assert_eq!(
+            html("[a](https://a.example/?b=1&c=2) https://b.example\nfoo search"),
+            "<p><a href=\"https://a.example/?b=1&amp;c=2\"><span>a</span></a><span> </span>\
+            <a href=\"https://b.example\">https://b.example</a>\
+            <a href=\"/search/foo\">foo search</a></p>"
+        );
+    }
+
+    #[test]
+    fn mention() {
+        let note = note::Model {
+            mentioned_remote_users: r#"[
+                {"uri": "https://a.example/users/1", "url": "https://a.example/@bob", "username": "bob", "host": "a.example"},
+                {"uri": "https://b.example/users/2", "username": "carol", "host": "b.example"}
+            ]"#
+            .to_string(),
+            ..Default::default()
+        };
+        let users = MentionedRemoteUser::from_note(&note);
+        assert_eq!(
+            to_html(&parse("@alice @bob@a.example @carol@b.example"), URL, &users),
+            "<p><a href=\"https://example.com/@alice\" class=\"u-url mention\">@alice</a><span> </span>\
+            <a href=\"https://a.example/@bob\" class=\"u-url mention\">@bob@a.example</a><span> </span>\
+            <a href=\"https://b.example/users/2\" class=\"u-url mention\">@carol@b.example</a></p>"
+        );
+        assert_eq!(
+            MentionedRemoteUser::from_note(&note::Model::default()),
+            vec![]
+        );
+    }
+}
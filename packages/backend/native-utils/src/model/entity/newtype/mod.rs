@@ -28,3 +28,30 @@ cfg_if! {
         pub type I32Vec = Vec<i32>;
     }
 }
+
+// `.into()` is a no-op unless the `noarray` feature is enabled, so the
+// conversions between array columns and vectors are kept here.
+
+/// Converts a `Vec<String>` into a [StringVec] column value.
+#[allow(clippy::useless_conversion)]
+pub fn string_vec(v: Vec<String>) -> StringVec {
+    v.into()
+}
+
+/// Converts a [StringVec] column value into a `Vec<String>`.
+#[allow(clippy::useless_conversion)]
+pub fn from_string_vec(v: StringVec) -> Vec<String> {
+    v.into()
+}
+
+/// Converts a `Vec<i32>` into an [I32Vec] column value.
+#[allow(clippy::useless_conversion)]
+pub fn i32_vec(v: Vec<i32>) -> I32Vec {
+    v.into()
+}
+
+/// Converts an [I32Vec] column value into a `Vec<i32>`.
+#[allow(clippy::useless_conversion)]
+pub fn from_i32_vec(v: I32Vec) -> Vec<i32> {
+    v.into()
+}
@@ -8,6 +8,8 @@ pub enum Error {
     DbConnError(#[from] crate::database::error::Error),
     #[error("Database operation error: {0}")]
     DbOperationError(#[from] sea_orm::DbErr),
+    #[error("Cache error: {0}")]
+    CacheError(#[from] crate::cache::error::Error),
     #[error("Requested entity not found")]
     NotFound,
 }
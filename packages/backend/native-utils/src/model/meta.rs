@@ -0,0 +1,12 @@
+use sea_orm::{ConnectionTrait, DbErr, EntityTrait, QueryOrder};
+
+use super::entity::meta;
+
+/// Returns the instance settings in use. The newest row is used if there
+/// are more than one, as TypeScript does.
+pub async fn current_meta<C: ConnectionTrait>(db: &C) -> Result<Option<meta::Model>, DbErr> {
+    meta::Entity::find()
+        .order_by_desc(meta::Column::Id)
+        .one(db)
+        .await
+}
@@ -1,33 +1,103 @@
 pub mod antenna;
+pub mod drive_file;
+pub mod note;
+pub mod user;
 
 use async_trait::async_trait;
 use schemars::JsonSchema;
 
 use super::error::Error;
 
+/// Information about who is requesting the packed models. Packers use it
+/// to hide what the viewer cannot see and to fill viewer-specific fields.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PackContext {
+    /// Id of the user viewing the models. `None` if not signed in.
+    pub viewer_id: Option<String>,
+    /// Whether to pack detailed fields, such as the reply and renote of a
+    /// note.
+    pub detail: bool,
+    /// Preferred language of the viewer, e.g. `ja-JP`.
+    pub lang: Option<String>,
+}
+
+impl PackContext {
+    /// Returns the context of `viewer_id` requesting detailed fields.
+    pub fn detailed(viewer_id: Option<String>) -> Self {
+        Self {
+            viewer_id,
+            detail: true,
+            ..Default::default()
+        }
+    }
+}
+
 /// Repositories have a packer that converts a database model to its
 /// corresponding API schema.
 #[async_trait]
 pub trait Repository<T: JsonSchema> {
-    async fn pack(self) -> Result<T, Error>;
+    async fn pack(self, ctx: &PackContext) -> Result<T, Error>;
     /// Retrieves one model by its id and pack it.
-    async fn pack_by_id(id: String) -> Result<T, Error>;
+    async fn pack_by_id(id: String, ctx: &PackContext) -> Result<T, Error>;
+    /// Packs the models in the input order. Repositories with relations
+    /// should override this to load them for the whole batch at once.
+    async fn pack_many(models: Vec<Self>, ctx: &PackContext) -> Result<Vec<T>, Error>
+    where
+        Self: Sized + Send,
+        T: Send,
+    {
+        let mut packed = Vec::with_capacity(models.len());
+        for m in models {
+            packed.push(m.pack(ctx).await?);
+        }
+        Ok(packed)
+    }
+    /// Retrieves models by their ids and packs them in the order of `ids`.
+    /// Ids that are not found are skipped.
+    async fn pack_by_ids(ids: Vec<String>, ctx: &PackContext) -> Result<Vec<T>, Error>;
+}
+
+/// Sorts `models` in the order of `ids`. Models whose id is not in `ids` are
+/// moved to the end.
+pub(crate) fn sort_by_ids<M>(models: &mut [M], ids: &[String], id_of: impl Fn(&M) -> &str) {
+    models.sort_by_key(|m| {
+        ids.iter()
+            .position(|id| id == id_of(m))
+            .unwrap_or(usize::MAX)
+    });
 }
 
 mod macros {
     /// Provides the default implementation of
     /// [crate::model::repository::Repository::pack_by_id].
     macro_rules! impl_pack_by_id {
-        ($a:ty, $b:ident) => {
+        ($a:ty, $b:ident, $ctx:ident) => {
             match <$a>::find_by_id($b)
                 .one(crate::database::get_database()?)
                 .await?
             {
                 None => Err(Error::NotFound),
-                Some(m) => m.pack().await,
+                Some(m) => m.pack($ctx).await,
             }
         };
     }
 
+    /// Provides the default implementation of
+    /// [crate::model::repository::Repository::pack_by_ids]. The second
+    /// argument is the id column of the entity.
+    macro_rules! impl_pack_by_ids {
+        ($a:ty, $c:expr, $b:ident, $ctx:ident) => {{
+            use sea_orm::{ColumnTrait, QueryFilter};
+
+            let mut models = <$a>::find()
+                .filter($c.is_in($b.to_owned()))
+                .all(crate::database::get_database()?)
+                .await?;
+            crate::model::repository::sort_by_ids(&mut models, &$b, |m| &m.id);
+            Self::pack_many(models, $ctx).await
+        }};
+    }
+
     pub(crate) use impl_pack_by_id;
+    pub(crate) use impl_pack_by_ids;
 }
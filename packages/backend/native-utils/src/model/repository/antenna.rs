@@ -1,20 +1,25 @@
+use std::collections::HashMap;
+
 use async_trait::async_trait;
 use cfg_if::cfg_if;
-use sea_orm::EntityTrait;
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
 
+use crate::antenna::timeline;
+use crate::cache::error::Error as CacheError;
 use crate::database;
+use crate::model::entity::newtype::from_string_vec;
 use crate::model::entity::{antenna, user_group_joining};
 use crate::model::error::Error;
 use crate::model::schema::Antenna;
 
-use super::macros::impl_pack_by_id;
-use super::Repository;
+use super::macros::{impl_pack_by_id, impl_pack_by_ids};
+use super::{PackContext, Repository};
 
 #[async_trait]
 impl Repository<Antenna> for antenna::Model {
-    async fn pack(self) -> Result<Antenna, Error> {
+    async fn pack(self, _ctx: &PackContext) -> Result<Antenna, Error> {
         let db = database::get_database()?;
-        let user_group_joining = match self.user_group_joining_id {
+        let user_group_joining = match self.user_group_joining_id.to_owned() {
             None => None,
             Some(id) => user_group_joining::Entity::find_by_id(id).one(db).await?,
         };
@@ -23,34 +28,92 @@ impl Repository<Antenna> for antenna::Model {
             Some(m) => Some(m.user_group_id),
         };
 
-        cfg_if! {
-            if #[cfg(feature = "napi")] {
-                let created_at: String = self.created_at.to_rfc3339();
-            } else {
-                let created_at: chrono::DateTime<chrono::Utc> = self.created_at.into();
-            }
+        let has_unread_note = has_unread_notes(&[self.id.to_owned()]).await?[0];
+        pack_with(self, user_group_id, has_unread_note)
+    }
+
+    async fn pack_by_id(id: String, ctx: &PackContext) -> Result<Antenna, Error> {
+        impl_pack_by_id!(antenna::Entity, id, ctx)
+    }
+
+    /// Loads the user groups of all the antennas with one query, and the
+    /// unread states with one batch of cache lookups.
+    async fn pack_many(models: Vec<Self>, _ctx: &PackContext) -> Result<Vec<Antenna>, Error> {
+        let db = database::get_database()?;
+        let joining_ids: Vec<String> = models
+            .iter()
+            .filter_map(|m| m.user_group_joining_id.to_owned())
+            .collect();
+        let user_group_ids: HashMap<String, String> = match joining_ids.is_empty() {
+            true => HashMap::new(),
+            false => user_group_joining::Entity::find()
+                .filter(user_group_joining::Column::Id.is_in(joining_ids))
+                .all(db)
+                .await?
+                .into_iter()
+                .map(|m| (m.id, m.user_group_id))
+                .collect(),
+        };
+
+        let ids: Vec<String> = models.iter().map(|m| m.id.to_owned()).collect();
+        let unread = has_unread_notes(&ids).await?;
+
+        let mut packed = Vec::with_capacity(models.len());
+        for (m, has_unread_note) in models.into_iter().zip(unread) {
+            let user_group_id = m
+                .user_group_joining_id
+                .as_ref()
+                .and_then(|id| user_group_ids.get(id).cloned());
+            packed.push(pack_with(m, user_group_id, has_unread_note)?);
         }
+        Ok(packed)
+    }
+
+    async fn pack_by_ids(ids: Vec<String>, ctx: &PackContext) -> Result<Vec<Antenna>, Error> {
+        impl_pack_by_ids!(antenna::Entity, antenna::Column::Id, ids, ctx)
+    }
+}
 
-        Ok(Antenna {
-            id: self.id,
-            created_at,
-            name: self.name,
-            keywords: self.keywords.into(),
-            exclude_keywords: self.exclude_keywords.into(),
-            src: self.src.try_into()?,
-            user_list_id: self.user_list_id,
-            user_group_id,
-            users: self.users,
-            instances: self.instances.into(),
-            case_sensitive: self.case_sensitive,
-            notify: self.notify,
-            with_replies: self.with_replies,
-            with_file: self.with_file,
-            has_unread_note: false,
-        })
+/// Returns whether each antenna has unread notes. Every antenna is read if
+/// the cache is not initialized, e.g. in processes where TypeScript does not
+/// call `nativeInitCache`.
+async fn has_unread_notes(antenna_ids: &[String]) -> Result<Vec<bool>, Error> {
+    match timeline::has_unread_notes(antenna_ids).await {
+        Err(CacheError::Uninitialized) => Ok(vec![false; antenna_ids.len()]),
+        result => Ok(result?),
     }
+}
 
-    async fn pack_by_id(id: String) -> Result<Antenna, Error> {
-        impl_pack_by_id!(antenna::Entity, id)
+/// Packs the antenna with the already resolved id of the user group and
+/// unread state.
+fn pack_with(
+    antenna: antenna::Model,
+    user_group_id: Option<String>,
+    has_unread_note: bool,
+) -> Result<Antenna, Error> {
+    cfg_if! {
+        if #[cfg(feature = "napi")] {
+            let created_at: String = antenna.created_at.to_rfc3339();
+        } else {
+            let created_at: chrono::DateTime<chrono::Utc> = antenna.created_at.into();
+        }
     }
+
+    Ok(Antenna {
+        id: antenna.id,
+        created_at,
+        name: antenna.name,
+        keywords: antenna.keywords.into(),
+        exclude_keywords: antenna.exclude_keywords.into(),
+        src: antenna.src.try_into()?,
+        user_list_id: antenna.user_list_id,
+        user_group_id,
+        users: from_string_vec(antenna.users),
+        instances: antenna.instances.into(),
+        case_sensitive: antenna.case_sensitive,
+        notify: antenna.notify,
+        with_replies: antenna.with_replies,
+        with_file: antenna.with_file,
+        has_unread_note,
+    })
 }
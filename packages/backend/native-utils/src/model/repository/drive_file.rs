@@ -0,0 +1,96 @@
+use async_trait::async_trait;
+use sea_orm::EntityTrait;
+
+use crate::model::entity::drive_file;
+use crate::model::error::Error;
+use crate::model::schema::{DriveFile, DriveFileProperties};
+
+use super::macros::{impl_pack_by_id, impl_pack_by_ids};
+use super::{PackContext, Repository};
+
+/// MIME types of images that can be used as thumbnails as they are.
+const IMAGE_TYPES: [&str; 7] = [
+    "image/png",
+    "image/apng",
+    "image/gif",
+    "image/jpeg",
+    "image/webp",
+    "image/svg+xml",
+    "image/avif",
+];
+
+impl drive_file::Model {
+    /// Returns the URL of the file, or of its thumbnail if `thumbnail` is
+    /// `true`.
+    pub fn public_url(&self, thumbnail: bool) -> Option<String> {
+        if thumbnail {
+            self.thumbnail_url.to_owned().or_else(|| {
+                if IMAGE_TYPES.contains(&self.r#type.as_str()) {
+                    self.webpublic_url.to_owned().or(Some(self.url.to_owned()))
+                } else {
+                    None
+                }
+            })
+        } else {
+            self.webpublic_url.to_owned().or(Some(self.url.to_owned()))
+        }
+    }
+
+    /// Returns the properties with `width` and `height` swapped according to
+    /// the EXIF orientation.
+    pub fn public_properties(&self) -> DriveFileProperties {
+        let get_u32 = |key: &str| {
+            self.properties
+                .get(key)
+                .and_then(serde_json::Value::as_u64)
+                .and_then(|v| u32::try_from(v).ok())
+        };
+        let (width, height) = match get_u32("orientation") {
+            Some(orientation) if orientation >= 5 => (get_u32("height"), get_u32("width")),
+            _ => (get_u32("width"), get_u32("height")),
+        };
+
+        DriveFileProperties {
+            width,
+            height,
+            avg_color: self
+                .properties
+                .get("avgColor")
+                .and_then(serde_json::Value::as_str)
+                .map(str::to_string),
+        }
+    }
+}
+
+#[async_trait]
+impl Repository<DriveFile> for drive_file::Model {
+    async fn pack(self, _ctx: &PackContext) -> Result<DriveFile, Error> {
+        let properties = self.public_properties();
+        let url = self.public_url(false);
+        let thumbnail_url = self.public_url(true);
+
+        Ok(DriveFile {
+            id: self.id,
+            created_at: self.created_at.into(),
+            name: self.name,
+            r#type: self.r#type,
+            md5: self.md5,
+            size: self.size,
+            is_sensitive: self.is_sensitive,
+            blurhash: self.blurhash,
+            properties,
+            url,
+            thumbnail_url,
+            comment: self.comment,
+            folder_id: self.folder_id,
+        })
+    }
+
+    async fn pack_by_id(id: String, ctx: &PackContext) -> Result<DriveFile, Error> {
+        impl_pack_by_id!(drive_file::Entity, id, ctx)
+    }
+
+    async fn pack_by_ids(ids: Vec<String>, ctx: &PackContext) -> Result<Vec<DriveFile>, Error> {
+        impl_pack_by_ids!(drive_file::Entity, drive_file::Column::Id, ids, ctx)
+    }
+}
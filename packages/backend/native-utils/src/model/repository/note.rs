@@ -0,0 +1,381 @@
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::pin::Pin;
+
+use async_trait::async_trait;
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+
+use crate::database;
+use crate::model::entity::newtype::{from_i32_vec, from_string_vec};
+use crate::model::entity::sea_orm_active_enums::NoteVisibilityEnum;
+use crate::model::entity::{
+    channel, drive_file, following, note, note_reaction, poll, poll_vote, user,
+};
+use crate::model::error::Error;
+use crate::model::schema::{
+    DriveFile, Note, NoteChannel, NotePoll, NotePollChoice, NoteVisibility, UserLite,
+};
+use crate::util::reaction::{convert_reactions, decode_reaction};
+
+use super::macros::{impl_pack_by_id, impl_pack_by_ids};
+use super::{PackContext, Repository};
+
+/// Replies and renotes nested deeper than this are not packed.
+const MAX_DEPTH: u8 = 2;
+
+type PackFuture<'a> = Pin<Box<dyn Future<Output = Result<Vec<Note>, Error>> + Send + 'a>>;
+
+#[async_trait]
+impl Repository<Note> for note::Model {
+    /// Returns [Error::NotFound] if the viewer cannot see the note.
+    async fn pack(self, ctx: &PackContext) -> Result<Note, Error> {
+        match pack_batch(vec![self], ctx, 0).await?.pop() {
+            None => Err(Error::NotFound),
+            Some(packed) => Ok(packed),
+        }
+    }
+
+    async fn pack_by_id(id: String, ctx: &PackContext) -> Result<Note, Error> {
+        impl_pack_by_id!(note::Entity, id, ctx)
+    }
+
+    /// Loads the authors, replies, renotes, files, polls, and channels of
+    /// all the notes with one query each. Notes that the viewer cannot see
+    /// are skipped.
+    async fn pack_many(models: Vec<Self>, ctx: &PackContext) -> Result<Vec<Note>, Error> {
+        pack_batch(models, ctx, 0).await
+    }
+
+    async fn pack_by_ids(ids: Vec<String>, ctx: &PackContext) -> Result<Vec<Note>, Error> {
+        impl_pack_by_ids!(note::Entity, note::Column::Id, ids, ctx)
+    }
+}
+
+/// Packs the notes visible to the viewer along with their replies and
+/// renotes up to [MAX_DEPTH]. Returns [Error::NotFound] if the author of any
+/// note does not exist.
+fn pack_batch(notes: Vec<note::Model>, ctx: &PackContext, depth: u8) -> PackFuture<'_> {
+    Box::pin(async move {
+        let notes = filter_visible(notes, ctx.viewer_id.as_deref()).await?;
+        if notes.is_empty() {
+            return Ok(vec![]);
+        }
+        let db = database::get_database()?;
+        let note_ids: Vec<String> = notes.iter().map(|n| n.id.to_owned()).collect();
+
+        let user_ids: Vec<String> = notes.iter().map(|n| n.user_id.to_owned()).collect();
+        let user_models = user::Entity::find()
+            .filter(user::Column::Id.is_in(user_ids))
+            .all(db)
+            .await?;
+        let users: HashMap<String, UserLite> = user::Model::pack_many(user_models, ctx)
+            .await?
+            .into_iter()
+            .map(|u: UserLite| (u.id.to_owned(), u))
+            .collect();
+
+        // Replies are packed without details, as TypeScript does.
+        let (replies, renotes) = match ctx.detail && depth < MAX_DEPTH {
+            false => (HashMap::new(), HashMap::new()),
+            true => {
+                let reply_ctx = PackContext {
+                    detail: false,
+                    ..ctx.to_owned()
+                };
+                let reply_ids = notes.iter().filter_map(|n| n.reply_id.to_owned());
+                let renote_ids = notes.iter().filter_map(|n| n.renote_id.to_owned());
+                (
+                    pack_related(reply_ids.collect(), &reply_ctx, depth + 1).await?,
+                    pack_related(renote_ids.collect(), ctx, depth + 1).await?,
+                )
+            }
+        };
+
+        let file_ids: Vec<String> = notes
+            .iter()
+            .flat_map(|n| from_string_vec(n.file_ids.to_owned()))
+            .collect();
+        let files = pack_files(file_ids, ctx).await?;
+
+        let poll_ids: Vec<String> = notes
+            .iter()
+            .filter(|n| n.has_poll)
+            .map(|n| n.id.to_owned())
+            .collect();
+        let mut polls = pack_polls(poll_ids, ctx.viewer_id.as_deref()).await?;
+
+        let channel_ids: Vec<String> = notes
+            .iter()
+            .filter_map(|n| n.channel_id.to_owned())
+            .collect();
+        let channels: HashMap<String, NoteChannel> = match channel_ids.is_empty() {
+            true => HashMap::new(),
+            false => channel::Entity::find()
+                .filter(channel::Column::Id.is_in(channel_ids))
+                .all(db)
+                .await?
+                .into_iter()
+                .map(|m| {
+                    (
+                        m.id.to_owned(),
+                        NoteChannel {
+                            id: m.id,
+                            name: m.name,
+                        },
+                    )
+                })
+                .collect(),
+        };
+
+        let my_reactions: HashMap<String, String> = match &ctx.viewer_id {
+            None => HashMap::new(),
+            Some(viewer_id) => note_reaction::Entity::find()
+                .filter(note_reaction::Column::UserId.eq(viewer_id.to_owned()))
+                .filter(note_reaction::Column::NoteId.is_in(note_ids))
+                .all(db)
+                .await?
+                .into_iter()
+                .map(|m| (m.note_id, decode_reaction(&m.reaction).reaction))
+                .collect(),
+        };
+
+        notes
+            .into_iter()
+            .map(|note| {
+                let user = users.get(&note.user_id).cloned().ok_or(Error::NotFound)?;
+                let find_related = |related: &HashMap<String, Note>, id: &Option<String>| {
+                    id.as_ref()
+                        .and_then(|id| related.get(id))
+                        .map(|n| Box::new(n.to_owned()))
+                };
+                let reply = find_related(&replies, &note.reply_id);
+                let renote = find_related(&renotes, &note.renote_id);
+                let file_ids = from_string_vec(note.file_ids);
+                let note_files = file_ids
+                    .iter()
+                    .filter_map(|id| files.get(id).cloned())
+                    .collect();
+                let poll = polls.remove(&note.id);
+                let channel = note
+                    .channel_id
+                    .as_ref()
+                    .and_then(|id| channels.get(id).cloned());
+                let my_reaction = my_reactions.get(&note.id).cloned();
+
+                let text = match (&note.name, note.url.as_ref().or(note.uri.as_ref())) {
+                    (Some(name), Some(url)) => Some(format!(
+                        "【{}】\n{}\n\n{}",
+                        name,
+                        note.text.as_deref().unwrap_or_default().trim(),
+                        url
+                    )),
+                    _ => note.text,
+                };
+
+                let visibility = note.visibility.try_into()?;
+                let visible_user_ids = match visibility {
+                    NoteVisibility::Specified => from_string_vec(note.visible_user_ids),
+                    _ => vec![],
+                };
+
+                Ok(Note {
+                    id: note.id,
+                    created_at: note.created_at.into(),
+                    updated_at: note.updated_at.map(Into::into),
+                    text,
+                    cw: note.cw,
+                    user_id: note.user_id,
+                    user,
+                    reply_id: note.reply_id,
+                    renote_id: note.renote_id,
+                    reply,
+                    renote,
+                    visibility,
+                    local_only: note.local_only,
+                    mentions: from_string_vec(note.mentions),
+                    visible_user_ids,
+                    file_ids,
+                    files: note_files,
+                    tags: from_string_vec(note.tags),
+                    poll,
+                    channel_id: note.channel_id,
+                    channel,
+                    reactions: convert_reactions(&note.reactions),
+                    renote_count: note.renote_count.into(),
+                    replies_count: note.replies_count.into(),
+                    uri: note.uri,
+                    url: note.url,
+                    my_reaction,
+                })
+            })
+            .collect()
+    })
+}
+
+/// Drops the notes that the viewer cannot see. This must be kept in sync with
+/// `isVisibleForMe` in `models/repositories/note.ts`.
+async fn filter_visible(
+    notes: Vec<note::Model>,
+    viewer_id: Option<&str>,
+) -> Result<Vec<note::Model>, Error> {
+    let is_restricted = |n: &note::Model| {
+        n.visibility == NoteVisibilityEnum::Specified
+            || n.visibility == NoteVisibilityEnum::Followers
+    };
+    if !notes.iter().any(is_restricted) {
+        return Ok(notes);
+    }
+    let viewer_id = match viewer_id {
+        None => return Ok(notes.into_iter().filter(|n| !is_restricted(n)).collect()),
+        Some(id) => id,
+    };
+
+    // Authors of followers-only notes that may be visible to the viewer by
+    // following them
+    let followee_ids: Vec<String> = notes
+        .iter()
+        .filter(|n| n.visibility == NoteVisibilityEnum::Followers && n.user_id != viewer_id)
+        .map(|n| n.user_id.to_owned())
+        .collect();
+    let (following, viewer_is_remote): (HashSet<String>, bool) = match followee_ids.is_empty() {
+        true => (HashSet::new(), false),
+        false => {
+            let db = database::get_database()?;
+            let following = following::Entity::find()
+                .filter(following::Column::FollowerId.eq(viewer_id))
+                .filter(following::Column::FolloweeId.is_in(followee_ids))
+                .all(db)
+                .await?
+                .into_iter()
+                .map(|m| m.followee_id)
+                .collect();
+            let viewer = user::Entity::find_by_id(viewer_id.to_string())
+                .one(db)
+                .await?;
+            (following, viewer.is_some_and(|v| v.host.is_some()))
+        }
+    };
+
+    Ok(notes
+        .into_iter()
+        .filter(|n| {
+            if n.user_id == viewer_id {
+                return true;
+            }
+            match n.visibility {
+                NoteVisibilityEnum::Specified => from_string_vec(n.visible_user_ids.to_owned())
+                    .iter()
+                    .any(|id| id == viewer_id),
+                NoteVisibilityEnum::Followers => {
+                    n.reply_user_id.as_deref() == Some(viewer_id)
+                        || from_string_vec(n.mentions.to_owned())
+                            .iter()
+                            .any(|id| id == viewer_id)
+                        || following.contains(&n.user_id)
+                        // Followings between remote users may be unknown.
+                        || (n.user_host.is_some() && viewer_is_remote)
+                }
+                _ => true,
+            }
+        })
+        .collect())
+}
+
+/// Packs the replies or renotes keyed by their ids. Deleted ones and ones
+/// invisible to the viewer are skipped.
+async fn pack_related(
+    ids: Vec<String>,
+    ctx: &PackContext,
+    depth: u8,
+) -> Result<HashMap<String, Note>, Error> {
+    if ids.is_empty() {
+        return Ok(HashMap::new());
+    }
+    let db = database::get_database()?;
+    let models = note::Entity::find()
+        .filter(note::Column::Id.is_in(ids))
+        .all(db)
+        .await?;
+
+    Ok(pack_batch(models, ctx, depth)
+        .await?
+        .into_iter()
+        .map(|n| (n.id.to_owned(), n))
+        .collect())
+}
+
+/// Packs the files keyed by their ids. Deleted ones are skipped.
+async fn pack_files(
+    ids: Vec<String>,
+    ctx: &PackContext,
+) -> Result<HashMap<String, DriveFile>, Error> {
+    if ids.is_empty() {
+        return Ok(HashMap::new());
+    }
+    let db = database::get_database()?;
+    let models = drive_file::Entity::find()
+        .filter(drive_file::Column::Id.is_in(ids))
+        .all(db)
+        .await?;
+
+    Ok(drive_file::Model::pack_many(models, ctx)
+        .await?
+        .into_iter()
+        .map(|f| (f.id.to_owned(), f))
+        .collect())
+}
+
+/// Packs the polls keyed by their note ids, marking the choices the viewer
+/// voted for.
+async fn pack_polls(
+    note_ids: Vec<String>,
+    viewer_id: Option<&str>,
+) -> Result<HashMap<String, NotePoll>, Error> {
+    if note_ids.is_empty() {
+        return Ok(HashMap::new());
+    }
+    let db = database::get_database()?;
+    let votes: Vec<poll_vote::Model> = match viewer_id {
+        None => vec![],
+        Some(viewer_id) => {
+            poll_vote::Entity::find()
+                .filter(poll_vote::Column::UserId.eq(viewer_id))
+                .filter(poll_vote::Column::NoteId.is_in(note_ids.to_owned()))
+                .all(db)
+                .await?
+        }
+    };
+
+    Ok(poll::Entity::find()
+        .filter(poll::Column::NoteId.is_in(note_ids))
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|m| {
+            let my_choices: HashSet<i32> = votes
+                .iter()
+                .filter(|v| v.note_id == m.note_id)
+                .map(|v| v.choice)
+                .collect();
+            (m.note_id.to_owned(), pack_poll(m, &my_choices))
+        })
+        .collect())
+}
+
+fn pack_poll(poll: poll::Model, my_choices: &HashSet<i32>) -> NotePoll {
+    let choices = from_string_vec(poll.choices);
+    let votes = from_i32_vec(poll.votes);
+    NotePoll {
+        multiple: poll.multiple,
+        expires_at: poll.expires_at.map(Into::into),
+        choices: choices
+            .into_iter()
+            .enumerate()
+            .map(|(i, text)| NotePollChoice {
+                text,
+                votes: votes.get(i).copied().unwrap_or_default(),
+                is_voted: my_choices.contains(&(i as i32)),
+            })
+            .collect(),
+    }
+}
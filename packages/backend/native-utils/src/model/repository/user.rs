@@ -0,0 +1,452 @@
+use std::collections::{HashMap, HashSet};
+
+use async_trait::async_trait;
+use chrono::{Duration, Utc};
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter, QueryOrder};
+
+use crate::database;
+use crate::model::entity::newtype::from_string_vec;
+use crate::model::entity::sea_orm_active_enums::UserProfileFfvisibilityEnum;
+use crate::model::entity::{
+    blocking, drive_file, emoji, follow_request, following, instance, muting, note, renote_muting,
+    user, user_note_pining, user_profile, user_security_key,
+};
+use crate::model::error::Error;
+use crate::model::schema::{
+    Note, PopulatedEmoji, UserDetailed, UserField, UserInstance, UserLite, UserOnlineStatus,
+};
+
+use super::macros::{impl_pack_by_id, impl_pack_by_ids};
+use super::{PackContext, Repository};
+
+impl user::Model {
+    /// Returns the online status shown to others.
+    pub fn online_status(&self) -> UserOnlineStatus {
+        if self.hide_online_status {
+            return UserOnlineStatus::Unknown;
+        }
+        match self.last_active_date {
+            None => UserOnlineStatus::Unknown,
+            Some(date) => {
+                let elapsed = Utc::now().signed_duration_since(date);
+                if elapsed < Duration::minutes(10) {
+                    UserOnlineStatus::Online
+                } else if elapsed < Duration::days(3) {
+                    UserOnlineStatus::Active
+                } else {
+                    UserOnlineStatus::Offline
+                }
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Repository<UserLite> for user::Model {
+    async fn pack(self, ctx: &PackContext) -> Result<UserLite, Error> {
+        match Repository::<UserLite>::pack_many(vec![self], ctx)
+            .await?
+            .pop()
+        {
+            None => Err(Error::NotFound),
+            Some(packed) => Ok(packed),
+        }
+    }
+
+    async fn pack_by_id(id: String, ctx: &PackContext) -> Result<UserLite, Error> {
+        impl_pack_by_id!(user::Entity, id, ctx)
+    }
+
+    /// Loads the avatars, instances, and custom emojis of all the users
+    /// with one query each.
+    async fn pack_many(models: Vec<Self>, _ctx: &PackContext) -> Result<Vec<UserLite>, Error> {
+        if models.is_empty() {
+            return Ok(vec![]);
+        }
+        let avatar_ids: Vec<String> = models
+            .iter()
+            .filter_map(|m| m.avatar_id.to_owned())
+            .collect();
+        let avatars = find_files(avatar_ids).await?;
+        let instances = find_instances(&models).await?;
+        let mut emojis = find_emojis(&models).await?;
+
+        Ok(models
+            .into_iter()
+            .map(|m| {
+                let avatar = m.avatar_id.as_ref().and_then(|id| avatars.get(id));
+                let instance = m
+                    .host
+                    .as_ref()
+                    .and_then(|host| instances.get(host).cloned());
+                let emojis = emojis.remove(&m.id).unwrap_or_default();
+                UserLite {
+                    avatar_url: avatar.and_then(|f| f.public_url(true)),
+                    avatar_blurhash: avatar.and_then(|f| f.blurhash.to_owned()),
+                    online_status: m.online_status(),
+                    id: m.id,
+                    name: m.name,
+                    username: m.username,
+                    host: m.host,
+                    is_admin: m.is_admin,
+                    is_moderator: m.is_moderator,
+                    is_bot: m.is_bot,
+                    is_locked: m.is_locked,
+                    is_cat: m.is_cat,
+                    speak_as_cat: m.speak_as_cat,
+                    is_indexable: m.is_indexable,
+                    instance,
+                    emojis,
+                    drive_capacity_override_mb: m.drive_capacity_override_mb,
+                }
+            })
+            .collect())
+    }
+
+    async fn pack_by_ids(ids: Vec<String>, ctx: &PackContext) -> Result<Vec<UserLite>, Error> {
+        impl_pack_by_ids!(user::Entity, user::Column::Id, ids, ctx)
+    }
+}
+
+#[async_trait]
+impl Repository<UserDetailed> for user::Model {
+    /// Returns [Error::NotFound] if the profile of the user does not exist.
+    async fn pack(self, ctx: &PackContext) -> Result<UserDetailed, Error> {
+        match Repository::<UserDetailed>::pack_many(vec![self], ctx)
+            .await?
+            .pop()
+        {
+            None => Err(Error::NotFound),
+            Some(packed) => Ok(packed),
+        }
+    }
+
+    async fn pack_by_id(id: String, ctx: &PackContext) -> Result<UserDetailed, Error> {
+        impl_pack_by_id!(user::Entity, id, ctx)
+    }
+
+    /// Loads the profiles, banners, pinned notes, and relations with the
+    /// viewer of all the users with one query each. Returns
+    /// [Error::NotFound] if the profile of any user does not exist.
+    async fn pack_many(models: Vec<Self>, ctx: &PackContext) -> Result<Vec<UserDetailed>, Error> {
+        if models.is_empty() {
+            return Ok(vec![]);
+        }
+        let db = database::get_database()?;
+        let user_ids: Vec<String> = models.iter().map(|m| m.id.to_owned()).collect();
+
+        let lites = Repository::<UserLite>::pack_many(models.to_owned(), ctx).await?;
+        let mut profiles: HashMap<String, user_profile::Model> = user_profile::Entity::find()
+            .filter(user_profile::Column::UserId.is_in(user_ids.to_owned()))
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|p| (p.user_id.to_owned(), p))
+            .collect();
+        let banner_ids: Vec<String> = models
+            .iter()
+            .filter_map(|m| m.banner_id.to_owned())
+            .collect();
+        let banners = find_files(banner_ids).await?;
+
+        let pins = user_note_pining::Entity::find()
+            .filter(user_note_pining::Column::UserId.is_in(user_ids.to_owned()))
+            .order_by_desc(user_note_pining::Column::Id)
+            .all(db)
+            .await?;
+        let pinned_ids: Vec<String> = pins.iter().map(|p| p.note_id.to_owned()).collect();
+        let pinned_notes: HashMap<String, Note> = match pinned_ids.is_empty() {
+            true => HashMap::new(),
+            false => note::Model::pack_by_ids(
+                pinned_ids,
+                &PackContext::detailed(ctx.viewer_id.to_owned()),
+            )
+            .await?
+            .into_iter()
+            .map(|n: Note| (n.id.to_owned(), n))
+            .collect(),
+        };
+
+        let with_security_keys: HashSet<String> = user_security_key::Entity::find()
+            .filter(user_security_key::Column::UserId.is_in(user_ids.to_owned()))
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|k| k.user_id)
+            .collect();
+
+        let relations = match &ctx.viewer_id {
+            None => None,
+            Some(viewer_id) => Some(Relations::load(viewer_id, user_ids).await?),
+        };
+
+        let mut packed = Vec::with_capacity(models.len());
+        for (m, lite) in models.into_iter().zip(lites) {
+            let profile = profiles.remove(&m.id).ok_or(Error::NotFound)?;
+            let banner = m.banner_id.as_ref().and_then(|id| banners.get(id));
+            let is_me = ctx.viewer_id.as_ref() == Some(&m.id);
+            let relation = match (&relations, is_me) {
+                (Some(r), false) => Some(r.of(&m.id)),
+                _ => None,
+            };
+
+            let ff_visible = match profile.ff_visibility {
+                UserProfileFfvisibilityEnum::Public => true,
+                _ if is_me => true,
+                UserProfileFfvisibilityEnum::Followers => {
+                    relation.as_ref().is_some_and(|r| r.is_following)
+                }
+                UserProfileFfvisibilityEnum::Private => false,
+            };
+            let user_pins: Vec<String> = pins
+                .iter()
+                .filter(|p| p.user_id == m.id)
+                .map(|p| p.note_id.to_owned())
+                .collect();
+
+            packed.push(UserDetailed {
+                url: profile.url,
+                uri: m.uri,
+                moved_to_uri: m.moved_to_uri,
+                also_known_as: m
+                    .also_known_as
+                    .map(|uris| uris.split(',').map(str::to_string).collect()),
+                created_at: m.created_at.into(),
+                updated_at: m.updated_at.map(Into::into),
+                last_fetched_at: m.last_fetched_at.map(Into::into),
+                banner_url: banner.and_then(|f| f.public_url(false)),
+                banner_blurhash: banner.and_then(|f| f.blurhash.to_owned()),
+                is_silenced: m.is_silenced,
+                is_suspended: m.is_suspended,
+                description: profile.description,
+                location: profile.location,
+                birthday: profile.birthday,
+                lang: profile.lang,
+                fields: serde_json::from_value::<Vec<UserField>>(profile.fields)
+                    .unwrap_or_default(),
+                followers_count: if ff_visible { m.followers_count } else { 0 },
+                following_count: if ff_visible { m.following_count } else { 0 },
+                notes_count: m.notes_count,
+                pinned_notes: user_pins
+                    .iter()
+                    .filter_map(|id| pinned_notes.get(id).cloned())
+                    .collect(),
+                pinned_note_ids: user_pins,
+                public_reactions: profile.public_reactions,
+                ff_visibility: profile.ff_visibility.try_into()?,
+                two_factor_enabled: profile.two_factor_enabled,
+                use_password_less_login: profile.use_password_less_login,
+                security_keys: profile.two_factor_enabled && with_security_keys.contains(&m.id),
+                is_following: relation.as_ref().map(|r| r.is_following),
+                is_followed: relation.as_ref().map(|r| r.is_followed),
+                has_pending_follow_request_from_you: relation
+                    .as_ref()
+                    .map(|r| r.has_pending_follow_request_from_you),
+                has_pending_follow_request_to_you: relation
+                    .as_ref()
+                    .map(|r| r.has_pending_follow_request_to_you),
+                is_blocking: relation.as_ref().map(|r| r.is_blocking),
+                is_blocked: relation.as_ref().map(|r| r.is_blocked),
+                is_muted: relation.as_ref().map(|r| r.is_muted),
+                is_renote_muted: relation.as_ref().map(|r| r.is_renote_muted),
+                lite,
+            });
+        }
+        Ok(packed)
+    }
+
+    async fn pack_by_ids(ids: Vec<String>, ctx: &PackContext) -> Result<Vec<UserDetailed>, Error> {
+        impl_pack_by_ids!(user::Entity, user::Column::Id, ids, ctx)
+    }
+}
+
+/// Relation between the viewer and a user.
+struct Relation {
+    is_following: bool,
+    is_followed: bool,
+    has_pending_follow_request_from_you: bool,
+    has_pending_follow_request_to_you: bool,
+    is_blocking: bool,
+    is_blocked: bool,
+    is_muted: bool,
+    is_renote_muted: bool,
+}
+
+/// Ids of the users that the viewer has each relation with.
+#[derive(Default)]
+struct Relations {
+    following: HashSet<String>,
+    followed: HashSet<String>,
+    requested: HashSet<String>,
+    requested_by: HashSet<String>,
+    blocking: HashSet<String>,
+    blocked_by: HashSet<String>,
+    muting: HashSet<String>,
+    renote_muting: HashSet<String>,
+}
+
+impl Relations {
+    async fn load(viewer_id: &str, user_ids: Vec<String>) -> Result<Self, Error> {
+        let db = database::get_database()?;
+        Ok(Self {
+            following: following::Entity::find()
+                .filter(following::Column::FollowerId.eq(viewer_id))
+                .filter(following::Column::FolloweeId.is_in(user_ids.to_owned()))
+                .all(db)
+                .await?
+                .into_iter()
+                .map(|m| m.followee_id)
+                .collect(),
+            followed: following::Entity::find()
+                .filter(following::Column::FolloweeId.eq(viewer_id))
+                .filter(following::Column::FollowerId.is_in(user_ids.to_owned()))
+                .all(db)
+                .await?
+                .into_iter()
+                .map(|m| m.follower_id)
+                .collect(),
+            requested: follow_request::Entity::find()
+                .filter(follow_request::Column::FollowerId.eq(viewer_id))
+                .filter(follow_request::Column::FolloweeId.is_in(user_ids.to_owned()))
+                .all(db)
+                .await?
+                .into_iter()
+                .map(|m| m.followee_id)
+                .collect(),
+            requested_by: follow_request::Entity::find()
+                .filter(follow_request::Column::FolloweeId.eq(viewer_id))
+                .filter(follow_request::Column::FollowerId.is_in(user_ids.to_owned()))
+                .all(db)
+                .await?
+                .into_iter()
+                .map(|m| m.follower_id)
+                .collect(),
+            blocking: blocking::Entity::find()
+                .filter(blocking::Column::BlockerId.eq(viewer_id))
+                .filter(blocking::Column::BlockeeId.is_in(user_ids.to_owned()))
+                .all(db)
+                .await?
+                .into_iter()
+                .map(|m| m.blockee_id)
+                .collect(),
+            blocked_by: blocking::Entity::find()
+                .filter(blocking::Column::BlockeeId.eq(viewer_id))
+                .filter(blocking::Column::BlockerId.is_in(user_ids.to_owned()))
+                .all(db)
+                .await?
+                .into_iter()
+                .map(|m| m.blocker_id)
+                .collect(),
+            muting: muting::Entity::find()
+                .filter(muting::Column::MuterId.eq(viewer_id))
+                .filter(muting::Column::MuteeId.is_in(user_ids.to_owned()))
+                .all(db)
+                .await?
+                .into_iter()
+                .map(|m| m.mutee_id)
+                .collect(),
+            renote_muting: renote_muting::Entity::find()
+                .filter(renote_muting::Column::MuterId.eq(viewer_id))
+                .filter(renote_muting::Column::MuteeId.is_in(user_ids))
+                .all(db)
+                .await?
+                .into_iter()
+                .map(|m| m.mutee_id)
+                .collect(),
+        })
+    }
+
+    fn of(&self, user_id: &str) -> Relation {
+        Relation {
+            is_following: self.following.contains(user_id),
+            is_followed: self.followed.contains(user_id),
+            has_pending_follow_request_from_you: self.requested.contains(user_id),
+            has_pending_follow_request_to_you: self.requested_by.contains(user_id),
+            is_blocking: self.blocking.contains(user_id),
+            is_blocked: self.blocked_by.contains(user_id),
+            is_muted: self.muting.contains(user_id),
+            is_renote_muted: self.renote_muting.contains(user_id),
+        }
+    }
+}
+
+/// Returns the drive files keyed by their ids.
+async fn find_files(ids: Vec<String>) -> Result<HashMap<String, drive_file::Model>, Error> {
+    if ids.is_empty() {
+        return Ok(HashMap::new());
+    }
+    Ok(drive_file::Entity::find()
+        .filter(drive_file::Column::Id.is_in(ids))
+        .all(database::get_database()?)
+        .await?
+        .into_iter()
+        .map(|f| (f.id.to_owned(), f))
+        .collect())
+}
+
+/// Returns the instances of the remote users keyed by their hosts.
+async fn find_instances(users: &[user::Model]) -> Result<HashMap<String, UserInstance>, Error> {
+    let hosts: HashSet<String> = users.iter().filter_map(|u| u.host.to_owned()).collect();
+    if hosts.is_empty() {
+        return Ok(HashMap::new());
+    }
+    Ok(instance::Entity::find()
+        .filter(instance::Column::Host.is_in(hosts))
+        .all(database::get_database()?)
+        .await?
+        .into_iter()
+        .map(|i| {
+            (
+                i.host,
+                UserInstance {
+                    name: i.name,
+                    software_name: i.software_name,
+                    software_version: i.software_version,
+                    icon_url: i.icon_url,
+                    favicon_url: i.favicon_url,
+                    theme_color: i.theme_color,
+                },
+            )
+        })
+        .collect())
+}
+
+/// Returns the custom emojis used by the users keyed by the user ids. The
+/// emojis of a user are looked up on the host of the user and unknown ones
+/// are skipped.
+async fn find_emojis(users: &[user::Model]) -> Result<HashMap<String, Vec<PopulatedEmoji>>, Error> {
+    let names: HashSet<String> = users
+        .iter()
+        .flat_map(|u| from_string_vec(u.emojis.to_owned()))
+        .collect();
+    if names.is_empty() {
+        return Ok(HashMap::new());
+    }
+    let emojis = emoji::Entity::find()
+        .filter(emoji::Column::Name.is_in(names))
+        .all(database::get_database()?)
+        .await?;
+
+    Ok(users
+        .iter()
+        .map(|u| {
+            let populated = from_string_vec(u.emojis.to_owned())
+                .into_iter()
+                .filter_map(|name| {
+                    let e = emojis.iter().find(|e| e.name == name && e.host == u.host)?;
+                    let url = match e.public_url.is_empty() {
+                        true => e.original_url.to_owned(),
+                        false => e.public_url.to_owned(),
+                    };
+                    Some(PopulatedEmoji {
+                        name,
+                        url,
+                        width: e.width,
+                        height: e.height,
+                    })
+                })
+                .collect();
+            (u.id.to_owned(), populated)
+        })
+        .collect())
+}
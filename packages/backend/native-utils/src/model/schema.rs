@@ -1,5 +1,9 @@
 pub mod antenna;
 pub mod app;
+pub mod drive_file;
+pub mod emoji;
+pub mod note;
+pub mod user;
 
 use cfg_if::cfg_if;
 use jsonschema::JSONSchema;
@@ -33,3 +37,10 @@ cfg_if! {
         pub use app::AppPermission;
     }
 }
+
+pub use drive_file::{DriveFile, DriveFileProperties};
+pub use emoji::PopulatedEmoji;
+pub use note::{Note, NoteChannel, NotePoll, NotePollChoice, NoteVisibility};
+pub use user::{
+    UserDetailed, UserFfVisibility, UserField, UserInstance, UserLite, UserOnlineStatus,
+};
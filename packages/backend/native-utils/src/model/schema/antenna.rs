@@ -67,7 +67,7 @@ cfg_if! {
         use napi_derive::napi;
 
         use crate::model::entity::antenna;
-        use crate::model::repository::Repository;
+        use crate::model::repository::{PackContext, Repository};
 
         /// For NAPI because [chrono] is not supported.
         #[napi(object)]
@@ -112,7 +112,7 @@ cfg_if! {
 
         #[napi]
         pub async fn native_pack_antenna_by_id(id: String) -> napi::Result<NativeAntennaSchema> {
-            antenna::Model::pack_by_id(id).await.map_err(Into::into)
+            antenna::Model::pack_by_id(id, &PackContext::default()).await.map_err(Into::into)
         }
     }
 }
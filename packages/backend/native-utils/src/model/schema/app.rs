@@ -105,7 +105,7 @@ mod unit_test {
 
     #[test]
     fn app_valid() {
-        init_id(16, "");
+        init_id(16, "").unwrap();
         let instance = json!({
             "id": create_id(0).unwrap(),
             "name": "Test App",
@@ -119,7 +119,7 @@ mod unit_test {
 
     #[test]
     fn app_invalid() {
-        init_id(16, "");
+        init_id(16, "").unwrap();
         let instance = json!({
             "id": create_id(0).unwrap(),
             // "name" is required
@@ -0,0 +1,99 @@
+use jsonschema::JSONSchema;
+use once_cell::sync::Lazy;
+use schemars::JsonSchema;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use super::Schema;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct DriveFile {
+    pub id: String,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub name: String,
+    /// MIME type of the file.
+    pub r#type: String,
+    pub md5: String,
+    pub size: i32,
+    #[serde(default)]
+    pub is_sensitive: bool,
+    pub blurhash: Option<String>,
+    #[schema(inline)]
+    pub properties: DriveFileProperties,
+    #[schemars(url)]
+    pub url: Option<String>,
+    #[schemars(url)]
+    pub thumbnail_url: Option<String>,
+    pub comment: Option<String>,
+    pub folder_id: Option<String>,
+}
+
+/// Public properties of the file. `width` and `height` are already swapped
+/// according to the EXIF orientation.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct DriveFileProperties {
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub avg_color: Option<String>,
+}
+
+impl Schema<Self> for DriveFile {}
+
+pub static VALIDATOR: Lazy<JSONSchema> = Lazy::new(DriveFile::validator);
+
+#[cfg(test)]
+mod unit_test {
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    use super::VALIDATOR;
+
+    #[test]
+    fn drive_file_valid() {
+        let instance = json!({
+            "id": "9fil64s6g7cskdrb",
+            "createdAt": "2023-05-24T06:56:14.323Z",
+            "name": "image.webp",
+            "type": "image/webp",
+            "md5": "d41d8cd98f00b204e9800998ecf8427e",
+            "size": 1024,
+            "blurhash": null,
+            "properties": { "width": 640, "height": 480 },
+            "url": "https://example.com/files/image.webp",
+            "thumbnailUrl": null,
+            "comment": null,
+            "folderId": null,
+        });
+
+        assert!(VALIDATOR.is_valid(&instance));
+    }
+
+    #[test]
+    fn drive_file_invalid() {
+        let instance = json!({
+            "id": "9fil64s6g7cskdrb",
+            "createdAt": "2023-05-24T06:56:14.323Z",
+            "name": "image.webp",
+            // "type" is required
+            "md5": "d41d8cd98f00b204e9800998ecf8427e",
+            // "size" must be an integer
+            "size": 1.5,
+            // "width" must be a non-negative integer
+            "properties": { "width": -1 },
+            // "url" must be a URL
+            "url": "not a url",
+        });
+
+        let result = VALIDATOR
+            .validate(&instance)
+            .expect_err("validation must fail");
+        let mut paths: Vec<String> = result
+            .map(|e| e.instance_path.to_string())
+            .filter(|e| !e.is_empty())
+            .collect();
+        paths.sort();
+        assert_eq!(paths, vec!["/properties/width", "/size", "/url"]);
+    }
+}
@@ -0,0 +1,53 @@
+use jsonschema::JSONSchema;
+use once_cell::sync::Lazy;
+use schemars::JsonSchema;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use super::Schema;
+
+/// Custom emoji attached to users and notes.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PopulatedEmoji {
+    /// Name of the emoji as written in the text, without colons.
+    pub name: String,
+    #[schemars(url)]
+    pub url: String,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+}
+
+impl Schema<Self> for PopulatedEmoji {}
+
+pub static VALIDATOR: Lazy<JSONSchema> = Lazy::new(PopulatedEmoji::validator);
+
+#[cfg(test)]
+mod unit_test {
+    use serde_json::json;
+
+    use super::VALIDATOR;
+
+    #[test]
+    fn emoji_valid() {
+        let instance = json!({
+            "name": "blobcat",
+            "url": "https://example.com/emoji/blobcat.png",
+            "width": 128,
+            "height": null,
+        });
+
+        assert!(VALIDATOR.is_valid(&instance));
+    }
+
+    #[test]
+    fn emoji_invalid() {
+        let instance = json!({
+            "name": "blobcat",
+            // "url" is required
+            "width": "128",
+        });
+
+        assert!(!VALIDATOR.is_valid(&instance));
+    }
+}
@@ -0,0 +1,250 @@
+use std::collections::HashMap;
+
+use cfg_if::cfg_if;
+use jsonschema::JSONSchema;
+use once_cell::sync::Lazy;
+use parse_display::FromStr;
+use schemars::JsonSchema;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use super::{DriveFile, Schema, UserLite};
+use crate::model;
+use crate::model::entity::sea_orm_active_enums::NoteVisibilityEnum;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Note {
+    pub id: String,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub text: Option<String>,
+    pub cw: Option<String>,
+    pub user_id: String,
+    pub user: UserLite,
+    pub reply_id: Option<String>,
+    pub renote_id: Option<String>,
+    /// Omitted if the nesting is too deep or the reply has been deleted.
+    pub reply: Option<Box<Note>>,
+    /// Omitted if the nesting is too deep or the renote has been deleted.
+    pub renote: Option<Box<Note>>,
+    #[schema(inline)]
+    pub visibility: NoteVisibility,
+    #[serde(default)]
+    pub local_only: bool,
+    pub mentions: Vec<String>,
+    /// Empty unless `visibility` is `specified`.
+    pub visible_user_ids: Vec<String>,
+    pub file_ids: Vec<String>,
+    pub files: Vec<DriveFile>,
+    pub tags: Vec<String>,
+    pub poll: Option<NotePoll>,
+    pub channel_id: Option<String>,
+    pub channel: Option<NoteChannel>,
+    /// Reaction counts keyed by decoded reactions.
+    pub reactions: HashMap<String, i32>,
+    pub renote_count: i32,
+    pub replies_count: i32,
+    #[schemars(url)]
+    pub uri: Option<String>,
+    #[schemars(url)]
+    pub url: Option<String>,
+    /// Decoded reaction of the viewer. `None` if the viewer has not reacted
+    /// or is not signed in.
+    pub my_reaction: Option<String>,
+}
+
+#[derive(Clone, Debug, FromStr, PartialEq, Eq, Serialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+#[display(style = "camelCase")]
+#[display("'{}'")]
+pub enum NoteVisibility {
+    Public,
+    Home,
+    Followers,
+    Specified,
+    Hidden,
+}
+
+impl TryFrom<NoteVisibilityEnum> for NoteVisibility {
+    type Error = model::error::Error;
+
+    fn try_from(value: NoteVisibilityEnum) -> Result<Self, Self::Error> {
+        value.to_string().parse().map_err(model::error::Error::from)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct NotePoll {
+    pub multiple: bool,
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub choices: Vec<NotePollChoice>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct NotePollChoice {
+    pub text: String,
+    pub votes: i32,
+    #[serde(default)]
+    pub is_voted: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct NoteChannel {
+    pub id: String,
+    pub name: String,
+}
+
+impl Schema<Self> for Note {}
+
+pub static VALIDATOR: Lazy<JSONSchema> = Lazy::new(Note::validator);
+
+cfg_if! {
+    if #[cfg(feature = "napi")] {
+        use napi_derive::napi;
+
+        use crate::model::entity::note;
+        use crate::model::repository::{PackContext, Repository};
+
+        /// Returns the packed note as JSON because NAPI objects cannot
+        /// contain nested notes. `detail` defaults to `true`.
+        #[napi]
+        pub async fn native_pack_note_by_id(
+            id: String,
+            me_id: Option<String>,
+            detail: Option<bool>,
+        ) -> napi::Result<serde_json::Value> {
+            let ctx = PackContext {
+                viewer_id: me_id,
+                detail: detail.unwrap_or(true),
+                ..Default::default()
+            };
+            let packed = note::Model::pack_by_id(id, &ctx).await.map_err(Into::<napi::Error>::into)?;
+            serde_json::to_value(packed).map_err(|e| napi::Error::from_reason(e.to_string()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod unit_test {
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    use crate::model::{entity::sea_orm_active_enums::NoteVisibilityEnum, schema::NoteVisibility};
+
+    use super::VALIDATOR;
+
+    #[test]
+    fn visibility_from_active_enum() {
+        let visibility = NoteVisibility::try_from(NoteVisibilityEnum::Specified).unwrap();
+        assert_eq!(visibility, NoteVisibility::Specified);
+    }
+
+    #[test]
+    fn note_valid() {
+        let user = json!({
+            "id": "9fil64s6g7cskdrb",
+            "name": null,
+            "username": "alice",
+            "host": null,
+            "isAdmin": false,
+            "isModerator": false,
+            "isBot": false,
+            "isLocked": false,
+            "isCat": false,
+            "speakAsCat": false,
+        });
+        let instance = json!({
+            "id": "9fil66brl1udxau2",
+            "createdAt": "2023-05-24T06:56:14.323Z",
+            "updatedAt": null,
+            "text": "Hello",
+            "cw": null,
+            "userId": "9fil64s6g7cskdrb",
+            "user": user,
+            "replyId": "9fil65jzhtjpi3xn",
+            "renoteId": null,
+            // nested notes are packed with the same schema
+            "reply": {
+                "id": "9fil65jzhtjpi3xn",
+                "createdAt": "2023-05-24T06:55:14.323Z",
+                "text": null,
+                "userId": "9fil64s6g7cskdrb",
+                "user": user,
+                "visibility": "home",
+                "mentions": [],
+                "visibleUserIds": [],
+                "fileIds": [],
+                "files": [],
+                "tags": [],
+                "reactions": {},
+                "renoteCount": 0,
+                "repliesCount": 1,
+            },
+            "visibility": "public",
+            // "localOnly" is false if omitted
+            "mentions": [],
+            "visibleUserIds": [],
+            "fileIds": [],
+            "files": [],
+            "tags": ["firefish"],
+            "poll": {
+                "multiple": false,
+                "expiresAt": null,
+                "choices": [
+                    { "text": "foo", "votes": 1, "isVoted": false },
+                    { "text": "bar", "votes": 0 },
+                ],
+            },
+            "reactions": { "👍": 2, ":blobcat@.:": 1 },
+            "renoteCount": 0,
+            "repliesCount": 0,
+            "uri": null,
+            "url": null,
+        });
+
+        assert!(VALIDATOR.is_valid(&instance));
+    }
+
+    #[test]
+    fn note_invalid() {
+        let instance = json!({
+            "id": "9fil66brl1udxau2",
+            "createdAt": "2023-05-24T06:56:14.323Z",
+            // "text" is a nullable string
+            "text": 1,
+            "userId": "9fil64s6g7cskdrb",
+            // "user" must be an object
+            "user": "9fil64s6g7cskdrb",
+            // "visibility" must be one of "public", "home", "followers",
+            // "specified", and "hidden"
+            "visibility": "private",
+            "mentions": [],
+            "visibleUserIds": [],
+            "fileIds": [],
+            "files": [],
+            // "tags" must be an array of strings
+            "tags": [1],
+            "reactions": {},
+            // "renoteCount" must be an integer
+            "renoteCount": "0",
+            "repliesCount": 0,
+        });
+
+        let result = VALIDATOR
+            .validate(&instance)
+            .expect_err("validation must fail");
+        let mut paths: Vec<String> = result
+            .map(|e| e.instance_path.to_string())
+            .filter(|e| !e.is_empty())
+            .collect();
+        paths.sort();
+        assert_eq!(
+            paths,
+            vec!["/renoteCount", "/tags/0", "/text", "/user", "/visibility"]
+        );
+    }
+}
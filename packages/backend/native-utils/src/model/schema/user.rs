@@ -0,0 +1,383 @@
+use cfg_if::cfg_if;
+use jsonschema::JSONSchema;
+use once_cell::sync::Lazy;
+use parse_display::FromStr;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::{Note, PopulatedEmoji, Schema};
+use crate::model;
+use crate::model::entity::sea_orm_active_enums::UserProfileFfvisibilityEnum;
+
+/// Minimum user representation embedded in other schemas.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct UserLite {
+    pub id: String,
+    pub name: Option<String>,
+    pub username: String,
+    /// `None` if the user is local.
+    pub host: Option<String>,
+    /// `None` if the user has no avatar.
+    #[schemars(url)]
+    pub avatar_url: Option<String>,
+    pub avatar_blurhash: Option<String>,
+    #[serde(default)]
+    pub is_admin: bool,
+    #[serde(default)]
+    pub is_moderator: bool,
+    #[serde(default)]
+    pub is_bot: bool,
+    #[serde(default)]
+    pub is_locked: bool,
+    #[serde(default)]
+    pub is_cat: bool,
+    #[serde(default)]
+    pub speak_as_cat: bool,
+    #[serde(default)]
+    pub is_indexable: bool,
+    /// Instance of the user. `None` if the user is local or the instance is
+    /// unknown.
+    pub instance: Option<UserInstance>,
+    /// Custom emojis used in the name.
+    #[serde(default)]
+    pub emojis: Vec<PopulatedEmoji>,
+    #[serde(default)]
+    #[schema(inline)]
+    pub online_status: UserOnlineStatus,
+    pub drive_capacity_override_mb: Option<i32>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct UserInstance {
+    pub name: Option<String>,
+    pub software_name: Option<String>,
+    pub software_version: Option<String>,
+    #[schemars(url)]
+    pub icon_url: Option<String>,
+    #[schemars(url)]
+    pub favicon_url: Option<String>,
+    pub theme_color: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum UserOnlineStatus {
+    /// The user hides the status or has never been active.
+    #[default]
+    Unknown,
+    /// Active within the last 10 minutes.
+    Online,
+    /// Active within the last 3 days.
+    Active,
+    Offline,
+}
+
+/// User representation with the profile, shown on the user page.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct UserDetailed {
+    #[serde(flatten)]
+    pub lite: UserLite,
+    /// Website of the user.
+    #[schemars(url)]
+    pub url: Option<String>,
+    #[schemars(url)]
+    pub uri: Option<String>,
+    #[schemars(url)]
+    pub moved_to_uri: Option<String>,
+    pub also_known_as: Option<Vec<String>>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub last_fetched_at: Option<chrono::DateTime<chrono::Utc>>,
+    #[schemars(url)]
+    pub banner_url: Option<String>,
+    pub banner_blurhash: Option<String>,
+    #[serde(default)]
+    pub is_silenced: bool,
+    #[serde(default)]
+    pub is_suspended: bool,
+    pub description: Option<String>,
+    pub location: Option<String>,
+    pub birthday: Option<String>,
+    pub lang: Option<String>,
+    pub fields: Vec<UserField>,
+    /// `0` if hidden from the viewer by `ffVisibility`.
+    pub followers_count: i32,
+    /// `0` if hidden from the viewer by `ffVisibility`.
+    pub following_count: i32,
+    pub notes_count: i32,
+    pub pinned_note_ids: Vec<String>,
+    /// Pinned notes visible to the viewer.
+    pub pinned_notes: Vec<Note>,
+    #[serde(default)]
+    pub public_reactions: bool,
+    #[schema(inline)]
+    pub ff_visibility: UserFfVisibility,
+    #[serde(default)]
+    pub two_factor_enabled: bool,
+    #[serde(default)]
+    pub use_password_less_login: bool,
+    #[serde(default)]
+    pub security_keys: bool,
+    /// Relations with the viewer below are omitted if the viewer is not
+    /// signed in or is the user.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_following: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_followed: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub has_pending_follow_request_from_you: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub has_pending_follow_request_to_you: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_blocking: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_blocked: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_muted: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_renote_muted: Option<bool>,
+}
+
+/// Profile field, stored as JSON in `user_profile.fields`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema, ToSchema)]
+pub struct UserField {
+    pub name: String,
+    pub value: String,
+}
+
+/// Who can see the follower and following counts of the user.
+#[derive(Clone, Debug, FromStr, PartialEq, Eq, Serialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+#[display(style = "camelCase")]
+#[display("'{}'")]
+pub enum UserFfVisibility {
+    Public,
+    Followers,
+    Private,
+}
+
+impl TryFrom<UserProfileFfvisibilityEnum> for UserFfVisibility {
+    type Error = model::error::Error;
+
+    fn try_from(value: UserProfileFfvisibilityEnum) -> Result<Self, Self::Error> {
+        value.to_string().parse().map_err(model::error::Error::from)
+    }
+}
+
+impl Schema<Self> for UserLite {}
+
+pub static VALIDATOR: Lazy<JSONSchema> = Lazy::new(UserLite::validator);
+
+impl Schema<Self> for UserDetailed {}
+
+pub static DETAILED_VALIDATOR: Lazy<JSONSchema> = Lazy::new(UserDetailed::validator);
+
+cfg_if! {
+    if #[cfg(feature = "napi")] {
+        use napi_derive::napi;
+
+        use crate::model::entity::user;
+        use crate::model::repository::{PackContext, Repository};
+
+        /// Returns [UserDetailed] if `detail` is `true`, or [UserLite]
+        /// otherwise, as JSON because NAPI objects cannot contain nested
+        /// notes. `detail` defaults to `false`.
+        #[napi]
+        pub async fn native_pack_user_by_id(
+            id: String,
+            me_id: Option<String>,
+            detail: Option<bool>,
+        ) -> napi::Result<serde_json::Value> {
+            let ctx = PackContext {
+                viewer_id: me_id,
+                detail: detail.unwrap_or(false),
+                ..Default::default()
+            };
+            let packed = match ctx.detail {
+                true => serde_json::to_value(
+                    <user::Model as Repository<UserDetailed>>::pack_by_id(id, &ctx).await.map_err(Into::<napi::Error>::into)?,
+                ),
+                false => serde_json::to_value(
+                    <user::Model as Repository<UserLite>>::pack_by_id(id, &ctx).await.map_err(Into::<napi::Error>::into)?,
+                ),
+            };
+            packed.map_err(|e| napi::Error::from_reason(e.to_string()))
+        }
+
+        /// Packs the users in the order of `ids` like
+        /// [native_pack_user_by_id]. Missing users are skipped.
+        #[napi]
+        pub async fn native_pack_users_by_ids(
+            ids: Vec<String>,
+            me_id: Option<String>,
+            detail: Option<bool>,
+        ) -> napi::Result<serde_json::Value> {
+            let ctx = PackContext {
+                viewer_id: me_id,
+                detail: detail.unwrap_or(false),
+                ..Default::default()
+            };
+            let packed = match ctx.detail {
+                true => serde_json::to_value(
+                    <user::Model as Repository<UserDetailed>>::pack_by_ids(ids, &ctx).await.map_err(Into::<napi::Error>::into)?,
+                ),
+                false => serde_json::to_value(
+                    <user::Model as Repository<UserLite>>::pack_by_ids(ids, &ctx).await.map_err(Into::<napi::Error>::into)?,
+                ),
+            };
+            packed.map_err(|e| napi::Error::from_reason(e.to_string()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod unit_test {
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    use crate::model::{
+        entity::sea_orm_active_enums::UserProfileFfvisibilityEnum, schema::UserFfVisibility,
+    };
+
+    use super::{DETAILED_VALIDATOR, VALIDATOR};
+
+    #[test]
+    fn ff_visibility_from_active_enum() {
+        let visibility =
+            UserFfVisibility::try_from(UserProfileFfvisibilityEnum::Followers).unwrap();
+        assert_eq!(visibility, UserFfVisibility::Followers);
+    }
+
+    #[test]
+    fn user_lite_valid() {
+        let instance = json!({
+            "id": "9fil64s6g7cskdrb",
+            "name": "Alice",
+            "username": "alice",
+            "host": "example.com",
+            // "isAdmin", "isModerator", "isBot", "isLocked", "isCat", and
+            // "speakAsCat" are false if omitted
+            "isCat": true,
+            "avatarUrl": "https://example.com/files/alice.png",
+            "instance": {
+                "name": "Example",
+                "softwareName": "firefish",
+                "softwareVersion": null,
+                "iconUrl": null,
+                "faviconUrl": null,
+                "themeColor": "#31748f",
+            },
+            "emojis": [{
+                "name": "blobcat",
+                "url": "https://example.com/emoji/blobcat.png",
+                "width": null,
+                "height": null,
+            }],
+            "onlineStatus": "active",
+        });
+
+        assert!(VALIDATOR.is_valid(&instance));
+    }
+
+    #[test]
+    fn user_detailed_valid() {
+        let instance = json!({
+            "id": "9fil64s6g7cskdrb",
+            "name": null,
+            "username": "alice",
+            "host": null,
+            "avatarUrl": null,
+            "onlineStatus": "unknown",
+            "url": null,
+            "uri": null,
+            "movedToUri": null,
+            "alsoKnownAs": null,
+            "createdAt": "2023-05-24T06:56:14.323Z",
+            "updatedAt": null,
+            "lastFetchedAt": null,
+            "bannerUrl": null,
+            "description": "Hello",
+            "location": null,
+            "birthday": "2000-01-01",
+            "lang": "en",
+            "fields": [{ "name": "Website", "value": "https://example.com" }],
+            "followersCount": 0,
+            "followingCount": 1,
+            "notesCount": 2,
+            "pinnedNoteIds": [],
+            "pinnedNotes": [],
+            "ffVisibility": "followers",
+            // relations are omitted if the viewer is the user
+        });
+
+        assert!(DETAILED_VALIDATOR.is_valid(&instance));
+    }
+
+    #[test]
+    fn user_detailed_invalid() {
+        let instance = json!({
+            "id": "9fil64s6g7cskdrb",
+            "username": "alice",
+            // "onlineStatus" must be one of "unknown", "online", "active",
+            // and "offline"
+            "onlineStatus": "away",
+            "createdAt": "2023-05-24T06:56:14.323Z",
+            // "fields" must have "name" and "value"
+            "fields": [{ "name": "Website" }],
+            "followersCount": 0,
+            "followingCount": 1,
+            "notesCount": 2,
+            "pinnedNoteIds": [],
+            "pinnedNotes": [],
+            // "ffVisibility" must be one of "public", "followers", and
+            // "private"
+            "ffVisibility": "mutual",
+            // "isFollowing" is boolean
+            "isFollowing": 1,
+        });
+
+        let result = DETAILED_VALIDATOR
+            .validate(&instance)
+            .expect_err("validation must fail");
+        let mut paths: Vec<String> = result
+            .map(|e| e.instance_path.to_string())
+            .filter(|e| !e.is_empty())
+            .collect();
+        paths.sort();
+        assert_eq!(
+            paths,
+            vec![
+                "/ffVisibility",
+                "/fields/0",
+                "/isFollowing",
+                "/onlineStatus"
+            ]
+        );
+    }
+
+    #[test]
+    fn user_lite_invalid() {
+        let instance = json!({
+            "id": "9fil64s6g7cskdrb",
+            // "username" is required
+            // "host" is a nullable string
+            "host": false,
+            // "isBot" is boolean
+            "isBot": "yes",
+        });
+
+        let result = VALIDATOR
+            .validate(&instance)
+            .expect_err("validation must fail");
+        let mut paths: Vec<String> = result
+            .map(|e| e.instance_path.to_string())
+            .filter(|e| !e.is_empty())
+            .collect();
+        paths.sort();
+        assert_eq!(paths, vec!["/host", "/isBot"]);
+    }
+}
@@ -0,0 +1,25 @@
+use crate::impl_into_napi_error;
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum Error {
+    #[error("Unsupported NodeInfo version: {0}")]
+    UnsupportedVersion(String),
+    #[error("NodeInfo does not conform to the schema: {0}")]
+    InvalidDocument(String),
+    #[error("Instance metadata not found")]
+    MetaNotFound,
+    #[error("Cache error: {0}")]
+    CacheError(#[from] crate::cache::error::Error),
+    #[error("Failed to get database connection: {0}")]
+    DbConnError(#[from] crate::database::error::Error),
+    #[error("Database operation error: {0}")]
+    DbOperationError(#[from] sea_orm::DbErr),
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(value: serde_json::Error) -> Self {
+        Self::InvalidDocument(value.to_string())
+    }
+}
+
+impl_into_napi_error!(Error);
@@ -0,0 +1,526 @@
+//! NodeInfo 2.0 and 2.1, ported from `server/nodeinfo.ts`
+//!
+//! The documents are built from `meta` and usage statistics of local users,
+//! and cached for [CACHE_TTL] since counting users and notes is expensive.
+//! They are cached in this process if the shared cache is not initialized.
+
+pub mod error;
+
+use std::fmt;
+use std::str::FromStr;
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+
+use cfg_if::cfg_if;
+use chrono::Utc;
+use error::Error;
+use jsonschema::JSONSchema;
+use once_cell::sync::Lazy;
+use sea_orm::{ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter, QuerySelect};
+use serde::{Deserialize, Serialize};
+
+use crate::acct::webfinger::{Jrd, Link};
+use crate::cache::error::Error as CacheError;
+use crate::model::entity::newtype::from_string_vec;
+use crate::model::entity::{meta, note, user};
+use crate::model::meta::current_meta;
+use crate::{cache, database};
+
+/// Name of the server software, which must match `^[a-z0-9-]+$`.
+pub const SOFTWARE_NAME: &str = "rosekey";
+pub const SOFTWARE_HOMEPAGE: &str = "https://rosekey.sbs";
+pub const CACHE_TTL: Duration = Duration::from_secs(10 * 60);
+const CACHE_KEY: &str = "nodeinfo";
+const DEFAULT_THEME_COLOR: &str = "#31748f";
+const ACTIVE_MONTH_DAYS: i64 = 30;
+const ACTIVE_HALFYEAR_DAYS: i64 = 180;
+
+/// The document cached with the time it was cached, used when the shared
+/// cache is not initialized.
+static LOCAL_CACHE: Lazy<RwLock<Option<(Instant, NodeInfo)>>> = Lazy::new(Default::default);
+
+static SCHEMA_2_0: Lazy<JSONSchema> = Lazy::new(|| compile(include_str!("schema/2.0.json")));
+static SCHEMA_2_1: Lazy<JSONSchema> = Lazy::new(|| compile(include_str!("schema/2.1.json")));
+
+fn compile(schema: &str) -> JSONSchema {
+    let schema = serde_json::from_str(schema).expect("NodeInfo schema invalid");
+    JSONSchema::options()
+        .with_draft(jsonschema::Draft::Draft4)
+        .compile(&schema)
+        .expect("Unable to compile NodeInfo schema")
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Version {
+    V2_0,
+    V2_1,
+}
+
+impl Version {
+    /// Supported versions, the latest first as listed in
+    /// `/.well-known/nodeinfo`.
+    pub const ALL: [Version; 2] = [Version::V2_1, Version::V2_0];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Version::V2_0 => "2.0",
+            Version::V2_1 => "2.1",
+        }
+    }
+
+    /// Returns the `rel` of the link to the document.
+    pub fn schema_url(&self) -> String {
+        format!("http://nodeinfo.diaspora.software/ns/schema/{}", self)
+    }
+
+    fn schema(&self) -> &'static JSONSchema {
+        match self {
+            Version::V2_0 => &SCHEMA_2_0,
+            Version::V2_1 => &SCHEMA_2_1,
+        }
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Version {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Version::ALL
+            .into_iter()
+            .find(|v| v.as_str() == s)
+            .ok_or_else(|| Error::UnsupportedVersion(s.to_string()))
+    }
+}
+
+/// Values of the server not stored in `meta`.
+#[cfg_attr(feature = "napi", napi_derive::napi(object))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Server {
+    /// Origin of the server, i.e. `config.url`.
+    pub url: String,
+    /// Version of the server software, i.e. `config.version`.
+    pub version: String,
+    pub max_note_length: u32,
+    pub max_caption_length: u32,
+    /// Whether search filters are available, i.e. `config.meilisearch` is
+    /// set.
+    pub search_filters: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NodeInfo {
+    pub version: String,
+    pub software: Software,
+    pub protocols: Vec<String>,
+    pub services: Services,
+    pub open_registrations: bool,
+    pub usage: Usage,
+    pub metadata: Metadata,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Software {
+    pub name: String,
+    pub version: String,
+    /// Only in 2.1.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repository: Option<String>,
+    /// Only in 2.1.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub homepage: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Services {
+    pub inbound: Vec<String>,
+    pub outbound: Vec<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Usage {
+    pub users: Users,
+    pub local_posts: u64,
+    pub local_comments: u64,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Users {
+    pub total: u64,
+    pub active_halfyear: u64,
+    pub active_month: u64,
+}
+
+/// Software specific values, as `server/nodeinfo.ts` puts them.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Metadata {
+    pub node_name: Option<String>,
+    pub node_description: Option<String>,
+    pub maintainer: Maintainer,
+    pub langs: Vec<String>,
+    pub tos_url: Option<String>,
+    pub repository_url: String,
+    pub feedback_url: Option<String>,
+    pub disable_registration: bool,
+    pub disable_local_timeline: bool,
+    pub disable_recommended_timeline: bool,
+    pub disable_global_timeline: bool,
+    pub email_required_for_signup: bool,
+    pub search_filters: bool,
+    pub post_editing: bool,
+    pub post_imports: bool,
+    pub enable_hcaptcha: bool,
+    pub enable_recaptcha: bool,
+    pub max_note_text_length: u32,
+    pub max_caption_text_length: u32,
+    pub enable_email: bool,
+    pub enable_service_worker: bool,
+    pub proxy_account_name: Option<String>,
+    pub theme_color: String,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Maintainer {
+    pub name: Option<String>,
+    pub email: Option<String>,
+}
+
+impl NodeInfo {
+    /// Converts the document to `version`, dropping the fields the version
+    /// does not have.
+    pub fn to_version(mut self, version: Version) -> Self {
+        if version == Version::V2_0 {
+            self.software.repository = None;
+            self.software.homepage = None;
+        }
+        self.version = version.to_string();
+        self
+    }
+
+    /// Validates the document against the official schema of its version.
+    pub fn validate(&self) -> Result<(), Error> {
+        let version: Version = self.version.parse()?;
+        let value = serde_json::to_value(self)?;
+        version.schema().validate(&value).map_err(|errors| {
+            Error::InvalidDocument(
+                errors
+                    .map(|e| format!("{} at {}", e, e.instance_path))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            )
+        })
+    }
+}
+
+/// Renders the response of `GET /.well-known/nodeinfo`.
+pub fn links(url: &str) -> Jrd {
+    Jrd {
+        links: Version::ALL
+            .into_iter()
+            .map(|v| Link {
+                rel: Some(v.schema_url()),
+                href: Some(format!("{}/nodeinfo/{}", url, v)),
+                ..Default::default()
+            })
+            .collect(),
+        ..Default::default()
+    }
+}
+
+/// Renders the NodeInfo 2.1 document.
+pub fn render(
+    server: &Server,
+    meta: &meta::Model,
+    proxy_account_name: Option<String>,
+    usage: Usage,
+) -> NodeInfo {
+    NodeInfo {
+        version: Version::V2_1.to_string(),
+        software: Software {
+            name: SOFTWARE_NAME.to_string(),
+            version: server.version.to_owned(),
+            repository: Some(meta.repository_url.to_owned()).filter(|url| !url.is_empty()),
+            homepage: Some(SOFTWARE_HOMEPAGE.to_string()),
+        },
+        protocols: vec!["activitypub".to_string()],
+        services: Services {
+            inbound: Vec::new(),
+            outbound: vec!["atom1.0".to_string(), "rss2.0".to_string()],
+        },
+        open_registrations: !meta.disable_registration,
+        usage,
+        metadata: Metadata {
+            node_name: meta.name.to_owned(),
+            node_description: meta.description.to_owned(),
+            maintainer: Maintainer {
+                name: meta.maintainer_name.to_owned(),
+                email: meta.maintainer_email.to_owned(),
+            },
+            langs: from_string_vec(meta.langs.to_owned()),
+            tos_url: meta.to_s_url.to_owned(),
+            repository_url: meta.repository_url.to_owned(),
+            feedback_url: meta.feedback_url.to_owned(),
+            disable_registration: meta.disable_registration,
+            disable_local_timeline: meta.disable_local_timeline,
+            disable_recommended_timeline: meta.disable_recommended_timeline,
+            disable_global_timeline: meta.disable_global_timeline,
+            email_required_for_signup: meta.email_required_for_signup,
+            search_filters: server.search_filters,
+            post_editing: true,
+            post_imports: meta.experimental_features["postImports"]
+                .as_bool()
+                .unwrap_or_default(),
+            enable_hcaptcha: meta.enable_hcaptcha,
+            enable_recaptcha: meta.enable_recaptcha,
+            max_note_text_length: server.max_note_length,
+            max_caption_text_length: server.max_caption_length,
+            enable_email: meta.enable_email,
+            enable_service_worker: meta.enable_service_worker,
+            proxy_account_name,
+            theme_color: meta
+                .theme_color
+                .to_owned()
+                .unwrap_or_else(|| DEFAULT_THEME_COLOR.to_string()),
+        },
+    }
+}
+
+/// Counts local users and notes. Users are active if `lastActiveDate` is
+/// within the last 30 or 180 days.
+pub async fn usage() -> Result<Usage, Error> {
+    let db = database::get_database()?;
+    let local_users = || user::Entity::find().filter(user::Column::Host.is_null());
+    let active_since = |days: i64| {
+        local_users().filter(
+            user::Column::LastActiveDate
+                .gt((Utc::now() - chrono::Duration::days(days)).fixed_offset()),
+        )
+    };
+
+    Ok(Usage {
+        users: Users {
+            total: local_users().count(db).await?,
+            active_halfyear: active_since(ACTIVE_HALFYEAR_DAYS).count(db).await?,
+            active_month: active_since(ACTIVE_MONTH_DAYS).count(db).await?,
+        },
+        local_posts: note::Entity::find()
+            .filter(note::Column::UserHost.is_null())
+            .count(db)
+            .await?,
+        local_comments: 0,
+    })
+}
+
+/// Returns the NodeInfo document of `version`. The document is built and
+/// validated against the schemas of all versions on a cache miss, so
+/// cached documents are always valid.
+pub async fn nodeinfo(server: &Server, version: Version) -> Result<NodeInfo, Error> {
+    if let Some(info) = get_cached().await? {
+        return Ok(info.to_version(version));
+    }
+
+    let db = database::get_database()?;
+    let meta = current_meta(db).await?.ok_or(Error::MetaNotFound)?;
+    let proxy_account_name = match &meta.proxy_account_id {
+        None => None,
+        Some(id) => {
+            user::Entity::find_by_id(id.to_owned())
+                .select_only()
+                .column(user::Column::Username)
+                .into_tuple::<String>()
+                .one(db)
+                .await?
+        }
+    };
+    let info = render(server, &meta, proxy_account_name, usage().await?);
+    for v in Version::ALL {
+        info.clone().to_version(v).validate()?;
+    }
+
+    set_cached(&info).await?;
+    Ok(info.to_version(version))
+}
+
+async fn get_cached() -> Result<Option<NodeInfo>, Error> {
+    match cache::get_cache() {
+        Ok(cache) => Ok(cache.get_json(CACHE_KEY).await?),
+        Err(CacheError::Uninitialized) => Ok(LOCAL_CACHE
+            .read()
+            .unwrap()
+            .as_ref()
+            .filter(|(cached_at, _)| cached_at.elapsed() < CACHE_TTL)
+            .map(|(_, info)| info.to_owned())),
+        Err(e) => Err(e.into()),
+    }
+}
+
+async fn set_cached(info: &NodeInfo) -> Result<(), Error> {
+    match cache::get_cache() {
+        Ok(cache) => Ok(cache.set_json(CACHE_KEY, info, Some(CACHE_TTL)).await?),
+        Err(CacheError::Uninitialized) => {
+            *LOCAL_CACHE.write().unwrap() = Some((Instant::now(), info.to_owned()));
+            Ok(())
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+cfg_if! {
+    if #[cfg(feature = "napi")] {
+        use napi_derive::napi;
+
+        /// Returns the JRD for `GET /.well-known/nodeinfo`.
+        #[napi]
+        pub fn native_nodeinfo_links(url: String) -> napi::Result<serde_json::Value> {
+            serde_json::to_value(links(&url)).map_err(|e| napi::Error::from_reason(e.to_string()))
+        }
+
+        /// Returns the document for `GET /nodeinfo/:version`. Fails with
+        /// `UnsupportedVersion` for 404.
+        #[napi]
+        pub async fn native_nodeinfo(server: Server, version: String) -> napi::Result<serde_json::Value> {
+            let version: Version = version.parse().map_err(Into::<napi::Error>::into)?;
+            let info = nodeinfo(&server, version).await.map_err(Into::<napi::Error>::into)?;
+            serde_json::to_value(info).map_err(|e| napi::Error::from_reason(e.to_string()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod unit_test {
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    use super::error::Error;
+    use super::{links, render, Server, Usage, Users, Version};
+    use crate::model::entity::meta;
+    use crate::model::entity::newtype::string_vec;
+
+    fn server() -> Server {
+        Server {
+            url: "https://example.com".to_string(),
+            version: "1.0.4-beta".to_string(),
+            max_note_length: 3000,
+            max_caption_length: 1500,
+            search_filters: false,
+        }
+    }
+
+    fn meta() -> meta::Model {
+        meta::Model {
+            id: "x".to_string(),
+            name: Some("Example".to_string()),
+            maintainer_name: Some("Alice".to_string()),
+            langs: string_vec(vec!["en".to_string(), "ja".to_string()]),
+            repository_url: "https://git.joinfirefish.org/firefish/firefish".to_string(),
+            disable_registration: true,
+            experimental_features: json!({ "postImports": true }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn versions() {
+        assert_eq!("2.0".parse::<Version>(), Ok(Version::V2_0));
+        assert_eq!("2.1".parse::<Version>(), Ok(Version::V2_1));
+        assert_eq!(
+            "1.0".parse::<Version>(),
+            Err(Error::UnsupportedVersion("1.0".to_string()))
+        );
+        assert_eq!(
+            serde_json::to_value(links("https://example.com")).unwrap(),
+            json!({
+                "links": [
+                    {
+                        "rel": "http://nodeinfo.diaspora.software/ns/schema/2.1",
+                        "href": "https://example.com/nodeinfo/2.1",
+                    },
+                    {
+                        "rel": "http://nodeinfo.diaspora.software/ns/schema/2.0",
+                        "href": "https://example.com/nodeinfo/2.0",
+                    },
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn documents() {
+        let usage = Usage {
+            users: Users {
+                total: 3,
+                active_halfyear: 2,
+                active_month: 1,
+            },
+            local_posts: 10,
+            local_comments: 0,
+        };
+        let info = render(&server(), &meta(), Some("proxy".to_string()), usage);
+        info.validate().unwrap();
+
+        let value = serde_json::to_value(&info).unwrap();
+        assert_eq!(
+            value["software"],
+            json!({
+                "name": "rosekey",
+                "version": "1.0.4-beta",
+                "repository": "https://git.joinfirefish.org/firefish/firefish",
+                "homepage": "https://rosekey.sbs",
+            })
+        );
+        assert_eq!(value["openRegistrations"], json!(false));
+        assert_eq!(
+            value["usage"],
+            json!({
+                "users": { "total": 3, "activeHalfyear": 2, "activeMonth": 1 },
+                "localPosts": 10,
+                "localComments": 0,
+            })
+        );
+        assert_eq!(value["metadata"]["nodeName"], json!("Example"));
+        assert_eq!(
+            value["metadata"]["maintainer"],
+            json!({ "name": "Alice", "email": null })
+        );
+        assert_eq!(value["metadata"]["langs"], json!(["en", "ja"]));
+        assert_eq!(value["metadata"]["proxyAccountName"], json!("proxy"));
+        assert_eq!(value["metadata"]["themeColor"], json!("#31748f"));
+        assert_eq!(value["metadata"]["searchFilters"], json!(false));
+        assert_eq!(value["metadata"]["postEditing"], json!(true));
+        assert_eq!(value["metadata"]["postImports"], json!(true));
+        assert_eq!(value["metadata"]["maxNoteTextLength"], json!(3000));
+        assert_eq!(value["metadata"]["maxCaptionTextLength"], json!(1500));
+
+        let old = info.to_version(Version::V2_0);
+        old.validate().unwrap();
+        let value = serde_json::to_value(&old).unwrap();
+        assert_eq!(value["version"], json!("2.0"));
+        assert_eq!(value["software"].get("repository"), None);
+        assert_eq!(value["software"].get("homepage"), None);
+    }
+
+    #[test]
+    fn invalid_documents() {
+        let mut info = render(&server(), &meta(), None, Usage::default());
+        info.software.name = "Firefish".to_string();
+        assert!(matches!(info.validate(), Err(Error::InvalidDocument(_))));
+
+        let mut info = render(&server(), &meta(), None, Usage::default());
+        info.protocols.clear();
+        assert!(matches!(info.validate(), Err(Error::InvalidDocument(_))));
+
+        // 2.0 has no `repository`.
+        let mut info = render(&server(), &meta(), None, Usage::default());
+        info.version = "2.0".to_string();
+        assert!(matches!(info.validate(), Err(Error::InvalidDocument(_))));
+    }
+}
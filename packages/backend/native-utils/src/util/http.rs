@@ -0,0 +1,133 @@
+//! HTTP client for federation
+//!
+//! Modules that talk to remote servers take a `&dyn HttpClient`, so that
+//! tests can answer requests with a stand-in server instead of the network.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use async_trait::async_trait;
+
+use crate::impl_into_napi_error;
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum Error {
+    #[error("Invalid request: {0}")]
+    InvalidRequest(String),
+    #[error("Request failed: {0}")]
+    RequestFailed(String),
+    #[error("Request timed out")]
+    Timeout,
+}
+
+impl_into_napi_error!(Error);
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Method {
+    #[default]
+    Get,
+    Post,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Request {
+    pub method: Method,
+    pub url: String,
+    /// Headers keyed by lower-case names.
+    pub headers: HashMap<String, String>,
+    pub body: Option<String>,
+}
+
+impl Request {
+    /// Returns a GET request with `Accept`.
+    pub fn get(url: &str, accept: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            headers: HashMap::from([("accept".to_string(), accept.to_string())]),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Response {
+    pub status: u16,
+    /// Headers keyed by lower-case names.
+    pub headers: HashMap<String, String>,
+    pub body: String,
+}
+
+impl Response {
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+
+    /// Parses the body as JSON.
+    pub fn json<T: serde::de::DeserializeOwned>(&self) -> Result<T, serde_json::Error> {
+        serde_json::from_str(&self.body)
+    }
+}
+
+#[async_trait]
+pub trait HttpClient: Send + Sync {
+    /// Sends the request. Responses with error statuses are not errors.
+    async fn send(&self, request: Request) -> Result<Response, Error>;
+}
+
+/// [HttpClient] over the network.
+#[derive(Clone, Debug)]
+pub struct ReqwestClient {
+    client: reqwest::Client,
+}
+
+impl ReqwestClient {
+    /// `user_agent` is sent with every request, like `config.userAgent`.
+    pub fn new(user_agent: &str, timeout: Duration) -> Result<Self, Error> {
+        reqwest::Client::builder()
+            .user_agent(user_agent)
+            .timeout(timeout)
+            .build()
+            .map(|client| Self { client })
+            .map_err(|e| Error::InvalidRequest(e.to_string()))
+    }
+}
+
+#[async_trait]
+impl HttpClient for ReqwestClient {
+    async fn send(&self, request: Request) -> Result<Response, Error> {
+        let mut builder = match request.method {
+            Method::Get => self.client.get(&request.url),
+            Method::Post => self.client.post(&request.url),
+        };
+        for (name, value) in &request.headers {
+            builder = builder.header(name, value);
+        }
+        if let Some(body) = request.body {
+            builder = builder.body(body);
+        }
+
+        let error = |e: reqwest::Error| match e.is_timeout() {
+            true => Error::Timeout,
+            false => Error::RequestFailed(e.to_string()),
+        };
+        let response = builder.send().await.map_err(error)?;
+        let status = response.status().as_u16();
+        let headers = response
+            .headers()
+            .iter()
+            .filter_map(|(name, value)| {
+                value
+                    .to_str()
+                    .ok()
+                    .map(|v| (name.as_str().to_string(), v.to_string()))
+            })
+            .collect();
+        let body = response.text().await.map_err(error)?;
+
+        Ok(Response {
+            status,
+            headers,
+            body,
+        })
+    }
+}
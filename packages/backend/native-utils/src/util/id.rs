@@ -1,64 +1,350 @@
-//! ID generation utility based on [cuid2]
+//! ID generation utility
+//!
+//! New ids are generated by the [IdGenerator] chosen by [init_id_generator],
+//! which is [Cuid2] by default. Ids imported from Misskey may be in any of
+//! the other formats, so [get_timestamp] detects the format by itself.
 
 use basen::BASE36;
 use cfg_if::cfg_if;
 use chrono::Utc;
 use once_cell::sync::OnceCell;
+use rand::{thread_rng, Rng};
 use std::cmp;
+use std::sync::atomic::{AtomicU32, Ordering};
 
+use crate::config::IdMethod;
 use crate::impl_into_napi_error;
 
 #[derive(thiserror::Error, Debug, PartialEq, Eq)]
-#[error("ID generator has not been initialized yet")]
-pub struct ErrorUninitialized;
+pub enum Error {
+    #[error("ID generator has not been initialized yet")]
+    Uninitialized,
+    #[error("ID generator has already been initialized with different settings")]
+    AlreadyInitialized,
+    #[error("Invalid ID length: {0} (must be from 16 to 24)")]
+    InvalidLength(u16),
+}
+
+impl_into_napi_error!(Error);
 
-impl_into_napi_error!(ErrorUninitialized);
+/// Settings the global generator was initialized with.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Settings {
+    method: IdMethod,
+    /// `0` unless the method is [IdMethod::Cuid2].
+    length: u16,
+    fingerprint: String,
+}
 
 static FINGERPRINT: OnceCell<String> = OnceCell::new();
-static GENERATOR: OnceCell<cuid2::CuidConstructor> = OnceCell::new();
+static GENERATOR: OnceCell<(Settings, Box<dyn IdGenerator>)> = OnceCell::new();
 
 const TIME_2000: i64 = 946_684_800_000;
 const TIMESTAMP_LENGTH: u16 = 8;
+/// Added to the timestamps of meids so that they start with `8` to `f`.
+const MEID_OFFSET: i64 = 0x8000_0000_0000;
+const CROCKFORD_BASE32: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+/// Generates ids in one of the formats and extracts timestamps from them.
+pub trait IdGenerator: Send + Sync {
+    /// Returns a new id for the UNIX time `date_num` in milliseconds.
+    fn create(&self, date_num: i64) -> String;
+    /// Returns the UNIX time in milliseconds encoded in the id, or `None` if
+    /// the id is not in this format.
+    fn get_timestamp(&self, id: &str) -> Option<i64>;
+}
+
+/// Returns `n` in base 36, padded or truncated to the last `width` digits.
+fn base36(n: u64, width: usize) -> String {
+    let s = format!("{:0>width$}", BASE36.encode_var_len(&n), width = width);
+    s[s.len() - width..].to_string()
+}
+
+fn random_hex(length: usize) -> String {
+    let mut rng = thread_rng();
+    (0..length)
+        .map(|_| char::from_digit(rng.gen_range(0..16), 16).unwrap())
+        .collect()
+}
+
+fn is_base36(id: &str) -> bool {
+    id.bytes()
+        .all(|b| b.is_ascii_digit() || b.is_ascii_lowercase())
+}
+
+fn is_hex(id: &str) -> bool {
+    id.bytes()
+        .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+}
+
+/// Encodes milliseconds since 2000-01-01 in 8 base 36 digits, which is
+/// shared by [Aid], [Aidx], and [Cuid2].
+fn encode_time_2000(date_num: i64) -> String {
+    base36(cmp::max(date_num - TIME_2000, 0) as u64, 8)
+}
+
+fn decode_time_2000(id: &str) -> Option<i64> {
+    let time = id.get(0..8).filter(|t| is_base36(t))?;
+    let n: Option<u64> = BASE36.decode_var_len(time);
+    n.map(|n| n as i64 + TIME_2000)
+}
+
+/// `[8 chars timestamp] + [2 chars counter]` of Misskey.
+pub struct Aid {
+    counter: AtomicU32,
+}
+
+impl Default for Aid {
+    fn default() -> Self {
+        Self {
+            counter: AtomicU32::new(thread_rng().gen()),
+        }
+    }
+}
+
+impl IdGenerator for Aid {
+    fn create(&self, date_num: i64) -> String {
+        let counter = self.counter.fetch_add(1, Ordering::Relaxed);
+        format!(
+            "{}{}",
+            encode_time_2000(date_num),
+            base36(counter.into(), 2)
+        )
+    }
+
+    fn get_timestamp(&self, id: &str) -> Option<i64> {
+        match id.len() {
+            10 => decode_time_2000(id),
+            _ => None,
+        }
+    }
+}
+
+/// `[8 chars timestamp] + [4 chars node key] + [4 chars counter]` of
+/// Misskey. The node key is random for each generator.
+pub struct Aidx {
+    key: String,
+    counter: AtomicU32,
+}
+
+impl Default for Aidx {
+    fn default() -> Self {
+        let mut rng = thread_rng();
+        Self {
+            key: base36(rng.gen_range(0..36u64.pow(4)), 4),
+            counter: AtomicU32::new(rng.gen()),
+        }
+    }
+}
+
+impl IdGenerator for Aidx {
+    fn create(&self, date_num: i64) -> String {
+        let counter = self.counter.fetch_add(1, Ordering::Relaxed);
+        format!(
+            "{}{}{}",
+            encode_time_2000(date_num),
+            self.key,
+            base36(counter.into(), 4)
+        )
+    }
+
+    fn get_timestamp(&self, id: &str) -> Option<i64> {
+        match id.len() {
+            16 => decode_time_2000(id),
+            _ => None,
+        }
+    }
+}
+
+/// `[12 hex timestamp with the highest bit set] + [12 hex random]` of
+/// Misskey.
+#[derive(Default)]
+pub struct Meid;
+
+impl IdGenerator for Meid {
+    fn create(&self, date_num: i64) -> String {
+        let time = match cmp::max(date_num, 0) {
+            0 => "0".repeat(12),
+            time => format!("{:012x}", time + MEID_OFFSET),
+        };
+        format!("{}{}", time, random_hex(12))
+    }
+
+    fn get_timestamp(&self, id: &str) -> Option<i64> {
+        if id.len() != 24 || !is_hex(id) {
+            return None;
+        }
+        let time = i64::from_str_radix(&id[0..12], 16).ok()?;
+        Some(cmp::max(time - MEID_OFFSET, 0))
+    }
+}
 
-/// Initializes Cuid2 generator. Must be called before any [create_id].
-pub fn init_id<'a>(length: u16, fingerprint: &'a str) {
-    FINGERPRINT.get_or_init(move || format!("{}{}", fingerprint, cuid2::create_id()));
-    GENERATOR.get_or_init(move || {
-        cuid2::CuidConstructor::new()
-            // length to pass shoule be greater than or equal to 8.
-            .with_length(cmp::max(length - TIMESTAMP_LENGTH, 8))
-            .with_fingerprinter(|| FINGERPRINT.get().unwrap().clone())
-    });
-}
-
-/// Returns Cuid2 with the length specified by [init_id]. Must be called after
-/// [init_id], otherwise returns [ErrorUninitialized].
-/// The current timestamp via [chrono::Utc] is used if `date_num` is `0`.
-pub fn create_id(date_num: i64) -> Result<String, ErrorUninitialized> {
+/// `[8 hex timestamp in seconds] + [16 hex random]`, i.e. MongoDB ObjectId
+/// of Misskey.
+#[derive(Default)]
+pub struct ObjectId;
+
+impl IdGenerator for ObjectId {
+    fn create(&self, date_num: i64) -> String {
+        format!("{:08x}{}", cmp::max(date_num, 0) / 1000, random_hex(16))
+    }
+
+    fn get_timestamp(&self, id: &str) -> Option<i64> {
+        if id.len() != 24 || !is_hex(id) {
+            return None;
+        }
+        Some(i64::from_str_radix(&id[0..8], 16).ok()? * 1000)
+    }
+}
+
+/// `[10 chars timestamp] + [16 chars random]` in Crockford's Base32.
+#[derive(Default)]
+pub struct Ulid;
+
+impl IdGenerator for Ulid {
+    fn create(&self, date_num: i64) -> String {
+        let time = cmp::max(date_num, 0) as u64;
+        let mut rng = thread_rng();
+        let time_chars = (0..10).rev().map(|i| (time >> (i * 5)) as usize & 31);
+        let random_chars = (0..16).map(|_| rng.gen_range(0..32));
+        time_chars
+            .chain(random_chars)
+            .map(|i| char::from(CROCKFORD_BASE32[i]))
+            .collect()
+    }
+
+    fn get_timestamp(&self, id: &str) -> Option<i64> {
+        if id.len() != 26 {
+            return None;
+        }
+        id[0..10].bytes().try_fold(0i64, |time, b| {
+            let digit = CROCKFORD_BASE32
+                .iter()
+                .position(|&c| c == b.to_ascii_uppercase())?;
+            Some(time << 5 | digit as i64)
+        })
+    }
+}
+
+/// `[8 chars timestamp] + [cuid2]` of Firefish.
+pub struct Cuid2 {
+    length: u16,
+    constructor: cuid2::CuidConstructor,
+}
+
+impl Cuid2 {
+    /// `length` includes the timestamp and must be from 16 to 24. As the
+    /// fingerprint is process-wide, only the first one is used.
+    pub fn new(length: u16, fingerprint: &str) -> Result<Self, Error> {
+        if !(16..=24).contains(&length) {
+            return Err(Error::InvalidLength(length));
+        }
+        FINGERPRINT.get_or_init(|| format!("{}{}", fingerprint, cuid2::create_id()));
+        Ok(Self {
+            length,
+            constructor: cuid2::CuidConstructor::new()
+                .with_length(length - TIMESTAMP_LENGTH)
+                .with_fingerprinter(|| FINGERPRINT.get().unwrap().clone()),
+        })
+    }
+}
+
+impl IdGenerator for Cuid2 {
+    fn create(&self, date_num: i64) -> String {
+        format!(
+            "{}{}",
+            encode_time_2000(date_num),
+            self.constructor.create_id()
+        )
+    }
+
+    fn get_timestamp(&self, id: &str) -> Option<i64> {
+        match id.len() == usize::from(self.length) {
+            true => decode_time_2000(id),
+            false => None,
+        }
+    }
+}
+
+/// Initializes the global generator. Must be called before any
+/// [create_id]. `length` and `fingerprint` are only used by
+/// [IdMethod::Cuid2]. Calling this again with the same settings does
+/// nothing, and with different ones returns [Error::AlreadyInitialized].
+pub fn init_id_generator(method: IdMethod, length: u16, fingerprint: &str) -> Result<(), Error> {
+    let settings = match method {
+        IdMethod::Cuid2 => Settings {
+            method,
+            length,
+            fingerprint: fingerprint.to_string(),
+        },
+        _ => Settings {
+            method,
+            length: 0,
+            fingerprint: String::new(),
+        },
+    };
+    if let Some((initialized, _)) = GENERATOR.get() {
+        return match *initialized == settings {
+            true => Ok(()),
+            false => Err(Error::AlreadyInitialized),
+        };
+    }
+
+    let generator: Box<dyn IdGenerator> = match method {
+        IdMethod::Aid => Box::<Aid>::default(),
+        IdMethod::Aidx => Box::<Aidx>::default(),
+        IdMethod::Meid => Box::new(Meid),
+        IdMethod::ObjectId => Box::new(ObjectId),
+        IdMethod::Ulid => Box::new(Ulid),
+        IdMethod::Cuid2 => Box::new(Cuid2::new(length, fingerprint)?),
+    };
+    let (initialized, _) = GENERATOR.get_or_init(|| (settings.to_owned(), generator));
+    match *initialized == settings {
+        true => Ok(()),
+        false => Err(Error::AlreadyInitialized),
+    }
+}
+
+/// Initializes the global generator with [Cuid2]. See [init_id_generator].
+pub fn init_id(length: u16, fingerprint: &str) -> Result<(), Error> {
+    init_id_generator(IdMethod::Cuid2, length, fingerprint)
+}
+
+/// Returns a new id in the format specified by [init_id_generator]. Must be
+/// called after [init_id_generator], otherwise returns
+/// [Error::Uninitialized]. The current timestamp via [chrono::Utc] is used
+/// if `date_num` is `0`.
+pub fn create_id(date_num: i64) -> Result<String, Error> {
     match GENERATOR.get() {
-        None => Err(ErrorUninitialized),
-        Some(gen) => {
+        None => Err(Error::Uninitialized),
+        Some((_, gen)) => {
             let date_num = if date_num > 0 {
                 date_num
             } else {
                 Utc::now().timestamp_millis()
             };
-            let time = cmp::max(date_num - TIME_2000, 0);
-            Ok(format!(
-                "{:0>8}{}",
-                BASE36.encode_var_len(&(time as u64)),
-                gen.create_id()
-            ))
+            Ok(gen.create(date_num))
         }
     }
 }
 
+/// Returns the UNIX time in milliseconds encoded in the id of any format,
+/// or `-1` if the format is unknown.
+///
+/// 24 hex digits are a meid if they start with `8` to `f` and an ObjectId
+/// otherwise. Other ids of 10 to 24 base 36 digits are aid, aidx, or cuid2,
+/// which share the timestamp format.
 pub fn get_timestamp(id: &str) -> i64 {
-    let n: Option<u64> = BASE36.decode_var_len(&id[0..8]);
-    match n {
-        None => -1,
-        Some(n) => n as i64 + TIME_2000,
-    }
+    let timestamp = match id.len() {
+        26 => Ulid.get_timestamp(id),
+        24 if is_hex(id) => match id.as_bytes()[0] >= b'8' {
+            true => Meid.get_timestamp(id),
+            false => ObjectId.get_timestamp(id),
+        },
+        10..=24 if is_base36(id) => decode_time_2000(id),
+        _ => None,
+    };
+    timestamp.unwrap_or(-1)
 }
 
 cfg_if! {
@@ -67,14 +353,26 @@ cfg_if! {
 
         /// Calls [init_id] inside. Must be called before [native_create_id].
         #[napi]
-        pub fn native_init_id_generator(length: u16, fingerprint: String) {
-            init_id(length, &fingerprint);
+        pub fn native_init_id_generator(length: u16, fingerprint: String) -> napi::Result<()> {
+            init_id(length, &fingerprint).map_err(Into::into)
+        }
+
+        /// Calls [init_id_generator] inside. `method` is one of `aid`,
+        /// `aidx`, `meid`, `objectid`, `ulid`, and `cuid2`.
+        #[napi]
+        pub fn native_init_id_generator_with_method(method: String, length: u16, fingerprint: String) -> napi::Result<()> {
+            let method: IdMethod = serde_json::from_value(serde_json::Value::String(method))
+                .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+            init_id_generator(method, length, &fingerprint).map_err(Into::into)
         }
 
-        /// Generates
+        /// Returns a new id for the UNIX time `date_num` in milliseconds, or
+        /// for now if it is `0`. Throws if the generator has not been
+        /// initialized by [native_init_id_generator] or
+        /// [native_init_id_generator_with_method].
         #[napi]
-        pub fn native_create_id(date_num: i64) -> String {
-            create_id(date_num).unwrap()
+        pub fn native_create_id(date_num: i64) -> napi::Result<String> {
+            create_id(date_num).map_err(Into::into)
         }
 
         #[napi]
@@ -86,15 +384,21 @@ cfg_if! {
 
 #[cfg(test)]
 mod unit_test {
+    use crate::config::IdMethod;
     use crate::util::id;
     use chrono::Utc;
     use pretty_assertions::{assert_eq, assert_ne};
     use std::thread;
 
+    use super::{Aid, Aidx, Cuid2, IdGenerator, Meid, ObjectId, Ulid};
+
     #[test]
     fn can_create_and_decode() {
-        assert_eq!(id::create_id(0), Err(id::ErrorUninitialized));
-        id::init_id(16, "");
+        // tests in other modules may initialize the generator first
+        if id::GENERATOR.get().is_none() {
+            assert_eq!(id::create_id(0), Err(id::Error::Uninitialized));
+        }
+        id::init_id(16, "").unwrap();
         assert_eq!(id::create_id(0).unwrap().len(), 16);
         assert_ne!(id::create_id(0).unwrap(), id::create_id(0).unwrap());
         let id1 = thread::spawn(|| id::create_id(0).unwrap());
@@ -105,5 +409,55 @@ mod unit_test {
         let test_id = id::create_id(now).unwrap();
         let timestamp = id::get_timestamp(&test_id);
         assert_eq!(now, timestamp);
+
+        // initializing again only succeeds with the same settings
+        assert_eq!(id::init_id(16, ""), Ok(()));
+        assert_eq!(id::init_id(20, ""), Err(id::Error::AlreadyInitialized));
+        assert_eq!(
+            id::init_id_generator(IdMethod::Aid, 16, ""),
+            Err(id::Error::AlreadyInitialized)
+        );
+    }
+
+    #[test]
+    fn generators() {
+        let now = 1_690_000_000_123;
+        let generators: Vec<(Box<dyn IdGenerator>, usize, i64)> = vec![
+            (Box::<Aid>::default(), 10, now),
+            (Box::<Aidx>::default(), 16, now),
+            (Box::new(Meid), 24, now),
+            (Box::new(ObjectId), 24, now - 123),
+            (Box::new(Ulid), 26, now),
+            (Box::new(Cuid2::new(24, "").unwrap()), 24, now),
+        ];
+
+        for (generator, length, timestamp) in generators {
+            let (id1, id2) = (generator.create(now), generator.create(now));
+            assert_eq!(id1.len(), length);
+            assert_ne!(id1, id2);
+            assert_eq!(generator.get_timestamp(&id1), Some(timestamp));
+            assert_eq!(id::get_timestamp(&id1), timestamp, "{}", id1);
+        }
+        assert!(Cuid2::new(25, "").is_err());
+    }
+
+    #[test]
+    fn legacy_timestamps() {
+        // ids of Misskey
+        assert_eq!(id::get_timestamp("9fil64s6g7"), 1_685_737_961_142);
+        assert_eq!(
+            id::get_timestamp("64b9d4800000000000000000"),
+            1_689_900_160_000
+        );
+        assert_eq!(
+            id::get_timestamp("81897b5e2e00000000000000"),
+            1_689_991_917_056
+        );
+        assert_eq!(
+            id::get_timestamp("01H5ZKX3PV0000000000000000"),
+            1_690_056_953_563
+        );
+        assert_eq!(id::get_timestamp("invalid"), -1);
+        assert_eq!(id::get_timestamp("0123456789ABCDEF"), -1);
     }
 }
@@ -1,2 +1,5 @@
+pub mod http;
 pub mod id;
+pub mod keypair;
 pub mod random;
+pub mod reaction;
@@ -0,0 +1,118 @@
+//! Utilities for note reactions, ported from `misc/reaction-lib.ts`
+
+use std::collections::HashMap;
+
+/// Reaction decoded by [decode_reaction].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DecodedReaction {
+    /// Unicode emoji, `:name@host:`, or `:name@.:` for local custom emojis.
+    pub reaction: String,
+    /// Name of the custom emoji.
+    pub name: Option<String>,
+    /// Host of the custom emoji. `None` if it is a local or unicode emoji.
+    pub host: Option<String>,
+}
+
+/// Returns `(name, host)` if `reaction` is a custom emoji like `:name:` or
+/// `:name@host:`.
+fn parse_custom_emoji(reaction: &str) -> Option<(&str, Option<&str>)> {
+    let inner = reaction.strip_prefix(':')?.strip_suffix(':')?;
+    let (name, host) = match inner.split_once('@') {
+        None => (inner, None),
+        Some((name, host)) => (name, Some(host)),
+    };
+
+    let is_name_char = |c: char| c.is_alphanumeric() || c == '_' || c == '+' || c == '-';
+    let is_host_char = |c: char| c.is_alphanumeric() || c == '_' || c == '.' || c == '-';
+    if name.is_empty() || !name.chars().all(is_name_char) {
+        return None;
+    }
+    match host {
+        Some(h) if h.is_empty() || !h.chars().all(is_host_char) => None,
+        _ => Some((name, host)),
+    }
+}
+
+/// Decodes a reaction stored in the database. Local custom emojis are
+/// normalized to `:name@.:`.
+pub fn decode_reaction(reaction: &str) -> DecodedReaction {
+    match parse_custom_emoji(reaction) {
+        Some((name, host)) => {
+            let host = host.filter(|h| *h != ".");
+            DecodedReaction {
+                reaction: format!(":{}@{}:", name, host.unwrap_or(".")),
+                name: Some(name.to_string()),
+                host: host.map(str::to_string),
+            }
+        }
+        None => DecodedReaction {
+            reaction: reaction.to_string(),
+            name: None,
+            host: None,
+        },
+    }
+}
+
+/// Converts `note.reactions` to reaction counts keyed by decoded reactions.
+/// Entries with non-positive counts are dropped.
+pub fn convert_reactions(reactions: &serde_json::Value) -> HashMap<String, i32> {
+    let mut result = HashMap::new();
+    if let Some(reactions) = reactions.as_object() {
+        for (reaction, count) in reactions {
+            let count = count.as_i64().unwrap_or_default();
+            if count <= 0 {
+                continue;
+            }
+            let decoded = decode_reaction(reaction).reaction;
+            *result.entry(decoded).or_default() += count as i32;
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod unit_test {
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    use super::{convert_reactions, decode_reaction, DecodedReaction};
+
+    #[test]
+    fn can_decode_reaction() {
+        assert_eq!(
+            decode_reaction(":blobcat@misskey.io:"),
+            DecodedReaction {
+                reaction: ":blobcat@misskey.io:".to_string(),
+                name: Some("blobcat".to_string()),
+                host: Some("misskey.io".to_string()),
+            }
+        );
+        assert_eq!(
+            decode_reaction(":blobcat:"),
+            DecodedReaction {
+                reaction: ":blobcat@.:".to_string(),
+                name: Some("blobcat".to_string()),
+                host: None,
+            }
+        );
+        assert_eq!(decode_reaction(":blobcat@.:").reaction, ":blobcat@.:");
+        assert_eq!(decode_reaction("👍").name, None);
+        assert_eq!(decode_reaction(":not valid:").reaction, ":not valid:");
+    }
+
+    #[test]
+    fn can_convert_reactions() {
+        let reactions = json!({
+            ":blobcat:": 2,
+            ":blobcat@.:": 1,
+            "👍": 3,
+            "❤️": 0,
+        });
+        assert_eq!(
+            convert_reactions(&reactions),
+            HashMap::from([(":blobcat@.:".to_string(), 3), ("👍".to_string(), 3)])
+        );
+        assert_eq!(convert_reactions(&json!(null)), HashMap::new());
+    }
+}
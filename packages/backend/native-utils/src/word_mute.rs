@@ -0,0 +1,334 @@
+//! Word mutes, ported from `misc/check-word-mute.ts`
+//!
+//! `user_profile.muted_words` is a JSON array whose entries are either
+//! arrays of keywords, which match if all of them appear in the text, or
+//! strings of the form `/pattern/flags`, which are regular expressions.
+
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use cfg_if::cfg_if;
+use once_cell::sync::Lazy;
+use regex::{Regex, RegexBuilder};
+use sea_orm::EntityTrait;
+
+use crate::database;
+use crate::impl_into_napi_error;
+use crate::model::entity::{note, user_profile};
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum Error {
+    #[error("Muted words must be an array of keyword arrays and regular expressions")]
+    InvalidFormat,
+    #[error("Invalid regular expression: {0}")]
+    InvalidRegex(String),
+    #[error("Failed to load muted words: {0}")]
+    ModelError(#[from] crate::model::error::Error),
+}
+
+impl From<crate::database::error::Error> for Error {
+    fn from(value: crate::database::error::Error) -> Self {
+        Self::ModelError(value.into())
+    }
+}
+
+impl From<sea_orm::DbErr> for Error {
+    fn from(value: sea_orm::DbErr) -> Self {
+        Self::ModelError(value.into())
+    }
+}
+
+impl_into_napi_error!(Error);
+
+/// Compiled muted words are cached for this long, as TypeScript does.
+const CACHE_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// Compiled muted words keyed by user ids, with the time they were cached.
+type Cache = HashMap<String, (Instant, Arc<MutedWords>)>;
+
+static CACHE: Lazy<RwLock<Cache>> = Lazy::new(Default::default);
+
+#[derive(Clone, Debug)]
+enum Pattern {
+    /// Lowercased non-empty keywords, all of which must appear.
+    Keywords(Vec<String>),
+    Regex(Regex),
+}
+
+/// Compiled muted words of a user.
+#[derive(Clone, Debug, Default)]
+pub struct MutedWords {
+    patterns: Vec<Pattern>,
+}
+
+/// Compiles `/pattern/flags`. Flags `i`, `m`, and `s` have the same meaning
+/// as in JavaScript, and `g`, `u`, `y`, and `d` do not change whether the
+/// text matches.
+fn compile_regex(src: &str) -> Result<Regex, Error> {
+    let invalid = || Error::InvalidRegex(src.to_string());
+    let (pattern, flags) = src
+        .strip_prefix('/')
+        .and_then(|s| s.rsplit_once('/'))
+        .filter(|(pattern, _)| !pattern.is_empty())
+        .ok_or_else(invalid)?;
+
+    let mut builder = RegexBuilder::new(pattern);
+    for flag in flags.chars() {
+        match flag {
+            'i' => builder.case_insensitive(true),
+            'm' => builder.multi_line(true),
+            's' => builder.dot_matches_new_line(true),
+            'g' | 'u' | 'y' | 'd' => &mut builder,
+            _ => return Err(invalid()),
+        };
+    }
+    builder.build().map_err(|_| invalid())
+}
+
+impl MutedWords {
+    /// Parses `user_profile.muted_words`. Returns an error if any entry is
+    /// invalid, so that it can be used to validate user input.
+    pub fn parse(value: &serde_json::Value) -> Result<Self, Error> {
+        let entries = value.as_array().ok_or(Error::InvalidFormat)?;
+        let mut patterns = Vec::with_capacity(entries.len());
+        for entry in entries {
+            match entry {
+                serde_json::Value::String(src) => {
+                    patterns.push(Pattern::Regex(compile_regex(src)?))
+                }
+                serde_json::Value::Array(keywords) => {
+                    let mut lowered = Vec::with_capacity(keywords.len());
+                    for keyword in keywords {
+                        let keyword = keyword.as_str().ok_or(Error::InvalidFormat)?;
+                        if !keyword.is_empty() {
+                            lowered.push(keyword.to_lowercase());
+                        }
+                    }
+                    if !lowered.is_empty() {
+                        patterns.push(Pattern::Keywords(lowered));
+                    }
+                }
+                _ => return Err(Error::InvalidFormat),
+            }
+        }
+        Ok(Self { patterns })
+    }
+
+    /// Parses `user_profile.muted_words`, skipping invalid entries instead
+    /// of failing, like TypeScript does when checking notes.
+    pub fn parse_lossy(value: &serde_json::Value) -> Self {
+        let patterns = value
+            .as_array()
+            .map(|entries| {
+                entries
+                    .iter()
+                    .filter_map(|entry| Self::parse(&serde_json::json!([entry])).ok())
+                    .flat_map(|parsed| parsed.patterns)
+                    .collect()
+            })
+            .unwrap_or_default();
+        Self { patterns }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    /// Returns `true` if `text` contains all the keywords of any entry or
+    /// matches any regular expression. Blank text never matches.
+    pub fn is_match(&self, text: &str) -> bool {
+        let text = text.trim();
+        if text.is_empty() {
+            return false;
+        }
+        let lowered = text.to_lowercase();
+        self.patterns.iter().any(|pattern| match pattern {
+            Pattern::Keywords(keywords) => keywords.iter().all(|k| lowered.contains(k.as_str())),
+            Pattern::Regex(regex) => regex.is_match(text),
+        })
+    }
+
+    /// Returns `true` if the content warning, the text, or the comments on
+    /// the attached files of the note match.
+    pub fn is_note_match(
+        &self,
+        cw: Option<&str>,
+        text: Option<&str>,
+        file_comments: &[String],
+    ) -> bool {
+        let mut target = format!("{} {}", cw.unwrap_or_default(), text.unwrap_or_default());
+        if !file_comments.is_empty() {
+            target.push(' ');
+            target.push_str(&file_comments.join(" "));
+        }
+        self.is_match(&target)
+    }
+}
+
+/// Returns the muted words of the user, compiled and cached. Users without a
+/// profile have no muted words.
+pub async fn get_muted_words(user_id: &str) -> Result<Arc<MutedWords>, Error> {
+    if let Some((cached_at, muted_words)) = CACHE.read().unwrap().get(user_id) {
+        if cached_at.elapsed() < CACHE_TTL {
+            return Ok(muted_words.to_owned());
+        }
+    }
+
+    let profile = user_profile::Entity::find_by_id(user_id.to_string())
+        .one(database::get_database()?)
+        .await?;
+    let muted_words = Arc::new(
+        profile
+            .map(|p| MutedWords::parse_lossy(&p.muted_words))
+            .unwrap_or_default(),
+    );
+    let mut cache = CACHE.write().unwrap();
+    // Drop expired entries so that users who went away do not pile up.
+    cache.retain(|_, (cached_at, _)| cached_at.elapsed() < CACHE_TTL);
+    cache.insert(
+        user_id.to_string(),
+        (Instant::now(), muted_words.to_owned()),
+    );
+    Ok(muted_words)
+}
+
+/// Drops the cached muted words of the user. Call this after updating
+/// `user_profile.muted_words`.
+pub fn invalidate_muted_words(user_id: &str) {
+    CACHE.write().unwrap().remove(user_id);
+}
+
+/// Returns `true` if the note, its reply, or its renote is hard-muted by the
+/// muted words of `me_id`. Each note is given with the comments on its
+/// attached files. Notes of the user themselves are never muted.
+pub async fn get_word_hard_mute(
+    note: (&note::Model, &[String]),
+    reply: Option<(&note::Model, &[String])>,
+    renote: Option<(&note::Model, &[String])>,
+    me_id: &str,
+) -> Result<bool, Error> {
+    if note.0.user_id == me_id {
+        return Ok(false);
+    }
+    let muted_words = get_muted_words(me_id).await?;
+    if muted_words.is_empty() {
+        return Ok(false);
+    }
+
+    Ok([Some(note), reply, renote]
+        .into_iter()
+        .flatten()
+        .any(|(n, file_comments)| {
+            muted_words.is_note_match(n.cw.as_deref(), n.text.as_deref(), file_comments)
+        }))
+}
+
+cfg_if! {
+    if #[cfg(feature = "napi")] {
+        use napi_derive::napi;
+
+        /// Fields of a note needed by the word mute.
+        #[napi(object)]
+        #[derive(Clone, Debug, Default, PartialEq, Eq)]
+        pub struct NativeMutableNote {
+            pub user_id: String,
+            pub text: Option<String>,
+            pub cw: Option<String>,
+            pub file_comments: Vec<String>,
+        }
+
+        /// Returns `true` if any of the notes, i.e. a note and its reply and
+        /// renote, is hard-muted by `me_id`. The first note is the main one.
+        #[napi]
+        pub async fn native_get_word_hard_mute(notes: Vec<NativeMutableNote>, me_id: String) -> napi::Result<bool> {
+            match notes.first() {
+                None => return Ok(false),
+                Some(note) if note.user_id == me_id => return Ok(false),
+                _ => {}
+            }
+            let muted_words = get_muted_words(&me_id).await.map_err(Into::<napi::Error>::into)?;
+            Ok(notes
+                .iter()
+                .any(|n| muted_words.is_note_match(n.cw.as_deref(), n.text.as_deref(), &n.file_comments)))
+        }
+
+        /// Returns an error if the muted words are invalid.
+        #[napi]
+        pub fn native_validate_muted_words(muted_words: serde_json::Value) -> napi::Result<()> {
+            MutedWords::parse(&muted_words).map(|_| ()).map_err(Into::into)
+        }
+
+        #[napi]
+        pub fn native_invalidate_muted_words(user_id: String) {
+            invalidate_muted_words(&user_id)
+        }
+    }
+}
+
+#[cfg(test)]
+mod unit_test {
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    use super::{Error, MutedWords};
+
+    #[test]
+    fn keywords() {
+        let muted_words = MutedWords::parse(&json!([["Foo", "bar"], ["baz"], [""]])).unwrap();
+
+        assert!(muted_words.is_match("foo BAR"));
+        assert!(muted_words.is_match("bazooka"));
+        assert!(!muted_words.is_match("foo"));
+        assert!(!muted_words.is_match("   "));
+    }
+
+    #[test]
+    fn regex() {
+        let muted_words =
+            MutedWords::parse(&json!(["/^hello/i", "/a\\/b/", "/foo.bar/s"])).unwrap();
+
+        assert!(muted_words.is_match("Hello, world!"));
+        assert!(muted_words.is_match("a/b"));
+        assert!(muted_words.is_match("foo\nbar"));
+        assert!(!muted_words.is_match("Oh, hello"));
+    }
+
+    #[test]
+    fn note() {
+        let muted_words = MutedWords::parse(&json!([["spoiler"], ["cat", "picture"]])).unwrap();
+
+        assert!(muted_words.is_note_match(Some("Spoiler"), Some("Nothing here"), &[]));
+        assert!(muted_words.is_note_match(None, Some("A cat"), &["picture".to_string()]));
+        assert!(!muted_words.is_note_match(None, None, &[]));
+    }
+
+    #[test]
+    fn invalid() {
+        assert_eq!(
+            MutedWords::parse(&json!(["not a regex"])).unwrap_err(),
+            Error::InvalidRegex("not a regex".to_string())
+        );
+        assert_eq!(
+            MutedWords::parse(&json!(["/(/"])).unwrap_err(),
+            Error::InvalidRegex("/(/".to_string())
+        );
+        assert_eq!(
+            MutedWords::parse(&json!(["/foo/x"])).unwrap_err(),
+            Error::InvalidRegex("/foo/x".to_string())
+        );
+        assert_eq!(
+            MutedWords::parse(&json!([1])).unwrap_err(),
+            Error::InvalidFormat
+        );
+        assert_eq!(
+            MutedWords::parse(&json!({})).unwrap_err(),
+            Error::InvalidFormat
+        );
+
+        // invalid entries are skipped when checking notes
+        let muted_words = MutedWords::parse_lossy(&json!(["/(/", ["foo"], 1]));
+        assert!(muted_words.is_match("foo"));
+        assert!(MutedWords::parse_lossy(&json!(null)).is_empty());
+    }
+}
@@ -0,0 +1,34 @@
+mod int_test {
+    use native_utils::acct::{error::Error, webfinger};
+
+    use pretty_assertions::assert_eq;
+
+    use crate::{cleanup, prepare};
+
+    #[tokio::test]
+    async fn webfinger_local() {
+        prepare().await;
+
+        let jrd = webfinger::local_jrd("https://example.com", "acct:ALICE@example.com")
+            .await
+            .expect("alice not found");
+        assert_eq!(jrd.subject.as_deref(), Some("acct:alice@example.com"));
+        let self_href = jrd.self_link().and_then(|l| l.href.to_owned()).unwrap();
+
+        let by_id = webfinger::local_jrd("https://example.com", &self_href)
+            .await
+            .unwrap();
+        assert_eq!(by_id, jrd);
+
+        assert_eq!(
+            webfinger::local_jrd("https://example.com", "acct:bob@example.com").await,
+            Err(Error::NotFound)
+        );
+        assert_eq!(
+            webfinger::local_jrd("https://example.com", "acct:alice@remote.example").await,
+            Err(Error::ForeignHost("remote.example".to_string()))
+        );
+
+        cleanup().await;
+    }
+}
@@ -0,0 +1,1021 @@
+mod int_test {
+    use std::collections::{HashMap, HashSet};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use chrono::Utc;
+    use native_utils::activitypub::deliver::{
+        deliver_activity, skipped_hosts, DeliverConfig, Deliverer, Outcome, Recipients,
+    };
+    use native_utils::activitypub::error::Error;
+    use native_utils::activitypub::http_signature::{
+        parse_request, verify_digest, verify_signature, Headers, SigningKey, DEFAULT_CLOCK_SKEW,
+    };
+    use native_utils::activitypub::instance::{InstanceFetchConfig, InstanceFetcher};
+    use native_utils::activitypub::kernel::{perform, Effect};
+    use native_utils::activitypub::policy;
+    use native_utils::activitypub::resolver::{Resolver, ResolverConfig};
+    use native_utils::activitypub::Activity;
+    use native_utils::util::http::ReqwestClient;
+    use native_utils::util::id::create_id;
+    use native_utils::util::keypair::{rekey_users, KeyType};
+    use native_utils::{database, model};
+
+    use model::entity::newtype::{from_i32_vec, from_string_vec, string_vec};
+    use model::entity::{
+        follow_request, following, instance, meta, note, note_reaction, poll, user, user_profile,
+        user_publickey,
+    };
+    use pretty_assertions::assert_eq;
+    use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, IntoActiveModel, QueryFilter, Set};
+    use serde_json::json;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    use crate::{cleanup, prepare};
+
+    /// Request received by [MockServer].
+    struct Received {
+        path: String,
+        headers: Headers,
+        body: String,
+    }
+
+    /// Responses of each path, and the number of requests to it.
+    type Responses = Arc<Mutex<HashMap<String, (Vec<(u16, String)>, usize)>>>;
+
+    /// HTTP server that answers each path with the responses queued by
+    /// [MockServer::respond] in order, repeating the last one, and 404 for
+    /// other paths.
+    struct MockServer {
+        origin: String,
+        responses: Responses,
+        received: Arc<Mutex<Vec<Received>>>,
+    }
+
+    impl MockServer {
+        async fn start() -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let origin = format!("http://{}", listener.local_addr().unwrap());
+            let responses: Responses = Default::default();
+            let received = Arc::new(Mutex::new(Vec::new()));
+            let (queues, log) = (Arc::clone(&responses), Arc::clone(&received));
+
+            tokio::spawn(async move {
+                loop {
+                    let (mut stream, _) = listener.accept().await.unwrap();
+                    let (queues, log) = (Arc::clone(&queues), Arc::clone(&log));
+                    tokio::spawn(async move {
+                        let mut buf = Vec::new();
+                        let mut chunk = [0u8; 4096];
+                        let (head, mut body) = loop {
+                            let n = stream.read(&mut chunk).await.unwrap();
+                            buf.extend_from_slice(&chunk[..n]);
+                            let text = String::from_utf8_lossy(&buf).to_string();
+                            if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                                break (head.to_string(), body.to_string());
+                            }
+                        };
+                        let mut lines = head.lines();
+                        let path = lines.next().unwrap().split(' ').nth(1).unwrap().to_string();
+                        let headers: Headers = lines
+                            .filter_map(|l| l.split_once(": "))
+                            .map(|(k, v)| (k.to_lowercase(), v.to_string()))
+                            .collect();
+                        let length: usize = headers
+                            .get("content-length")
+                            .map_or(0, |l| l.parse().unwrap());
+                        while body.len() < length {
+                            let n = stream.read(&mut chunk).await.unwrap();
+                            body.push_str(&String::from_utf8_lossy(&chunk[..n]));
+                        }
+
+                        let (status, content) = match queues.lock().unwrap().get_mut(&path) {
+                            Some((queue, served)) => {
+                                *served += 1;
+                                queue[(*served - 1).min(queue.len() - 1)].to_owned()
+                            }
+                            None => (404, String::new()),
+                        };
+                        log.lock().unwrap().push(Received {
+                            path,
+                            headers,
+                            body,
+                        });
+                        let response = format!(
+                            "HTTP/1.1 {} Mock\r\ncontent-type: application/activity+json\r\n\
+                            content-length: {}\r\nconnection: close\r\n\r\n{}",
+                            status,
+                            content.len(),
+                            content
+                        );
+                        stream.write_all(response.as_bytes()).await.unwrap();
+                    });
+                }
+            });
+
+            Self {
+                origin,
+                responses,
+                received,
+            }
+        }
+
+        fn respond(&self, path: &str, status: u16, body: String) {
+            self.responses
+                .lock()
+                .unwrap()
+                .entry(path.to_string())
+                .or_default()
+                .0
+                .push((status, body));
+        }
+
+        /// Returns the number of requests to the path.
+        fn count(&self, path: &str) -> usize {
+            self.received
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|r| r.path == path)
+                .count()
+        }
+    }
+
+    async fn insert_remote_user(
+        username: &str,
+        inbox: String,
+        shared_inbox: Option<String>,
+    ) -> user::Model {
+        let db = database::get_database().unwrap();
+        user::Model {
+            id: create_id(0).unwrap(),
+            created_at: Utc::now().into(),
+            username: username.to_string(),
+            username_lower: username.to_string(),
+            host: Some("127.0.0.1".to_string()),
+            inbox: Some(inbox),
+            shared_inbox,
+            ..Default::default()
+        }
+        .into_active_model()
+        .reset_all()
+        .insert(db)
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn deliver() {
+        prepare().await;
+        let db = database::get_database().unwrap();
+        let server = MockServer::start().await;
+        server.respond("/inbox", 500, String::new());
+        server.respond("/inbox", 202, String::new());
+        server.respond("/gone", 410, String::new());
+        server.respond("/down", 503, String::new());
+        let origin = server.origin.to_owned();
+        let host = origin.trim_start_matches("http://").to_string();
+
+        let alice = user::Entity::find()
+            .filter(user::Column::Username.eq("alice"))
+            .one(db)
+            .await
+            .unwrap()
+            .expect("alice not found");
+        let keypair = rekey_users(&[alice.id.to_owned()], KeyType::Rsa2048)
+            .await
+            .unwrap()
+            .pop()
+            .unwrap();
+        meta::Model {
+            id: "x".to_string(),
+            blocked_hosts: string_vec(vec!["blocked.example".to_string()]),
+            allowed_hosts: Some(string_vec(vec![])),
+            ..Default::default()
+        }
+        .into_active_model()
+        .reset_all()
+        .insert(db)
+        .await
+        .unwrap();
+
+        let bob = insert_remote_user(
+            "bob",
+            format!("{}/users/bob/inbox", origin),
+            Some(format!("{}/inbox", origin)),
+        )
+        .await;
+        following::Model {
+            id: create_id(0).unwrap(),
+            created_at: Utc::now().into(),
+            followee_id: alice.id.to_owned(),
+            follower_id: bob.id.to_owned(),
+            follower_host: Some("127.0.0.1".to_string()),
+            follower_inbox: bob.inbox.to_owned(),
+            follower_shared_inbox: bob.shared_inbox.to_owned(),
+            ..Default::default()
+        }
+        .into_active_model()
+        .reset_all()
+        .insert(db)
+        .await
+        .unwrap();
+        let carol = insert_remote_user("carol", format!("{}/gone", origin), None).await;
+        let dave = insert_remote_user(
+            "dave",
+            "https://sub.blocked.example/inbox".to_string(),
+            None,
+        )
+        .await;
+
+        let deliverer = Arc::new(Deliverer::new(
+            Arc::new(ReqwestClient::new("Firefish/test", Duration::from_secs(5)).unwrap()),
+            DeliverConfig {
+                max_attempts: 3,
+                base_delay: Duration::from_millis(10),
+                ..Default::default()
+            },
+        ));
+        let activity = json!({ "type": "Delete", "actor": "https://example.com/users/alice" });
+        let mut outcomes: HashMap<String, Outcome> = deliver_activity(
+            &deliverer,
+            "https://example.com",
+            &alice.id,
+            &activity,
+            &Recipients {
+                followers: true,
+                direct: vec![bob, carol, dave],
+            },
+        )
+        .await
+        .unwrap()
+        .into_iter()
+        .collect();
+
+        // retried later by the caller
+        let inbox = format!("{}/inbox", origin);
+        match outcomes.remove(&inbox) {
+            Some(Outcome::Retry {
+                attempts_made,
+                delay,
+                reason,
+            }) => {
+                assert_eq!(attempts_made, 1);
+                assert!(delay >= Duration::from_millis(10));
+                assert_eq!(reason, "status 500");
+            }
+            other => panic!("unexpected outcome: {:?}", other),
+        }
+        assert_eq!(
+            outcomes,
+            HashMap::from([
+                (format!("{}/gone", origin), Outcome::Rejected(410)),
+                (
+                    "https://sub.blocked.example/inbox".to_string(),
+                    Outcome::Skipped
+                ),
+            ])
+        );
+
+        let key = SigningKey::from_keypair("https://example.com", &keypair);
+        let body = activity.to_string();
+        assert_eq!(
+            deliverer.deliver(&key, &inbox, &body, 1).await,
+            Ok(Outcome::Delivered)
+        );
+        // gives up on the last attempt
+        let down = format!("{}/down", origin);
+        assert!(matches!(
+            deliverer.deliver(&key, &down, &body, 1).await,
+            Ok(Outcome::Retry {
+                attempts_made: 2,
+                ..
+            })
+        ));
+        assert_eq!(
+            deliverer.deliver(&key, &down, &body, 2).await,
+            Ok(Outcome::GaveUp("status 503".to_string()))
+        );
+
+        {
+            assert_eq!(server.count("/inbox"), 2);
+            assert_eq!(server.count("/down"), 2);
+            assert_eq!(server.count("/gone"), 1);
+            let received = server.received.lock().unwrap();
+            for request in received.iter() {
+                assert_eq!(request.headers["host"], host);
+                assert!(verify_digest(
+                    request.body.as_bytes(),
+                    &request.headers["digest"]
+                ));
+                let signature = parse_request(
+                    "POST",
+                    &request.path,
+                    &request.headers,
+                    &["(request-target)", "digest", "host", "date"],
+                    DEFAULT_CLOCK_SKEW,
+                )
+                .unwrap();
+                assert_eq!(
+                    signature.key_id,
+                    format!("https://example.com/users/{}#main-key", alice.id)
+                );
+                assert!(verify_signature(&signature, &keypair.public_key));
+            }
+        }
+
+        // registered once by the concurrent deliveries
+        let instances = instance::Entity::find()
+            .filter(instance::Column::Host.eq(host))
+            .all(db)
+            .await
+            .unwrap();
+        assert_eq!(instances.len(), 1);
+        let instance = &instances[0];
+        // the health of the last attempt
+        assert_eq!(instance.latest_status, Some(503));
+        assert!(instance.is_not_responding);
+        assert!(instance.latest_request_sent_at.is_some());
+
+        cleanup().await;
+    }
+
+    async fn perform_json(activity: serde_json::Value) -> Effect {
+        let activity: Activity = serde_json::from_value(activity).unwrap();
+        perform("https://example.com", &activity).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn kernel() {
+        prepare().await;
+        let db = database::get_database().unwrap();
+        let alice = user::Entity::find()
+            .filter(user::Column::Username.eq("alice"))
+            .one(db)
+            .await
+            .unwrap()
+            .expect("alice not found");
+        let alice_uri = format!("https://example.com/users/{}", alice.id);
+        let notes = note::Entity::find()
+            .filter(note::Column::UserId.eq(&alice.id))
+            .all(db)
+            .await
+            .unwrap();
+        let note = notes.iter().find(|n| !n.has_poll).expect("note not found");
+        let note_uri = format!("https://example.com/notes/{}", note.id);
+        let question = notes
+            .iter()
+            .find(|n| n.has_poll)
+            .expect("question not found");
+        let bob_uri = "https://remote.example/users/bob";
+        let bob = user::Model {
+            id: create_id(0).unwrap(),
+            created_at: Utc::now().into(),
+            username: "bob".to_string(),
+            username_lower: "bob".to_string(),
+            host: Some("remote.example".to_string()),
+            uri: Some(bob_uri.to_string()),
+            inbox: Some(format!("{}/inbox", bob_uri)),
+            shared_inbox: Some("https://remote.example/inbox".to_string()),
+            followers_uri: Some(format!("{}/followers", bob_uri)),
+            ..Default::default()
+        }
+        .into_active_model()
+        .reset_all()
+        .insert(db)
+        .await
+        .unwrap();
+
+        assert_eq!(
+            perform_json(json!({
+                "type": "Follow",
+                "actor": "https://unknown.example/users/eve",
+                "object": alice_uri,
+            }))
+            .await,
+            Effect::Ignored("actor not found".to_string())
+        );
+
+        // follow
+        let follow = json!({
+            "id": "https://remote.example/follows/1",
+            "type": "Follow",
+            "actor": bob_uri,
+            "object": alice_uri,
+        });
+        assert_eq!(perform_json(follow.to_owned()).await, Effect::Followed);
+        assert_eq!(
+            perform_json(follow.to_owned()).await,
+            Effect::AlreadyApplied
+        );
+        let followings = following::Entity::find()
+            .filter(following::Column::FollowerId.eq(&bob.id))
+            .all(db)
+            .await
+            .unwrap();
+        assert_eq!(followings.len(), 1);
+        assert_eq!(followings[0].followee_id, alice.id);
+        assert_eq!(
+            followings[0].follower_shared_inbox.as_deref(),
+            Some("https://remote.example/inbox")
+        );
+
+        // reaction
+        let like = json!({
+            "id": "https://remote.example/likes/1",
+            "type": "Like",
+            "actor": bob_uri,
+            "object": note_uri,
+            "_misskey_reaction": ":blobcat:",
+        });
+        assert_eq!(perform_json(like.to_owned()).await, Effect::Applied);
+        assert_eq!(perform_json(like.to_owned()).await, Effect::AlreadyApplied);
+        let reactions = note_reaction::Entity::find()
+            .filter(note_reaction::Column::UserId.eq(&bob.id))
+            .all(db)
+            .await
+            .unwrap();
+        assert_eq!(reactions.len(), 1);
+        assert_eq!(reactions[0].reaction, ":blobcat@remote.example:");
+        let reacted = note::Entity::find_by_id(note.id.to_owned())
+            .one(db)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(reacted.reactions[":blobcat@remote.example:"], 1);
+
+        // reply
+        let reply_uri = "https://remote.example/notes/1";
+        let create = json!({
+            "id": "https://remote.example/notes/1/activity",
+            "type": "Create",
+            "actor": bob_uri,
+            "object": {
+                "id": reply_uri,
+                "type": "Note",
+                "attributedTo": bob_uri,
+                "inReplyTo": note_uri,
+                "content": "<p><a href=\"https://example.com/@alice\">@alice</a> hi <a href=\"https://remote.example/tags/Rust\">#Rust</a></p>",
+                "published": "2023-06-01T00:00:00Z",
+                "to": ["https://www.w3.org/ns/activitystreams#Public"],
+                "cc": [format!("{}/followers", bob_uri), alice_uri],
+                "tag": [
+                    { "type": "Mention", "href": alice_uri, "name": "@alice@example.com" },
+                    { "type": "Hashtag", "href": "https://remote.example/tags/Rust", "name": "#Rust" },
+                ],
+            },
+        });
+        assert_eq!(perform_json(create.to_owned()).await, Effect::Applied);
+        assert_eq!(
+            perform_json(create.to_owned()).await,
+            Effect::AlreadyApplied
+        );
+        let reply = note::Entity::find()
+            .filter(note::Column::Uri.eq(reply_uri))
+            .one(db)
+            .await
+            .unwrap()
+            .expect("reply not created");
+        assert_eq!(reply.user_id, bob.id);
+        assert_eq!(reply.reply_id.as_ref(), Some(&note.id));
+        assert_eq!(reply.text.as_deref(), Some("@alice@example.com hi #Rust"));
+        assert_eq!(from_string_vec(reply.tags), vec!["rust"]);
+        assert_eq!(from_string_vec(reply.mentions), vec![alice.id.to_owned()]);
+        let replied = note::Entity::find_by_id(note.id.to_owned())
+            .one(db)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(replied.replies_count, note.replies_count + 1);
+
+        // vote
+        let vote = |n: u8| {
+            json!({
+                "id": format!("https://remote.example/votes/{}/activity", n),
+                "type": "Create",
+                "actor": bob_uri,
+                "object": {
+                    "id": format!("https://remote.example/votes/{}", n),
+                    "type": "Note",
+                    "attributedTo": bob_uri,
+                    "name": "foo",
+                    "inReplyTo": format!("https://example.com/notes/{}", question.id),
+                    "to": alice_uri,
+                },
+            })
+        };
+        assert_eq!(perform_json(vote(1)).await, Effect::Applied);
+        assert_eq!(perform_json(vote(2)).await, Effect::AlreadyApplied);
+        let poll = poll::Entity::find_by_id(question.id.to_owned())
+            .one(db)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(from_i32_vec(poll.votes), vec![2, 0]);
+
+        // undo
+        let undo = |object: &serde_json::Value| {
+            json!({
+                "type": "Undo",
+                "actor": bob_uri,
+                "object": object,
+            })
+        };
+        assert_eq!(perform_json(undo(&like)).await, Effect::Applied);
+        assert_eq!(perform_json(undo(&like)).await, Effect::AlreadyApplied);
+        let unreacted = note::Entity::find_by_id(note.id.to_owned())
+            .one(db)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(unreacted.reactions.get(":blobcat@remote.example:"), None);
+        assert_eq!(perform_json(undo(&follow)).await, Effect::Applied);
+        assert_eq!(perform_json(undo(&follow)).await, Effect::AlreadyApplied);
+        assert_eq!(
+            following::Entity::find()
+                .filter(following::Column::FollowerId.eq(&bob.id))
+                .all(db)
+                .await
+                .unwrap(),
+            vec![]
+        );
+        assert_eq!(
+            follow_request::Entity::find()
+                .filter(follow_request::Column::FollowerId.eq(&bob.id))
+                .all(db)
+                .await
+                .unwrap(),
+            vec![]
+        );
+
+        // delete
+        let delete = |object: &str| {
+            json!({
+                "type": "Delete",
+                "actor": bob_uri,
+                "object": { "id": object, "type": "Tombstone" },
+            })
+        };
+        assert_eq!(perform_json(delete(reply_uri)).await, Effect::Applied);
+        assert_eq!(
+            perform_json(delete(reply_uri)).await,
+            Effect::AlreadyApplied
+        );
+        assert_eq!(
+            note::Entity::find()
+                .filter(note::Column::Uri.eq(reply_uri))
+                .one(db)
+                .await
+                .unwrap(),
+            None
+        );
+        assert_eq!(perform_json(delete(bob_uri)).await, Effect::Applied);
+        let deleted = user::Entity::find_by_id(bob.id.to_owned())
+            .one(db)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(deleted.is_deleted);
+
+        cleanup().await;
+    }
+
+    #[tokio::test]
+    async fn resolve() {
+        prepare().await;
+        let db = database::get_database().unwrap();
+        let server = MockServer::start().await;
+        let origin = server.origin.to_owned();
+        let host = origin.trim_start_matches("http://").to_string();
+        let client = Arc::new(ReqwestClient::new("Firefish/test", Duration::from_secs(5)).unwrap());
+        let resolver = |config: ResolverConfig| {
+            Resolver::new(client.to_owned(), "https://example.com", None, config)
+        };
+
+        let carol_uri = format!("{}/users/carol", origin);
+        let carol = |name: &str| {
+            json!({
+                "id": carol_uri,
+                "type": "Person",
+                "preferredUsername": "Carol",
+                "name": name,
+                "summary": "<p>hi</p>",
+                "inbox": format!("{}/inbox", carol_uri),
+                "endpoints": { "sharedInbox": format!("{}/inbox", origin) },
+                "followers": format!("{}/followers", carol_uri),
+                "manuallyApprovesFollowers": true,
+                "tag": [{ "type": "Hashtag", "name": "#Rust" }],
+                "attachment": [{ "type": "PropertyValue", "name": "Site", "value": "<p>none</p>" }],
+                "publicKey": {
+                    "id": format!("{}#main-key", carol_uri),
+                    "owner": carol_uri,
+                    "publicKeyPem": "-----BEGIN PUBLIC KEY-----\n...",
+                },
+            })
+            .to_string()
+        };
+        let note = |n: u8, reply: u8| {
+            json!({
+                "id": format!("{}/notes/{}", origin, n),
+                "type": "Note",
+                "attributedTo": carol_uri,
+                "content": format!("<p>{}</p>", n),
+                "inReplyTo": format!("{}/notes/{}", origin, reply),
+                "to": "https://www.w3.org/ns/activitystreams#Public",
+            })
+            .to_string()
+        };
+        server.respond("/users/carol", 200, carol("Carol"));
+        server.respond("/notes/1", 200, note(1, 2));
+        server.respond("/notes/2", 200, note(2, 1));
+        server.respond(
+            "/users/mallory",
+            200,
+            json!({
+                "id": "https://evil.example/users/mallory",
+                "type": "Person",
+                "preferredUsername": "mallory",
+            })
+            .to_string(),
+        );
+        let dave_uri = format!("{}/users/dave", origin);
+        let dave = |key_id: &str, owner: &str| {
+            json!({
+                "id": dave_uri,
+                "type": "Person",
+                "preferredUsername": "dave",
+                "inbox": format!("{}/inbox", dave_uri),
+                "publicKey": {
+                    "id": key_id,
+                    "owner": owner,
+                    "publicKeyPem": "-----BEGIN PUBLIC KEY-----\n...",
+                },
+            })
+            .to_string()
+        };
+
+        // notes referring to each other
+        let mut resolver1 = resolver(ResolverConfig::default());
+        let note2 = resolver1
+            .resolve_note(&format!("{}/notes/2", origin))
+            .await
+            .unwrap();
+        assert_eq!(resolver1.fetches(), 3);
+        assert_eq!(server.count("/notes/2"), 1);
+        let note1 = note::Entity::find()
+            .filter(note::Column::Uri.eq(format!("{}/notes/1", origin)))
+            .one(db)
+            .await
+            .unwrap()
+            .expect("replied note not stored");
+        assert_eq!(note2.reply_id, Some(note1.id.to_owned()));
+        assert_eq!(note1.reply_id, None);
+        assert_eq!(note2.text.as_deref(), Some("2"));
+
+        let user = user::Entity::find_by_id(note2.user_id.to_owned())
+            .one(db)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(user.uri.as_deref(), Some(carol_uri.as_str()));
+        assert_eq!(user.username_lower, "carol");
+        assert_eq!(user.host.as_deref(), Some(host.as_str()));
+        assert_eq!(user.shared_inbox, Some(format!("{}/inbox", origin)));
+        assert!(user.is_locked);
+        assert_eq!(from_string_vec(user.tags.to_owned()), vec!["rust"]);
+        let profile = user_profile::Entity::find_by_id(user.id.to_owned())
+            .one(db)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(profile.description.as_deref(), Some("hi"));
+        assert_eq!(profile.fields, json!([{ "name": "Site", "value": "none" }]));
+        let public_key = user_publickey::Entity::find_by_id(user.id.to_owned())
+            .one(db)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(public_key.key_id, format!("{}#main-key", carol_uri));
+        let instance = instance::Entity::find()
+            .filter(instance::Column::Host.eq(host))
+            .one(db)
+            .await
+            .unwrap()
+            .expect("instance not registered");
+        assert_eq!(instance.users_count, 1);
+
+        // cached and refreshed actors
+        server.respond("/users/carol", 200, carol("Caroline"));
+        let mut resolver2 = resolver(ResolverConfig::default());
+        assert_eq!(
+            resolver2.resolve_user(&carol_uri).await.unwrap().id,
+            user.id
+        );
+        assert_eq!(
+            resolver2
+                .resolve_note(&format!("{}/notes/1", origin))
+                .await
+                .unwrap()
+                .id,
+            note1.id
+        );
+        assert_eq!(resolver2.fetches(), 0);
+        let mut resolver3 = resolver(ResolverConfig {
+            actor_ttl: Duration::ZERO,
+            ..Default::default()
+        });
+        let refreshed = resolver3.resolve_user(&carol_uri).await.unwrap();
+        assert_eq!(refreshed.id, user.id);
+        assert_eq!(refreshed.name.as_deref(), Some("Caroline"));
+        assert_eq!(resolver3.fetches(), 1);
+
+        // limits and failures
+        let mut resolver4 = resolver(ResolverConfig {
+            max_fetches: 0,
+            ..Default::default()
+        });
+        assert_eq!(
+            resolver4.resolve_note(&format!("{}/notes/3", origin)).await,
+            Err(Error::FetchLimitExceeded)
+        );
+        let mut resolver5 = resolver(ResolverConfig {
+            max_depth: 0,
+            ..Default::default()
+        });
+        assert_eq!(
+            resolver5.resolve_note(&format!("{}/notes/3", origin)).await,
+            Err(Error::RecursionLimitExceeded)
+        );
+        assert_eq!(
+            resolver5
+                .resolve_user(&format!("{}/users/nobody", origin))
+                .await,
+            Err(Error::HttpStatus(404))
+        );
+        assert!(matches!(
+            resolver5
+                .resolve_user(&format!("{}/users/mallory", origin))
+                .await,
+            Err(Error::InvalidObject(_))
+        ));
+        // keys of other servers or other actors
+        for (key_id, owner) in [
+            (
+                "https://evil.example/users/dave#main-key",
+                dave_uri.to_owned(),
+            ),
+            (
+                format!("{}#main-key", dave_uri).as_str(),
+                carol_uri.to_owned(),
+            ),
+        ] {
+            server.respond("/users/dave", 200, dave(key_id, &owner));
+            assert!(matches!(
+                resolver(ResolverConfig::default())
+                    .resolve_user(&dave_uri)
+                    .await,
+                Err(Error::InvalidObject(_))
+            ));
+        }
+        let stored = user::Entity::find()
+            .filter(user::Column::Uri.eq(dave_uri.to_owned()))
+            .one(db)
+            .await
+            .unwrap();
+        assert_eq!(stored, None);
+        assert_eq!(
+            resolver5
+                .resolve_user("https://example.com/users/unknown")
+                .await,
+            Err(Error::NotFound)
+        );
+
+        cleanup().await;
+    }
+
+    #[tokio::test]
+    async fn instance_metadata() {
+        prepare().await;
+        let server = MockServer::start().await;
+        let origin = server.origin.to_owned();
+        let host = origin.trim_start_matches("http://").to_string();
+        server.respond(
+            "/.well-known/nodeinfo",
+            200,
+            json!({
+                "links": [{
+                    "rel": "http://nodeinfo.diaspora.software/ns/schema/2.0",
+                    "href": format!("{}/nodeinfo/2.0", origin),
+                }],
+            })
+            .to_string(),
+        );
+        server.respond(
+            "/nodeinfo/2.0",
+            200,
+            json!({
+                "version": "2.0",
+                "software": { "name": "Misskey", "version": "13.14.2" },
+                "openRegistrations": false,
+                "metadata": {
+                    "nodeName": "Remote",
+                    "maintainer": { "name": "Bob", "email": "bob@remote.example" },
+                    "themeColor": "#86b300",
+                },
+            })
+            .to_string(),
+        );
+        server.respond(
+            "/",
+            200,
+            r#"<html><head><meta name="description" content="Hello"></head></html>"#.to_string(),
+        );
+        server.respond("/favicon.ico", 200, String::new());
+
+        let fetcher = InstanceFetcher::new(
+            Arc::new(ReqwestClient::new("Firefish/test", Duration::from_secs(5)).unwrap()),
+            InstanceFetchConfig {
+                scheme: "http".to_string(),
+                ..Default::default()
+            },
+        );
+        let updated = fetcher
+            .update(&host, false)
+            .await
+            .unwrap()
+            .expect("instance not updated");
+        assert_eq!(updated.host, host);
+        assert_eq!(updated.software_name.as_deref(), Some("misskey"));
+        assert_eq!(updated.software_version.as_deref(), Some("13.14.2"));
+        assert_eq!(updated.open_registrations, Some(false));
+        assert_eq!(updated.name.as_deref(), Some("Remote"));
+        assert_eq!(updated.description.as_deref(), Some("Hello"));
+        assert_eq!(
+            updated.maintainer_email.as_deref(),
+            Some("bob@remote.example")
+        );
+        assert_eq!(updated.theme_color.as_deref(), Some("#86b300"));
+        let favicon = format!("{}/favicon.ico", origin);
+        assert_eq!(updated.favicon_url.as_deref(), Some(favicon.as_str()));
+        assert_eq!(updated.icon_url.as_deref(), Some(favicon.as_str()));
+        assert!(updated.info_updated_at.is_some());
+        assert_eq!(server.count("/manifest.json"), 1);
+
+        // Up to date until `stale_after` passes.
+        assert_eq!(fetcher.update(&host, false).await, Ok(None));
+        assert_eq!(server.count("/.well-known/nodeinfo"), 1);
+
+        // Values missing from the sources are kept.
+        server.respond("/", 500, String::new());
+        let forced = fetcher.update(&host, true).await.unwrap().unwrap();
+        assert_eq!(server.count("/.well-known/nodeinfo"), 2);
+        assert_eq!(forced.description.as_deref(), Some("Hello"));
+        assert!(forced.info_updated_at > updated.info_updated_at);
+
+        let stored = instance::Entity::find()
+            .filter(instance::Column::Host.eq(&host))
+            .one(database::get_database().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(stored, Some(forced));
+
+        cleanup().await;
+    }
+
+    #[tokio::test]
+    async fn federation_policy() {
+        prepare().await;
+        let db = database::get_database().unwrap();
+        let alice = user::Entity::find()
+            .filter(user::Column::Username.eq("alice"))
+            .one(db)
+            .await
+            .unwrap()
+            .expect("alice not found");
+        let alice_uri = format!("https://example.com/users/{}", alice.id);
+        for (username, host) in [
+            ("carol", "sub.silenced.example"),
+            ("dave", "blocked.example"),
+        ] {
+            user::Model {
+                id: create_id(0).unwrap(),
+                created_at: Utc::now().into(),
+                username: username.to_string(),
+                username_lower: username.to_string(),
+                host: Some(host.to_string()),
+                uri: Some(format!("https://{}/users/{}", host, username)),
+                inbox: Some(format!("https://{}/inbox", host)),
+                ..Default::default()
+            }
+            .into_active_model()
+            .reset_all()
+            .insert(db)
+            .await
+            .unwrap();
+        }
+        let meta = meta::Model {
+            id: "x".to_string(),
+            blocked_hosts: string_vec(vec!["blocked.example".to_string()]),
+            silenced_hosts: string_vec(vec!["*.silenced.example".to_string()]),
+            allowed_hosts: Some(string_vec(vec!["remote.example".to_string()])),
+            ..Default::default()
+        }
+        .into_active_model()
+        .reset_all()
+        .insert(db)
+        .await
+        .unwrap();
+
+        assert_eq!(
+            perform_json(json!({
+                "type": "Follow",
+                "actor": "https://blocked.example/users/dave",
+                "object": alice_uri,
+            }))
+            .await,
+            Effect::Ignored("host is blocked".to_string())
+        );
+        assert_eq!(
+            perform_json(json!({
+                "id": "https://sub.silenced.example/follows/1",
+                "type": "Follow",
+                "actor": "https://sub.silenced.example/users/carol",
+                "object": alice_uri,
+            }))
+            .await,
+            Effect::Requested
+        );
+        let requests = follow_request::Entity::find()
+            .filter(follow_request::Column::FolloweeId.eq(&alice.id))
+            .all(db)
+            .await
+            .unwrap();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(
+            requests[0].follower_host.as_deref(),
+            Some("sub.silenced.example")
+        );
+        assert_eq!(
+            skipped_hosts(&["blocked.example".to_string(), "remote.example".to_string()])
+                .await
+                .unwrap(),
+            HashSet::from(["blocked.example".to_string()])
+        );
+
+        assert_eq!(
+            policy::is_muted_for_user(&alice.id, "sub.remote.example").await,
+            Ok(false)
+        );
+        user_profile::ActiveModel {
+            muted_instances: Set(json!(["remote.example"])),
+            ..user_profile::Entity::find_by_id(alice.id.to_owned())
+                .one(db)
+                .await
+                .unwrap()
+                .unwrap()
+                .into_active_model()
+        }
+        .update(db)
+        .await
+        .unwrap();
+        // The compiled muted instances are used until they are invalidated.
+        assert_eq!(
+            policy::is_muted_for_user(&alice.id, "sub.remote.example").await,
+            Ok(false)
+        );
+        policy::invalidate_muted_instances(&alice.id);
+        assert_eq!(
+            policy::is_muted_for_user(&alice.id, "sub.remote.example").await,
+            Ok(true)
+        );
+        assert_eq!(
+            policy::is_muted_for_user(&alice.id, "other.example").await,
+            Ok(false)
+        );
+
+        // The compiled policy is used until it expires or is invalidated.
+        meta::ActiveModel {
+            private_mode: Set(Some(true)),
+            ..meta.into_active_model()
+        }
+        .update(db)
+        .await
+        .unwrap();
+        assert!(policy::get_policy()
+            .await
+            .unwrap()
+            .can_accept("other.example"));
+        policy::invalidate_policy();
+        let reloaded = policy::get_policy().await.unwrap();
+        assert!(!reloaded.can_accept("other.example"));
+        assert!(reloaded.can_deliver("remote.example"));
+        assert!(reloaded.requires_signed_fetch());
+
+        follow_request::Entity::delete_many()
+            .exec(db)
+            .await
+            .unwrap();
+        cleanup().await;
+    }
+}
@@ -1,25 +1,37 @@
 #![cfg(not(feature = "napi"))]
 
+mod acct;
+mod activitypub;
 mod model;
+mod nodeinfo;
+mod util;
+mod word_mute;
 
 use chrono::Utc;
-use native_utils::database;
+use native_utils::activitypub::policy;
 use native_utils::model::entity;
-use native_utils::model::entity::sea_orm_active_enums::AntennaSrcEnum;
+use native_utils::model::entity::newtype::{i32_vec, string_vec};
+use native_utils::model::entity::sea_orm_active_enums::{
+    AntennaSrcEnum, UserProfileFfvisibilityEnum,
+};
 use native_utils::util::{
     id::{create_id, init_id},
     random::gen_string,
 };
+use native_utils::{cache, database};
 use sea_orm::{
-    sea_query::TableCreateStatement, ActiveModelTrait, ConnectionTrait, DbBackend, DbConn, DbErr,
-    EntityTrait, IntoActiveModel, TransactionTrait,
+    sea_query::{Index, TableCreateStatement},
+    ActiveModelTrait, ConnectionTrait, DbBackend, DbConn, DbErr, EntityTrait, IntoActiveModel, Set,
+    TransactionTrait,
 };
+use serde_json::json;
 
 /// Insert predefined entries in the database.
 async fn prepare() {
     database::init_database("sqlite::memory:")
         .await
         .expect("Unable to initialize database connection");
+    cache::init_memory_cache("example.com").expect("Unable to initialize cache");
     let db = database::get_database().expect("Unable to get database connection from pool");
     setup_schema(db).await;
     setup_model(db).await;
@@ -118,6 +130,25 @@ async fn setup_schema(db: &DbConn) {
     })
     .await
     .expect("Unable to setup schemas");
+
+    // Unique indexes that are not constraints, which the entities lack.
+    for mut index in [
+        Index::create()
+            .name("IDX_instance_host")
+            .table(entity::instance::Entity)
+            .col(entity::instance::Column::Host)
+            .to_owned(),
+        Index::create()
+            .name("IDX_note_uri")
+            .table(entity::note::Entity)
+            .col(entity::note::Column::Uri)
+            .to_owned(),
+    ] {
+        index.if_not_exists().unique();
+        db.execute(db.get_database_backend().build(&index))
+            .await
+            .expect("Unable to create indexes");
+    }
 }
 
 /// Delete all entries in the database.
@@ -130,16 +161,42 @@ async fn cleanup() {
                 .exec(txn)
                 .await
                 .unwrap();
+            entity::drive_file::Entity::delete_many()
+                .exec(txn)
+                .await
+                .unwrap();
+            entity::user_profile::Entity::delete_many()
+                .exec(txn)
+                .await
+                .unwrap();
+            entity::emoji::Entity::delete_many()
+                .exec(txn)
+                .await
+                .unwrap();
+            entity::user_keypair::Entity::delete_many()
+                .exec(txn)
+                .await
+                .unwrap();
+            entity::following::Entity::delete_many()
+                .exec(txn)
+                .await
+                .unwrap();
+            entity::instance::Entity::delete_many()
+                .exec(txn)
+                .await
+                .unwrap();
+            entity::meta::Entity::delete_many().exec(txn).await.unwrap();
 
             Ok(())
         })
     })
     .await
     .expect("Unable to delete predefined models");
+    policy::invalidate_policy();
 }
 
 async fn setup_model(db: &DbConn) {
-    init_id(16, "");
+    init_id(16, "").unwrap();
 
     db.transaction::<_, (), DbErr>(|txn| {
         Box::pin(async move {
@@ -153,9 +210,36 @@ async fn setup_model(db: &DbConn) {
                 name: Some(name.to_string()),
                 token: Some(gen_string(16)),
                 is_admin: true,
+                emojis: string_vec(vec!["blobcat".to_string()]),
                 ..Default::default()
             };
             user_model
+                .to_owned()
+                .into_active_model()
+                .reset_all()
+                .insert(txn)
+                .await?;
+            let profile_model = entity::user_profile::Model {
+                user_id: user_id.to_owned(),
+                description: Some("Hello, world!".to_string()),
+                fields: json!([{ "name": "Website", "value": "https://example.com" }]),
+                ff_visibility: UserProfileFfvisibilityEnum::Followers,
+                ..Default::default()
+            };
+            profile_model
+                .into_active_model()
+                .reset_all()
+                .insert(txn)
+                .await?;
+            let emoji_model = entity::emoji::Model {
+                id: create_id(0).unwrap(),
+                name: "blobcat".to_string(),
+                original_url: "https://example.com/emoji/blobcat.png".to_string(),
+                width: Some(128),
+                height: Some(128),
+                ..Default::default()
+            };
+            emoji_model
                 .into_active_model()
                 .reset_all()
                 .insert(txn)
@@ -193,6 +277,60 @@ async fn setup_model(db: &DbConn) {
                 ..Default::default()
             };
             note_model
+                .to_owned()
+                .into_active_model()
+                .reset_all()
+                .insert(txn)
+                .await?;
+            let file_model = entity::drive_file::Model {
+                id: create_id(0).unwrap(),
+                created_at: Utc::now().into(),
+                user_id: Some(user_id.to_owned()),
+                md5: "d41d8cd98f00b204e9800998ecf8427e".to_string(),
+                name: "alice.png".to_string(),
+                r#type: "image/png".to_string(),
+                size: 1024,
+                properties: json!({ "width": 480, "height": 640, "orientation": 6 }),
+                url: "https://example.com/files/alice.png".to_string(),
+                ..Default::default()
+            };
+            file_model
+                .to_owned()
+                .into_active_model()
+                .reset_all()
+                .insert(txn)
+                .await?;
+            entity::user::ActiveModel {
+                avatar_id: Set(Some(file_model.id.to_owned())),
+                ..user_model.into_active_model()
+            }
+            .update(txn)
+            .await?;
+            let reply_model = entity::note::Model {
+                id: create_id(0).unwrap(),
+                created_at: Utc::now().into(),
+                reply_id: Some(note_model.id.to_owned()),
+                text: Some("Reply with poll".to_string()),
+                user_id: user_id.to_owned(),
+                file_ids: string_vec(vec![file_model.id.to_owned()]),
+                reactions: json!({ ":blobcat:": 1, "👍": 2 }),
+                has_poll: true,
+                ..Default::default()
+            };
+            reply_model
+                .to_owned()
+                .into_active_model()
+                .reset_all()
+                .insert(txn)
+                .await?;
+            let poll_model = entity::poll::Model {
+                note_id: reply_model.id.to_owned(),
+                choices: string_vec(vec!["foo".to_string(), "bar".to_string()]),
+                votes: i32_vec(vec![1, 0]),
+                user_id: user_id.to_owned(),
+                ..Default::default()
+            };
+            poll_model
                 .into_active_model()
                 .reset_all()
                 .insert(txn)
@@ -0,0 +1,36 @@
+mod int_test {
+    use native_utils::database;
+    use native_utils::model::entity::meta;
+    use native_utils::model::entity::newtype::string_vec;
+    use native_utils::model::meta::current_meta;
+    use pretty_assertions::assert_eq;
+    use sea_orm::{ActiveModelTrait, IntoActiveModel};
+
+    use crate::{cleanup, prepare};
+
+    #[tokio::test]
+    async fn newest_meta() {
+        prepare().await;
+        let db = database::get_database().unwrap();
+
+        assert_eq!(current_meta(db).await, Ok(None));
+
+        for (id, name) in [("9b", "New"), ("9a", "Old")] {
+            meta::Model {
+                id: id.to_string(),
+                name: Some(name.to_string()),
+                allowed_hosts: Some(string_vec(vec![])),
+                ..Default::default()
+            }
+            .into_active_model()
+            .reset_all()
+            .insert(db)
+            .await
+            .unwrap();
+        }
+        let meta = current_meta(db).await.unwrap().expect("meta not found");
+        assert_eq!(meta.name.as_deref(), Some("New"));
+
+        cleanup().await;
+    }
+}
@@ -1 +1,2 @@
+mod meta;
 mod repository;
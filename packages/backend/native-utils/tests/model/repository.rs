@@ -1 +1,3 @@
 mod antenna;
+mod note;
+mod user;
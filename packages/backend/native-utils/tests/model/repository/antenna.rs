@@ -1,13 +1,13 @@
 mod int_test {
-    use native_utils::{database, model, util};
+    use native_utils::{antenna::timeline, database, model};
 
     use model::{
-        entity::{antenna, antenna_note, note, user},
-        repository::Repository,
+        entity::{antenna, note, user},
+        repository::{PackContext, Repository},
         schema,
     };
     use pretty_assertions::assert_eq;
-    use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, IntoActiveModel, QueryFilter};
+    use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
 
     use crate::{cleanup, prepare};
 
@@ -28,13 +28,14 @@ mod int_test {
 
         let packed = alice_antenna
             .to_owned()
-            .pack()
+            .pack(&PackContext::default())
             .await
             .expect("Unable to pack");
 
-        let packed_by_id = antenna::Model::pack_by_id(alice_antenna.id.to_owned())
-            .await
-            .expect("Unable to pack");
+        let packed_by_id =
+            antenna::Model::pack_by_id(alice_antenna.id.to_owned(), &PackContext::default())
+                .await
+                .expect("Unable to pack");
 
         let result = schema::Antenna {
             id: alice_antenna.id,
@@ -63,6 +64,12 @@ mod int_test {
         assert_eq!(packed, result);
         assert_eq!(packed_by_id, result);
 
+        let packed_many =
+            antenna::Model::pack_by_ids(vec![result.id.to_owned()], &PackContext::default())
+                .await
+                .expect("Unable to pack");
+        assert_eq!(packed_many, vec![result]);
+
         cleanup().await;
     }
 
@@ -81,7 +88,7 @@ mod int_test {
         let alice_antenna = alice_antenna.expect("alice's antenna not found");
         let packed = alice_antenna
             .to_owned()
-            .pack()
+            .pack(&PackContext::default())
             .await
             .expect("Unable to pack");
         assert_eq!(packed.has_unread_note, false);
@@ -92,25 +99,25 @@ mod int_test {
             .await
             .unwrap()
             .expect("note not found");
-        let antenna_note = antenna_note::Model {
-            id: util::id::create_id(0).unwrap(),
-            antenna_id: alice_antenna.id.to_owned(),
-            note_id: note_model.id.to_owned(),
-            read: false,
-        };
-        antenna_note
-            .into_active_model()
-            .reset_all()
-            .insert(db)
+        timeline::push_note(&alice_antenna.id, &note_model.id)
             .await
             .unwrap();
         let packed = alice_antenna
             .to_owned()
-            .pack()
+            .pack(&PackContext::default())
             .await
             .expect("Unable to pack");
         assert_eq!(packed.has_unread_note, true);
 
+        timeline::read(&alice_antenna.id).await.unwrap();
+        let packed = alice_antenna
+            .to_owned()
+            .pack(&PackContext::default())
+            .await
+            .expect("Unable to pack");
+        assert_eq!(packed.has_unread_note, false);
+
+        timeline::delete(&alice_antenna.id).await.unwrap();
         cleanup().await;
     }
 }
@@ -0,0 +1,246 @@
+mod int_test {
+    use std::collections::HashMap;
+
+    use chrono::Utc;
+    use native_utils::{database, model, util};
+
+    use model::{
+        entity::{
+            newtype::string_vec, note, note_reaction, poll_vote,
+            sea_orm_active_enums::NoteVisibilityEnum, user,
+        },
+        repository::{PackContext, Repository},
+        schema,
+    };
+    use pretty_assertions::assert_eq;
+    use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, IntoActiveModel, QueryFilter};
+
+    use crate::{cleanup, prepare};
+
+    #[tokio::test]
+    async fn can_pack() {
+        prepare().await;
+        let db = database::get_database().unwrap();
+        let ctx = PackContext::detailed(None);
+
+        let alice = user::Entity::find()
+            .filter(user::Column::Username.eq("alice"))
+            .one(db)
+            .await
+            .unwrap()
+            .expect("alice not found");
+        let reply_model = note::Entity::find()
+            .filter(note::Column::UserId.eq(alice.id.to_owned()))
+            .filter(note::Column::ReplyId.is_not_null())
+            .one(db)
+            .await
+            .unwrap()
+            .expect("reply not found");
+
+        let packed: schema::Note = reply_model
+            .to_owned()
+            .pack(&ctx)
+            .await
+            .expect("Unable to pack");
+        let packed_by_id = note::Model::pack_by_id(reply_model.id.to_owned(), &ctx)
+            .await
+            .expect("Unable to pack");
+        assert_eq!(packed, packed_by_id);
+
+        assert_eq!(packed.text, Some("Reply with poll".to_string()));
+        assert_eq!(packed.user.username, "alice");
+        assert_eq!(packed.visibility, schema::NoteVisibility::Public);
+        assert_eq!(
+            packed.reactions,
+            HashMap::from([(":blobcat@.:".to_string(), 1), ("👍".to_string(), 2)])
+        );
+
+        let reply = packed.reply.expect("reply not packed");
+        assert_eq!(Some(reply.id), reply_model.reply_id);
+        assert_eq!(reply.text, Some("Testing 123".to_string()));
+        assert_eq!(reply.reply, None);
+
+        let file = packed.files.first().expect("file not packed");
+        assert_eq!(packed.file_ids, vec![file.id.to_owned()]);
+        assert_eq!(
+            file.url,
+            Some("https://example.com/files/alice.png".to_string())
+        );
+        assert_eq!(file.thumbnail_url, file.url);
+        // width and height are swapped by the orientation
+        assert_eq!(file.properties.width, Some(640));
+        assert_eq!(file.properties.height, Some(480));
+
+        let poll = packed.poll.expect("poll not packed");
+        assert_eq!(poll.multiple, false);
+        assert_eq!(
+            poll.choices,
+            vec![
+                schema::NotePollChoice {
+                    text: "foo".to_string(),
+                    votes: 1,
+                    is_voted: false,
+                },
+                schema::NotePollChoice {
+                    text: "bar".to_string(),
+                    votes: 0,
+                    is_voted: false,
+                },
+            ]
+        );
+
+        cleanup().await;
+    }
+
+    #[tokio::test]
+    async fn can_pack_many() {
+        prepare().await;
+        let db = database::get_database().unwrap();
+        let ctx = PackContext::detailed(None);
+
+        let reply_model = note::Entity::find()
+            .filter(note::Column::ReplyId.is_not_null())
+            .one(db)
+            .await
+            .unwrap()
+            .expect("reply not found");
+        let note_id = reply_model.reply_id.to_owned().unwrap();
+
+        // input order is kept and missing ids are skipped
+        let packed = note::Model::pack_by_ids(
+            vec![
+                reply_model.id.to_owned(),
+                "non-existent".to_string(),
+                note_id.to_owned(),
+            ],
+            &ctx,
+        )
+        .await
+        .expect("Unable to pack");
+        let ids: Vec<String> = packed.iter().map(|n| n.id.to_owned()).collect();
+        assert_eq!(ids, vec![reply_model.id.to_owned(), note_id.to_owned()]);
+
+        let packed_one: schema::Note = reply_model.pack(&ctx).await.expect("Unable to pack");
+        assert_eq!(packed[0], packed_one);
+        assert_eq!(packed[0].reply.as_deref(), Some(&packed[1]));
+
+        let packed: Vec<schema::Note> = note::Model::pack_many(vec![], &ctx)
+            .await
+            .expect("Unable to pack");
+        assert!(packed.is_empty());
+
+        cleanup().await;
+    }
+
+    #[tokio::test]
+    async fn viewer_aware() {
+        prepare().await;
+        let db = database::get_database().unwrap();
+
+        let reply_model = note::Entity::find()
+            .filter(note::Column::ReplyId.is_not_null())
+            .one(db)
+            .await
+            .unwrap()
+            .expect("reply not found");
+        let alice_id = reply_model.user_id.to_owned();
+        let alice_ctx = PackContext::detailed(Some(alice_id.to_owned()));
+
+        note_reaction::Model {
+            id: util::id::create_id(0).unwrap(),
+            created_at: Utc::now().into(),
+            user_id: alice_id.to_owned(),
+            note_id: reply_model.id.to_owned(),
+            reaction: ":blobcat:".to_string(),
+        }
+        .into_active_model()
+        .reset_all()
+        .insert(db)
+        .await
+        .unwrap();
+        poll_vote::Model {
+            id: util::id::create_id(0).unwrap(),
+            created_at: Utc::now().into(),
+            user_id: alice_id.to_owned(),
+            note_id: reply_model.id.to_owned(),
+            choice: 1,
+        }
+        .into_active_model()
+        .reset_all()
+        .insert(db)
+        .await
+        .unwrap();
+
+        let packed: schema::Note = reply_model
+            .to_owned()
+            .pack(&alice_ctx)
+            .await
+            .expect("Unable to pack");
+        assert_eq!(packed.my_reaction, Some(":blobcat@.:".to_string()));
+        let voted: Vec<bool> = packed
+            .poll
+            .expect("poll not packed")
+            .choices
+            .iter()
+            .map(|c| c.is_voted)
+            .collect();
+        assert_eq!(voted, vec![false, true]);
+
+        // signed out viewers get no viewer-specific fields
+        let packed: schema::Note = reply_model
+            .to_owned()
+            .pack(&PackContext::default())
+            .await
+            .expect("Unable to pack");
+        assert_eq!(packed.my_reaction, None);
+        assert_eq!(packed.reply, None);
+
+        // direct notes are only visible to the author and the recipients
+        let direct_model = note::Model {
+            id: util::id::create_id(0).unwrap(),
+            created_at: Utc::now().into(),
+            text: Some("Secret".to_string()),
+            user_id: alice_id.to_owned(),
+            visibility: NoteVisibilityEnum::Specified,
+            visible_user_ids: string_vec(vec!["bob".to_string()]),
+            ..Default::default()
+        };
+        direct_model
+            .to_owned()
+            .into_active_model()
+            .reset_all()
+            .insert(db)
+            .await
+            .unwrap();
+        let bob_ctx = PackContext::detailed(Some("bob".to_string()));
+        let carol_ctx = PackContext::detailed(Some("carol".to_string()));
+        for (ctx, visible) in [
+            (&alice_ctx, true),
+            (&bob_ctx, true),
+            (&carol_ctx, false),
+            (&PackContext::default(), false),
+        ] {
+            let packed: Result<schema::Note, _> = direct_model.to_owned().pack(ctx).await;
+            assert_eq!(packed.is_ok(), visible);
+        }
+        let packed =
+            note::Model::pack_by_ids(vec![direct_model.id, reply_model.id.to_owned()], &carol_ctx)
+                .await
+                .expect("Unable to pack");
+        assert_eq!(packed.len(), 1);
+        assert_eq!(packed[0].id, reply_model.id);
+
+        cleanup().await;
+    }
+
+    #[tokio::test]
+    async fn not_found() {
+        prepare().await;
+
+        let result: Result<schema::Note, _> =
+            note::Model::pack_by_id("non-existent".to_string(), &PackContext::default()).await;
+        assert_eq!(result, Err(model::error::Error::NotFound));
+
+        cleanup().await;
+    }
+}
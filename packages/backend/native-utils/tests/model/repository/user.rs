@@ -0,0 +1,182 @@
+mod int_test {
+    use chrono::Utc;
+    use native_utils::{database, model, util};
+
+    use model::{
+        entity::{following, note, user, user_note_pining},
+        repository::{PackContext, Repository},
+        schema,
+    };
+    use pretty_assertions::assert_eq;
+    use sea_orm::{
+        ActiveModelTrait, ColumnTrait, EntityTrait, IntoActiveModel, QueryFilter, QueryOrder, Set,
+    };
+
+    use crate::{cleanup, prepare};
+
+    async fn find_alice() -> user::Model {
+        user::Entity::find()
+            .filter(user::Column::Username.eq("alice"))
+            .one(database::get_database().unwrap())
+            .await
+            .unwrap()
+            .expect("alice not found")
+    }
+
+    #[tokio::test]
+    async fn can_pack_lite() {
+        prepare().await;
+        let alice = find_alice().await;
+
+        let packed: schema::UserLite = alice
+            .to_owned()
+            .pack(&PackContext::default())
+            .await
+            .expect("Unable to pack");
+        let packed_by_id: schema::UserLite =
+            user::Model::pack_by_id(alice.id.to_owned(), &PackContext::default())
+                .await
+                .expect("Unable to pack");
+        assert_eq!(packed, packed_by_id);
+
+        assert_eq!(packed.username, "alice");
+        assert_eq!(packed.is_admin, true);
+        assert_eq!(
+            packed.avatar_url,
+            Some("https://example.com/files/alice.png".to_string())
+        );
+        assert_eq!(packed.instance, None);
+        assert_eq!(packed.online_status, schema::UserOnlineStatus::Unknown);
+        assert_eq!(
+            packed.emojis,
+            vec![schema::PopulatedEmoji {
+                name: "blobcat".to_string(),
+                url: "https://example.com/emoji/blobcat.png".to_string(),
+                width: Some(128),
+                height: Some(128),
+            }]
+        );
+
+        cleanup().await;
+    }
+
+    #[tokio::test]
+    async fn can_pack_detailed() {
+        prepare().await;
+        let db = database::get_database().unwrap();
+        let alice = user::ActiveModel {
+            followers_count: Set(1),
+            following_count: Set(2),
+            ..find_alice().await.into_active_model()
+        }
+        .update(db)
+        .await
+        .unwrap();
+        let pinned_note = note::Entity::find()
+            .filter(note::Column::UserId.eq(alice.id.to_owned()))
+            .order_by_asc(note::Column::Id)
+            .one(db)
+            .await
+            .unwrap()
+            .expect("note not found");
+        user_note_pining::Model {
+            id: util::id::create_id(0).unwrap(),
+            created_at: Utc::now().into(),
+            user_id: alice.id.to_owned(),
+            note_id: pinned_note.id.to_owned(),
+        }
+        .into_active_model()
+        .reset_all()
+        .insert(db)
+        .await
+        .unwrap();
+
+        let packed: schema::UserDetailed = alice
+            .to_owned()
+            .pack(&PackContext::default())
+            .await
+            .expect("Unable to pack");
+        assert_eq!(packed.lite.username, "alice");
+        assert_eq!(packed.description, Some("Hello, world!".to_string()));
+        assert_eq!(
+            packed.fields,
+            vec![schema::UserField {
+                name: "Website".to_string(),
+                value: "https://example.com".to_string(),
+            }]
+        );
+        assert_eq!(packed.ff_visibility, schema::UserFfVisibility::Followers);
+        assert_eq!(packed.pinned_note_ids, vec![pinned_note.id.to_owned()]);
+        assert_eq!(packed.pinned_notes.len(), 1);
+        assert_eq!(packed.pinned_notes[0].id, pinned_note.id);
+        // hidden from signed-out viewers
+        assert_eq!(packed.followers_count, 0);
+        assert_eq!(packed.following_count, 0);
+        assert_eq!(packed.is_following, None);
+
+        // visible to alice herself, without relations
+        let ctx = PackContext::detailed(Some(alice.id.to_owned()));
+        let packed: schema::UserDetailed = alice.to_owned().pack(&ctx).await.unwrap();
+        assert_eq!(packed.followers_count, 1);
+        assert_eq!(packed.following_count, 2);
+        assert_eq!(packed.is_following, None);
+
+        // hidden from viewers not following alice
+        let bob = user::Model {
+            id: util::id::create_id(0).unwrap(),
+            created_at: Utc::now().into(),
+            username: "bob".to_string(),
+            username_lower: "bob".to_string(),
+            ..Default::default()
+        };
+        bob.to_owned()
+            .into_active_model()
+            .reset_all()
+            .insert(db)
+            .await
+            .unwrap();
+        let ctx = PackContext::detailed(Some(bob.id.to_owned()));
+        let packed: schema::UserDetailed = alice.to_owned().pack(&ctx).await.unwrap();
+        assert_eq!(packed.followers_count, 0);
+        assert_eq!(packed.is_following, Some(false));
+        assert_eq!(packed.is_blocked, Some(false));
+
+        // visible to followers
+        following::Model {
+            id: util::id::create_id(0).unwrap(),
+            created_at: Utc::now().into(),
+            followee_id: alice.id.to_owned(),
+            follower_id: bob.id.to_owned(),
+            ..Default::default()
+        }
+        .into_active_model()
+        .reset_all()
+        .insert(db)
+        .await
+        .unwrap();
+        let packed: schema::UserDetailed = alice.to_owned().pack(&ctx).await.unwrap();
+        assert_eq!(packed.followers_count, 1);
+        assert_eq!(packed.following_count, 2);
+        assert_eq!(packed.is_following, Some(true));
+        assert_eq!(packed.is_followed, Some(false));
+
+        let packed_by_ids: Vec<schema::UserDetailed> =
+            user::Model::pack_by_ids(vec!["non-existent".to_string(), alice.id], &ctx)
+                .await
+                .expect("Unable to pack");
+        assert_eq!(packed_by_ids, vec![packed]);
+
+        cleanup().await;
+    }
+
+    #[tokio::test]
+    async fn not_found() {
+        prepare().await;
+
+        let result: Result<schema::UserLite, _> =
+            user::Model::pack_by_id("non-existent".to_string(), &PackContext::default()).await;
+        assert_eq!(result, Err(model::error::Error::NotFound));
+
+        cleanup().await;
+    }
+}
@@ -0,0 +1,104 @@
+mod int_test {
+    use chrono::{Duration, Utc};
+    use native_utils::model::entity::newtype::string_vec;
+    use native_utils::model::entity::{meta, user};
+    use native_utils::nodeinfo::{self, error::Error, Server, Users, Version};
+    use native_utils::{cache, database};
+    use pretty_assertions::assert_eq;
+    use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, IntoActiveModel, QueryFilter, Set};
+
+    use crate::{cleanup, prepare};
+
+    #[tokio::test]
+    async fn nodeinfo_usage() {
+        prepare().await;
+        let db = database::get_database().unwrap();
+        let server = Server {
+            url: "https://example.com".to_string(),
+            version: "1.0.4".to_string(),
+            max_note_length: 3000,
+            max_caption_length: 1500,
+            search_filters: true,
+        };
+
+        assert_eq!(
+            nodeinfo::nodeinfo(&server, Version::V2_1).await,
+            Err(Error::MetaNotFound)
+        );
+
+        let alice = user::Entity::find()
+            .filter(user::Column::Username.eq("alice"))
+            .one(db)
+            .await
+            .unwrap()
+            .unwrap();
+        user::ActiveModel {
+            last_active_date: Set(Some((Utc::now() - Duration::days(60)).into())),
+            ..alice.to_owned().into_active_model()
+        }
+        .update(db)
+        .await
+        .unwrap();
+        user::Model {
+            id: "remote".to_string(),
+            created_at: Utc::now().into(),
+            username: "bob".to_string(),
+            username_lower: "bob".to_string(),
+            host: Some("remote.example".to_string()),
+            last_active_date: Some(Utc::now().into()),
+            emojis: string_vec(vec![]),
+            ..Default::default()
+        }
+        .into_active_model()
+        .reset_all()
+        .insert(db)
+        .await
+        .unwrap();
+        meta::Model {
+            id: "x".to_string(),
+            name: Some("Example".to_string()),
+            proxy_account_id: Some(alice.id.to_owned()),
+            allowed_hosts: Some(string_vec(vec![])),
+            ..Default::default()
+        }
+        .into_active_model()
+        .reset_all()
+        .insert(db)
+        .await
+        .unwrap();
+
+        let info = nodeinfo::nodeinfo(&server, Version::V2_1).await.unwrap();
+        assert_eq!(
+            info.usage.users,
+            Users {
+                total: 1,
+                active_halfyear: 1,
+                active_month: 0,
+            }
+        );
+        assert_eq!(info.usage.local_posts, 2);
+        assert_eq!(info.metadata.node_name.as_deref(), Some("Example"));
+        assert_eq!(info.metadata.proxy_account_name.as_deref(), Some("alice"));
+        assert!(info.metadata.search_filters);
+        assert!(!info.metadata.post_imports);
+
+        // Served from the cache until it expires.
+        user::ActiveModel {
+            last_active_date: Set(Some(Utc::now().into())),
+            ..alice.into_active_model()
+        }
+        .update(db)
+        .await
+        .unwrap();
+        let old = nodeinfo::nodeinfo(&server, Version::V2_0).await.unwrap();
+        assert_eq!(old.version, "2.0");
+        assert_eq!(old.usage, info.usage);
+
+        cache::get_cache().unwrap().del("nodeinfo").await.unwrap();
+        let info = nodeinfo::nodeinfo(&server, Version::V2_0).await.unwrap();
+        assert_eq!(info.usage.users.active_month, 1);
+
+        cache::get_cache().unwrap().del("nodeinfo").await.unwrap();
+        cleanup().await;
+    }
+}
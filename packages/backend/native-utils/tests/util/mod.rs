@@ -0,0 +1,50 @@
+mod int_test {
+    use native_utils::util::keypair::{rekey_instance_actor, Error, KeyType};
+    use native_utils::{database, model};
+
+    use model::entity::{user, user_keypair};
+    use pretty_assertions::{assert_eq, assert_ne};
+    use sea_orm::{ActiveModelTrait, EntityTrait, IntoActiveModel};
+
+    use crate::{cleanup, prepare};
+
+    #[tokio::test]
+    async fn rekey() {
+        prepare().await;
+        let db = database::get_database().unwrap();
+
+        assert_eq!(
+            rekey_instance_actor(KeyType::Rsa2048).await,
+            Err(Error::NotFound)
+        );
+        assert_eq!(
+            rekey_instance_actor(KeyType::Ed25519).await,
+            Err(Error::UnsupportedKeyType(KeyType::Ed25519))
+        );
+
+        user::Model {
+            id: "instance-actor".to_string(),
+            username: "instance.actor".to_string(),
+            username_lower: "instance.actor".to_string(),
+            ..Default::default()
+        }
+        .into_active_model()
+        .reset_all()
+        .insert(db)
+        .await
+        .unwrap();
+
+        let first = rekey_instance_actor(KeyType::Rsa2048).await.unwrap();
+        let second = rekey_instance_actor(KeyType::Rsa2048).await.unwrap();
+        assert_ne!(first.public_key, second.public_key);
+        assert_eq!(
+            user_keypair::Entity::find_by_id("instance-actor".to_string())
+                .one(db)
+                .await
+                .unwrap(),
+            Some(second)
+        );
+
+        cleanup().await;
+    }
+}
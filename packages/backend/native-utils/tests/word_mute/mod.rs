@@ -0,0 +1,90 @@
+mod int_test {
+    use native_utils::{database, model, word_mute};
+
+    use model::entity::{note, user, user_profile};
+    use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, IntoActiveModel, QueryFilter, Set};
+    use serde_json::json;
+
+    use crate::{cleanup, prepare};
+
+    #[tokio::test]
+    async fn hard_mute() {
+        prepare().await;
+        let db = database::get_database().unwrap();
+        let alice = user::Entity::find()
+            .filter(user::Column::Username.eq("alice"))
+            .one(db)
+            .await
+            .unwrap()
+            .expect("alice not found");
+        let profile = user_profile::Entity::find_by_id(alice.id.to_owned())
+            .one(db)
+            .await
+            .unwrap()
+            .expect("alice's profile not found");
+        user_profile::ActiveModel {
+            muted_words: Set(json!([["foo", "bar"], "/^baz/i"])),
+            ..profile.into_active_model()
+        }
+        .update(db)
+        .await
+        .unwrap();
+        word_mute::invalidate_muted_words(&alice.id);
+
+        let note = |user_id: &str, text: &str| note::Model {
+            user_id: user_id.to_string(),
+            text: Some(text.to_string()),
+            ..Default::default()
+        };
+        let muted = note("bob", "Baz!");
+        let not_muted = note("bob", "foo");
+        let no_files: &[String] = &[];
+
+        assert!(
+            word_mute::get_word_hard_mute((&muted, no_files), None, None, &alice.id)
+                .await
+                .unwrap()
+        );
+        assert!(
+            !word_mute::get_word_hard_mute((&not_muted, no_files), None, None, &alice.id)
+                .await
+                .unwrap()
+        );
+        // comments on the attached files are also checked
+        assert!(word_mute::get_word_hard_mute(
+            (&not_muted, &["a bar".to_string()]),
+            None,
+            None,
+            &alice.id
+        )
+        .await
+        .unwrap());
+        // replies and renotes are also checked
+        assert!(word_mute::get_word_hard_mute(
+            (&not_muted, no_files),
+            Some((&note("carol", "bar foo"), no_files)),
+            None,
+            &alice.id
+        )
+        .await
+        .unwrap());
+        // own notes are never muted
+        assert!(!word_mute::get_word_hard_mute(
+            (&note(&alice.id, "baz"), no_files),
+            None,
+            None,
+            &alice.id
+        )
+        .await
+        .unwrap());
+        // users without a profile have no muted words
+        assert!(
+            !word_mute::get_word_hard_mute((&muted, no_files), None, None, "carol")
+                .await
+                .unwrap()
+        );
+
+        word_mute::invalidate_muted_words(&alice.id);
+        cleanup().await;
+    }
+}
@@ -85,6 +85,11 @@ export default define(meta, paramDef, async (ps, user) => {
 		return [];
 	}
 
+	// 最新のノートを表示したら既読にする
+	if (!ps.untilId && !ps.untilDate) {
+		await redisClient.set(`antennaLastRead:${antenna.id}`, noteIdsRes[0][0]);
+	}
+
 	const noteIds = noteIdsRes
 		.map((x) => x[1][1])
 		.filter((x) => x !== ps.untilId && x !== ps.sinceId);